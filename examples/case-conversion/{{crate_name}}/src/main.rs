@@ -0,0 +1,10 @@
+//! {{ project_name | title_case }}, served at /{{ slug }}
+
+/// Read from `{{ env_prefix }}_CONFIG`
+struct {{ struct_name }} {
+    {{ binary_name | camel_case }}: String,
+}
+
+fn main() {
+    println!("{{ binary_name }}");
+}
@@ -0,0 +1,452 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, TimeZone, Timelike, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::debug;
+use zip::write::SimpleFileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::errors::{map_io_err, new_error, Error, ErrorKind, Result};
+use crate::utils::create_directory;
+
+/// The extensions of the archives that can be used as templates
+const ARCHIVE_EXTENSIONS: [&str; 3] = [".tar.gz", ".tgz", ".zip"];
+
+pub fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+/// The name of the archive without the query string if it is a URL
+fn file_name(input: &str) -> String {
+    let path = if is_url(input) { input.split(['?', '#']).next().unwrap_or(input) } else { input };
+    path.rsplit(['/', '\\']).next().unwrap_or(path).to_ascii_lowercase()
+}
+
+/// Whether the input is a `.tar.gz`, `.tgz` or `.zip` archive according to its extension
+pub fn is_archive(input: &str) -> bool {
+    let name = file_name(input);
+    ARCHIVE_EXTENSIONS.iter().any(|ext| name.len() > ext.len() && name.ends_with(ext))
+}
+
+fn is_zip(input: &str) -> bool {
+    file_name(input).ends_with(".zip")
+}
+
+/// Download `url` in `dest`, following redirects. Responses with an error status, eg 404, are
+/// errors.
+pub fn download(url: &str, dest: &Path) -> Result<()> {
+    let download_error =
+        |reason: String| new_error(ErrorKind::Download { url: url.to_string(), reason });
+    debug!("Downloading {url}");
+    let response = ureq::get(url).call().map_err(|err| download_error(err.to_string()))?;
+    let mut file = map_io_err(File::create(dest), dest)?;
+    io::copy(&mut response.into_reader(), &mut file)
+        .map_err(|err| download_error(err.to_string()))?;
+    Ok(())
+}
+
+/// Why an entry of an archive can't be extracted, if it can't: it would end up outside of the
+/// directory it is extracted in, or it is a link, which could point anywhere
+fn check_entry(name: &str, is_link: bool) -> std::result::Result<(), String> {
+    // Windows paths are not recognised as absolute on Unix and the other way around
+    let is_absolute = name.starts_with(['/', '\\']) || name.get(1..2) == Some(":");
+    if is_absolute || name.split(['/', '\\']).any(|part| part == "..") {
+        return Err(format!("`{name}` would be extracted outside of the archive"));
+    }
+    if is_link {
+        return Err(format!("`{name}` is a link, which is not supported"));
+    }
+    Ok(())
+}
+
+/// Write a file of an archive at `target`, creating its parent directories.
+/// The permissions are only kept on Unix.
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn write_entry(target: &Path, content: &mut impl Read, mode: Option<u32>) -> Result<()> {
+    if let Some(parent) = target.parent() {
+        create_directory(parent)?;
+    }
+    let mut file = map_io_err(File::create(target), target)?;
+    map_io_err(io::copy(content, &mut file), target)?;
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        let permissions = fs::Permissions::from_mode(mode & 0o777);
+        map_io_err(fs::set_permissions(target, permissions), target)?;
+    }
+    Ok(())
+}
+
+fn extract_tar_gz(path: &Path, dest: &Path, invalid: impl Fn(String) -> Error) -> Result<()> {
+    let open = || -> Result<tar::Archive<GzDecoder<File>>> {
+        Ok(tar::Archive::new(GzDecoder::new(map_io_err(File::open(path), path)?)))
+    };
+    let mut archive = open()?;
+    for entry in archive.entries().map_err(|err| invalid(err.to_string()))? {
+        let entry = entry.map_err(|err| invalid(err.to_string()))?;
+        let name = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+        let kind = entry.header().entry_type();
+        check_entry(&name, kind.is_symlink() || kind.is_hard_link()).map_err(&invalid)?;
+    }
+
+    // It is read a second time to extract it now that everything is known to be fine
+    let mut archive = open()?;
+    for entry in archive.entries().map_err(|err| invalid(err.to_string()))? {
+        let mut entry = entry.map_err(|err| invalid(err.to_string()))?;
+        let target = dest.join(String::from_utf8_lossy(&entry.path_bytes()).as_ref());
+        let kind = entry.header().entry_type();
+        if kind.is_dir() {
+            create_directory(&target)?;
+        } else if kind.is_file() {
+            let mode = entry.header().mode().ok();
+            write_entry(&target, &mut entry, mode)?;
+        } else {
+            debug!("Not extracting {}: it is not a file or a directory", target.display());
+        }
+    }
+    Ok(())
+}
+
+fn extract_zip(path: &Path, dest: &Path, invalid: impl Fn(String) -> Error) -> Result<()> {
+    let file = map_io_err(File::open(path), path)?;
+    let mut archive = ZipArchive::new(file).map_err(|err| invalid(err.to_string()))?;
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index).map_err(|err| invalid(err.to_string()))?;
+        check_entry(entry.name(), entry.is_symlink()).map_err(&invalid)?;
+    }
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(|err| invalid(err.to_string()))?;
+        let target = dest.join(entry.name());
+        if entry.is_dir() {
+            create_directory(&target)?;
+        } else {
+            let mode = entry.unix_mode();
+            write_entry(&target, &mut entry, mode)?;
+        }
+    }
+    Ok(())
+}
+
+/// Extract the `.tar.gz` or `.zip` archive at `path` in `dest`, depending on its extension.
+/// `name` is the archive as given by the user, to use in errors.
+/// Archives with entries that would end up outside of `dest`, eg `../evil`, or that are links
+/// are refused before extracting anything.
+pub fn extract(name: &str, path: &Path, dest: &Path) -> Result<()> {
+    let invalid =
+        |reason: String| new_error(ErrorKind::InvalidArchive { archive: name.to_string(), reason });
+    debug!("Extracting {} in {}", path.display(), dest.display());
+    if is_zip(name) {
+        extract_zip(path, dest, invalid)
+    } else {
+        extract_tar_gz(path, dest, invalid)
+    }
+}
+
+/// What [create] writes at a path of an archive
+pub enum Entry<'a> {
+    Directory,
+    /// The content of a file and its Unix permissions
+    File(Cow<'a, [u8]>, u32),
+    /// A file copied from that path as the archive is written, so it isn't read in memory, and
+    /// its Unix permissions
+    Copy(PathBuf, u32),
+}
+
+/// The file an [Entry::Copy] is copied from, with its path in the error
+fn open_copied(path: &Path) -> io::Result<File> {
+    File::open(path).map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())))
+}
+
+/// The modification time of the entries of a zip, which can only be between 1980 and 2107
+fn zip_time(timestamp: i64) -> std::result::Result<zip::DateTime, String> {
+    let time = Utc.timestamp_opt(timestamp, 0).single().ok_or("invalid timestamp")?;
+    zip::DateTime::from_date_and_time(
+        time.year().try_into().map_err(|_| "the year doesn't fit in a zip")?,
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+    )
+    .map_err(|err| err.to_string())
+}
+
+fn write_zip(file: File, entries: &[(String, &Entry)], timestamp: i64) -> io::Result<()> {
+    let time = zip_time(timestamp).map_err(io::Error::other)?;
+    let mut zip = ZipWriter::new(file);
+    for (name, entry) in entries {
+        let options = SimpleFileOptions::default().last_modified_time(time);
+        match entry {
+            Entry::Directory => zip.add_directory(name, options.unix_permissions(0o755))?,
+            Entry::File(content, mode) => {
+                zip.start_file(name, options.unix_permissions(mode & 0o777))?;
+                zip.write_all(content)?;
+            }
+            Entry::Copy(path, mode) => {
+                let mut source = open_copied(path)?;
+                zip.start_file(name, options.unix_permissions(mode & 0o777))?;
+                io::copy(&mut source, &mut zip)?;
+            }
+        }
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+fn write_tar_gz(file: File, entries: &[(String, &Entry)], timestamp: i64) -> io::Result<()> {
+    // Without the name and time of the tar in the gzip header
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    for (name, entry) in entries {
+        // Owned by uid and gid 0, without user or group names
+        let mut header = tar::Header::new_gnu();
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_mtime(timestamp.max(0) as u64);
+        match entry {
+            Entry::Directory => {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_mode(0o755);
+                header.set_size(0);
+                builder.append_data(&mut header, name, io::empty())?;
+            }
+            Entry::File(content, mode) => {
+                header.set_mode(mode & 0o777);
+                header.set_size(content.len() as u64);
+                builder.append_data(&mut header, name, &content[..])?;
+            }
+            Entry::Copy(path, mode) => {
+                let source = open_copied(path)?;
+                header.set_mode(mode & 0o777);
+                header.set_size(source.metadata()?.len());
+                builder.append_data(&mut header, name, source)?;
+            }
+        }
+    }
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Write `entries`, whose paths are relative to the root of the archive, to an archive at
+/// `dest`: a `.tar.gz` or a `.zip` depending on its extension. The entries are in order, get
+/// `timestamp` as modification time and the archive doesn't record who owns them, so it only
+/// depends on what is in it.
+/// Paths that would be extracted outside of the archive are refused before writing anything
+/// and the archive is written in a temporary directory next to `dest`, only moved there once
+/// complete.
+pub fn create(entries: &BTreeMap<PathBuf, Entry>, dest: &Path, timestamp: i64) -> Result<()> {
+    let failed =
+        |reason: String| new_error(ErrorKind::WriteArchive { archive: dest.to_path_buf(), reason });
+    let name = dest.to_string_lossy();
+    if !is_archive(&name) || is_url(&name) {
+        return Err(failed("it needs to end with `.tar.gz`, `.tgz` or `.zip`".to_string()));
+    }
+
+    let mut named = Vec::new();
+    for (path, entry) in entries {
+        let entry_name = path.to_string_lossy().replace('\\', "/");
+        check_entry(&entry_name, false).map_err(&failed)?;
+        named.push((entry_name, entry));
+    }
+
+    let parent = dest.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let tmp = map_io_err(
+        tempfile::Builder::new().prefix(".kickstart-archive-").tempdir_in(parent),
+        parent,
+    )?;
+    let written = tmp.path().join("archive");
+    let file = map_io_err(File::create(&written), &written)?;
+    if is_zip(&name) {
+        write_zip(file, &named, timestamp)
+    } else {
+        write_tar_gz(file, &named, timestamp)
+    }
+    .map_err(|err| failed(err.to_string()))?;
+    map_io_err(fs::rename(&written, dest), dest)
+}
+
+/// Where the template is in the extracted archive: archives often have everything in a single
+/// top-level directory, which is then used instead
+pub fn root(dest: &Path) -> Result<PathBuf> {
+    if dest.join("template.toml").exists() {
+        return Ok(dest.to_path_buf());
+    }
+    let entries = map_io_err(fs::read_dir(dest), dest)?.filter_map(|e| e.ok()).collect::<Vec<_>>();
+    match &entries[..] {
+        [single] if single.path().is_dir() => Ok(single.path()),
+        _ => Ok(dest.to_path_buf()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_detect_archives() {
+        for input in [
+            "template.tar.gz",
+            "dir/template.TGZ",
+            "template.zip",
+            "https://example.com/templates/rust.tar.gz?token=abc",
+            "https://example.com/rust.zip#main",
+        ] {
+            assert!(is_archive(input), "{input}");
+        }
+        for input in ["template", ".zip", "https://github.com/Keats/kickstart", "dir.zip/x"] {
+            assert!(!is_archive(input), "{input}");
+        }
+    }
+
+    #[test]
+    fn can_download_archives() {
+        use std::io::BufRead;
+        use std::net::TcpListener;
+
+        // Answers the requests with the status and body of the path
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = io::BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let (status, body) = if request.starts_with("GET /template.tar.gz ") {
+                    ("200 OK", "archive")
+                } else {
+                    ("404 Not Found", "nope")
+                };
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("template.tar.gz");
+        download(&format!("http://{addr}/template.tar.gz"), &dest).unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "archive");
+
+        let err = download(&format!("http://{addr}/missing.tar.gz"), &dest).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Download { .. }));
+        assert!(err.to_string().contains("404"), "{err}");
+    }
+
+    #[test]
+    fn archives_with_unsafe_entries_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("dest");
+        fs::create_dir(&dest).unwrap();
+
+        let tarball = dir.path().join("link.tar.gz");
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            File::create(&tarball).unwrap(),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(2);
+        builder.append_data(&mut header, "ok.txt", &b"ok"[..]).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder.append_link(&mut header, "link", "/etc/passwd").unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let err = extract("link.tar.gz", &tarball, &dest).unwrap_err();
+        assert!(err.to_string().contains("`link` is a link, which is not supported"), "{err}");
+
+        for name in ["../evil", "/evil", "C:\\evil", "a/../../evil"] {
+            let archive = dir.path().join("evil.zip");
+            let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+            zip.start_file("ok.txt", zip::write::SimpleFileOptions::default()).unwrap();
+            zip.write_all(b"ok").unwrap();
+            zip.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+            zip.write_all(b"evil").unwrap();
+            zip.finish().unwrap();
+
+            let err = extract("evil.zip", &archive, &dest).unwrap_err();
+            assert!(err.to_string().contains("would be extracted outside of the archive"), "{err}");
+        }
+        // Nothing was written, not even the files before
+        assert_eq!(fs::read_dir(&dest).unwrap().count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn can_create_deterministic_archives() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file =
+            |content: &'static str, mode| Entry::File(Cow::Borrowed(content.as_bytes()), mode);
+        let mut entries = BTreeMap::from([
+            (PathBuf::from("README.md"), file("Hello", 0o644)),
+            (PathBuf::from("run.sh"), file("echo hi", 0o755)),
+            (PathBuf::from("src"), Entry::Directory),
+            (PathBuf::from("src/main.rs"), file("fn main() {}", 0o644)),
+        ]);
+        let data = dir.path().join("data.csv");
+        fs::write(&data, "a,b\n1,2\n").unwrap();
+        entries.insert(PathBuf::from("data.csv"), Entry::Copy(data.clone(), 0o644));
+
+        for name in ["project.tar.gz", "project.zip"] {
+            let dest = dir.path().join(name);
+            create(&entries, &dest, 946_684_800).unwrap();
+            let first = fs::read(&dest).unwrap();
+            create(&entries, &dest, 946_684_800).unwrap();
+            assert_eq!(fs::read(&dest).unwrap(), first, "{name}");
+
+            let extracted = dir.path().join(format!("extracted-{name}"));
+            fs::create_dir(&extracted).unwrap();
+            extract(name, &dest, &extracted).unwrap();
+            assert_eq!(fs::read_to_string(extracted.join("src/main.rs")).unwrap(), "fn main() {}");
+            assert_eq!(fs::read(extracted.join("data.csv")).unwrap(), fs::read(&data).unwrap());
+            let mode = fs::metadata(extracted.join("run.sh")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o111, 0o111, "{name}");
+        }
+        let tarball = File::open(dir.path().join("project.tar.gz")).unwrap();
+        let mut archive = tar::Archive::new(GzDecoder::new(tarball));
+        let listed: Vec<_> = archive
+            .entries()
+            .unwrap()
+            .map(|e| {
+                let e = e.unwrap();
+                assert_eq!(
+                    (e.header().mtime().unwrap(), e.header().uid().unwrap()),
+                    (946_684_800, 0)
+                );
+                e.path().unwrap().to_string_lossy().into_owned()
+            })
+            .collect();
+        assert_eq!(listed, ["README.md", "data.csv", "run.sh", "src", "src/main.rs"]);
+
+        let err = create(&entries, &dir.path().join("project.rar"), 0).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::WriteArchive { .. }));
+        entries.insert(PathBuf::from("../evil"), file("evil", 0o644));
+        let err = create(&entries, &dir.path().join("evil.zip"), 0).unwrap_err();
+        assert!(err.to_string().contains("would be extracted outside of the archive"), "{err}");
+        entries.remove(Path::new("../evil"));
+        let missing = dir.path().join("missing.csv");
+        entries.insert(PathBuf::from("data.csv"), Entry::Copy(missing.clone(), 0o644));
+        let err = create(&entries, &dir.path().join("missing.zip"), 946_684_800).unwrap_err();
+        assert!(err.to_string().contains(&missing.display().to_string()), "{err}");
+        // The 2 archives, where they were extracted and the copied file, no temporary directory
+        let left: Vec<_> =
+            fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(left.len(), 5);
+    }
+}
@@ -0,0 +1,113 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::errors::{map_io_err, Result};
+use crate::utils::hash_content;
+use crate::GitRef;
+
+/// The environment variable that can be set to change where remote templates are cached
+pub const CACHE_DIR_ENV_VAR: &str = "KICKSTART_CACHE_DIR";
+
+/// Where remote templates are cached: the `KICKSTART_CACHE_DIR` environment variable if set,
+/// `kickstart` in the cache directory of the user otherwise, eg `~/.cache/kickstart`.
+/// Returns `None` if the cache directory of the user can't be found.
+pub fn default_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os(CACHE_DIR_ENV_VAR).filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+
+    let base = if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .filter(|d| d.is_absolute())
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+    };
+    base.map(|base| base.join("kickstart"))
+}
+
+/// Where the clone of `remote` is kept in the cache.
+/// Everything changing what gets cloned is part of the key, so a pinned tag doesn't share its
+/// clone with the head of a branch.
+pub fn entry_dir(
+    cache_dir: &Path,
+    remote: &str,
+    git_ref: Option<&GitRef>,
+    full_clone: bool,
+    directory: Option<&str>,
+) -> PathBuf {
+    let key = format!(
+        "{remote}\n{}\n{full_clone}\n{}",
+        git_ref.map(|r| format!("{r:?}")).unwrap_or_default(),
+        directory.unwrap_or_default()
+    );
+    cache_dir.join(&hash_content(key.as_bytes())[..16])
+}
+
+/// Whether `name` is the one of a directory kickstart creates in the cache: an [entry_dir] or
+/// the `.partial` one it is cloned in first
+fn is_entry_name(name: &str) -> bool {
+    let key = name.strip_suffix(".partial").unwrap_or(name);
+    key.len() == 16 && key.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// Remove all the cached templates. Only what kickstart creates is removed, the cache directory
+/// could be shared with other files.
+pub fn clear(cache_dir: &Path) -> Result<()> {
+    if !cache_dir.exists() {
+        return Ok(());
+    }
+    for entry in map_io_err(fs::read_dir(cache_dir), cache_dir)? {
+        let path = map_io_err(entry, cache_dir)?.path();
+        let is_entry = path.file_name().and_then(|n| n.to_str()).is_some_and(is_entry_name);
+        if is_entry && path.is_dir() {
+            map_io_err(fs::remove_dir_all(&path), &path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_are_keyed_by_url_and_ref() {
+        let dir = Path::new("cache");
+        let url = "https://github.com/Keats/kickstart";
+        let tag = GitRef::Tag("v1".to_string());
+        let branch = GitRef::Branch("v1".to_string());
+
+        let entry = entry_dir(dir, url, Some(&tag), false, None);
+        assert_eq!(entry, entry_dir(dir, url, Some(&tag), false, None));
+        assert!(entry.starts_with(dir));
+        for other in [
+            entry_dir(dir, url, None, false, None),
+            entry_dir(dir, url, Some(&branch), false, None),
+            entry_dir(dir, "https://github.com/Keats/tera", Some(&tag), false, None),
+            entry_dir(dir, url, Some(&tag), true, None),
+            entry_dir(dir, url, Some(&tag), false, Some("examples")),
+        ] {
+            assert_ne!(entry, other);
+        }
+    }
+
+    #[test]
+    fn clear_only_removes_the_cached_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry = entry_dir(dir.path(), "https://github.com/Keats/kickstart", None, false, None);
+        fs::create_dir_all(entry.join(".git")).unwrap();
+        fs::create_dir_all(entry.with_extension("partial")).unwrap();
+        fs::create_dir_all(dir.path().join("notes")).unwrap();
+        fs::write(dir.path().join("notes").join("todo.txt"), "Mine").unwrap();
+        fs::write(dir.path().join("0123456789abcdef"), "Not a directory").unwrap();
+
+        clear(dir.path()).unwrap();
+        let mut left: Vec<_> =
+            fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        left.sort();
+        assert_eq!(left, ["0123456789abcdef", "notes"]);
+    }
+}
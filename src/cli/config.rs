@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{new_error, ErrorKind, Result};
+use crate::utils::{create_directory, read_file, write_file};
+use crate::GitRef;
+
+/// The environment variable that can be set to use another config file
+pub const CONFIG_ENV_VAR: &str = "KICKSTART_CONFIG";
+
+/// A short name for a template, used instead of its source on the command line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Alias {
+    /// Where the template is, with the same syntax as on the command line
+    pub source: String,
+    /// The directory of the template in the source
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directory: Option<String>,
+    /// The branch to use if the source is a git repository, fetched again every time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    /// The tag to use if the source is a git repository
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// The commit, or anything `git checkout` accepts, to use if the source is a git repository
+    #[serde(alias = "ref", skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+}
+
+impl Alias {
+    /// What to check out if the source is a git repository, if anything in particular
+    pub fn git_ref(&self) -> Option<GitRef> {
+        match (&self.branch, &self.tag, &self.rev) {
+            (Some(branch), _, _) => Some(GitRef::Branch(branch.clone())),
+            (_, Some(tag), _) => Some(GitRef::Tag(tag.clone())),
+            (_, _, Some(rev)) => Some(GitRef::Rev(rev.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// The config file of the user
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The aliases of templates, by name
+    #[serde(default)]
+    pub templates: BTreeMap<String, Alias>,
+}
+
+impl Config {
+    /// Where the config file is: the `KICKSTART_CONFIG` environment variable if set,
+    /// `kickstart/config.toml` in the config directory of the user otherwise, eg
+    /// `~/.config/kickstart/config.toml`.
+    /// Returns `None` if the config directory of the user can't be found.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os(CONFIG_ENV_VAR).filter(|p| !p.is_empty()) {
+            return Some(PathBuf::from(path));
+        }
+
+        let base = if cfg!(windows) {
+            env::var_os("APPDATA").map(PathBuf::from)
+        } else {
+            env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .filter(|d| d.is_absolute())
+                .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        };
+        base.map(|base| base.join("kickstart").join("config.toml"))
+    }
+
+    /// Load the config file, which doesn't have to exist
+    pub fn load(path: &Path) -> Result<Config> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let invalid = |reason: String| {
+            new_error(ErrorKind::InvalidConfig { path: path.to_path_buf(), reason })
+        };
+        let config: Config =
+            toml::from_str(&read_file(path)?).map_err(|err| invalid(err.message().to_string()))?;
+        for (name, alias) in &config.templates {
+            let refs = [&alias.branch, &alias.tag, &alias.rev];
+            if refs.iter().filter(|r| r.is_some()).count() > 1 {
+                return Err(invalid(format!(
+                    "the `{name}` alias can only have one of `branch`, `tag` and `rev`"
+                )));
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            create_directory(parent)?;
+        }
+        // The config only has strings, it can always be serialized
+        let content = toml::to_string(self).unwrap();
+        write_file(path, &content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn can_save_and_load_aliases() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("kickstart").join("config.toml");
+        assert_eq!(Config::load(&path).unwrap(), Config::default());
+
+        let mut config = Config::default();
+        config.templates.insert(
+            "service".to_string(),
+            Alias {
+                source: "git@gitlab.internal:infra/templates.git".to_string(),
+                directory: Some("service".to_string()),
+                branch: Some("main".to_string()),
+                tag: None,
+                rev: None,
+            },
+        );
+        config.save(&path).unwrap();
+        assert!(read_file(&path).unwrap().contains("branch = \"main\""));
+        assert_eq!(Config::load(&path).unwrap(), config);
+        assert_eq!(config.templates["service"].git_ref(), Some(GitRef::Branch("main".to_string())));
+
+        write_file(&path, "[templates]\nservice = { source = \"x\", ref = \"v2\" }\n").unwrap();
+        let alias = &Config::load(&path).unwrap().templates["service"];
+        assert_eq!(alias.git_ref(), Some(GitRef::Rev("v2".to_string())));
+
+        write_file(&path, "[templates]\nservice = { source = \"x\", tag = \"v2\", rev = \"a\" }\n")
+            .unwrap();
+        let err = Config::load(&path).unwrap_err();
+        assert!(err.to_string().contains("can only have one of"), "{err}");
+
+        write_file(&path, "[templates]\nservice = { url = \"x\" }\n").unwrap();
+        assert!(matches!(Config::load(&path).unwrap_err().kind, ErrorKind::InvalidConfig { .. }));
+    }
+}
@@ -0,0 +1,502 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read};
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::cli::terminal;
+use crate::errors::{new_error, ErrorKind, Result};
+use crate::utils::{read_file, write_file};
+use crate::{Template, Value};
+
+/// The formats we can read answers from
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum InputFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl InputFormat {
+    /// Guess the format from the file extension, defaulting to JSON
+    pub fn from_path(path: &Path) -> InputFormat {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => InputFormat::Yaml,
+            Some("toml") => InputFormat::Toml,
+            _ => InputFormat::Json,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            InputFormat::Json => "JSON",
+            InputFormat::Yaml => "YAML",
+            InputFormat::Toml => "TOML",
+        }
+    }
+}
+
+fn from_json(val: &serde_json::Value, allow_array: bool) -> Option<Value> {
+    match val {
+        serde_json::Value::String(s) => Some(Value::String(s.clone())),
+        serde_json::Value::Bool(b) => Some(Value::Boolean(*b)),
+        serde_json::Value::Number(n) => {
+            n.as_i64().map(Value::Integer).or_else(|| n.as_f64().map(Value::Float))
+        }
+        serde_json::Value::Array(items) if allow_array => {
+            items.iter().map(|i| from_json(i, false)).collect::<Option<_>>().map(Value::List)
+        }
+        _ => None,
+    }
+}
+
+fn from_yaml(val: &serde_yaml::Value, allow_array: bool) -> Option<Value> {
+    match val {
+        serde_yaml::Value::String(s) => Some(Value::String(s.clone())),
+        serde_yaml::Value::Bool(b) => Some(Value::Boolean(*b)),
+        serde_yaml::Value::Number(n) => n
+            .as_i64()
+            .map(Value::Integer)
+            .or_else(|| n.as_f64().filter(|f| f.is_finite()).map(Value::Float)),
+        serde_yaml::Value::Sequence(items) if allow_array => {
+            items.iter().map(|i| from_yaml(i, false)).collect::<Option<_>>().map(Value::List)
+        }
+        _ => None,
+    }
+}
+
+fn from_toml(val: &toml::Value, allow_array: bool) -> Option<Value> {
+    match val {
+        toml::Value::String(s) => Some(Value::String(s.clone())),
+        toml::Value::Boolean(b) => Some(Value::Boolean(*b)),
+        toml::Value::Integer(i) => Some(Value::Integer(*i)),
+        toml::Value::Float(f) if f.is_finite() => Some(Value::Float(*f)),
+        toml::Value::Array(items) if allow_array => {
+            items.iter().map(|i| from_toml(i, false)).collect::<Option<_>>().map(Value::List)
+        }
+        _ => None,
+    }
+}
+
+/// Parse the document into a flat map of values, rejecting anything we can't represent.
+/// Arrays are accepted for multi choices but they can't be nested.
+fn parse_document(
+    content: &str,
+    format: InputFormat,
+) -> std::result::Result<Vec<(String, Value)>, String> {
+    let mut vals = Vec::new();
+
+    match format {
+        InputFormat::Json => {
+            let doc: serde_json::Value =
+                serde_json::from_str(content).map_err(|e| e.to_string())?;
+            let serde_json::Value::Object(map) = doc else {
+                return Err("the document needs to be an object".to_string());
+            };
+            for (key, val) in map {
+                let Some(val) = from_json(&val, true) else {
+                    return Err(format!("unsupported value `{val}` for variable `{key}`"));
+                };
+                vals.push((key, val));
+            }
+        }
+        InputFormat::Yaml => {
+            let doc: serde_yaml::Value =
+                serde_yaml::from_str(content).map_err(|e| e.to_string())?;
+            let serde_yaml::Value::Mapping(map) = doc else {
+                return Err("the document needs to be a mapping".to_string());
+            };
+            for (key, val) in map {
+                let serde_yaml::Value::String(key) = key else {
+                    return Err(format!("keys need to be strings, got {key:?}"));
+                };
+                let Some(val) = from_yaml(&val, true) else {
+                    return Err(format!("unsupported value {val:?} for variable `{key}`"));
+                };
+                vals.push((key, val));
+            }
+        }
+        InputFormat::Toml => {
+            let map: toml::Table = toml::from_str(content).map_err(|e| e.to_string())?;
+            for (key, val) in map {
+                let Some(val) = from_toml(&val, true) else {
+                    return Err(format!(
+                        "unsupported type {} for variable `{key}`",
+                        val.type_str()
+                    ));
+                };
+                vals.push((key, val));
+            }
+        }
+    }
+
+    Ok(vals)
+}
+
+/// Whether the given input file path means reading from stdin
+pub fn is_stdin(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Read everything piped to stdin
+fn read_stdin() -> Result<String> {
+    let mut content = String::new();
+    io::stdin().read_to_string(&mut content).map_err(|_| new_error(ErrorKind::UnreadableStdin))?;
+    Ok(content)
+}
+
+/// Load the answers to the template questions from a file, or from stdin if the path is `-`.
+/// The format is guessed from the extension unless `format` is given, stdin defaulting to JSON.
+///
+/// Variables are resolved in order so `only_if` conditions are evaluated the same way as when
+/// prompting: values for skipped variables are ignored with a warning, as well as unknown keys.
+/// Values from `overrides` win over the ones from the file.
+/// Optional variables that are missing are left empty.
+/// Errors if a required variable that should be asked is missing or has a value of the wrong
+/// type, if a computed variable is given a value or if values don't pass the `choices` and
+/// `validation` of their variable, all of those being reported at once.
+pub fn load_values_from_file(
+    template: &Template,
+    path: &Path,
+    format: Option<InputFormat>,
+    overrides: &HashMap<String, Value>,
+) -> Result<HashMap<String, Value>> {
+    let format = format.unwrap_or_else(|| InputFormat::from_path(path));
+    let invalid = |reason: String| {
+        new_error(ErrorKind::InvalidInputFile {
+            format: format.name(),
+            path: path.to_path_buf(),
+            reason,
+        })
+    };
+
+    let content = if is_stdin(path) { read_stdin()? } else { read_file(path)? };
+    let mut input: HashMap<String, Value> =
+        parse_document(&content, format).map_err(invalid)?.into_iter().collect();
+
+    let mut vals = HashMap::new();
+    for var in &template.definition.variables {
+        let provided = input.remove(&var.name);
+        if !template.should_ask_variable(&var.name, &vals)? {
+            if provided.is_some() {
+                terminal::warning(&format!(
+                    "Ignoring value for variable `{}` as its `only_if` condition is not met\n",
+                    var.name
+                ));
+            }
+            continue;
+        }
+
+        if var.computed.is_some() {
+            if provided.is_some() {
+                return Err(invalid(format!(
+                    "variable `{}` is computed from the other variables, it can't be given a value",
+                    var.name
+                )));
+            }
+            vals.insert(var.name.clone(), template.get_default_for(&var.name, &vals)?);
+            continue;
+        }
+
+        if let Some(val) = overrides.get(&var.name) {
+            vals.insert(var.name.clone(), val.clone());
+            continue;
+        }
+
+        // `2` is a fine value for a float variable
+        let provided = match (provided, &var.default) {
+            (Some(Value::Integer(i)), Value::Float(..)) => Some(Value::Float(i as f64)),
+            (p, _) => p,
+        };
+
+        match provided {
+            Some(val) => {
+                if val.type_str() != var.default.type_str() {
+                    return Err(invalid(format!(
+                        "variable `{}` is of type {} but got `{}` ({})",
+                        var.name,
+                        var.default.type_str(),
+                        val,
+                        val.type_str()
+                    )));
+                }
+                vals.insert(var.name.clone(), val);
+            }
+            // Optional variables are unset unless given
+            None if !var.required => {
+                vals.insert(var.name.clone(), Value::String(String::new()));
+            }
+            None => return Err(new_error(ErrorKind::MissingVariable(var.name.clone()))),
+        }
+    }
+
+    let mut unknown: Vec<_> = input.into_keys().collect();
+    unknown.sort();
+    for name in unknown {
+        terminal::warning(&format!("Ignoring unknown variable `{name}` in the input file\n"));
+    }
+
+    template.check_values(&vals)?;
+    Ok(vals)
+}
+
+/// Write the answers as a JSON file that can be given back to `--input-file`.
+/// Secrets are left out and will need to be given another way, eg with `--define`, as well as
+/// computed variables which can't be given.
+pub fn save_answers(template: &Template, path: &Path, vals: &HashMap<String, Value>) -> Result<()> {
+    // Sorted to make the file diffable
+    let sorted: BTreeMap<_, _> = template
+        .definition
+        .variables
+        .iter()
+        .filter(|var| !var.secret && var.computed.is_none())
+        .filter_map(|var| vals.get(&var.name).map(|val| (&var.name, val)))
+        .collect();
+    let content = serde_json::to_string_pretty(&sorted).expect("values to serialize");
+    write_file(path, &format!("{content}\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn load(filename: &str, content: &str) -> Result<HashMap<String, Value>> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(filename);
+        fs::write(&path, content).unwrap();
+        let tpl = Template::from_input("examples/complex", None).unwrap();
+        load_values_from_file(&tpl, &path, None, &HashMap::new())
+    }
+
+    #[test]
+    fn can_load_all_formats() {
+        // The same answers in every format supported
+        let inputs = vec![
+            (
+                "answers.json",
+                r#"{"project_name": "hello", "database": "sqlite", "auth_method": "none",
+                "sentry": false, "spa": true, "js_framework": "Vue", "typescript": true,
+                "features": ["ci", "docker"]}"#,
+            ),
+            (
+                "answers.yml",
+                "project_name: hello\ndatabase: sqlite\nauth_method: none\nsentry: false\n\
+                spa: true\njs_framework: Vue\ntypescript: true\nfeatures: [ci, docker]\n",
+            ),
+            (
+                "answers.toml",
+                "project_name = 'hello'\ndatabase = 'sqlite'\nauth_method = 'none'\n\
+                sentry = false\nspa = true\njs_framework = 'Vue'\ntypescript = true\n\
+                features = ['ci', 'docker']\n",
+            ),
+        ];
+        let expected: HashMap<String, Value> = [
+            ("project_name", Value::String("hello".to_string())),
+            ("database", Value::String("sqlite".to_string())),
+            ("auth_method", Value::String("none".to_string())),
+            ("sentry", Value::Boolean(false)),
+            ("spa", Value::Boolean(true)),
+            ("js_framework", Value::String("Vue".to_string())),
+            ("typescript", Value::Boolean(true)),
+            (
+                "features",
+                Value::List(vec![
+                    Value::String("ci".to_string()),
+                    Value::String("docker".to_string()),
+                ]),
+            ),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+
+        for (filename, content) in inputs {
+            assert_eq!(load(filename, content).unwrap(), expected, "{filename}");
+        }
+    }
+
+    #[test]
+    fn saved_answers_can_be_loaded_back() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("answers.json");
+        let tpl = Template::from_input("examples/complex", None).unwrap();
+        let vals = tpl.definition.default_values().unwrap();
+        save_answers(&tpl, &path, &vals).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains(r#""sentry": true"#));
+        assert_eq!(load_values_from_file(&tpl, &path, None, &HashMap::new()).unwrap(), vals);
+    }
+
+    #[test]
+    fn integers_are_accepted_for_floats() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            "name = 'Floats'\nkickstart_version = 1\n\n[[variables]]\nname = 'threshold'\n\
+            default = 0.5\nprompt = 'Threshold?'\n",
+        )
+        .unwrap();
+        let tpl = Template::from_input(&dir.path().to_string_lossy(), None).unwrap();
+
+        for (content, expected) in [(r#"{"threshold": 0.75}"#, 0.75), (r#"{"threshold": 2}"#, 2.0)]
+        {
+            let path = dir.path().join("answers.json");
+            fs::write(&path, content).unwrap();
+            let vals = load_values_from_file(&tpl, &path, None, &HashMap::new()).unwrap();
+            assert_eq!(vals["threshold"], Value::Float(expected));
+        }
+    }
+
+    #[test]
+    fn errors_on_invalid_input_files() {
+        let inputs = vec![
+            ("answers.json", r#"["project_name"]"#),
+            ("answers.json", r#"{"project_name": {"nested": true}}"#),
+            ("answers.json", r#"{"project_name": 1.5}"#),
+            ("answers.json", r#"{"project_name": null}"#),
+            ("answers.json", r#"{"project_name": 1}"#),
+            ("answers.yaml", "project_name: [a, b]"),
+            ("answers.yaml", "project_name: ~"),
+            ("answers.toml", "project_name = 1.5"),
+            ("answers.toml", "project_name = 1979-05-27T07:32:00Z"),
+            ("answers.toml", "project_name = ['a']"),
+            ("answers.toml", "[project_name]\na = 1"),
+            ("answers.json", r#"{"features": [["ci"]]}"#),
+            // Missing required variables
+            ("answers.yaml", "project_name: hello"),
+        ];
+
+        for (filename, content) in inputs {
+            assert!(load(filename, content).is_err(), "{content} should have failed");
+        }
+    }
+
+    #[test]
+    fn values_need_to_be_among_the_choices() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            r#"
+name = "Choices"
+kickstart_version = 1
+
+[[variables]]
+name = "port"
+default = 3000
+prompt = "Port?"
+choices = [3000, 8080, 8443]
+
+[[variables]]
+name = "features"
+default = ["ci"]
+prompt = "Features?"
+choices = ["ci", { value = "docker", label = "Docker" }]
+multi = true
+"#,
+        )
+        .unwrap();
+        let tpl = Template::from_input(dir.path().to_str().unwrap(), None).unwrap();
+        let path = dir.path().join("answers.json");
+        let load = |content: &str| {
+            fs::write(&path, content).unwrap();
+            load_values_from_file(&tpl, &path, None, &HashMap::new())
+        };
+
+        let vals = load(r#"{"port": 8080, "features": ["docker"]}"#).unwrap();
+        assert_eq!(vals["port"], Value::Integer(8080));
+        let err = load(r#"{"port": 1234, "features": ["ci", "Docker"]}"#).unwrap_err();
+        match err.kind {
+            ErrorKind::ConstraintViolations(ref violations) => assert_eq!(
+                violations,
+                &[
+                    (
+                        "port".to_string(),
+                        "The value `1234` is not one of the choices: 3000, 8080, 8443".to_string()
+                    ),
+                    (
+                        "features".to_string(),
+                        "The value `Docker` is not one of the choices: ci, docker".to_string()
+                    ),
+                ]
+            ),
+            _ => panic!("unexpected error {err}"),
+        }
+    }
+
+    #[test]
+    fn optional_variables_can_be_left_out() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            r#"
+name = "Optional"
+kickstart_version = 1
+
+[[variables]]
+name = "homepage"
+default = ""
+prompt = "Homepage?"
+required = false
+validation = "url"
+"#,
+        )
+        .unwrap();
+        let tpl = Template::from_input(dir.path().to_str().unwrap(), None).unwrap();
+        let path = dir.path().join("answers.json");
+        let load = |content: &str| {
+            fs::write(&path, content).unwrap();
+            load_values_from_file(&tpl, &path, None, &HashMap::new())
+        };
+
+        assert_eq!(load("{}").unwrap()["homepage"], Value::String(String::new()));
+        assert_eq!(load(r#"{"homepage": ""}"#).unwrap()["homepage"], Value::String(String::new()));
+        assert!(load(r#"{"homepage": "https://example.com"}"#).is_ok());
+        assert!(load(r#"{"homepage": "example"}"#).is_err());
+    }
+
+    #[test]
+    fn only_asks_the_variables_whose_conditions_are_met() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            r#"
+name = "Conditions"
+kickstart_version = 1
+
+[[variables]]
+name = "workers"
+default = 1
+prompt = "How many workers?"
+
+[[variables]]
+name = "queue"
+default = "redis"
+prompt = "Which queue?"
+only_if = { name = "workers", gt = 1 }
+
+[[variables]]
+name = "dead_letters"
+default = false
+prompt = "Keep failed jobs?"
+only_if = { all_of = [{ name = "workers", gt = 1 }, { not = { name = "queue", value = "sqs" } }] }
+"#,
+        )
+        .unwrap();
+        let tpl = Template::from_input(dir.path().to_str().unwrap(), None).unwrap();
+        let path = dir.path().join("answers.json");
+        let load = |content: &str| {
+            fs::write(&path, content).unwrap();
+            load_values_from_file(&tpl, &path, None, &HashMap::new())
+        };
+
+        assert_eq!(load(r#"{"workers": 1}"#).unwrap().len(), 1);
+        assert_eq!(load(r#"{"workers": 2, "queue": "sqs"}"#).unwrap().len(), 2);
+        let err = load(r#"{"workers": 2, "queue": "redis"}"#).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::MissingVariable(ref name) if name == "dead_letters"));
+    }
+}
@@ -0,0 +1,213 @@
+//! What happens when the CLI is interrupted, eg by Ctrl-C: the hooks being run are killed and
+//! waiting for the user to answer a prompt errors, so the main thread stops at its next step.
+//! What it created temporarily is removed on the way out, when it goes out of scope, and the
+//! files kept in the output directory are listed so the user can decide what to do with them.
+//! Nothing is removed from the thread handling the signal as the main thread could be using it.
+use std::fs;
+#[cfg(unix)]
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::cli::terminal;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// The end of a pipe that becomes readable once interrupted, for [wait_for_stdin] to stop
+/// waiting
+static WAKE: AtomicI32 = AtomicI32::new(-1);
+
+struct Registry {
+    /// The pids of the hooks being run, negated if it is the leader of its own process group
+    /// so the whole group gets killed
+    hooks: Vec<i32>,
+    /// The files and directories created in the output directory, along with the id of the
+    /// [ListOnInterrupt] they come from
+    created: Vec<(usize, PathBuf)>,
+    next_id: usize,
+}
+
+static REGISTRY: Mutex<Registry> =
+    Mutex::new(Registry { hooks: Vec::new(), created: Vec::new(), next_id: 0 });
+
+fn registry() -> MutexGuard<'static, Registry> {
+    // Nothing can be left half-updated, the data is still fine after a panic
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Whether the process was interrupted
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Stop cleanly when interrupted, see the module documentation. Ctrl-C kills the process as
+/// usual on other platforms than Unix.
+pub fn handle_interrupts() {
+    #[cfg(unix)]
+    {
+        // The handler only writes to a pipe, which is safe in a signal handler, and a thread
+        // waiting on the other end does everything else
+        static PIPE: AtomicI32 = AtomicI32::new(-1);
+        extern "C" fn on_signal(_: libc::c_int) {
+            let byte = 1u8;
+            unsafe {
+                libc::write(PIPE.load(Ordering::SeqCst), &byte as *const u8 as *const _, 1);
+            }
+        }
+
+        let (mut signals, mut wake) = ([0; 2], [0; 2]);
+        if unsafe { libc::pipe(signals.as_mut_ptr()) } != 0
+            || unsafe { libc::pipe(wake.as_mut_ptr()) } != 0
+        {
+            return;
+        }
+        PIPE.store(signals[1], Ordering::SeqCst);
+        WAKE.store(wake[0], Ordering::SeqCst);
+        terminal::save_state();
+        std::thread::spawn(move || {
+            let mut byte = 0u8;
+            while unsafe { libc::read(signals[0], &mut byte as *mut u8 as *mut _, 1) } != 1 {}
+            INTERRUPTED.store(true, Ordering::SeqCst);
+            terminal::restore();
+            // Never read so it stays readable
+            unsafe {
+                libc::write(wake[1], &byte as *const u8 as *const _, 1);
+            }
+            for pid in &registry().hooks {
+                unsafe {
+                    libc::kill(*pid, libc::SIGKILL);
+                }
+            }
+        });
+        unsafe {
+            libc::signal(libc::SIGINT, on_signal as *const () as libc::sighandler_t);
+        }
+    }
+}
+
+/// Wait until stdin can be read, at most `timeout_ms` if set. Returns whether it can, an error
+/// meaning the process was interrupted.
+#[cfg(unix)]
+pub fn wait_for_stdin(timeout_ms: Option<i32>) -> io::Result<bool> {
+    let mut fds = [
+        libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 },
+        libc::pollfd { fd: WAKE.load(Ordering::SeqCst), events: libc::POLLIN, revents: 0 },
+    ];
+    loop {
+        let res = unsafe { libc::poll(fds.as_mut_ptr(), 2, timeout_ms.unwrap_or(-1)) };
+        if res < 0 && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+            continue;
+        }
+        if fds[1].revents != 0 {
+            return Err(io::Error::other("interrupted"));
+        }
+        return if res < 0 { Err(io::Error::last_os_error()) } else { Ok(res > 0) };
+    }
+}
+
+/// Block the thread until the process is interrupted, which never happens if interruptions are
+/// not handled
+pub fn wait_for_interrupt() {
+    #[cfg(unix)]
+    {
+        let mut fd =
+            libc::pollfd { fd: WAKE.load(Ordering::SeqCst), events: libc::POLLIN, revents: 0 };
+        if fd.fd >= 0 {
+            while unsafe { libc::poll(&mut fd, 1, -1) } <= 0 || fd.revents == 0 {}
+            return;
+        }
+    }
+    loop {
+        std::thread::park();
+    }
+}
+
+/// Kills the hook if interrupted until it is dropped, once the hook exited
+pub struct KillOnInterrupt {
+    pid: i32,
+}
+
+impl KillOnInterrupt {
+    /// `group` is whether the hook is the leader of its own process group, in which case the
+    /// whole group is killed
+    pub fn new(pid: u32, group: bool) -> Self {
+        let pid = if group { -(pid as i32) } else { pid as i32 };
+        let mut registry = registry();
+        // Started as the process was being interrupted
+        #[cfg(unix)]
+        if is_interrupted() {
+            unsafe {
+                libc::kill(pid, libc::SIGKILL);
+            }
+        }
+        registry.hooks.push(pid);
+        KillOnInterrupt { pid }
+    }
+}
+
+impl Drop for KillOnInterrupt {
+    fn drop(&mut self) {
+        registry().hooks.retain(|p| *p != self.pid);
+    }
+}
+
+/// Lists what was created in the output directory if interrupted until it is dropped, see
+/// [created]
+pub struct ListOnInterrupt {
+    id: usize,
+}
+
+impl ListOnInterrupt {
+    pub fn new(created: &[PathBuf]) -> Self {
+        let mut registry = registry();
+        let id = registry.next_id;
+        registry.next_id += 1;
+        registry.created.extend(created.iter().map(|path| (id, path.clone())));
+        ListOnInterrupt { id }
+    }
+}
+
+impl Drop for ListOnInterrupt {
+    fn drop(&mut self) {
+        // Going out of scope because of the interruption, they are listed afterwards
+        if is_interrupted() {
+            return;
+        }
+        registry().created.retain(|(id, _)| *id != self.id);
+    }
+}
+
+/// What was created in the output directory and still exists, in the order it was created.
+/// What is in a directory that was created is not included.
+pub fn created() -> Vec<PathBuf> {
+    let mut created: Vec<PathBuf> = Vec::new();
+    for (_, path) in &registry().created {
+        if fs::symlink_metadata(path).is_ok() && !created.iter().any(|c| path.starts_with(c)) {
+            created.push(path.clone());
+        }
+    }
+    created
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn lists_what_was_created_until_dropped() {
+        let dir = tempdir().unwrap();
+        let (nested, file) = (dir.path().join("src"), dir.path().join("README.md"));
+        fs::create_dir_all(nested.join("bin")).unwrap();
+        fs::write(&file, "hello").unwrap();
+
+        let first = ListOnInterrupt::new(&[file.clone(), dir.path().join("rolled-back")]);
+        let second = ListOnInterrupt::new(&[nested.clone(), nested.join("bin")]);
+        assert_eq!(created(), [file.clone(), nested.clone()]);
+        drop(first);
+        assert_eq!(created(), [nested]);
+        drop(second);
+        assert!(created().is_empty());
+    }
+}
@@ -0,0 +1,95 @@
+//! Shows the logs of kickstart on stderr, eg which git commands are run and why a file was not
+//! generated, and keeps them in a file if asked to, eg to attach them to a bug report.
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::errors::{map_io_err, Result};
+
+/// What is logged to the file at least, whatever is shown
+const FILE_LEVEL: LevelFilter = LevelFilter::Debug;
+
+struct Logger {
+    /// What is shown on stderr
+    level: LevelFilter,
+    file: Option<Mutex<File>>,
+}
+
+impl Logger {
+    fn file_level(&self) -> LevelFilter {
+        match self.file {
+            Some(_) => self.level.max(FILE_LEVEL),
+            None => LevelFilter::Off,
+        }
+    }
+}
+
+/// Only the events of kickstart are verbose, the dependencies only get to warn
+fn max_level_for(target: &str, level: LevelFilter) -> LevelFilter {
+    if target == "kickstart" || target.starts_with("kickstart::") {
+        level
+    } else {
+        level.min(LevelFilter::Warn)
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= max_level_for(metadata.target(), self.level.max(self.file_level()))
+    }
+
+    fn log(&self, record: &Record) {
+        let target = record.target();
+        let line = format!("{:<5} {target}: {}", record.level(), record.args());
+        if record.level() <= max_level_for(target, self.level) {
+            eprintln!("{line}");
+        }
+        if let Some(ref file) = self.file {
+            if record.level() <= max_level_for(target, self.file_level()) {
+                let now = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z");
+                let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+                let _ = writeln!(file, "{now} {line}");
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(ref file) = self.file {
+            let _ = file.lock().unwrap_or_else(|e| e.into_inner()).flush();
+        }
+    }
+}
+
+/// Show the logs up to `level` on stderr and write them to `log_file` as well if given, where
+/// the debug logs are always included. Can only be called once.
+pub fn init(level: LevelFilter, log_file: Option<&Path>) -> Result<()> {
+    let file = match log_file {
+        Some(path) => Some(Mutex::new(map_io_err(
+            OpenOptions::new().create(true).append(true).open(path),
+            path,
+        )?)),
+        None => None,
+    };
+    let logger = Logger { level, file };
+    log::set_max_level(logger.level.max(logger.file_level()));
+    // Only fails if called twice, the first logger is kept then
+    let _ = log::set_logger(Box::leak(Box::new(logger)));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_kickstart_is_verbose() {
+        assert_eq!(max_level_for("kickstart", LevelFilter::Trace), LevelFilter::Trace);
+        assert_eq!(max_level_for("kickstart::generation", LevelFilter::Debug), LevelFilter::Debug);
+        assert_eq!(max_level_for("kickstartx", LevelFilter::Debug), LevelFilter::Warn);
+        assert_eq!(max_level_for("globset", LevelFilter::Trace), LevelFilter::Warn);
+        assert_eq!(max_level_for("globset", LevelFilter::Error), LevelFilter::Error);
+    }
+}
@@ -1,2 +1,7 @@
+pub mod config;
+pub mod input;
+pub mod interrupt;
+pub mod logger;
 pub mod prompt;
+pub mod report;
 pub mod terminal;
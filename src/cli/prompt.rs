@@ -1,123 +1,739 @@
-use std::io::{self, BufRead, Write};
+use std::io::{self, IsTerminal, Write};
+use std::str;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use crate::cli::terminal;
+#[cfg(unix)]
+use crate::cli::interrupt;
+use crate::cli::terminal::{self, Key, RawMode};
 use crate::errors::{new_error, ErrorKind, Result};
-use crate::Value;
-use regex::Regex;
+use crate::validation::{check_items, check_range, describe_items, describe_range};
+use crate::{Choice, Conflict, ConflictResolution, Value, Variable};
+
+/// Wait for user input and return what they typed.
+/// It is read a byte at a time from the file descriptor so an interruption stops the wait, see
+/// [interrupt::wait_for_stdin], and nothing after the line is taken from stdin.
+#[cfg(unix)]
+fn read_line() -> Result<String> {
+    let unreadable = || new_error(ErrorKind::UnreadableStdin);
+    let mut line = Vec::new();
+    loop {
+        interrupt::wait_for_stdin(None).map_err(|_| unreadable())?;
+        let mut byte = 0u8;
+        match unsafe { libc::read(libc::STDIN_FILENO, &mut byte as *mut u8 as *mut _, 1) } {
+            1 if byte == b'\n' => break,
+            1 => line.push(byte),
+            0 if !line.is_empty() => break,
+            0 => return Err(unreadable()),
+            _ if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+            _ => return Err(unreadable()),
+        }
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
 
 /// Wait for user input and return what they typed
+#[cfg(not(unix))]
 fn read_line() -> Result<String> {
+    use std::io::BufRead;
     let stdin = io::stdin();
     let stdin = stdin.lock();
     let mut lines = stdin.lines();
     lines.next().and_then(|l| l.ok()).ok_or_else(|| new_error(ErrorKind::UnreadableStdin))
 }
 
-/// Ask a yes/no question to the user
-pub fn ask_bool(prompt: &str, default: bool) -> Result<bool> {
-    terminal::bool_question(prompt, default);
-    let _ = io::stdout().flush();
-    let input = read_line()?;
+/// How many invalid answers in a row we accept for a question before giving up
+pub const DEFAULT_MAX_ATTEMPTS: usize = 5;
+
+static SIMPLE_PROMPTS: AtomicBool = AtomicBool::new(false);
+
+/// Always ask for the number of a choice instead of letting the user move through them with
+/// the arrows, eg for terminals that don't support it
+pub fn set_simple_prompts(simple: bool) {
+    SIMPLE_PROMPTS.store(simple, Ordering::Relaxed);
+}
+
+static ASSUME_TTY: AtomicBool = AtomicBool::new(false);
+
+/// Consider stdin is a terminal even if it doesn't look like one, eg in terminals where the
+/// detection is wrong
+pub fn set_assume_tty(assume: bool) {
+    ASSUME_TTY.store(assume, Ordering::Relaxed);
+}
+
+/// Whether questions can be asked: stdin has to be a terminal, unless [set_assume_tty] was
+/// called
+pub fn is_interactive() -> bool {
+    ASSUME_TTY.load(Ordering::Relaxed) || io::stdin().is_terminal()
+}
 
-    let res = match &*input {
-        "y" | "Y" | "yes" | "YES" | "true" => true,
-        "n" | "N" | "no" | "NO" | "false" => false,
-        "" => default,
-        _ => {
-            terminal::error(&format!("Invalid choice: '{}'\n", input));
-            ask_bool(prompt, default)?
+/// Errors with [ErrorKind::NotInteractive] if questions can't be asked, see [is_interactive]
+pub fn ensure_interactive() -> Result<()> {
+    if is_interactive() {
+        Ok(())
+    } else {
+        Err(new_error(ErrorKind::NotInteractive))
+    }
+}
+
+/// Show one of the options of a choice question, followed by its description if it has one
+fn show_choice(line: &str, choice: &Choice) {
+    terminal::bold(line);
+    if let Some(description) = choice.description() {
+        terminal::dim(&format!(" - {description}"));
+    }
+    terminal::bold("\n");
+}
+
+/// Whether all the characters of the query are in the text in the same order, ignoring case,
+/// eg `pgsql` matches `PostgreSQL`
+fn fuzzy_match(query: &str, text: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    query.chars().flat_map(char::to_lowercase).all(|q| text.any(|t| t == q))
+}
+
+/// Cut the text to that many characters
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+/// How many choices the selector shows at most, scrolling to show the others
+const SELECTOR_ROWS: usize = 10;
+
+/// What the selector ended with
+#[derive(Debug, PartialEq)]
+enum Selection {
+    Picked(usize),
+    /// The choices selected with space are the answer, when any number of them can be picked
+    Accepted,
+    Cancelled,
+}
+
+/// The state of the selector used by [Prompter::ask_choices] in a terminal: the choices matching what
+/// was typed so far, with one of them highlighted
+struct Selector<'a> {
+    choices: &'a [Choice],
+    filter: String,
+    /// Whether characters go in the filter rather than `j` and `k` moving, which is the case
+    /// once anything else is typed or after `/`
+    filtering: bool,
+    /// The indices of the choices matching the filter
+    matches: Vec<usize>,
+    /// The position of the highlighted choice in `matches`
+    current: usize,
+    /// Whether each choice is selected, if any number of them can be picked
+    selected: Option<Vec<bool>>,
+    /// The help of the question, shown under it
+    help: Option<&'a str>,
+}
+
+impl<'a> Selector<'a> {
+    fn new(choices: &'a [Choice], default_index: usize) -> Self {
+        Selector {
+            choices,
+            filter: String::new(),
+            filtering: false,
+            matches: (0..choices.len()).collect(),
+            current: default_index,
+            selected: None,
+            help: None,
         }
-    };
-
-    Ok(res)
-}
-
-/// Ask a question to the user where they can write any string
-pub fn ask_string(prompt: &str, default: &str, validation: &Option<String>) -> Result<String> {
-    terminal::basic_question(prompt, &default, validation);
-    let _ = io::stdout().flush();
-    let input = read_line()?;
-
-    let res = match &*input {
-        "" => default.to_string(),
-        _ => {
-            if let Some(ref pattern) = validation {
-                let re = Regex::new(pattern).unwrap();
-                if re.is_match(&input) {
-                    input
-                } else {
-                    terminal::error(&format!("The value needs to pass the regex: {}\n", pattern));
-                    ask_string(prompt, default, validation)?
+    }
+
+    /// A selector where any number of choices can be picked, the ones in `default` being
+    /// selected to begin with
+    fn new_multi(choices: &'a [Choice], default: &[Value]) -> Self {
+        let selected = choices.iter().map(|c| default.contains(c.value())).collect();
+        Selector { selected: Some(selected), ..Selector::new(choices, 0) }
+    }
+
+    /// Filter the choices again, keeping the same one highlighted if it still matches
+    fn update_matches(&mut self) {
+        let highlighted = self.matches.get(self.current).copied();
+        self.matches = (0..self.choices.len())
+            .filter(|i| fuzzy_match(&self.filter, &self.choices[*i].label()))
+            .collect();
+        self.current =
+            highlighted.and_then(|h| self.matches.iter().position(|m| *m == h)).unwrap_or(0);
+    }
+
+    fn press(&mut self, key: Key) -> Option<Selection> {
+        match key {
+            Key::Enter if self.selected.is_some() => return Some(Selection::Accepted),
+            Key::Char(' ') if self.selected.is_some() => {
+                if let (Some(selected), Some(&index)) =
+                    (&mut self.selected, self.matches.get(self.current))
+                {
+                    selected[index] = !selected[index];
                 }
+            }
+            Key::Enter => return self.matches.get(self.current).map(|i| Selection::Picked(*i)),
+            Key::Escape => return Some(Selection::Cancelled),
+            Key::Up => self.current = self.current.saturating_sub(1),
+            Key::Down => {
+                self.current = (self.current + 1).min(self.matches.len().saturating_sub(1))
+            }
+            Key::Char('k') if !self.filtering => self.current = self.current.saturating_sub(1),
+            Key::Char('j') if !self.filtering => return self.press(Key::Down),
+            Key::Char('/') if !self.filtering => self.filtering = true,
+            Key::Char(c) => {
+                self.filtering = true;
+                self.filter.push(c);
+                self.update_matches();
+            }
+            Key::Backspace => {
+                self.filter.pop();
+                self.update_matches();
+            }
+            Key::Other => (),
+        }
+        None
+    }
+
+    /// The lines to show, none of them longer than `width`, styled if `colors` is `true`
+    fn lines(&self, prompt: &str, width: usize, colors: bool) -> Vec<String> {
+        let style = |code: &str, text: String| {
+            if colors {
+                format!("\x1b[{code}m{text}\x1b[0m")
             } else {
-                input
+                text
+            }
+        };
+        let width = width.saturating_sub(1).max(10);
+        let mut lines = vec![style("1", truncate(&format!("{prompt}: {}", self.filter), width))];
+        if let Some(help) = self.help {
+            for line in terminal::wrap(help, width.saturating_sub(2)) {
+                lines.push(style("2", format!("  {line}")));
             }
         }
-    };
 
-    Ok(res)
+        let start = (self.current + 1).saturating_sub(SELECTOR_ROWS);
+        for (pos, &index) in self.matches.iter().enumerate().skip(start).take(SELECTOR_ROWS) {
+            let choice = &self.choices[index];
+            let marker = if pos == self.current { ">" } else { " " };
+            let mark = match self.selected {
+                Some(ref selected) if selected[index] => "[x] ",
+                Some(_) => "[ ] ",
+                None => "",
+            };
+            let label = truncate(&format!("{marker} {mark}{}", choice.label()), width);
+            let description = choice
+                .description()
+                .map(|d| truncate(&format!(" - {d}"), width - label.chars().count()))
+                .filter(|d| !d.is_empty())
+                .map(|d| style("2", d))
+                .unwrap_or_default();
+            let label = if pos == self.current { style("1;36", label) } else { label };
+            lines.push(format!("{label}{description}"));
+        }
+        if self.matches.is_empty() {
+            lines.push(style("2", "  No choice matches".to_string()));
+        }
+
+        let action = if self.selected.is_some() {
+            "space to toggle, enter to accept"
+        } else {
+            "enter to pick"
+        };
+        let hint = format!(
+            "  {}/{}, arrows or j/k to move, type to filter, {action}, esc to cancel",
+            self.matches.len(),
+            self.choices.len()
+        );
+        lines.push(style("2", truncate(&hint, width)));
+        lines
+    }
 }
 
-/// Ask a question to the user where they can write an integer
-pub fn ask_integer(prompt: &str, default: i64) -> Result<i64> {
-    terminal::basic_question(prompt, &default, &None);
-    let _ = io::stdout().flush();
-    let input = read_line()?;
+/// Let the user pick a choice by moving through them, redrawing the list after each key.
+/// The list is cleared once they are done.
+fn select(raw: &RawMode, prompt: &str, selector: &mut Selector) -> Result<Selection> {
+    let mut drawn = 0;
+    let mut stdout = io::stdout();
+    // Go back to the first line drawn, clearing everything after it
+    let clear =
+        |drawn: usize| if drawn > 0 { format!("\x1b[{drawn}A\r\x1b[J") } else { String::new() };
 
-    let res = match &*input {
-        "" => default,
-        _ => match input.parse::<i64>() {
-            Ok(i) => i,
-            Err(_) => {
-                terminal::error(&format!("Invalid integer: '{}'\n", input));
-                ask_integer(prompt, default)?
-            }
-        },
-    };
+    loop {
+        let lines = selector.lines(prompt, terminal::width(), terminal::use_colors(false));
+        let _ = writeln!(stdout, "{}{}", clear(drawn), lines.join("\n"));
+        let _ = stdout.flush();
+        drawn = lines.len();
+
+        let key = raw.read_key().map_err(|_| new_error(ErrorKind::UnreadableStdin))?;
+        let Some(selection) = selector.press(key) else {
+            continue;
+        };
+        let _ = write!(stdout, "{}", clear(drawn));
+        let _ = stdout.flush();
+        return match selection {
+            Selection::Cancelled => Err(new_error(ErrorKind::Aborted)),
+            selection => Ok(selection),
+        };
+    }
+}
 
-    Ok(res)
+/// Asks the questions, giving up on one after too many invalid answers in a row
+#[derive(Debug, Clone)]
+pub struct Prompter {
+    max_attempts: usize,
+    help: Option<String>,
 }
 
-/// Ask users to make a choice between various options
-pub fn ask_choices(prompt: &str, default: &Value, choices: &[Value]) -> Result<Value> {
-    terminal::bold(&format!("{}: \n", prompt));
-    let mut lines = vec![];
-    let mut default_index = 1;
+impl Default for Prompter {
+    fn default() -> Self {
+        Prompter { max_attempts: DEFAULT_MAX_ATTEMPTS, help: None }
+    }
+}
 
-    for (index, choice) in choices.iter().enumerate() {
-        terminal::bold(&format!("  {}. {}\n", index + 1, choice.as_str().unwrap()));
+impl Prompter {
+    /// Accept that many invalid answers in a row for a question before aborting, at least 1
+    pub fn new(max_attempts: usize) -> Self {
+        Prompter { max_attempts: max_attempts.max(1), help: None }
+    }
 
-        lines.push(format!("{}", index + 1));
-        if choice == default {
-            default_index = index + 1;
+    /// The same prompter showing that help text under the next questions
+    pub fn with_help(&self, help: Option<&str>) -> Self {
+        Prompter { help: help.map(str::to_string), ..self.clone() }
+    }
+
+    /// Show the help under a question listing options, if there is one
+    fn show_help(&self) {
+        if let Some(ref help) = self.help {
+            terminal::help(help);
         }
     }
 
-    terminal::basic_question(
-        &format!("  > Choose from {}..{}", 1, lines.len()),
-        &default_index,
-        &None,
-    );
-
-    let _ = io::stdout().flush();
-    let input = read_line()?;
-
-    let res = match &*input {
-        "" => default.clone(),
-        _ => {
-            if let Ok(num) = input.parse::<usize>() {
-                if num > choices.len() {
-                    terminal::error(&format!("Invalid choice: '{}'\n", input));
-                    ask_choices(prompt, default, choices)?
-                } else {
-                    choices[num - 1].clone()
+    /// Show the help under a one-line question, if there is one, the answer being typed after
+    /// it rather than next to the question
+    fn show_help_before_answer(&self) {
+        if self.help.is_some() {
+            println!();
+            self.show_help();
+            terminal::bold("  > ");
+        }
+    }
+
+    /// Keep asking until `attempt` gets a valid answer, showing the error message in between.
+    /// Errors once the maximum number of attempts is reached, or right away if it is not
+    /// interactive.
+    fn retry<T>(
+        &self,
+        mut attempt: impl FnMut() -> Result<std::result::Result<T, String>>,
+    ) -> Result<T> {
+        ensure_interactive()?;
+        let max = self.max_attempts;
+        for _ in 0..max {
+            match attempt()? {
+                Ok(val) => return Ok(val),
+                Err(e) => terminal::error(&format!("{}\n", e)),
+            }
+        }
+        Err(new_error(ErrorKind::TooManyAttempts(max)))
+    }
+
+    /// Ask a yes/no question to the user
+    pub fn ask_bool(&self, prompt: &str, default: bool) -> Result<bool> {
+        self.retry(|| {
+            terminal::bool_question(prompt, default);
+            self.show_help_before_answer();
+            let _ = io::stdout().flush();
+            let input = read_line()?;
+
+            Ok(match &*input {
+                "y" | "Y" | "yes" | "YES" | "true" => Ok(true),
+                "n" | "N" | "no" | "NO" | "false" => Ok(false),
+                "" => Ok(default),
+                _ => Err(format!("Invalid choice: '{}'", input)),
+            })
+        })
+    }
+
+    /// Ask the question of a variable where the user can write any string, which needs to pass
+    /// the constraints of the variable
+    pub fn ask_string(&self, prompt: &str, var: &Variable, default: &str) -> Result<String> {
+        let hint = var.string_hint();
+        let prompt = if var.required { prompt.to_string() } else { format!("{prompt} (optional)") };
+        self.retry(|| {
+            terminal::basic_question(&prompt, &default, &hint);
+            self.show_help_before_answer();
+            let _ = io::stdout().flush();
+            let input = read_line()?;
+
+            Ok(match &*input {
+                "" => Ok(default.to_string()),
+                _ => var.check_string(&input).map(|_| input),
+            })
+        })
+    }
+
+    /// Ask for the entries of a list variable, one per line until an empty one. Each entry is
+    /// checked against the `validation` and length of the variable and asked again if it doesn't
+    /// pass. Entering nothing keeps the default.
+    pub fn ask_list(&self, prompt: &str, var: &Variable, default: &[Value]) -> Result<Vec<Value>> {
+        let hint = match (describe_items(var.min_items, var.max_items), var.string_hint()) {
+            (Some(items), Some(entries)) => Some(format!("{items}, each {entries}")),
+            (items, entries) => items.or(entries),
+        };
+        let prompt = format!("{prompt} (one per line, an empty line to finish)");
+        let default_list = Value::List(default.to_vec());
+        self.retry(|| {
+            terminal::basic_question(&prompt, &default_list, &hint);
+            println!();
+            self.show_help();
+            let mut entries = Vec::new();
+            loop {
+                print!("  - ");
+                let _ = io::stdout().flush();
+                let input = read_line()?;
+                if input.is_empty() {
+                    break;
                 }
+                match var.check_string(&input) {
+                    Ok(()) => entries.push(Value::String(input)),
+                    Err(e) => terminal::error(&format!("{e}\n")),
+                }
+            }
+            let entries = if entries.is_empty() { default.to_vec() } else { entries };
+            Ok(check_items(entries.len(), var.min_items, var.max_items).map(|_| entries))
+        })
+    }
+
+    /// Ask the question of a variable without echoing what they type, eg for a password.
+    /// There is no default: an empty answer is kept as is if the constraints allow it.
+    pub fn ask_secret(&self, prompt: &str, var: &Variable) -> Result<String> {
+        self.retry(|| {
+            let optional = if var.required { "" } else { ", optional" };
+            terminal::bold(&format!("{prompt} (hidden{optional}): "));
+            self.show_help_before_answer();
+            let _ = io::stdout().flush();
+            #[cfg(unix)]
+            let input = if io::stdin().is_terminal() {
+                terminal::without_echo(read_line)?
             } else {
-                terminal::error(&format!("Invalid choice: '{}'\n", input));
-                ask_choices(prompt, default, choices)?
+                read_line()?
+            };
+            #[cfg(not(unix))]
+            let input = if io::stdin().is_terminal() {
+                rpassword::read_password().map_err(|_| new_error(ErrorKind::UnreadableStdin))?
+            } else {
+                read_line()?
+            };
+
+            Ok(var.check_string(&input).map(|_| input))
+        })
+    }
+
+    /// Ask a question to the user where they can write an integer, within the inclusive bounds
+    /// if there are some
+    pub fn ask_integer(
+        &self,
+        prompt: &str,
+        default: i64,
+        min: Option<i64>,
+        max: Option<i64>,
+    ) -> Result<i64> {
+        let range = describe_range(min, max);
+        self.retry(|| {
+            terminal::basic_question(prompt, &default, &range);
+            self.show_help_before_answer();
+            let _ = io::stdout().flush();
+            let input = read_line()?;
+
+            Ok(match &*input {
+                "" => Ok(default),
+                _ => input
+                    .parse::<i64>()
+                    .map_err(|_| format!("Invalid integer: '{}'", input))
+                    .and_then(|i| check_range(i, min, max).map(|_| i)),
+            })
+        })
+    }
+
+    /// Ask a question to the user where they can write a number, with or without decimals
+    pub fn ask_float(&self, prompt: &str, default: f64) -> Result<f64> {
+        self.retry(|| {
+            terminal::basic_question(prompt, &Value::Float(default), &None);
+            self.show_help_before_answer();
+            let _ = io::stdout().flush();
+            let input = read_line()?;
+
+            Ok(match &*input {
+                "" => Ok(default),
+                _ => match input.parse::<f64>() {
+                    Ok(f) if f.is_finite() => Ok(f),
+                    _ => Err(format!("Invalid number: '{}'", input)),
+                },
+            })
+        })
+    }
+
+    /// Ask users to make a choice between various options
+    /// The labels of the choices are shown but their value is returned.
+    /// In a terminal, they move through the choices with the arrows and can type to filter them,
+    /// unless [set_simple_prompts] was called. Otherwise they type the number of a choice.
+    pub fn ask_choices(&self, prompt: &str, default: &Value, choices: &[Choice]) -> Result<Value> {
+        let default_index = choices.iter().position(|c| c.value() == default).unwrap_or(0);
+        if !SIMPLE_PROMPTS.load(Ordering::Relaxed) {
+            if let Some(raw) = RawMode::enable() {
+                let mut selector = Selector {
+                    help: self.help.as_deref(),
+                    ..Selector::new(choices, default_index)
+                };
+                let Selection::Picked(index) = select(&raw, prompt, &mut selector)? else {
+                    unreachable!("only the selectors of multiple choices accept a selection")
+                };
+                // Only the answer is kept on screen, like the other questions
+                terminal::bold(&format!("{prompt}: "));
+                println!("{}", choices[index].label());
+                return Ok(choices[index].value().clone());
+            }
+        }
+
+        self.retry(|| {
+            terminal::bold(&format!("{}: \n", prompt));
+            self.show_help();
+            for (index, choice) in choices.iter().enumerate() {
+                show_choice(&format!("  {}. {}", index + 1, choice.label()), choice);
+            }
+
+            terminal::basic_question(
+                &format!("  > Choose from {}..{}", 1, choices.len()),
+                &(default_index + 1),
+                &None,
+            );
+
+            let _ = io::stdout().flush();
+            let input = read_line()?;
+
+            Ok(match &*input {
+                "" => Ok(default.clone()),
+                _ => match input.parse::<usize>() {
+                    Ok(num) if (1..=choices.len()).contains(&num) => {
+                        Ok(choices[num - 1].value().clone())
+                    }
+                    _ => Err(format!("Invalid choice: '{}'", input)),
+                },
+            })
+        })
+    }
+
+    /// Ask users to pick any number of options, the default ones being selected to begin with.
+    /// In a terminal, they move through the choices like with [Prompter::ask_choices], toggle them with
+    /// space and accept the selection with enter. Otherwise typing the number of a choice toggles
+    /// it and an empty line accepts the selection.
+    pub fn ask_multi_choices(
+        &self,
+        prompt: &str,
+        default: &[Value],
+        choices: &[Choice],
+    ) -> Result<Vec<Value>> {
+        if !SIMPLE_PROMPTS.load(Ordering::Relaxed) {
+            if let Some(raw) = RawMode::enable() {
+                let mut selector = Selector {
+                    help: self.help.as_deref(),
+                    ..Selector::new_multi(choices, default)
+                };
+                select(&raw, prompt, &mut selector)?;
+                let picked: Vec<_> = choices
+                    .iter()
+                    .zip(selector.selected.unwrap_or_default())
+                    .filter(|(_, s)| *s)
+                    .map(|(c, _)| c)
+                    .collect();
+                let labels: Vec<_> = picked.iter().map(|c| c.label()).collect();
+                terminal::bold(&format!("{prompt}: "));
+                println!("{}", labels.join(", "));
+                return Ok(picked.into_iter().map(|c| c.value().clone()).collect());
             }
         }
-    };
 
-    Ok(res)
+        let mut selected: Vec<bool> = choices.iter().map(|c| default.contains(c.value())).collect();
+
+        loop {
+            let toggled = self.retry(|| {
+                terminal::bold(&format!("{}: \n", prompt));
+                self.show_help();
+                for (index, choice) in choices.iter().enumerate() {
+                    let mark = if selected[index] { "x" } else { " " };
+                    show_choice(&format!("  {}. [{}] {}", index + 1, mark, choice.label()), choice);
+                }
+                terminal::bold(&format!(
+                    "  > Toggle choices from {}..{} (eg `1 3`), press enter to accept: ",
+                    1,
+                    choices.len()
+                ));
+                let _ = io::stdout().flush();
+                let input = read_line()?;
+
+                Ok(input
+                    .split(|c: char| c.is_whitespace() || c == ',')
+                    .filter(|s| !s.is_empty())
+                    .map(|s| match s.parse::<usize>() {
+                        Ok(num) if (1..=choices.len()).contains(&num) => Ok(num),
+                        _ => Err(format!("Invalid choice: '{}'", input)),
+                    })
+                    .collect::<std::result::Result<Vec<_>, _>>())
+            })?;
+
+            if toggled.is_empty() {
+                break;
+            }
+            for num in toggled {
+                selected[num - 1] = !selected[num - 1];
+            }
+        }
+
+        Ok(choices
+            .iter()
+            .zip(selected)
+            .filter(|(_, s)| *s)
+            .map(|(c, _)| c.value().clone())
+            .collect())
+    }
+
+    /// Ask the user what to do with a file that already exists in the output directory.
+    /// They can look at the diff between the existing file and the generated one before choosing.
+    pub fn ask_conflict(&self, conflict: &Conflict) -> Result<ConflictResolution> {
+        self.retry(|| {
+            terminal::bold(&format!(
+                "`{}` already exists: [o]verwrite, [s]kip, show [d]iff or [a]bort? ",
+                conflict.path.display()
+            ));
+            let _ = io::stdout().flush();
+            let input = read_line()?;
+
+            Ok(match &*input {
+                "o" | "O" | "overwrite" => Ok(ConflictResolution::Overwrite),
+                "s" | "S" | "skip" => Ok(ConflictResolution::Skip),
+                "a" | "A" | "abort" => Ok(ConflictResolution::Abort),
+                "d" | "D" | "diff" => {
+                    match (str::from_utf8(conflict.existing), str::from_utf8(conflict.generated)) {
+                        (Ok(existing), Ok(generated)) => {
+                            let name = conflict.path.display().to_string();
+                            terminal::diff(existing, generated, &name, &format!("{name} (new)"));
+                        }
+                        _ => terminal::error("Binary files differ, no diff to show\n"),
+                    }
+                    return self.ask_conflict(conflict).map(Ok);
+                }
+                _ => Err(format!("Invalid choice: '{}'", input)),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_gives_up_after_max_attempts() {
+        // Nothing is read from stdin, which is not a terminal when testing
+        set_assume_tty(true);
+        let mut answers = vec![Ok(1), Err("no".to_string()), Err("no".to_string())];
+        assert_eq!(Prompter::default().retry(|| Ok(answers.pop().unwrap())).unwrap(), 1);
+
+        let mut calls = 0;
+        let res: Result<()> = Prompter::default().retry(|| {
+            calls += 1;
+            Ok(Err("no".to_string()))
+        });
+        assert!(matches!(res.unwrap_err().kind, ErrorKind::TooManyAttempts(DEFAULT_MAX_ATTEMPTS)));
+        assert_eq!(calls, DEFAULT_MAX_ATTEMPTS);
+
+        let res: Result<()> = Prompter::new(2).retry(|| Ok(Err("no".to_string())));
+        assert!(matches!(res.unwrap_err().kind, ErrorKind::TooManyAttempts(2)));
+    }
+
+    #[test]
+    fn can_fuzzy_match() {
+        assert!(fuzzy_match("", "anything"));
+        assert!(fuzzy_match("pgsql", "PostgreSQL"));
+        assert!(fuzzy_match("ÉTÉ", "été"));
+        assert!(!fuzzy_match("sqlpg", "PostgreSQL"));
+    }
+
+    #[test]
+    fn can_move_through_and_filter_choices() {
+        let choices: Vec<Choice> = ["postgres", "mysql", "sqlite", "kafka"]
+            .map(|c| Value::String(c.to_string()).into())
+            .to_vec();
+        let mut selector = Selector::new(&choices, 1);
+        assert_eq!(selector.press(Key::Down), None);
+        assert_eq!(selector.press(Key::Char('j')), None);
+        assert_eq!(selector.press(Key::Char('j')), None);
+        assert_eq!(selector.current, 3);
+        assert_eq!(selector.press(Key::Char('k')), None);
+        assert_eq!(selector.press(Key::Up), None);
+        assert_eq!(selector.press(Key::Enter), Some(Selection::Picked(1)));
+
+        // Typing keeps the highlighted choice if it still matches
+        selector.press(Key::Char('s'));
+        selector.press(Key::Char('q'));
+        assert_eq!(selector.matches, [1, 2]);
+        assert_eq!(selector.press(Key::Enter), Some(Selection::Picked(1)));
+        // `k` is typed once filtering
+        selector.press(Key::Char('k'));
+        assert!(selector.matches.is_empty());
+        assert_eq!(selector.press(Key::Enter), None);
+        selector.press(Key::Backspace);
+        selector.press(Key::Backspace);
+        selector.press(Key::Backspace);
+        assert_eq!(selector.matches, [0, 1, 2, 3]);
+
+        let selector = Selector::new(&choices, 3);
+        assert_eq!(
+            selector.lines("Database?", 80, false),
+            [
+                "Database?: ",
+                "  postgres",
+                "  mysql",
+                "  sqlite",
+                "> kafka",
+                "  4/4, arrows or j/k to move, type to filter, enter to pick, esc to cancel"
+            ]
+        );
+
+        // The help goes under the question
+        let selector =
+            Selector { help: Some("Where the data is stored"), ..Selector::new(&choices, 0) };
+        assert_eq!(
+            selector.lines("Database?", 20, false)[..3],
+            ["Database?: ", "  Where the data is", "  stored"]
+        );
+
+        let mut selector = Selector::new(&choices, 0);
+        selector.press(Key::Char('/'));
+        selector.press(Key::Char('k'));
+        assert_eq!(selector.press(Key::Enter), Some(Selection::Picked(3)));
+        assert_eq!(selector.press(Key::Escape), Some(Selection::Cancelled));
+    }
+
+    #[test]
+    fn can_toggle_choices_with_space() {
+        let choices: Vec<Choice> =
+            ["ci", "docker", "docs"].map(|c| Value::String(c.to_string()).into()).to_vec();
+        let mut selector = Selector::new_multi(&choices, &[Value::String("docs".to_string())]);
+        assert_eq!(selector.press(Key::Char(' ')), None);
+        selector.press(Key::Down);
+        selector.press(Key::Down);
+        assert_eq!(selector.press(Key::Char(' ')), None);
+        assert_eq!(selector.selected, Some(vec![true, false, false]));
+        assert_eq!(
+            selector.lines("Features?", 100, false),
+            [
+                "Features?: ",
+                "  [x] ci",
+                "  [ ] docker",
+                "> [ ] docs",
+                "  3/3, arrows or j/k to move, type to filter, space to toggle, enter to accept, \
+                 esc to cancel"
+            ]
+        );
+        assert_eq!(selector.press(Key::Enter), Some(Selection::Accepted));
+        assert_eq!(selector.press(Key::Escape), Some(Selection::Cancelled));
+    }
 }
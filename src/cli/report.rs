@@ -0,0 +1,175 @@
+//! What a generation tells the user. By default it is shown as it happens, with `--format json`
+//! it is collected instead and printed as a single JSON document at the end for other tools.
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use serde::Serialize;
+
+use crate::cli::terminal;
+use crate::errors::ErrorKind;
+use crate::{GeneratedFile, Value};
+
+/// A hook that was run, successfully or not
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HookRun {
+    pub name: String,
+    /// `pre-prompt`, `pre-gen` or `post-gen`
+    pub stage: String,
+    /// `None` if it was killed, eg after timing out
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+}
+
+/// Why the run failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReportedError {
+    /// The [crate::errors::ErrorKind] of the error, or `Other` for the errors of the CLI itself
+    pub kind: String,
+    pub message: String,
+}
+
+impl ReportedError {
+    /// `kind` is `None` for the errors of the CLI itself
+    pub fn new(kind: Option<&ErrorKind>, message: String) -> Self {
+        let kind = kind.map_or("Other", ErrorKind::name).to_string();
+        ReportedError { kind, message }
+    }
+}
+
+/// Everything that happened during a run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunReport {
+    pub success: bool,
+    /// Whether nothing was written because of `--dry-run`
+    pub dry_run: bool,
+    pub source: Option<String>,
+    /// The commit checked out if the template was cloned
+    pub commit: Option<String>,
+    pub output_dir: Option<PathBuf>,
+    /// The archive written instead with `--archive`
+    pub archive: Option<PathBuf>,
+    /// The values of the variables, except the secret ones
+    pub variables: BTreeMap<String, Value>,
+    pub files: Vec<GeneratedFile>,
+    pub hooks: Vec<HookRun>,
+    pub warnings: Vec<String>,
+    /// The `post_gen_message` of the template, rendered
+    pub post_gen_message: Option<String>,
+    pub error: Option<ReportedError>,
+}
+
+/// `Some` when collecting instead of showing
+static REPORT: Mutex<Option<RunReport>> = Mutex::new(None);
+
+fn report() -> MutexGuard<'static, Option<RunReport>> {
+    // Nothing can be left half-updated, the data is still fine after a panic
+    REPORT.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Only show the warnings, not the steps of the run nor its details
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Whether the steps of the run are neither shown nor collected
+fn is_silent() -> bool {
+    QUIET.load(Ordering::Relaxed) || is_collecting()
+}
+
+/// Collect what happens to print it with [finish] instead of showing it
+pub fn collect() {
+    *report() = Some(RunReport::default());
+}
+
+/// Whether what happens is collected rather than shown
+pub fn is_collecting() -> bool {
+    report().is_some()
+}
+
+/// Fill in the report, does nothing if it is not being collected
+pub fn update(f: impl FnOnce(&mut RunReport)) {
+    if let Some(ref mut report) = *report() {
+        f(report);
+    }
+}
+
+/// Show a step of the run in bold, not collected
+pub fn bold(message: &str) {
+    if !is_silent() {
+        terminal::bold(message);
+    }
+}
+
+/// Show some details of the run, not collected
+pub fn plain(message: &str) {
+    if !is_silent() {
+        print!("{message}");
+    }
+}
+
+/// Show that something went well, not collected
+pub fn success(message: &str) {
+    if !is_silent() {
+        terminal::success(message);
+    }
+}
+
+/// Show a warning or add it to the report
+pub fn warning(message: &str) {
+    match *report() {
+        Some(ref mut report) => report.warnings.push(message.trim().to_string()),
+        None => terminal::warning(message),
+    }
+}
+
+/// Record that a hook was run
+pub fn hook_ran(hook: HookRun) {
+    update(|report| report.hooks.push(hook));
+}
+
+/// Stop collecting and return the report as JSON, with the error if the run failed.
+/// Returns `None` if the report was not being collected.
+pub fn finish(error: Option<ReportedError>) -> Option<String> {
+    let mut report = report().take()?;
+    report.success = error.is_none();
+    report.error = error;
+    // Only made of strings, numbers and values, it can always be serialized
+    Some(serde_json::to_string_pretty(&report).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::new_error;
+
+    #[test]
+    fn can_collect_a_run() {
+        collect();
+        warning("Skipping hook lint (condition not met)\n");
+        hook_ran(HookRun {
+            name: "lint".to_string(),
+            stage: "post-gen".to_string(),
+            exit_code: Some(2),
+            duration_ms: 12,
+        });
+        update(|r| {
+            r.variables.insert("name".to_string(), Value::Integer(1));
+        });
+        let err = new_error(ErrorKind::InvalidVariableName("nme".to_string()));
+        let error = ReportedError::new(Some(&err.kind), err.to_string());
+        let json: serde_json::Value = serde_json::from_str(&finish(Some(error)).unwrap()).unwrap();
+
+        assert_eq!(json["success"], false);
+        assert_eq!(json["warnings"][0], "Skipping hook lint (condition not met)");
+        assert_eq!(json["hooks"][0]["exit_code"], 2);
+        assert_eq!(json["variables"]["name"], 1);
+        assert_eq!(json["error"]["kind"], "InvalidVariableName");
+        assert_eq!(ReportedError::new(None, "Oops".to_string()).kind, "Other");
+        assert!(!is_collecting());
+        assert_eq!(finish(None), None);
+    }
+}
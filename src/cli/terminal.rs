@@ -1,105 +1,526 @@
+use std::env;
 use std::fmt;
 use std::io::prelude::*;
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-/// Show an error message
-pub fn error(message: &str) {
-    if let Some(mut t) = term::stderr() {
-        match t.fg(term::color::BRIGHT_RED) {
-            Ok(_) => {
-                write!(t, "{}", message).unwrap();
-                t.reset().unwrap();
-                t.flush().unwrap();
-            }
-            Err(_) => writeln!(t, "{}", message).unwrap(),
-        };
-    } else {
-        eprint!("{}", message);
+use clap::ValueEnum;
+
+#[cfg(unix)]
+use crate::cli::interrupt;
+
+/// When to style the output with colors, bold text, etc
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Only when writing to a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+static COLOR_CHOICE: AtomicU8 = AtomicU8::new(ColorChoice::Auto as u8);
+
+/// Change when the output is styled, see [ColorChoice]
+pub fn set_color_choice(choice: ColorChoice) {
+    COLOR_CHOICE.store(choice as u8, Ordering::Relaxed);
+}
+
+/// Whether what is written to stdout, or stderr if `stderr` is `true`, should be styled
+pub fn use_colors(stderr: bool) -> bool {
+    match COLOR_CHOICE.load(Ordering::Relaxed) {
+        c if c == ColorChoice::Always as u8 => true,
+        c if c == ColorChoice::Never as u8 => false,
+        _ => {
+            let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+            let is_terminal =
+                if stderr { io::stderr().is_terminal() } else { io::stdout().is_terminal() };
+            !no_color && is_terminal
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Style {
+    Color(term::color::Color),
+    Attr(term::Attr),
+}
+
+/// Write the message with that style if the terminal supports it
+fn write_styled<T: Write>(t: &mut dyn term::Terminal<Output = T>, style: Style, message: &str) {
+    let styled = match style {
+        Style::Color(c) => t.fg(c),
+        Style::Attr(a) => t.attr(a),
+    }
+    .is_ok();
+    let _ = write!(t, "{}", message);
+    if styled {
+        let _ = t.reset();
     }
+    let _ = t.flush();
+}
+
+/// Write the message on stdout, styled if colors are enabled
+fn print_styled(message: &str, style: Style) {
+    if use_colors(false) {
+        if let Some(mut t) = term::stdout() {
+            write_styled(&mut *t, style, message);
+            return;
+        }
+    }
+    print!("{}", message);
+    let _ = io::stdout().flush();
+}
+
+/// Write the message on stderr, styled if colors are enabled
+fn eprint_styled(message: &str, style: Style) {
+    if use_colors(true) {
+        if let Some(mut t) = term::stderr() {
+            write_styled(&mut *t, style, message);
+            return;
+        }
+    }
+    eprint!("{}", message);
+}
+
+/// Show an error message, on stderr
+pub fn error(message: &str) {
+    eprint_styled(message, Style::Color(term::color::BRIGHT_RED));
+}
+
+/// Show a warning message, on stderr
+pub fn warning(message: &str) {
+    eprint_styled(message, Style::Color(term::color::YELLOW));
 }
 
 /// Show a success message
 pub fn success(message: &str) {
-    if let Some(mut t) = term::stdout() {
-        match t.fg(term::color::BRIGHT_GREEN) {
-            Ok(_) => {
-                write!(t, "{}", message).unwrap();
-                t.reset().unwrap();
-                t.flush().unwrap();
-            }
-            Err(_) => writeln!(t, "{}", message).unwrap(),
-        };
-    } else {
-        eprint!("{}", message);
-    }
+    print_styled(message, Style::Color(term::color::BRIGHT_GREEN));
 }
 
 /// Show a message in bold
 pub fn bold(message: &str) {
-    if let Some(mut t) = term::stdout() {
-        match t.attr(term::Attr::Bold) {
-            Ok(_) => {
-                write!(t, "{}", message).unwrap();
-                t.reset().unwrap();
-                t.flush().unwrap();
+    print_styled(message, Style::Attr(term::Attr::Bold));
+}
+
+/// Show a message in dim text, eg to give details that are less important
+pub fn dim(message: &str) {
+    print_styled(message, Style::Attr(term::Attr::Dim));
+}
+
+/// The number of columns of the terminal, from `COLUMNS` if it can't be asked to the
+/// terminal and 80 if that's not set either
+pub fn width() -> usize {
+    #[cfg(unix)]
+    {
+        // SAFETY: the ioctl only writes in the struct we give it
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0
+            && size.ws_col > 0
+        {
+            return size.ws_col as usize;
+        }
+    }
+    std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()).filter(|c| *c > 0).unwrap_or(80)
+}
+
+/// Split the text in lines of at most `width` characters, breaking between words and keeping
+/// the line breaks already there. Words longer than that are on a line of their own.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut line));
             }
-            Err(_) => write!(t, "{}", message).unwrap(),
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Show the help text of a question, in dim and wrapped to the width of the terminal
+pub fn help(text: &str) {
+    const INDENT: &str = "  ";
+    let width = width().saturating_sub(INDENT.len()).max(20);
+    let lines: Vec<_> = wrap(text, width).into_iter().map(|l| format!("{INDENT}{l}\n")).collect();
+    dim(&lines.concat());
+}
+
+/// Show a unified diff between two texts, coloring added and removed lines
+pub fn diff(old: &str, new: &str, old_name: &str, new_name: &str) {
+    let diff = similar::TextDiff::from_lines(old, new);
+    let unified = diff.unified_diff().header(old_name, new_name).to_string();
+
+    for line in unified.lines() {
+        let color = if line.starts_with("+++") || line.starts_with("---") {
+            None
+        } else if line.starts_with('+') {
+            Some(term::color::GREEN)
+        } else if line.starts_with('-') {
+            Some(term::color::RED)
+        } else if line.starts_with("@@") {
+            Some(term::color::CYAN)
+        } else {
+            None
         };
-    } else {
-        eprint!("{}", message);
+        match color {
+            Some(c) => print_styled(line, Style::Color(c)),
+            None => print!("{}", line),
+        }
+        println!();
     }
 }
 
+/// Show how a file of a project changed, eg `modified: src/main.rs`, colored by status
+pub fn file_status(status: &str, path: &str) {
+    let color = match status {
+        "deleted" => term::color::RED,
+        "modified" => term::color::YELLOW,
+        _ => term::color::CYAN,
+    };
+    print_styled(&format!("{status:>9}"), Style::Color(color));
+    println!(": {path}");
+}
+
 /// Show a basic question with all necessary formatting applied
 pub fn basic_question<T: fmt::Display>(prompt: &str, default: &T, validation: &Option<String>) {
-    if let Some(mut t) = term::stdout() {
-        // check for colour/boldness at the beginning so we can unwrap later
-        if !t.supports_color() || !t.supports_attr(term::Attr::Bold) {
-            if let Some(ref pattern) = validation {
-                write!(t, "{} [default: {}, validation: {}]: ", prompt, default, pattern).unwrap();
-            } else {
-                write!(t, "{} [default: {}]: ", prompt, default).unwrap();
+    bold(&format!("{} ", prompt));
+    let hint = match validation {
+        Some(pattern) => format!("[default: {}, validation: {}]: ", default, pattern),
+        None => format!("[default: {}]: ", default),
+    };
+    print_styled(&hint, Style::Color(term::color::YELLOW));
+}
+
+/// Show a yes/no question with all necessary formatting applied
+pub fn bool_question(prompt: &str, default: bool) {
+    bold(&format!("{} ", prompt));
+    let hint = if default { "[Y/n]: " } else { "[y/N]: " };
+    print_styled(hint, Style::Color(term::color::YELLOW));
+}
+
+/// How long something took, eg `0.4s` or `2m05s`
+pub fn elapsed(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    }
+}
+
+/// Replace the progress line at the bottom of stderr, if it is a terminal
+pub fn progress(message: &str) {
+    if !io::stderr().is_terminal() {
+        return;
+    }
+    let line: String = message.chars().take(width().saturating_sub(1)).collect();
+    let mut stderr = io::stderr();
+    let _ = write!(stderr, "\r\x1b[K{line}");
+    let _ = stderr.flush();
+}
+
+/// Remove the progress line shown by [progress]
+pub fn clear_progress() {
+    progress("");
+}
+
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Shows that something is going on on stderr with the time it has been going on for, eg
+/// while cloning, until it is dropped. Nothing is shown if stderr is not a terminal.
+pub struct Spinner {
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Spinner {
+    pub fn start(message: &str) -> Spinner {
+        let stop = Arc::new(AtomicBool::new(false));
+        if !io::stderr().is_terminal() {
+            return Spinner { stop, thread: None };
+        }
+        let message = message.to_string();
+        let thread = thread::spawn({
+            let stop = stop.clone();
+            move || {
+                let start = Instant::now();
+                for frame in SPINNER_FRAMES.iter().cycle() {
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    progress(&format!("{frame} {message} ({})", elapsed(start.elapsed())));
+                    thread::park_timeout(Duration::from_millis(100));
+                }
+                clear_progress();
             }
-            return;
+        });
+        Spinner { stop, thread: Some(thread) }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
         }
+    }
+}
 
-        t.attr(term::Attr::Bold).unwrap();
-        write!(t, "{} ", prompt).unwrap();
-        t.reset().unwrap();
-        t.fg(term::color::YELLOW).unwrap();
-        if let Some(ref pattern) = validation {
-            write!(t, "[default: {}, validation: {}]: ", default, pattern).unwrap();
-        } else {
-            write!(t, "[default: {}]: ", default).unwrap();
+/// A key pressed while the terminal is in raw mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    Enter,
+    Escape,
+    Backspace,
+    Char(char),
+    /// Any other key or escape sequence, which is ignored
+    Other,
+}
+
+/// Decode a key from its first byte, calling `next` for the following ones.
+/// `next` returns `None` if nothing else was sent, which is how a lone Esc is told apart from
+/// the escape sequences of the arrows.
+fn decode_key(first: u8, mut next: impl FnMut() -> Option<u8>) -> Key {
+    match first {
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Backspace,
+        0x1b => match next() {
+            None => Key::Escape,
+            Some(b'[' | b'O') => match next() {
+                Some(b'A') => Key::Up,
+                Some(b'B') => Key::Down,
+                _ => Key::Other,
+            },
+            Some(_) => Key::Other,
+        },
+        0x00..=0x1f => Key::Other,
+        _ => {
+            let len = match first {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![first];
+            for _ in 1..len {
+                match next() {
+                    Some(b) => bytes.push(b),
+                    None => break,
+                }
+            }
+            match std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()) {
+                Some(c) => Key::Char(c),
+                None => Key::Other,
+            }
         }
-        t.reset().unwrap();
-        t.flush().unwrap();
-    } else {
-        eprint!("{} [default: {}]: ", prompt, default);
     }
 }
 
-/// Show a yes/no question with all necessary formatting applied
-pub fn bool_question(prompt: &str, default: bool) {
-    let default_str = if default { "[Y/n]" } else { "[y/N]" };
-    if let Some(mut t) = term::stdout() {
-        // check for colour/boldness at the beginning so we can unwrap later
-        if !t.supports_color() || !t.supports_attr(term::Attr::Bold) {
-            write!(t, "{} {}:  ", prompt, default_str).unwrap();
-            return;
+/// The settings of the terminal before it was put in raw mode
+#[cfg(unix)]
+static ORIGINAL_TERMIOS: std::sync::Mutex<Option<libc::termios>> = std::sync::Mutex::new(None);
+
+/// The terminal in raw mode, where keys are read as they are pressed without being echoed,
+/// with the cursor hidden. Ctrl-C still sends a signal.
+/// Everything is restored when it is dropped, including when unwinding from a panic, or by
+/// [restore] eg when interrupted.
+pub struct RawMode {
+    _private: (),
+}
+
+#[cfg(unix)]
+impl RawMode {
+    /// Switch to raw mode, returning `None` if stdin or stdout is not a terminal or if the
+    /// terminal can't be changed
+    pub fn enable() -> Option<RawMode> {
+        if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            return None;
+        }
+        // SAFETY: tcgetattr only writes in the struct we give it
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return None;
         }
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::IEXTEN);
+        raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        *ORIGINAL_TERMIOS.lock().unwrap_or_else(|e| e.into_inner()) = Some(original);
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            leave_raw_mode();
+            return None;
+        }
+        print!("\x1b[?25l");
+        let _ = io::stdout().flush();
+        Some(RawMode { _private: () })
+    }
 
-        t.attr(term::Attr::Bold).unwrap();
-        write!(t, "{} ", prompt).unwrap();
-        t.reset().unwrap();
-        t.fg(term::color::YELLOW).unwrap();
-        if default {
-            write!(t, "[Y/n]: ").unwrap()
-        } else {
-            write!(t, "[y/N]: ").unwrap()
+    /// Read a byte from stdin, waiting at most `timeout_ms` if set. Errors if interrupted while
+    /// waiting.
+    fn read_byte(&self, timeout_ms: Option<i32>) -> io::Result<Option<u8>> {
+        if !interrupt::wait_for_stdin(timeout_ms)? {
+            return Ok(None);
         }
-        t.reset().unwrap();
-        t.flush().unwrap();
-    } else {
-        eprint!("{} {}: ", prompt, default_str);
+        let mut byte = 0u8;
+        // Read from the file descriptor directly, as the buffer of `io::stdin` would hide the
+        // rest of the escape sequences from `poll`
+        match unsafe { libc::read(libc::STDIN_FILENO, &mut byte as *mut u8 as *mut _, 1) } {
+            1 => Ok(Some(byte)),
+            0 => Err(io::ErrorKind::UnexpectedEof.into()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Wait for the next key
+    pub fn read_key(&self) -> io::Result<Key> {
+        let first = loop {
+            match self.read_byte(None) {
+                Ok(Some(b)) => break b,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+                Ok(None) => unreachable!("no timeout"),
+            }
+        };
+        // The rest of a sequence is sent right away
+        Ok(decode_key(first, || self.read_byte(Some(50)).ok().flatten()))
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        leave_raw_mode();
+    }
+}
+
+/// Put back the settings the terminal had before raw mode, returning whether it was in it
+fn leave_raw_mode() -> bool {
+    #[cfg(unix)]
+    if let Some(original) = ORIGINAL_TERMIOS.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &original);
+        }
+        print!("\x1b[?25h");
+        let _ = std::io::stdout().flush();
+        return true;
+    }
+    false
+}
+
+/// The settings of the terminal when [save_state] was called
+#[cfg(unix)]
+static SAVED_TERMIOS: std::sync::Mutex<Option<libc::termios>> = std::sync::Mutex::new(None);
+
+/// Remember the current settings of the terminal, for [restore] to put them back if a prompt
+/// changes them, eg when reading a secret without echoing it
+pub fn save_state() {
+    #[cfg(unix)]
+    {
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut saved) } == 0 {
+            *SAVED_TERMIOS.lock().unwrap_or_else(|e| e.into_inner()) = Some(saved);
+        }
+    }
+}
+
+/// Run `f` without the terminal echoing what is typed, except the line break, eg to read a
+/// password
+#[cfg(unix)]
+pub fn without_echo<T>(f: impl FnOnce() -> T) -> T {
+    let mut original: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+        return f();
+    }
+    let mut silent = original;
+    silent.c_lflag &= !libc::ECHO;
+    silent.c_lflag |= libc::ECHONL;
+    unsafe {
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &silent);
+    }
+    let res = f();
+    unsafe {
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &original);
+    }
+    res
+}
+
+/// Leave raw mode or put back the settings saved with [save_state], eg when interrupted in the
+/// middle of a prompt
+pub fn restore() {
+    if leave_raw_mode() {
+        return;
+    }
+    #[cfg(unix)]
+    if let Some(saved) = *SAVED_TERMIOS.lock().unwrap_or_else(|e| e.into_inner()) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &saved);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+impl RawMode {
+    /// Raw mode is only supported on Unix for now
+    pub fn enable() -> Option<RawMode> {
+        None
+    }
+
+    pub fn read_key(&self) -> std::io::Result<Key> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_wrap_text() {
+        assert_eq!(wrap("a short one", 80), ["a short one"]);
+        assert_eq!(
+            wrap("how the sidecars of the services talk to each other", 20),
+            ["how the sidecars of", "the services talk to", "each other"]
+        );
+        assert_eq!(wrap("first\n\nsecond  paragraph", 10), ["first", "", "second", "paragraph"]);
+        assert_eq!(wrap("a supercalifragilistic word", 10), ["a", "supercalifragilistic", "word"]);
+        // Characters, not bytes
+        assert_eq!(wrap("été été été", 7), ["été été", "été"]);
+    }
+
+    #[test]
+    fn can_show_durations() {
+        assert_eq!(elapsed(Duration::from_millis(420)), "0.4s");
+        assert_eq!(elapsed(Duration::from_millis(12_340)), "12.3s");
+        assert_eq!(elapsed(Duration::from_secs(125)), "2m05s");
+    }
+
+    #[test]
+    fn can_decode_keys() {
+        let decode = |bytes: &[u8]| {
+            let mut rest = bytes[1..].iter().copied();
+            decode_key(bytes[0], || rest.next())
+        };
+        assert_eq!(decode(b"\x1b[A"), Key::Up);
+        assert_eq!(decode(b"\x1bOB"), Key::Down);
+        assert_eq!(decode(b"\x1b"), Key::Escape);
+        assert_eq!(decode(b"\x1b[C"), Key::Other);
+        assert_eq!(decode(b"\r"), Key::Enter);
+        assert_eq!(decode(b"\x7f"), Key::Backspace);
+        assert_eq!(decode(b"j"), Key::Char('j'));
+        assert_eq!(decode("é".as_bytes()), Key::Char('é'));
     }
 }
@@ -0,0 +1,672 @@
+//! Cookiecutter templates: the `cookiecutter.json` is turned into a [TemplateDefinition] and
+//! the Jinja that has a Tera equivalent, eg `{{ cookiecutter.name.lower() }}`, is translated
+//! when rendering. They can also be converted to kickstart templates for good.
+use std::borrow::Cow;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str;
+
+use log::debug;
+use regex::{Captures, Regex};
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use serde_json::Value as JsonValue;
+use walkdir::WalkDir;
+
+use crate::definition::TemplateDefinition;
+use crate::errors::{map_io_err, new_error, ErrorKind, Result};
+use crate::utils::{create_directory, is_binary, read_file};
+
+/// The file describing a cookiecutter template
+pub(crate) const CONFIG_FILE: &str = "cookiecutter.json";
+
+/// The options of cookiecutter.json that have no equivalent and are ignored
+const IGNORED_OPTIONS: [&str; 4] = ["_extensions", "_jinja2_env_vars", "_new_lines", "_checkout"];
+
+/// The entries of cookiecutter.json in the order they are written, which is the order the
+/// questions are asked in
+struct Entries(Vec<(String, JsonValue)>);
+
+impl<'de> Deserialize<'de> for Entries {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct EntriesVisitor;
+
+        impl<'de> Visitor<'de> for EntriesVisitor {
+            type Value = Entries;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "an object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<Entries, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(Entries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(EntriesVisitor)
+    }
+}
+
+/// Whether the template in that directory is a cookiecutter one
+pub(crate) fn is_cookiecutter(dir: &Path) -> bool {
+    dir.join(CONFIG_FILE).exists()
+}
+
+/// Rewrite the Jinja of the tags that has a Tera equivalent: the string methods, eg
+/// `x.lower()` to `x | lower`, and the positional arguments of `replace`.
+/// With `strip_namespace`, `cookiecutter.x` becomes `x` as well for where only the variables
+/// are in the context, eg the defaults.
+pub(crate) fn translate(text: &str, strip_namespace: bool) -> Cow<'_, str> {
+    if !text.contains("{{") && !text.contains("{%") {
+        return Cow::Borrowed(text);
+    }
+    let tags = Regex::new(r"(?s)\{\{.*?\}\}|\{%.*?%\}").unwrap();
+    let methods = Regex::new(r"\.(lower|upper|strip|title|capitalize)\(\)").unwrap();
+    let replace =
+        Regex::new(r#"(?:\.|\|\s*)replace\(\s*('[^']*'|"[^"]*")\s*,\s*('[^']*'|"[^"]*")\s*\)"#)
+            .unwrap();
+    let namespace = Regex::new(r"\bcookiecutter\.").unwrap();
+
+    tags.replace_all(text, |tag: &Captures| {
+        let tag = methods.replace_all(&tag[0], |m: &Captures| {
+            let filter = match &m[1] {
+                "strip" => "trim",
+                method => method,
+            };
+            format!(" | {filter}")
+        });
+        let tag = replace.replace_all(&tag, " | replace(from=$1, to=$2)");
+        match strip_namespace {
+            true => namespace.replace_all(&tag, "").into_owned(),
+            false => tag.into_owned(),
+        }
+    })
+}
+
+/// The single directory with the files to generate, eg `{{cookiecutter.project_slug}}`
+fn template_directory(dir: &Path) -> Result<String> {
+    let mut found = Vec::new();
+    for entry in map_io_err(fs::read_dir(dir), dir)? {
+        let entry = map_io_err(entry, dir)?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.path().is_dir() && name.contains("{{") && name.contains("cookiecutter") {
+            found.push(name);
+        }
+    }
+    match &found[..] {
+        [single] => Ok(single.clone()),
+        [] => Err(unsupported(
+            CONFIG_FILE,
+            "there is no `{{ cookiecutter.* }}` directory next to it".to_string(),
+        )),
+        _ => Err(unsupported(
+            CONFIG_FILE,
+            format!(
+                "there are several `{{{{ cookiecutter.* }}}}` directories: {}",
+                found.join(", ")
+            ),
+        )),
+    }
+}
+
+fn unsupported(path: impl Into<PathBuf>, reason: String) -> crate::errors::Error {
+    new_error(ErrorKind::UnsupportedCookiecutter { path: path.into(), reason })
+}
+
+/// A value of cookiecutter.json as the value of a variable
+fn scalar(name: &str, value: &JsonValue) -> std::result::Result<toml::Value, String> {
+    match value {
+        JsonValue::String(s) => Ok(toml::Value::String(translate(s, true).into_owned())),
+        JsonValue::Bool(b) => Ok(toml::Value::Boolean(*b)),
+        JsonValue::Number(n) => match n.as_i64() {
+            Some(i) => Ok(toml::Value::Integer(i)),
+            None => Ok(toml::Value::Float(n.as_f64().unwrap_or_default())),
+        },
+        JsonValue::Null => Err(format!("`{name}` has no value")),
+        JsonValue::Array(_) => Err(format!("`{name}` has lists in its choices")),
+        JsonValue::Object(_) => Err(format!("`{name}` is a dictionary variable")),
+    }
+}
+
+/// A variable that is asked: lists are choices, defaulting to the first one
+fn variable(
+    name: &str,
+    value: &JsonValue,
+    prompt: Option<&JsonValue>,
+) -> std::result::Result<toml::Table, String> {
+    let (prompt, labels) = match prompt {
+        Some(JsonValue::String(prompt)) => (prompt.as_str(), None),
+        Some(JsonValue::Object(prompts)) => {
+            (prompts.get("__prompt__").and_then(|p| p.as_str()).unwrap_or(name), Some(prompts))
+        }
+        _ => (name, None),
+    };
+    let mut var = toml::Table::new();
+    var.insert("name".to_string(), name.into());
+    var.insert("prompt".to_string(), prompt.into());
+
+    let JsonValue::Array(items) = value else {
+        var.insert("default".to_string(), scalar(name, value)?);
+        return Ok(var);
+    };
+    let Some(first) = items.first() else {
+        return Err(format!("`{name}` has no choices"));
+    };
+    var.insert("default".to_string(), scalar(name, first)?);
+    let mut choices = Vec::with_capacity(items.len());
+    for item in items {
+        let value = scalar(name, item)?;
+        let label = item.as_str().and_then(|i| labels?.get(i)?.as_str());
+        choices.push(match label {
+            Some(label) => {
+                let mut choice = toml::Table::new();
+                choice.insert("value".to_string(), value);
+                choice.insert("label".to_string(), label.into());
+                toml::Value::Table(choice)
+            }
+            None => value,
+        });
+    }
+    var.insert("choices".to_string(), toml::Value::Array(choices));
+    Ok(var)
+}
+
+/// A variable starting with an underscore, which is not asked
+fn private_variable(name: &str, value: &JsonValue) -> std::result::Result<toml::Table, String> {
+    let mut var = toml::Table::new();
+    var.insert("name".to_string(), name.into());
+    let (computed, computed_type) = match scalar(name, value)? {
+        toml::Value::String(s) => (s, None),
+        toml::Value::Boolean(b) => (b.to_string(), Some("bool")),
+        toml::Value::Integer(i) => (i.to_string(), Some("int")),
+        other => (other.to_string(), None),
+    };
+    var.insert("computed".to_string(), computed.into());
+    if let Some(computed_type) = computed_type {
+        var.insert("type".to_string(), computed_type.into());
+    }
+    Ok(var)
+}
+
+/// Where a cookiecutter hook is, what it is run with and where it goes in a converted template
+struct HookScript {
+    source: String,
+    interpreter: &'static str,
+    converted: String,
+}
+
+/// The cookiecutter hook with that name, if there is one kickstart can run
+fn hook(dir: &Path, name: &'static str) -> Option<HookScript> {
+    [("py", "python3"), ("sh", "sh")].into_iter().find_map(|(ext, interpreter)| {
+        let source = format!("hooks/{name}.{ext}");
+        dir.join(&source).is_file().then(|| HookScript {
+            source,
+            interpreter,
+            converted: format!("{name}.{ext}"),
+        })
+    })
+}
+
+/// What cookiecutter.json becomes, before being parsed as a [TemplateDefinition]
+struct Translated {
+    definition: toml::Table,
+    /// The directory with the files, as in the cookiecutter template
+    directory: String,
+    hooks: Vec<HookScript>,
+    /// The `_copy_without_render` patterns, relative to `directory`
+    copy_without_render: Vec<String>,
+    /// Why some keys could not be turned into variables, those are left out
+    unsupported: Vec<String>,
+    /// The options that have no equivalent and are left out
+    ignored: Vec<String>,
+}
+
+/// Turn the cookiecutter.json of the template in that directory into a definition: every key
+/// is a variable, asked unless it starts with an underscore, and the files are in the single
+/// `{{ cookiecutter.* }}` directory.
+/// With `converting`, the paths are the ones of the template converted to kickstart, where the
+/// names don't use `cookiecutter.` and the hooks are at the root.
+fn translate_definition(dir: &Path, converting: bool) -> Result<Translated> {
+    let Entries(entries) = serde_json::from_str(&read_file(&dir.join(CONFIG_FILE))?)
+        .map_err(|e| unsupported(CONFIG_FILE, format!("it is not a JSON object: {e}")))?;
+    let directory = template_directory(dir)?;
+    let converted_directory = match converting {
+        true => translate(&directory, true).into_owned(),
+        false => directory.clone(),
+    };
+    let prompts = entries
+        .iter()
+        .find(|(name, _)| name == "__prompts__" || name == "_prompts")
+        .and_then(|(_, prompts)| prompts.as_object());
+
+    let mut translated = Translated {
+        definition: toml::Table::new(),
+        directory,
+        hooks: Vec::new(),
+        copy_without_render: Vec::new(),
+        unsupported: Vec::new(),
+        ignored: Vec::new(),
+    };
+    let mut variables = Vec::new();
+    for (name, value) in &entries {
+        let var = if !name.starts_with('_') {
+            variable(name, value, prompts.and_then(|p| p.get(name)))
+        } else if name == "__prompts__" || name == "_prompts" {
+            continue;
+        } else if name == "_copy_without_render" {
+            let globs = value.as_array().into_iter().flatten().filter_map(|g| g.as_str());
+            translated.copy_without_render.extend(globs.map(|g| g.to_string()));
+            continue;
+        } else if value.is_array() || value.is_object() || IGNORED_OPTIONS.contains(&name.as_str())
+        {
+            translated.ignored.push(name.clone());
+            continue;
+        } else {
+            private_variable(name, value)
+        };
+        match var {
+            Ok(var) => variables.push(toml::Value::Table(var)),
+            Err(reason) => translated.unsupported.push(reason),
+        }
+    }
+
+    let definition = &mut translated.definition;
+    let name = dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    definition.insert("name".to_string(), name.into());
+    definition.insert("kickstart_version".to_string(), 1.into());
+    definition.insert("directory".to_string(), converted_directory.clone().into());
+    // They are relative to the directory with the files in cookiecutter
+    let copy_without_render: Vec<_> = translated
+        .copy_without_render
+        .iter()
+        .map(|glob| format!("{converted_directory}/{}", translate(glob, converting)))
+        .collect();
+    definition.insert("copy_without_render".to_string(), copy_without_render.into());
+    if !converting {
+        // It would end up next to the generated project rather than in it
+        definition.insert("write_lockfile".to_string(), false.into());
+    }
+    for (stage, name) in
+        [("pre_gen_hooks", "pre_gen_project"), ("post_gen_hooks", "post_gen_project")]
+    {
+        let mut hooks = Vec::new();
+        if let Some(script) = hook(dir, name) {
+            let mut hook = toml::Table::new();
+            hook.insert("name".to_string(), name.into());
+            let path = if converting { &script.converted } else { &script.source };
+            hook.insert("path".to_string(), path.clone().into());
+            hook.insert("interpreter".to_string(), vec![script.interpreter].into());
+            hooks.push(toml::Value::Table(hook));
+            translated.hooks.push(script);
+        }
+        definition.insert(stage.to_string(), hooks.into());
+    }
+    definition.insert("variables".to_string(), variables.into());
+    Ok(translated)
+}
+
+/// Build the definition of the cookiecutter template in that directory from its
+/// cookiecutter.json, see [translate_definition]
+pub(crate) fn definition(dir: &Path) -> Result<TemplateDefinition> {
+    let translated = translate_definition(dir, false)?;
+    if let Some(reason) = translated.unsupported.into_iter().next() {
+        return Err(unsupported(CONFIG_FILE, reason));
+    }
+    for name in &translated.ignored {
+        debug!("Ignoring `{name}` in {CONFIG_FILE}, kickstart has no equivalent");
+    }
+
+    toml::Value::Table(translated.definition)
+        .try_into()
+        .map_err(|e: toml::de::Error| unsupported(CONFIG_FILE, e.message().to_string()))
+}
+
+/// The keys of a converted template.toml, in the order they are written in
+const DEFINITION_KEYS: [&str; 6] = [
+    "name",
+    "kickstart_version",
+    "directory",
+    "copy_without_render",
+    "pre_gen_hooks",
+    "post_gen_hooks",
+];
+const VARIABLE_KEYS: [&str; 6] = ["name", "default", "prompt", "choices", "computed", "type"];
+
+/// The definition as a template.toml, with the keys in the usual order rather than sorted
+fn to_toml(definition: &toml::Table) -> String {
+    let mut out = String::new();
+    for key in DEFINITION_KEYS {
+        match definition.get(key) {
+            Some(toml::Value::Array(a)) if a.is_empty() => (),
+            Some(value) => out.push_str(&format!("{key} = {value}\n")),
+            None => (),
+        }
+    }
+    let variables = definition.get("variables").and_then(|v| v.as_array());
+    for var in variables.into_iter().flatten().filter_map(|v| v.as_table()) {
+        out.push_str("\n[[variables]]\n");
+        for key in VARIABLE_KEYS {
+            if let Some(value) = var.get(key) {
+                out.push_str(&format!("{key} = {value}\n"));
+            }
+        }
+    }
+    out
+}
+
+/// A file of a cookiecutter template converted to kickstart
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvertedFile {
+    /// Relative to the cookiecutter template
+    pub source: PathBuf,
+    /// Relative to the kickstart template
+    pub path: PathBuf,
+    /// Whether its content uses cookiecutter and was rewritten, rather than copied as is
+    pub rewritten: bool,
+}
+
+/// How a cookiecutter template is converted to a kickstart one, see [plan_conversion]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conversion {
+    /// The content of the template.toml
+    pub definition: String,
+    /// Every file of the template, in walking order
+    pub files: Vec<ConvertedFile>,
+    /// What could not be translated and needs to be fixed by hand
+    pub notes: Vec<String>,
+    /// The rewritten content of the files, in the order of `files`. `None` to copy as is
+    contents: Vec<Option<String>>,
+}
+
+/// Why Tera can't parse that text, if it can't
+fn parse_error(text: &str) -> Option<String> {
+    let err = tera::Tera::default().add_raw_template("converted", text).err()?;
+    let cause = std::error::Error::source(&err).map(|e| e.to_string());
+    Some(cause.unwrap_or_else(|| err.to_string()).trim_end().to_string())
+}
+
+/// Work out how to convert the cookiecutter template in that directory to a kickstart one,
+/// without writing anything: its cookiecutter.json becomes a template.toml, `cookiecutter.x`
+/// becomes `x` and the Python string methods become Tera filters in the names and contents of
+/// the files and the hooks are moved to the root. The notes say what is left to fix by hand.
+pub fn plan_conversion(dir: &Path) -> Result<Conversion> {
+    let translated = translate_definition(dir, true)?;
+    let mut notes: Vec<String> = translated
+        .unsupported
+        .iter()
+        .map(|reason| format!("{CONFIG_FILE}: {reason}, it was left out"))
+        .collect();
+    for name in &translated.ignored {
+        notes.push(match name.as_str() {
+            "_extensions" => format!(
+                "{CONFIG_FILE}: `_extensions` are Jinja extensions, what they provide to the \
+                 templates needs to be replaced by hand"
+            ),
+            _ => format!("{CONFIG_FILE}: `{name}` has no equivalent in kickstart, it was left out"),
+        });
+    }
+    if !translated.copy_without_render.is_empty() {
+        notes.push(format!(
+            "{CONFIG_FILE}: the `_copy_without_render` patterns are now in `copy_without_render` \
+             and relative to the template, check that they match the same files"
+        ));
+    }
+    let patterns: Vec<_> = translated
+        .copy_without_render
+        .iter()
+        .filter_map(|glob| glob::Pattern::new(glob).ok())
+        .collect();
+
+    let mut conversion = Conversion {
+        definition: to_toml(&translated.definition),
+        files: Vec::new(),
+        notes,
+        contents: Vec::new(),
+    };
+    let walker = WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.path().strip_prefix(dir).map_or(true, |p| !p.starts_with(".git")));
+    for entry in walker {
+        let entry =
+            entry.map_err(|e| new_error(ErrorKind::Io { err: e.into(), path: dir.into() }))?;
+        let source = entry.path().strip_prefix(dir).unwrap().to_path_buf();
+        if entry.file_type().is_dir() || source == Path::new(CONFIG_FILE) {
+            continue;
+        }
+        let hook = translated.hooks.iter().find(|h| source == Path::new(&h.source));
+        let in_directory = source.strip_prefix(&translated.directory).ok();
+
+        let (path, render) = match (hook, in_directory) {
+            (Some(hook), _) => (PathBuf::from(&hook.converted), true),
+            (None, Some(relative)) => {
+                let path =
+                    source.iter().map(|c| translate(&c.to_string_lossy(), true).into_owned());
+                let render = !patterns.iter().any(|p| p.matches_path(relative));
+                (path.collect(), render)
+            }
+            // Eg the README of the template, which is not generated
+            (None, None) => {
+                if source.starts_with("hooks") && source.to_string_lossy().contains("_gen_project")
+                {
+                    add_note(
+                        &mut conversion.notes,
+                        &source,
+                        "only the .py and .sh hooks can be converted, it was copied as is",
+                    );
+                }
+                (source.clone(), false)
+            }
+        };
+        if in_directory.is_some() {
+            let name = path.to_string_lossy();
+            if let Some(reason) = parse_error(&name) {
+                add_note(
+                    &mut conversion.notes,
+                    &source,
+                    &format!("Tera can't parse its name\n{reason}"),
+                );
+            }
+        }
+
+        let buffer = map_io_err(fs::read(entry.path()), entry.path())?;
+        let text = match str::from_utf8(&buffer) {
+            Ok(text) if render && !is_binary(&buffer) => Some(text),
+            _ => None,
+        };
+        let content =
+            text.and_then(|text| Some(translate(text, true).into_owned()).filter(|r| r != text));
+        if let Some(reason) = text.and_then(|text| parse_error(content.as_deref().unwrap_or(text)))
+        {
+            add_note(&mut conversion.notes, &source, &format!("Tera can't parse it\n{reason}"));
+        }
+        conversion.files.push(ConvertedFile { source, path, rewritten: content.is_some() });
+        conversion.contents.push(content);
+    }
+    Ok(conversion)
+}
+
+/// Add a note about a file of the template
+fn add_note(notes: &mut Vec<String>, path: &Path, note: &str) {
+    notes.push(format!("{}: {note}", path.display()));
+}
+
+/// Convert the cookiecutter template in `dir` to a kickstart template in `output`, as planned
+/// by [plan_conversion], which is returned
+pub fn convert(dir: &Path, output: &Path) -> Result<Conversion> {
+    let conversion = plan_conversion(dir)?;
+    create_directory(output)?;
+    let definition_path = output.join("template.toml");
+    map_io_err(fs::write(&definition_path, &conversion.definition), &definition_path)?;
+    for (file, content) in conversion.files.iter().zip(&conversion.contents) {
+        let (source, path) = (dir.join(&file.source), output.join(&file.path));
+        if let Some(parent) = path.parent() {
+            create_directory(parent)?;
+        }
+        match content {
+            Some(content) => {
+                map_io_err(fs::write(&path, content), &path)?;
+                let permissions = map_io_err(fs::metadata(&source), &source)?.permissions();
+                map_io_err(fs::set_permissions(&path, permissions), &path)?;
+            }
+            None => {
+                map_io_err(fs::copy(&source, &path), &path)?;
+            }
+        }
+    }
+    Ok(conversion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+    use tempfile::tempdir;
+
+    #[test]
+    fn can_translate_jinja() {
+        assert_eq!(
+            translate("{{ cookiecutter.name.lower().replace(' ', '_') }}.py", false),
+            "{{ cookiecutter.name | lower | replace(from=' ', to='_') }}.py"
+        );
+        assert_eq!(
+            translate("{% if cookiecutter.x|replace(\"-\", \"\") %}a.strip(){% endif %}", true),
+            "{% if x | replace(from=\"-\", to=\"\") %}a.strip(){% endif %}"
+        );
+        assert_eq!(translate("cookiecutter.name.title()", true), "cookiecutter.name.title()");
+    }
+
+    #[test]
+    fn can_read_cookiecutter_json() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("{{cookiecutter.project_slug}}")).unwrap();
+        fs::create_dir_all(dir.path().join("hooks")).unwrap();
+        fs::write(dir.path().join("hooks/post_gen_project.py"), "print('hi')").unwrap();
+        fs::write(
+            dir.path().join(CONFIG_FILE),
+            r#"{
+                "project_name": "My Project",
+                "project_slug": "{{ cookiecutter.project_name.lower().replace(' ', '_') }}",
+                "license": ["MIT", "BSD-3"],
+                "use_docker": false,
+                "_copy_without_render": ["*.html"],
+                "_extensions": ["jinja2_time.TimeExtension"],
+                "_year": 2024,
+                "__prompts__": {
+                    "project_name": "What is the name of the project?",
+                    "license": {"__prompt__": "Which license?", "MIT": "MIT License"}
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let definition = definition(dir.path()).unwrap();
+        let names: Vec<_> = definition.variables.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["project_name", "project_slug", "license", "use_docker", "_year"]);
+        assert_eq!(definition.variables[0].prompt, "What is the name of the project?");
+        assert_eq!(
+            definition.variables[1].raw_default(),
+            &Value::String("{{ project_name | lower | replace(from=' ', to='_') }}".to_string())
+        );
+        let license = &definition.variables[2];
+        assert_eq!(license.prompt, "Which license?");
+        assert_eq!(license.raw_default(), &Value::String("MIT".to_string()));
+        let labels: Vec<_> = license.choices.iter().flatten().map(|c| c.label()).collect();
+        assert_eq!(labels, ["MIT License", "BSD-3"]);
+        assert_eq!(definition.variables[3].raw_default(), &Value::Boolean(false));
+        assert_eq!(definition.variables[4].computed.as_deref(), Some("2024"));
+        assert_eq!(definition.directory.as_deref(), Some("{{cookiecutter.project_slug}}"));
+        assert_eq!(definition.copy_without_render, ["{{cookiecutter.project_slug}}/*.html"]);
+        assert_eq!(definition.post_gen_hooks[0].interpreter, Some(vec!["python3".to_string()]));
+        assert!(definition.pre_gen_hooks.is_empty());
+    }
+
+    #[test]
+    fn errors_on_unsupported_cookiecutter_json() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join(CONFIG_FILE), r#"{"name": "a"}"#).unwrap();
+        let err = definition(dir.path()).unwrap_err();
+        assert!(err.to_string().contains("there is no `{{ cookiecutter.* }}` directory"));
+
+        fs::create_dir_all(dir.path().join("{{ cookiecutter.name }}")).unwrap();
+        fs::write(dir.path().join(CONFIG_FILE), r#"{"db": {"engine": "postgres"}}"#).unwrap();
+        let err = definition(dir.path()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unsupported cookiecutter feature in `cookiecutter.json`: `db` is a dictionary variable"
+        );
+    }
+
+    #[test]
+    fn can_plan_a_conversion() {
+        let dir = tempdir().unwrap();
+        let files = dir.path().join("{{cookiecutter.slug}}");
+        fs::create_dir_all(&files).unwrap();
+        fs::create_dir_all(dir.path().join("hooks")).unwrap();
+        fs::write(
+            dir.path().join(CONFIG_FILE),
+            r#"{"slug": "hello", "_copy_without_render": ["*.html"], "_new_lines": "\n"}"#,
+        )
+        .unwrap();
+        fs::write(files.join("{{cookiecutter.slug}}.py"), "{{ cookiecutter.slug.upper() }}")
+            .unwrap();
+        fs::write(files.join("index.html"), "{{ cookiecutter.slug }}").unwrap();
+        fs::write(files.join("broken.txt"), "{{ a if b else c }}").unwrap();
+        fs::write(dir.path().join("hooks/pre_gen_project.sh"), "echo {{ cookiecutter.slug }}")
+            .unwrap();
+
+        let conversion = plan_conversion(dir.path()).unwrap();
+        assert_eq!(
+            conversion.definition,
+            r#"name = "{}"
+kickstart_version = 1
+directory = "{{slug}}"
+copy_without_render = ["{{slug}}/*.html"]
+pre_gen_hooks = [{ interpreter = ["sh"], name = "pre_gen_project", path = "pre_gen_project.sh" }]
+
+[[variables]]
+name = "slug"
+default = "hello"
+prompt = "slug"
+"#
+            .replace("{}", &dir.path().file_name().unwrap().to_string_lossy())
+        );
+        let files: Vec<_> = conversion
+            .files
+            .iter()
+            .map(|f| (f.path.to_string_lossy().replace('\\', "/"), f.rewritten))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                ("pre_gen_project.sh".to_string(), true),
+                ("{{slug}}/broken.txt".to_string(), false),
+                ("{{slug}}/index.html".to_string(), false),
+                ("{{slug}}/{{slug}}.py".to_string(), true),
+            ]
+        );
+        assert_eq!(conversion.notes.len(), 3, "{:?}", conversion.notes);
+        assert!(conversion.notes[0].contains("`_new_lines` has no equivalent"));
+        assert!(conversion.notes[2]
+            .starts_with("{{cookiecutter.slug}}/broken.txt: Tera can't parse it\n"));
+
+        let output = dir.path().join("converted");
+        convert(dir.path(), &output).unwrap();
+        assert_eq!(
+            fs::read_to_string(output.join("{{slug}}/{{slug}}.py")).unwrap(),
+            "{{ slug | upper }}"
+        );
+        assert_eq!(
+            fs::read_to_string(output.join("{{slug}}/index.html")).unwrap(),
+            "{{ cookiecutter.slug }}"
+        );
+        assert!(output.join("template.toml").exists());
+    }
+}
@@ -1,63 +1,791 @@
 use glob::Pattern;
 use regex::Regex;
-use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use semver::{Prerelease, Version, VersionReq};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::path::{Component, Path, PathBuf};
 
-use serde::Deserialize;
-use tera::Context;
+use serde::{Deserialize, Serialize};
 
 use crate::errors::{new_error, ErrorKind, Result};
-use crate::utils::{read_file, render_one_off_template};
+use crate::lockfile::DEFAULT_LOCKFILE_NAME;
+use crate::testing::DEFAULT_TESTS_DIR;
+use crate::utils::{
+    build_context, hook_env_var_name, read_file, render_one_off_template, BuiltinValues,
+    BUILTIN_CONTEXT_KEY, DEFAULT_FROM_SOURCES, HOOK_ENV_PREFIX,
+};
+use crate::validation::{
+    check_items, check_length, check_range, describe_items, describe_length, describe_range, Rule,
+    Validation,
+};
 use crate::Value;
 
-/// A condition for a question to be asked
-/// If the value is different or not found, the question should not be asked.
+/// A condition for a question to be asked, a hook to run or files to be generated.
+/// It is either a clause on the variable `name`, with one of `value`, `value_in` or the
+/// comparisons, or one of `not`, `all_of` and `any_of` combining other conditions.
+/// A clause on a variable that wasn't asked is false.
 /// Use [Template::should_ask_variable] rather than handling that yourself
-#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Condition {
-    pub name: String,
-    pub value: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The variable has that value
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+    /// The variable has one of those values
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_in: Option<Vec<Value>>,
+    /// The variable is an integer greater than that
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gt: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gte: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lt: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lte: Option<i64>,
+    /// That condition is false
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not: Option<Box<Condition>>,
+    /// All of those conditions are true
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub all_of: Option<Vec<Condition>>,
+    /// At least one of those conditions is true
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub any_of: Option<Vec<Condition>>,
+}
+
+impl Condition {
+    fn comparisons(&self) -> [(&'static str, Option<i64>); 4] {
+        [(">", self.gt), (">=", self.gte), ("<", self.lt), ("<=", self.lte)]
+    }
+
+    fn has_comparisons(&self) -> bool {
+        self.comparisons().iter().any(|(_, bound)| bound.is_some())
+    }
+
+    /// The names of all the variables the condition refers to
+    pub(crate) fn variable_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.name.as_deref().into_iter().collect();
+        for cond in self.not.iter().map(|c| &**c).chain(self.all_of.iter().flatten()) {
+            names.extend(cond.variable_names());
+        }
+        for cond in self.any_of.iter().flatten() {
+            names.extend(cond.variable_names());
+        }
+        names
+    }
+
+    /// Whether the condition is true with those values
+    pub fn is_met(&self, vals: &HashMap<String, Value>) -> bool {
+        if let Some(ref cond) = self.not {
+            return !cond.is_met(vals);
+        }
+        if let Some(ref conds) = self.all_of {
+            return conds.iter().all(|c| c.is_met(vals));
+        }
+        if let Some(ref conds) = self.any_of {
+            return conds.iter().any(|c| c.is_met(vals));
+        }
+
+        // Not having it means we didn't even ask the question
+        let Some(val) = self.name.as_ref().and_then(|name| vals.get(name)) else {
+            return false;
+        };
+        if let Some(ref expected) = self.value {
+            return val == expected;
+        }
+        if let Some(ref values) = self.value_in {
+            return values.contains(val);
+        }
+        match val {
+            Value::Integer(i) if self.has_comparisons() => {
+                self.gt.iter().all(|b| i > b)
+                    && self.gte.iter().all(|b| i >= b)
+                    && self.lt.iter().all(|b| i < b)
+                    && self.lte.iter().all(|b| i <= b)
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks that the condition is well-formed and matches the types of the variables it
+    /// refers to.
+    /// `subject` is what depends on the condition, eg ``Hook `greet` ``, and `unknown` says why a
+    /// variable that is not in `types` can't be used, eg `which is not a variable`.
+    pub(crate) fn errors(
+        &self,
+        subject: &str,
+        plural: bool,
+        types: &HashMap<String, &str>,
+        unknown: &str,
+    ) -> Vec<String> {
+        let (s, has) = if plural { ("", "have") } else { ("s", "has") };
+        let kinds =
+            [self.name.is_some(), self.not.is_some(), self.all_of.is_some(), self.any_of.is_some()];
+        if kinds.iter().filter(|k| **k).count() != 1 {
+            return vec![format!(
+                "{subject} {has} a condition that needs exactly one of `name`, `not`, `all_of` or `any_of`"
+            )];
+        }
+
+        if let Some(ref cond) = self.not {
+            return cond.errors(subject, plural, types, unknown);
+        }
+        if let Some(conds) = self.all_of.as_ref().or(self.any_of.as_ref()) {
+            if conds.is_empty() {
+                let field = if self.all_of.is_some() { "all_of" } else { "any_of" };
+                return vec![format!("{subject} {has} a condition with an empty `{field}`")];
+            }
+            return conds.iter().flat_map(|c| c.errors(subject, plural, types, unknown)).collect();
+        }
+
+        let name = self.name.as_ref().unwrap();
+        let tests = [self.value.is_some(), self.value_in.is_some(), self.has_comparisons()];
+        if tests.iter().filter(|t| **t).count() != 1 {
+            return vec![format!(
+                "{subject} {has} a condition on `{name}` that needs one of `value`, `value_in` or `gt`/`gte`/`lt`/`lte`"
+            )];
+        }
+        let Some(t) = types.get(name) else {
+            return vec![format!("{subject} depend{s} on `{name}`, {unknown}")];
+        };
+
+        let mut errs = vec![];
+        if let Some(ref value) = self.value {
+            if *t != value.type_str() {
+                errs.push(format!(
+                    "{subject} depend{s} on `{name}={value}`, but the type of `{name}` is {t}"
+                ));
+            }
+        }
+        for value in self.value_in.iter().flatten() {
+            if *t != value.type_str() {
+                errs.push(format!(
+                    "{subject} depend{s} on `{name}` being `{value}`, but the type of `{name}` is {t}"
+                ));
+            }
+        }
+        if self.has_comparisons() && *t != "integer" {
+            errs.push(format!(
+                "{subject} compare{s} `{name}` to a number, but the type of `{name}` is {t}"
+            ));
+        }
+        errs
+    }
+}
+
+impl std::fmt::Display for Condition {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let join = |conds: &[Condition], sep: &str| {
+            conds
+                .iter()
+                .map(|c| if c.name.is_some() { c.to_string() } else { format!("({c})") })
+                .collect::<Vec<_>>()
+                .join(sep)
+        };
+        if let Some(ref cond) = self.not {
+            return write!(f, "not ({cond})");
+        }
+        if let Some(ref conds) = self.all_of {
+            return write!(f, "{}", join(conds, " and "));
+        }
+        if let Some(ref conds) = self.any_of {
+            return write!(f, "{}", join(conds, " or "));
+        }
+
+        let name = self.name.as_deref().unwrap_or_default();
+        if let Some(ref value) = self.value {
+            return write!(f, "`{name}` is {value}");
+        }
+        if let Some(ref values) = self.value_in {
+            let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+            return write!(f, "`{name}` is one of {}", values.join(", "));
+        }
+        let bounds = self
+            .comparisons()
+            .iter()
+            .filter_map(|(op, bound)| bound.map(|b| format!("`{name}` {op} {b}")))
+            .collect::<Vec<_>>();
+        write!(f, "{}", bounds.join(" and "))
+    }
 }
 
 /// A list of paths to be deleted when `name` has `value`
-#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Cleanup {
     pub name: String,
     pub value: Value,
     pub paths: Vec<String>,
 }
 
+/// Paths of the template that are only generated when `only_if` is true
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConditionalFiles {
+    /// Globs matched against the paths relative to the template root. Everything in a matching
+    /// directory is skipped along with it
+    pub paths: Vec<String>,
+    pub only_if: Condition,
+}
+
+/// The `kickstart_version` of a template
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KickstartVersion {
+    /// The version of the template spec, only `1` exists
+    Spec(u8),
+    /// The versions of kickstart that can generate the template, a requirement such as
+    /// `>=0.5` or `>=0.5, <0.7` written like the ones of Cargo dependencies
+    Requirement(String),
+}
+
+/// How the line endings of the rendered files are written
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEndings {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    Crlf,
+    /// `\r\n` on Windows, `\n` elsewhere
+    Native,
+    /// As they are in the template
+    #[default]
+    Keep,
+}
+
+impl LineEndings {
+    /// The text with its line endings converted, as is if there is nothing to convert
+    pub fn apply(self, text: &str) -> Cow<'_, str> {
+        let crlf = match self {
+            LineEndings::Keep => return Cow::Borrowed(text),
+            LineEndings::Lf => false,
+            LineEndings::Crlf => true,
+            LineEndings::Native => cfg!(windows),
+        };
+        let crlf_count = text.matches("\r\n").count();
+        if crlf {
+            if crlf_count == text.matches('\n').count() {
+                return Cow::Borrowed(text);
+            }
+            Cow::Owned(text.replace("\r\n", "\n").replace('\n', "\r\n"))
+        } else if crlf_count == 0 {
+            Cow::Borrowed(text)
+        } else {
+            Cow::Owned(text.replace("\r\n", "\n"))
+        }
+    }
+}
+
+/// Rendered files whose line endings are not the ones of the template
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineEndingsOverride {
+    /// Globs matched against the rendered paths, like `copy_without_render`
+    pub paths: Vec<String>,
+    pub line_endings: LineEndings,
+}
+
+/// One of the choices of a variable: either a bare value or a table with the value, the label
+/// shown instead of it when asking and a description shown next to it, both optional, eg
+/// `{ value = "Apache-2.0", label = "Apache License 2.0", description = "Permissive, with patents" }`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Choice {
+    Value(Value),
+    Table {
+        value: Value,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+    },
+}
+
+impl Choice {
+    /// What the variable is set to when picking that choice
+    pub fn value(&self) -> &Value {
+        match self {
+            Choice::Value(value) | Choice::Table { value, .. } => value,
+        }
+    }
+
+    /// What is shown to the user when asking
+    pub fn label(&self) -> String {
+        match self {
+            Choice::Table { label: Some(label), .. } => label.clone(),
+            _ => self.value().to_string(),
+        }
+    }
+
+    /// What is shown next to the label when asking, if anything
+    pub fn description(&self) -> Option<&str> {
+        match self {
+            Choice::Table { description, .. } => description.as_deref(),
+            Choice::Value(..) => None,
+        }
+    }
+}
+
+impl From<Value> for Choice {
+    fn from(value: Value) -> Self {
+        Choice::Value(value)
+    }
+}
+
+/// The value, followed by the label in parentheses if there is one
+impl std::fmt::Display for Choice {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Choice::Table { value, label: Some(label), .. } => write!(f, "{value} ({label})"),
+            _ => write!(f, "{}", self.value()),
+        }
+    }
+}
+
+/// Choices depending on the answer to a variable asked before, eg the regions of the cloud
+/// provider picked
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChoicesFrom {
+    /// The variable whose answer picks the list of choices
+    pub name: String,
+    /// The choices for each of its values, eg `{ aws = ["us-east-1"], gcp = ["us-east1"] }`
+    pub choices: BTreeMap<String, Vec<Choice>>,
+}
+
+impl ChoicesFrom {
+    /// The choices for that answer to the variable, if there are some
+    pub fn choices_for(&self, answer: &Value) -> Option<&[Choice]> {
+        self.choices.get(&answer.to_string()).map(Vec::as_slice)
+    }
+}
+
 /// A question loaded from TOML
-#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RawVariable")]
 pub struct Variable {
     /// The variable name in the final context
     pub name: String,
     /// A default value is required. It can be a Tera expression if it is a string.
+    /// Computed variables don't have one, it is only there to know their type.
     pub(crate) default: Value,
+    /// The name of an environment variable to take the default from, if it is set and can be
+    /// converted to the type of `default`
+    pub default_from_env: Option<String>,
+    /// A value known by kickstart to take the default from if it is not empty, one of
+    /// [crate::DEFAULT_FROM_SOURCES], eg `git.user.name`. Only for strings.
+    pub default_from: Option<String>,
     /// The text asked to the user
     pub prompt: String,
+    /// More details about the question, shown when asking it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub help: Option<String>,
     /// Only for questions with choices
-    pub choices: Option<Vec<Value>>,
-    /// A regex pattern to validate the input. Only used where the value is meant to be a string.
+    pub choices: Option<Vec<Choice>>,
+    /// Choices depending on the answer to another variable, instead of `choices`.
+    /// When `default` is not one of them, the first one is the default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub choices_from: Option<ChoicesFrom>,
+    /// A command printing the choices, one per line, eg `["git", "tag", "--list"]`. It is run
+    /// in the template directory before asking, see [crate::Template::run_choices_cmds].
+    /// The `choices` are used instead if it fails.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub choices_cmd: Option<Vec<String>>,
+    /// Whether to fail if the `choices_cmd` fails instead of using the `choices`
+    #[serde(default)]
+    pub choices_cmd_required: bool,
+    /// Whether the user can pick several of the choices, in which case the value is a list.
+    /// The default needs to be a list of choices as well.
+    #[serde(default)]
+    pub multi: bool,
+    /// Whether the value is sensitive, eg an API token. The input is hidden when prompting and
+    /// the value is never written to the lockfile or saved answers.
+    #[serde(default)]
+    pub secret: bool,
+    /// Whether a string variable can be left empty, in which case its constraints are not
+    /// checked and templates get a `has_<name>` boolean telling whether it was filled
+    pub required: bool,
+    /// A regex pattern or the name of a built-in rule (see [crate::validation::Rule]) to validate
+    /// the input. Only used where the value is meant to be a string.
     pub validation: Option<String>,
+    /// What the values accepted by `validation` look like, shown instead of the regex
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern_hint: Option<String>,
+    /// The minimum number of characters of a string variable, inclusive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<usize>,
+    /// The maximum number of characters of a string variable, inclusive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+    /// The smallest value allowed for an integer variable, inclusive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<i64>,
+    /// The largest value allowed for an integer variable, inclusive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<i64>,
+    /// Whether the value is a list of strings entered one at a time, set with `type = "list"`.
+    /// Its `validation` applies to each of them.
+    #[serde(default)]
+    pub list: bool,
+    /// The minimum number of entries of a list variable, inclusive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_items: Option<usize>,
+    /// The maximum number of entries of a list variable, inclusive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_items: Option<usize>,
     /// Only ask this variable if that condition is true
     pub only_if: Option<Condition>,
+    /// The key of the group it is part of in [TemplateDefinition::groups]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// A Tera expression rendered with the values of the variables before it, in which case
+    /// the variable is never asked, eg `{{ project_name | slugify }}`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub computed: Option<String>,
+}
+
+/// The types a variable can be given with `type`: strings, booleans or integers for computed
+/// variables and lists for the others
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum VariableType {
+    String,
+    Bool,
+    Int,
+    List,
+}
+
+/// A [Variable] as written in the template.toml, where `default` and `prompt` are missing for
+/// computed variables
+#[derive(Deserialize)]
+struct RawVariable {
+    name: String,
+    default: Option<Value>,
+    default_from_env: Option<String>,
+    default_from: Option<String>,
+    prompt: Option<String>,
+    help: Option<String>,
+    choices: Option<Vec<Choice>>,
+    choices_from: Option<ChoicesFrom>,
+    choices_cmd: Option<Vec<String>>,
+    #[serde(default)]
+    choices_cmd_required: bool,
+    #[serde(default)]
+    multi: bool,
+    #[serde(default)]
+    secret: bool,
+    #[serde(default = "default_true")]
+    required: bool,
+    validation: Option<String>,
+    pattern_hint: Option<String>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    min: Option<i64>,
+    max: Option<i64>,
+    min_items: Option<usize>,
+    max_items: Option<usize>,
+    only_if: Option<Condition>,
+    group: Option<String>,
+    computed: Option<String>,
+    #[serde(rename = "type")]
+    variable_type: Option<VariableType>,
+}
+
+impl TryFrom<RawVariable> for Variable {
+    type Error = String;
+
+    fn try_from(raw: RawVariable) -> std::result::Result<Self, Self::Error> {
+        let list = raw.variable_type == Some(VariableType::List);
+        let (default, prompt) = match (&raw.computed, raw.default, raw.prompt) {
+            (Some(_), None, None) => {
+                let default = match raw.variable_type.unwrap_or(VariableType::String) {
+                    VariableType::String => Value::String(String::new()),
+                    VariableType::Bool => Value::Boolean(false),
+                    VariableType::Int => Value::Integer(0),
+                    VariableType::List => {
+                        return Err(format!(
+                            "variable `{}` is computed, it can't be a list",
+                            raw.name
+                        ))
+                    }
+                };
+                (default, String::new())
+            }
+            (Some(_), _, _) => {
+                return Err(format!(
+                    "variable `{}` is computed, it can't have a `default` or a `prompt`",
+                    raw.name
+                ))
+            }
+            (None, _, _) if raw.variable_type.is_some() && !list => {
+                return Err(format!(
+                    "variable `{}` has a `type` but only computed variables and lists can have one",
+                    raw.name
+                ))
+            }
+            (None, Some(default), Some(prompt)) => (default, prompt),
+            (None, None, _) => return Err(format!("variable `{}` needs a `default`", raw.name)),
+            (None, _, None) => return Err(format!("variable `{}` needs a `prompt`", raw.name)),
+        };
+
+        Ok(Variable {
+            name: raw.name,
+            default,
+            default_from_env: raw.default_from_env,
+            default_from: raw.default_from,
+            prompt,
+            help: raw.help,
+            choices: raw.choices,
+            choices_from: raw.choices_from,
+            choices_cmd: raw.choices_cmd,
+            choices_cmd_required: raw.choices_cmd_required,
+            multi: raw.multi,
+            secret: raw.secret,
+            required: raw.required,
+            validation: raw.validation,
+            pattern_hint: raw.pattern_hint,
+            min_length: raw.min_length,
+            max_length: raw.max_length,
+            min: raw.min,
+            max: raw.max,
+            list,
+            min_items: raw.min_items,
+            max_items: raw.max_items,
+            only_if: raw.only_if,
+            group: raw.group,
+            computed: raw.computed,
+        })
+    }
+}
+
+impl Variable {
+    /// The default as written in the template.toml, which might be a Tera expression.
+    /// Use [crate::Template::get_default_for] to get the actual default.
+    pub fn raw_default(&self) -> &Value {
+        &self.default
+    }
+
+    /// The default coming from `default_from_env`, if any, `env` giving the value of an
+    /// environment variable. Booleans accept `true/1/yes` and `false/0/no`.
+    pub(crate) fn env_default(&self, env: impl Fn(&str) -> Option<String>) -> Option<Value> {
+        let raw = env(self.default_from_env.as_ref()?)?;
+        match self.default {
+            Value::Boolean(..) => match raw.to_lowercase().as_str() {
+                "true" | "1" | "yes" => Some(Value::Boolean(true)),
+                "false" | "0" | "no" => Some(Value::Boolean(false)),
+                _ => None,
+            },
+            Value::String(..) | Value::Integer(..) => self.default.parse_same_type(&raw),
+            _ => None,
+        }
+    }
+
+    /// Render the `computed` expression of the variable with the values of the variables before
+    /// it and convert it to its type.
+    /// Returns `None` if the variable is not computed.
+    pub(crate) fn computed_value(
+        &self,
+        vals: &HashMap<String, Value>,
+        builtins: &BuiltinValues,
+    ) -> Result<Option<Value>> {
+        let Some(ref expr) = self.computed else {
+            return Ok(None);
+        };
+        let rendered = render_one_off_template(expr, &build_context(vals, builtins), None)?;
+        let rendered = if self.default.is_str() { rendered.as_str() } else { rendered.trim() };
+        match self.default.parse_same_type(rendered) {
+            Some(val) => Ok(Some(val)),
+            None => Err(new_error(ErrorKind::InvalidVariableValue {
+                name: self.name.clone(),
+                value: rendered.to_string(),
+                expected: self.default.type_str(),
+            })),
+        }
+    }
+
+    /// The default coming from `default_from`, if any
+    pub(crate) fn builtin_default(&self, builtins: &BuiltinValues) -> Option<Value> {
+        let value = builtins.get(self.default_from.as_ref()?)?;
+        if value.is_empty() || !self.default.is_str() {
+            return None;
+        }
+        Some(Value::String(value.to_string()))
+    }
+
+    /// The choices to pick from with those answers: the `choices`, or the ones of
+    /// `choices_from` for the answer to the variable it refers to, if there are some
+    pub fn choices_for(&self, vals: &HashMap<String, Value>) -> Option<&[Choice]> {
+        match self.choices_from {
+            Some(ref from) => from.choices_for(vals.get(&from.name)?),
+            None => self.choices.as_deref(),
+        }
+    }
+
+    /// Why the variable can't be asked with those answers because of its `choices_from`: the
+    /// variable it refers to was not asked or there are no choices for its answer
+    pub(crate) fn missing_choices(&self, vals: &HashMap<String, Value>) -> Option<String> {
+        let from = self.choices_from.as_ref()?;
+        match vals.get(&from.name) {
+            None => Some(format!("`{}` was not asked", from.name)),
+            Some(answer) if from.choices_for(answer).is_none() => {
+                Some(format!("there are no choices for `{}` = {answer}", from.name))
+            }
+            Some(_) => None,
+        }
+    }
+
+    /// Check the value against the constraints the prompts enforce: being one of the `choices`,
+    /// or only having some of them for multi choices, passing the `validation` and length for
+    /// strings and being within `min` and `max` for integers.
+    /// The choices of `choices_from` depend on other answers, see [Variable::check_answer].
+    /// Returns the message to show to the user if it doesn't.
+    pub fn check_value(&self, val: &Value) -> std::result::Result<(), String> {
+        self.check_value_in(val, self.choices.as_deref())
+    }
+
+    /// Check the value as [Variable::check_value] does, with the choices of `choices_from` for
+    /// the other answers if it has some
+    pub fn check_answer(
+        &self,
+        val: &Value,
+        vals: &HashMap<String, Value>,
+    ) -> std::result::Result<(), String> {
+        self.check_value_in(val, self.choices_for(vals))
+    }
+
+    fn check_value_in(
+        &self,
+        val: &Value,
+        choices: Option<&[Choice]>,
+    ) -> std::result::Result<(), String> {
+        if let Some(choices) = choices {
+            let is_choice = |v: &Value| choices.iter().any(|c| c.value() == v);
+            let invalid = match val.as_list() {
+                Some(items) if self.multi => items.iter().find(|i| !is_choice(i)),
+                _ => Some(val).filter(|v| !is_choice(v)),
+            };
+            if let Some(invalid) = invalid {
+                let values: Vec<_> = choices.iter().map(|c| c.value().to_string()).collect();
+                return Err(format!(
+                    "The value `{invalid}` is not one of the choices: {}",
+                    values.join(", ")
+                ));
+            }
+        }
+
+        match val {
+            Value::String(input) => self.check_string(input),
+            Value::Integer(i) => check_range(*i, self.min, self.max),
+            Value::List(items) if self.list => {
+                check_items(items.len(), self.min_items, self.max_items)?;
+                for item in items {
+                    let Some(entry) = item.as_str() else {
+                        return Err(format!("The entry `{item}` is not a string"));
+                    };
+                    self.check_string(entry).map_err(|e| format!("`{entry}`: {e}"))?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check a string against the length and `validation` of the variable, returning the
+    /// message to show to the user if it doesn't pass. Optional variables can always be empty.
+    pub fn check_string(&self, input: &str) -> std::result::Result<(), String> {
+        if !self.required && input.is_empty() {
+            return Ok(());
+        }
+        check_length(input, self.min_length, self.max_length)?;
+        // An invalid `validation` is reported by `validate`
+        let Some(Ok(validation)) = self.validation.as_deref().map(Validation::parse) else {
+            return Ok(());
+        };
+        validation.check(input).map_err(|err| match self.pattern_hint {
+            Some(ref hint) => format!("The value must be {hint}"),
+            None => err,
+        })
+    }
+
+    /// What to show next to the question about the constraints of a string, eg
+    /// `lowercase letters, between 3 and 30 characters`
+    pub fn string_hint(&self) -> Option<String> {
+        let parts: Vec<String> = self
+            .pattern_hint
+            .clone()
+            .or_else(|| self.validation.clone())
+            .into_iter()
+            .chain(describe_length(self.min_length, self.max_length))
+            .collect();
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
 }
 
+/// The operating systems that can be given in the `os` field of a hook
+pub const HOOK_OSES: [&str; 3] = ["linux", "macos", "windows"];
+
 /// A hook is a file that will get executed
-#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Hook {
     /// The display name for that hook
     pub name: String,
     /// The path to the executable file
     pub path: PathBuf,
+    /// The program to run the hook with, eg `["python3"]` or `["pwsh", "-File"]`.
+    /// The hook path is appended to it. If not set, the file is executed directly.
+    pub interpreter: Option<Vec<String>>,
+    /// The arguments given to the hook, after its path. Each of them is rendered with the
+    /// variables, eg `["--name", "{{ project_name }}"]`
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Only run this hook on those operating systems, see [HOOK_OSES]
+    pub os: Option<Vec<String>>,
+    /// Kill the hook if it is still running after that many seconds
+    pub timeout_secs: Option<u64>,
+    /// Only show the output of the hook if it fails
+    #[serde(default)]
+    pub quiet: bool,
+    /// Only warn if the hook fails and keep going, unless `--strict-hooks` is used
+    #[serde(default)]
+    pub allow_failure: bool,
+    /// Run the hook at the same time as the `parallel` hooks right before and after it
+    #[serde(default)]
+    pub parallel: bool,
     /// Only run this hook if that condition is true
     pub only_if: Option<Condition>,
 }
 
-/// The full template struct we get fom loading a TOML file
-#[derive(Debug, Clone, PartialEq, Deserialize)]
+/// An optional part of a template, eg a gRPC server for a base service: a directory of the
+/// template with its own template.toml, generated along with it if the user wants it.
+/// See [crate::Template::select_addons]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Addon {
+    /// How it is referred to, eg with `--addon`
+    pub name: String,
+    /// Where it is, relative to the template.toml of the template
+    pub directory: String,
+    /// The question asking whether to add it
+    pub prompt: String,
+    /// Whether it is added when nothing is asked
+    #[serde(default)]
+    pub default: bool,
+}
+
+/// Variables asked together under a heading, eg the settings of the database
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
+pub struct Group {
+    /// The heading shown before asking the first variable of the group
+    pub title: String,
+    /// Only ask the variables of the group if that condition is true, as if they all had it
+    pub only_if: Option<Condition>,
+}
+
+/// The full template struct we get fom loading a TOML file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TemplateDefinition {
     /// Name of the template
     pub name: String,
@@ -65,8 +793,8 @@ pub struct TemplateDefinition {
     pub description: Option<String>,
     /// Template version
     pub version: Option<String>,
-    /// Version of the kickstart template spec
-    pub kickstart_version: u8,
+    /// Version of the kickstart template spec, or the versions of kickstart the template needs
+    pub kickstart_version: KickstartVersion,
     /// Url of the template
     pub url: Option<String>,
     /// A list of the authors
@@ -78,6 +806,9 @@ pub struct TemplateDefinition {
     /// The directory in which the template files are.
     /// Useful if a template has its own docs, README, CI and various files
     pub directory: Option<String>,
+    /// The name of the directory to generate in when none is given, eg `{{ project_name }}`.
+    /// It is rendered with the answers and must be a single directory name.
+    pub output_dir: Option<String>,
     /// Whether to follow symlinks
     #[serde(default)]
     pub follow_symlinks: bool,
@@ -87,34 +818,444 @@ pub struct TemplateDefinition {
     /// Conditionally delete some files/dirs based on generator values
     #[serde(default)]
     pub cleanup: Vec<Cleanup>,
+    /// Only generate some files/dirs based on generator values
+    #[serde(default)]
+    pub conditional_files: Vec<ConditionalFiles>,
     /// Do not pass those files through Tera. Those can be globs
     #[serde(default)]
     pub copy_without_render: Vec<String>,
+    /// How to write the line endings of the rendered files, as they are in the template by
+    /// default. The files copied as is are never changed
+    #[serde(default)]
+    pub line_endings: LineEndings,
+    /// Other line endings for some rendered files, the first matching override winning
+    #[serde(default)]
+    pub line_endings_overrides: Vec<LineEndingsOverride>,
+    /// Hooks that should be ran before asking the questions, they can give the defaults of some
+    /// variables by writing a JSON object to the file in `KICKSTART_DEFAULTS_FILE`
+    #[serde(default)]
+    pub pre_prompt_hooks: Vec<Hook>,
     /// Hooks that should be ran after collecting all variables but before generating the template
     #[serde(default)]
     pub pre_gen_hooks: Vec<Hook>,
     /// Hooks that should be ran after generating the template
     #[serde(default)]
     pub post_gen_hooks: Vec<Hook>,
+    /// What to show once the project is generated, after the post-gen hooks, eg the commands to
+    /// run next. It is rendered with the variables
+    pub post_gen_message: Option<String>,
+    /// Whether to write a lockfile recording the template and answers in the generated project
+    #[serde(default = "default_true")]
+    pub write_lockfile: bool,
+    /// The name of that lockfile
+    #[serde(default = "default_lockfile_name")]
+    pub lockfile_name: String,
+    /// Whether to create a git repository with the generated files in a first commit, before
+    /// running the post-gen hooks
+    #[serde(default)]
+    pub git_init: bool,
+    /// The message of that commit, rendered with the variables
+    #[serde(default = "default_git_init_message")]
+    pub git_init_message: String,
+    /// The directory next to the template.toml with the test cases of the template, which is
+    /// never generated
+    #[serde(default = "default_tests_dir")]
+    pub tests_dir: String,
+    /// Whether the template is meant to be generated in existing projects, in which case the CLI
+    /// doesn't ask before generating in a non-empty directory
+    #[serde(default)]
+    pub allow_non_empty: bool,
+    /// Whether files using variables that don't exist render them as empty strings instead of
+    /// failing, for templates relying on that
+    #[serde(default)]
+    pub lenient_rendering: bool,
+    /// Whether to ask the variables after the ones their `only_if` and `computed` refer to
+    /// rather than in the order they are declared in
+    #[serde(default)]
+    pub reorder_variables: bool,
+    /// The optional parts of the template
+    #[serde(default)]
+    pub addons: Vec<Addon>,
+    /// The groups the variables can be part of, by the key they use in their `group`
+    #[serde(default)]
+    pub groups: BTreeMap<String, Group>,
     /// All the questions for that template
     pub variables: Vec<Variable>,
+    /// The top-level keys this version of kickstart doesn't know, eg ones added in a later one
+    #[serde(flatten, skip_serializing)]
+    pub unknown_keys: BTreeMap<String, toml::Value>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_lockfile_name() -> String {
+    DEFAULT_LOCKFILE_NAME.to_string()
+}
+
+fn default_tests_dir() -> String {
+    DEFAULT_TESTS_DIR.to_string()
+}
+
+fn default_git_init_message() -> String {
+    "Initial commit".to_string()
+}
+
+/// The words of Tera that look like identifiers but aren't read from the context
+const TERA_KEYWORDS: [&str; 30] = [
+    "and",
+    "or",
+    "not",
+    "in",
+    "is",
+    "if",
+    "elif",
+    "else",
+    "endif",
+    "for",
+    "endfor",
+    "set",
+    "set_global",
+    "block",
+    "endblock",
+    "extends",
+    "include",
+    "import",
+    "macro",
+    "endmacro",
+    "filter",
+    "endfilter",
+    "raw",
+    "endraw",
+    "break",
+    "continue",
+    "true",
+    "false",
+    "loop",
+    "super",
+];
+
+/// The identifiers a Tera template reads from its context, as far as we can tell without parsing
+/// it: the ones of its `{{ }}` and `{% %}` blocks which are not in a string, a filter, a test, an
+/// attribute, a function call, a keyword argument, a keyword or declared by the template itself
+/// with `for`, `set` or `macro`. In the order they are first used.
+pub(crate) fn template_identifiers(template: &str) -> Vec<String> {
+    let blocks = Regex::new(r"(?s)\{\{(.*?)\}\}|\{%(.*?)%\}").unwrap();
+    let strings = Regex::new(r#""[^"]*"|'[^']*'|`[^`]*`"#).unwrap();
+    let tests_and_namespaces = Regex::new(r"\bis\s+(?:not\s+)?\w+|\b\w+::").unwrap();
+    let locals = Regex::new(
+        r"\bfor\s+(\w+)(?:\s*,\s*(\w+))?\s+in\b|\bset(?:_global)?\s+(\w+)|\bmacro\s+\w+\s*\(([^)]*)\)",
+    )
+    .unwrap();
+    let identifiers =
+        Regex::new(r"([|.]\s*)?\b([A-Za-z_][A-Za-z0-9_]*)\b(\s*(?:\(|==|=))?").unwrap();
+
+    let codes: Vec<_> = blocks
+        .captures_iter(template)
+        .map(|block| {
+            let code = block.get(1).or_else(|| block.get(2)).unwrap().as_str();
+            let code = strings.replace_all(code, "\"\"");
+            tests_and_namespaces.replace_all(&code, " ").into_owned()
+        })
+        .collect();
+
+    let mut declared: Vec<&str> = Vec::new();
+    for caps in codes.iter().flat_map(|code| locals.captures_iter(code)) {
+        declared.extend(
+            caps.get(1).into_iter().chain(caps.get(2)).chain(caps.get(3)).map(|m| m.as_str()),
+        );
+        if let Some(args) = caps.get(4) {
+            declared.extend(
+                args.as_str().split(',').filter_map(|arg| arg.split('=').next()).map(str::trim),
+            );
+        }
+    }
+
+    let mut found: Vec<String> = Vec::new();
+    for ident in codes.iter().flat_map(|code| identifiers.captures_iter(code)) {
+        let is_call = ident.get(3).is_some_and(|m| !m.as_str().ends_with("=="));
+        let name = &ident[2];
+        if ident.get(1).is_some()
+            || is_call
+            || TERA_KEYWORDS.contains(&name)
+            || declared.contains(&name)
+            || found.iter().any(|f| f == name)
+        {
+            continue;
+        }
+        found.push(name.to_string());
+    }
+    found
+}
+
+/// The variables among `names` that a Tera template uses, see [template_identifiers]
+fn template_references<'a>(template: &str, names: &[&'a str]) -> Vec<&'a str> {
+    let identifiers = template_identifiers(template);
+    names.iter().filter(|n| identifiers.iter().any(|i| i == *n)).copied().collect()
 }
 
 impl TemplateDefinition {
+    /// The hooks of every stage, in the order they run in
+    pub fn all_hooks(&self) -> impl Iterator<Item = &Hook> {
+        self.pre_prompt_hooks.iter().chain(&self.pre_gen_hooks).chain(&self.post_gen_hooks)
+    }
+
     pub(crate) fn all_hooks_paths(&self) -> Vec<String> {
-        self.pre_gen_hooks
+        self.all_hooks().map(|h| format!("{}", h.path.display())).collect()
+    }
+
+    /// The conditions that all need to be true for the variable to be asked: the `only_if` of
+    /// its group, then its own
+    pub fn variable_conditions<'a>(
+        &'a self,
+        var: &'a Variable,
+    ) -> impl Iterator<Item = &'a Condition> {
+        let group = var.group.as_ref().and_then(|g| self.groups.get(g));
+        group.and_then(|g| g.only_if.as_ref()).into_iter().chain(&var.only_if)
+    }
+
+    /// The variables each variable needs to be asked after, because its `only_if`, `computed`
+    /// or `prompt` refers to them, in declaration order.
+    /// Variables that don't exist are not included.
+    fn variable_dependencies(&self) -> Vec<Vec<usize>> {
+        let names: Vec<&str> = self.variables.iter().map(|v| v.name.as_str()).collect();
+        self.variables
             .iter()
-            .chain(self.post_gen_hooks.iter())
-            .map(|h| format!("{}", h.path.display()))
+            .map(|var| {
+                let mut deps: Vec<&str> =
+                    self.variable_conditions(var).flat_map(|c| c.variable_names()).collect();
+                if let Some(ref expr) = var.computed {
+                    deps.extend(template_references(expr, &names));
+                }
+                deps.extend(var.choices_from.iter().map(|f| f.name.as_str()));
+                // A prompt can't show its own answer, which is only an error when asking
+                let prompt_deps = template_references(&var.prompt, &names);
+                deps.extend(prompt_deps.into_iter().filter(|d| *d != var.name));
+                let mut deps: Vec<usize> =
+                    deps.iter().filter_map(|d| names.iter().position(|n| n == d)).collect();
+                deps.sort_unstable();
+                deps.dedup();
+                deps
+            })
             .collect()
     }
 
+    /// The names of the variables used by the definition itself: in conditions, computed
+    /// variables, defaults, prompts, globs and the `output_dir`. They might not exist.
+    pub(crate) fn referenced_variables(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        let conditions = self
+            .variables
+            .iter()
+            .filter_map(|v| v.only_if.as_ref())
+            .chain(self.groups.values().filter_map(|g| g.only_if.as_ref()))
+            .chain(
+                self.pre_gen_hooks
+                    .iter()
+                    .chain(&self.post_gen_hooks)
+                    .filter_map(|h| h.only_if.as_ref()),
+            )
+            .chain(self.conditional_files.iter().map(|f| &f.only_if));
+        for condition in conditions {
+            names.extend(condition.variable_names().into_iter().map(|n| n.to_string()));
+        }
+        names.extend(self.cleanup.iter().map(|c| c.name.clone()));
+        names.extend(
+            self.variables.iter().filter_map(|v| Some(v.choices_from.as_ref()?.name.clone())),
+        );
+
+        let templates = self
+            .variables
+            .iter()
+            .filter_map(|v| match (&v.computed, &v.default) {
+                (Some(expr), _) | (None, Value::String(expr)) => Some(expr),
+                _ => None,
+            })
+            .chain(self.variables.iter().map(|v| &v.prompt))
+            .chain(&self.output_dir)
+            .chain(&self.post_gen_message)
+            .chain([&self.git_init_message])
+            .chain(&self.copy_without_render)
+            .chain(self.line_endings_overrides.iter().flat_map(|o| &o.paths))
+            .chain(self.conditional_files.iter().flat_map(|f| &f.paths))
+            .chain(self.cleanup.iter().flat_map(|c| &c.paths));
+        for template in templates {
+            names.extend(template_identifiers(template));
+        }
+
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// The cycles between variables, each as the names of the variables involved with the
+    /// first one repeated at the end, eg `["a", "b", "a"]`
+    fn dependency_cycles(&self) -> Vec<Vec<&str>> {
+        fn visit(
+            node: usize,
+            deps: &[Vec<usize>],
+            state: &mut [u8],
+            stack: &mut Vec<usize>,
+            cycles: &mut Vec<Vec<usize>>,
+        ) {
+            // 0: not visited, 1: in the current path, 2: done
+            state[node] = 1;
+            stack.push(node);
+            for &dep in &deps[node] {
+                match state[dep] {
+                    0 => visit(dep, deps, state, stack, cycles),
+                    1 => {
+                        let start = stack.iter().position(|n| *n == dep).unwrap();
+                        let mut cycle = stack[start..].to_vec();
+                        cycle.push(dep);
+                        cycles.push(cycle);
+                    }
+                    _ => (),
+                }
+            }
+            stack.pop();
+            state[node] = 2;
+        }
+
+        let deps = self.variable_dependencies();
+        let mut state = vec![0; deps.len()];
+        let mut cycles = Vec::new();
+        for node in 0..deps.len() {
+            if state[node] == 0 {
+                visit(node, &deps, &mut state, &mut Vec::new(), &mut cycles);
+            }
+        }
+        cycles
+            .into_iter()
+            .map(|cycle| cycle.into_iter().map(|i| self.variables[i].name.as_str()).collect())
+            .collect()
+    }
+
+    /// Sort the variables so each one comes after the ones it depends on, keeping the
+    /// declaration order otherwise. Does nothing if there is a cycle.
+    pub(crate) fn sort_variables(&mut self) {
+        let deps = self.variable_dependencies();
+        let mut sorted = Vec::with_capacity(deps.len());
+        let mut done = vec![false; deps.len()];
+        while sorted.len() < deps.len() {
+            let ready = (0..deps.len()).find(|&i| !done[i] && deps[i].iter().all(|&d| done[d]));
+            let Some(next) = ready else {
+                return;
+            };
+            done[next] = true;
+            sorted.push(next);
+        }
+        let mut variables: Vec<_> = self.variables.drain(..).map(Some).collect();
+        self.variables = sorted.into_iter().map(|i| variables[i].take().unwrap()).collect();
+    }
+
+    /// The errors of the `choices_from` of a variable: the variable it refers to needs to exist,
+    /// not be multi and have all the values it has choices for, which need to be lists of
+    /// values of the type of the variable with its default in each of them
+    fn choices_from_errors(&self, var: &Variable, from: &ChoicesFrom) -> Vec<String> {
+        let mut errs = Vec::new();
+        if var.multi {
+            errs.push(format!(
+                "Variable `{}` is multi, which `choices_from` doesn't support",
+                var.name
+            ));
+        }
+        if !matches!(var.default, Value::String(..) | Value::Integer(..) | Value::Boolean(..)) {
+            errs.push(format!(
+                "Variable `{}` is a {} but only strings, integers and booleans can have choices",
+                var.name,
+                var.default.type_str()
+            ));
+        }
+
+        match self.variables.iter().find(|v| v.name == from.name) {
+            None => errs.push(format!(
+                "Variable `{}` takes its choices from `{}`, which is not a variable",
+                var.name, from.name
+            )),
+            Some(other) if other.multi => errs.push(format!(
+                "Variable `{}` takes its choices from `{}`, which is multi",
+                var.name, from.name
+            )),
+            Some(other) => {
+                for key in from.choices.keys() {
+                    let is_value = match other.choices {
+                        Some(ref choices) => choices.iter().any(|c| c.value().to_string() == *key),
+                        None => other.default.parse_same_type(key).is_some(),
+                    };
+                    if !is_value {
+                        errs.push(format!(
+                            "Variable `{}` has choices for `{}`, which is not a value of `{}`",
+                            var.name, key, from.name
+                        ));
+                    }
+                }
+            }
+        }
+
+        let expected_type = var.default.type_str();
+        for (key, choices) in &from.choices {
+            if choices.is_empty() {
+                errs.push(format!(
+                    "Variable `{}` has no choices for `{}` = {}",
+                    var.name, from.name, key
+                ));
+            }
+            for value in choices.iter().map(|c| c.value()) {
+                if value.type_str() != expected_type {
+                    errs.push(format!(
+                        "Variable `{}` has `{}` in its choices for `{}`, which is of type {} instead of {}",
+                        var.name,
+                        value,
+                        key,
+                        value.type_str(),
+                        expected_type
+                    ));
+                }
+            }
+        }
+
+        // The default can depend on the answer, eg `{% if provider == "aws" %}`, and needs to be
+        // one of the choices whatever it is
+        let Some(other) = self.variables.iter().find(|v| v.name == from.name) else {
+            return errs;
+        };
+        let builtins = BuiltinValues::now();
+        let mut vals = self.default_values_with(&builtins, |_| None).unwrap_or_default();
+        for (key, choices) in &from.choices {
+            let Some(answer) = other.default.parse_same_type(key) else {
+                continue;
+            };
+            vals.insert(from.name.clone(), answer);
+            let default = match var.default {
+                Value::String(ref s) => {
+                    match render_one_off_template(s, &build_context(&vals, &builtins), None) {
+                        Ok(rendered) => Value::String(rendered),
+                        // Generating fails with the reason
+                        Err(_) => continue,
+                    }
+                }
+                ref default => default.clone(),
+            };
+            if !choices.is_empty() && !choices.iter().any(|c| *c.value() == default) {
+                errs.push(format!(
+                    "Variable `{}` has `{}` as default for `{}` = {}, which isn't in its choices",
+                    var.name, default, from.name, key
+                ));
+            }
+        }
+        errs
+    }
+
     /// Go through the struct and finds all errors such as invalid globs/regex,
     /// missing/invalid default variable, bad conditions.
     /// If this returns an empty vec, this means the file is valid.
     pub fn validate(&self) -> Vec<String> {
         let mut errs = vec![];
         let mut types = HashMap::new();
+        let all_types: HashMap<_, _> =
+            self.variables.iter().map(|v| (v.name.clone(), v.default.type_str())).collect();
 
         for pattern in &self.copy_without_render {
             if let Err(e) = Pattern::new(pattern) {
@@ -123,11 +1264,36 @@ impl TemplateDefinition {
                 ));
             }
         }
+        for pattern in self.line_endings_overrides.iter().flat_map(|o| &o.paths) {
+            if let Err(e) = Pattern::new(pattern) {
+                errs.push(format!(
+                    "In line_endings_overrides, `{pattern}` is not a valid pattern: {e}"
+                ));
+            }
+        }
 
-        for hook in self.all_hooks_paths() {
-            let p = Path::new(&hook);
-            if !p.exists() {
-                errs.push(format!("Hook file `{}` was not found", hook));
+        for (i, addon) in self.addons.iter().enumerate() {
+            if self.addons[..i].iter().any(|a| a.name == addon.name) {
+                errs.push(format!("Addon `{}` is declared several times", addon.name));
+            }
+            let directory = Path::new(&addon.directory);
+            let outside = directory.is_absolute()
+                || directory.components().any(|c| matches!(c, Component::ParentDir));
+            if addon.directory.is_empty() || outside {
+                errs.push(format!(
+                    "Addon `{}` needs to be in a directory of the template, not `{}`",
+                    addon.name, addon.directory
+                ));
+            }
+        }
+
+        for (key, group) in &self.groups {
+            if !self.variables.iter().any(|v| v.group.as_ref() == Some(key)) {
+                errs.push(format!("Group `{key}` has no variables"));
+            }
+            if let Some(ref cond) = group.only_if {
+                let subject = format!("Group `{key}`");
+                errs.extend(cond.errors(&subject, false, &all_types, "which is not a variable"));
             }
         }
 
@@ -135,73 +1301,444 @@ impl TemplateDefinition {
             let type_str = var.default.type_str();
             types.insert(var.name.to_string(), type_str);
 
-            if let Some(ref choices) = var.choices {
-                if var.default.is_str() {
-                    let mut choice_found = false;
-                    for c in choices {
-                        if *c == var.default {
-                            choice_found = true;
+            if var.name == BUILTIN_CONTEXT_KEY {
+                errs.push(format!(
+                    "Variable `{}` has a reserved name, it is where kickstart puts its own values",
+                    var.name
+                ));
+            }
+
+            if var.choices.is_some() && var.choices_from.is_some() {
+                errs.push(format!(
+                    "Variable `{}` has both `choices` and `choices_from`, only one can be set",
+                    var.name
+                ));
+            }
+            if let Some(ref cmd) = var.choices_cmd {
+                if cmd.is_empty() {
+                    errs.push(format!("Variable `{}` has an empty `choices_cmd`", var.name));
+                }
+                if !var.default.is_str() || var.multi || var.choices_from.is_some() {
+                    errs.push(format!(
+                        "Variable `{}` has a `choices_cmd` but only strings that are not multi and have no `choices_from` can have one",
+                        var.name
+                    ));
+                }
+            } else if var.choices_cmd_required {
+                errs.push(format!(
+                    "Variable `{}` has `choices_cmd_required` but no `choices_cmd`",
+                    var.name
+                ));
+            }
+            if var.computed.is_some() || var.list {
+                // Checked below, the default of computed variables is only there for the type
+            } else if let Some(ref choices) = var.choices {
+                let values: Vec<&Value> = choices.iter().map(|c| c.value()).collect();
+                for (i, value) in values.iter().enumerate() {
+                    // Only reported once, on the first duplicate
+                    if values[..i].iter().filter(|v| *v == value).count() == 1 {
+                        errs.push(format!(
+                            "Variable `{}` has `{}` several times in its choices",
+                            var.name, value
+                        ));
+                    }
+                }
+                if var.multi {
+                    if let Some(defaults) = var.default.as_list() {
+                        for d in defaults {
+                            if !values.contains(&d) {
+                                errs.push(format!(
+                                    "Variable `{}` has `{}` in its default, which isn't in the choices",
+                                    var.name, d
+                                ));
+                            }
                         }
+                    } else {
+                        errs.push(format!(
+                            "Variable `{}` is multi but its default is not a list",
+                            var.name
+                        ));
+                    }
+                } else if matches!(
+                    var.default,
+                    Value::String(..) | Value::Integer(..) | Value::Boolean(..)
+                ) {
+                    if !values.contains(&&var.default) {
+                        errs.push(format!(
+                            "Variable `{}` has `{}` as default, which isn't in the choices",
+                            var.name, var.default
+                        ));
+                    }
+                } else {
+                    errs.push(format!(
+                        "Variable `{}` is a {} but only strings, integers and booleans can have choices",
+                        var.name,
+                        var.default.type_str()
+                    ));
+                }
+
+                // The type of the value, or of the items of the list for multi choices
+                let expected_type = match var.default.as_list() {
+                    Some(defaults) => defaults.first().or(values.first().copied()),
+                    None => Some(&var.default),
+                }
+                .map(|v| v.type_str());
+                if let Some(expected_type) = expected_type {
+                    for value in values.iter().filter(|v| v.type_str() != expected_type) {
+                        errs.push(format!(
+                            "Variable `{}` has `{}` in its choices, which is of type {} instead of {}",
+                            var.name,
+                            value,
+                            value.type_str(),
+                            expected_type
+                        ));
                     }
-                    if !choice_found {
+                }
+            } else if let Some(ref from) = var.choices_from {
+                errs.extend(self.choices_from_errors(var, from));
+            } else if var.multi {
+                errs.push(format!("Variable `{}` is multi but has no choices", var.name));
+            }
+
+            if var.default_from_env.is_some()
+                && !matches!(
+                    var.default,
+                    Value::String(..) | Value::Boolean(..) | Value::Integer(..)
+                )
+            {
+                errs.push(format!(
+                    "Variable `{}` has `default_from_env` but is a {}: only strings, booleans and integers are supported",
+                    var.name,
+                    var.default.type_str()
+                ));
+            }
+
+            if var.computed.is_some()
+                && (var.choices.is_some()
+                    || var.choices_from.is_some()
+                    || var.choices_cmd.is_some()
+                    || var.multi
+                    || var.validation.is_some()
+                    || var.pattern_hint.is_some()
+                    || var.min_length.is_some()
+                    || var.max_length.is_some()
+                    || var.min.is_some()
+                    || var.max.is_some()
+                    || var.secret
+                    || var.default_from_env.is_some()
+                    || var.default_from.is_some())
+            {
+                errs.push(format!(
+                    "Variable `{}` is computed so it can't have choices, a validation, a range, be secret or take its default from elsewhere",
+                    var.name
+                ));
+            } else if var.min.is_some() || var.max.is_some() {
+                match var.default {
+                    _ if var.min.zip(var.max).is_some_and(|(min, max)| min > max) => {
+                        errs.push(format!(
+                            "Variable `{}` has a `min` greater than its `max`",
+                            var.name
+                        ));
+                    }
+                    Value::Integer(i) => {
+                        if check_range(i, var.min, var.max).is_err() {
+                            errs.push(format!(
+                                "Variable `{}` has `{}` as default but it needs to be {}",
+                                var.name,
+                                i,
+                                describe_range(var.min, var.max).unwrap()
+                            ));
+                        }
+                    }
+                    _ => errs.push(format!(
+                        "Variable `{}` has a `min` or a `max` but only integers can have a range",
+                        var.name
+                    )),
+                }
+            }
+
+            if let Some(ref source) = var.default_from {
+                if !DEFAULT_FROM_SOURCES.contains(&source.as_str()) {
+                    errs.push(format!(
+                        "Variable `{}` has an unknown `default_from` `{}`, expected one of {}",
+                        var.name,
+                        source,
+                        DEFAULT_FROM_SOURCES.join(", ")
+                    ));
+                }
+                if !var.default.is_str() {
+                    errs.push(format!(
+                        "Variable `{}` has `default_from` but is a {}: only strings are supported",
+                        var.name,
+                        var.default.type_str()
+                    ));
+                }
+            }
+
+            if var.secret {
+                match var.default.as_str() {
+                    Some("") => (),
+                    Some(_) => errs.push(format!(
+                        "Variable `{}` is secret but has a default, which would be stored in the template",
+                        var.name
+                    )),
+                    None => {
+                        errs.push(format!("Variable `{}` is secret but is not a string", var.name))
+                    }
+                }
+            }
+
+            if var.list {
+                let all_strings =
+                    var.default.as_list().is_some_and(|items| items.iter().all(|i| i.is_str()));
+                if !all_strings {
+                    errs.push(format!(
+                        "Variable `{}` is a list but its default is not a list of strings",
+                        var.name
+                    ));
+                }
+                if var.multi || var.choices.is_some() || var.choices_from.is_some() {
+                    errs.push(format!(
+                        "Variable `{}` is a list so it can't have choices, use `multi` instead",
+                        var.name
+                    ));
+                }
+                if var.min_items.zip(var.max_items).is_some_and(|(min, max)| min > max) {
+                    errs.push(format!(
+                        "Variable `{}` has a `min_items` greater than its `max_items`",
+                        var.name
+                    ));
+                } else if let (Some(items), true) = (var.default.as_list(), all_strings) {
+                    if check_items(items.len(), var.min_items, var.max_items).is_err() {
+                        errs.push(format!(
+                            "Variable `{}` has a default that doesn't have {}",
+                            var.name,
+                            describe_items(var.min_items, var.max_items).unwrap()
+                        ));
+                    }
+                }
+            } else if var.min_items.is_some() || var.max_items.is_some() {
+                errs.push(format!(
+                    "Variable `{}` has a `min_items` or a `max_items` but is not a list",
+                    var.name
+                ));
+            } else if var.default.as_list().is_some() && !var.multi {
+                errs.push(format!(
+                    "Variable `{}` has a list as default but is not multi",
+                    var.name
+                ));
+            }
+
+            // Variables declared later are checked below with the other dependencies
+            if let Some(ref cond) = var.only_if {
+                let subject = format!("Variable `{}`", var.name);
+                errs.extend(cond.errors(&subject, false, &all_types, "which is not a variable"));
+            }
+            if let Some(ref group) = var.group {
+                if !self.groups.contains_key(group) {
+                    errs.push(format!(
+                        "Variable `{}` is in the group `{}`, which is not in `groups`",
+                        var.name, group
+                    ));
+                }
+            }
+
+            if !var.required {
+                let presence = format!("has_{}", var.name);
+                if !var.default.is_str()
+                    || var.computed.is_some()
+                    || var.choices.is_some()
+                    || var.choices_from.is_some()
+                    || var.choices_cmd.is_some()
+                {
+                    errs.push(format!(
+                        "Variable `{}` has `required = false` but only strings that are not computed and have no choices can be optional",
+                        var.name
+                    ));
+                } else if self.variables.iter().any(|v| v.name == presence) {
+                    errs.push(format!(
+                        "Variable `{}` is optional so templates get `{}`, which is already a variable",
+                        var.name, presence
+                    ));
+                }
+            }
+
+            let has_length = var.min_length.is_some() || var.max_length.is_some();
+            if has_length && var.computed.is_none() {
+                match var.default.as_str() {
+                    _ if var.min_length.zip(var.max_length).is_some_and(|(min, max)| min > max) => {
+                        errs.push(format!(
+                            "Variable `{}` has a `min_length` greater than its `max_length`",
+                            var.name
+                        ));
+                    }
+                    Some(default) => {
+                        let is_unset = !var.required && default.is_empty();
+                        if !is_unset
+                            && check_length(default, var.min_length, var.max_length).is_err()
+                        {
+                            errs.push(format!(
+                                "Variable `{}` has a default that isn't {}",
+                                var.name,
+                                describe_length(var.min_length, var.max_length).unwrap()
+                            ));
+                        }
+                    }
+                    None => errs.push(format!(
+                        "Variable `{}` has a `min_length` or a `max_length` but is not a string",
+                        var.name
+                    )),
+                }
+            }
+            if var.pattern_hint.is_some() && var.validation.is_none() && var.computed.is_none() {
+                errs.push(format!(
+                    "Variable `{}` has a `pattern_hint` but no validation to describe",
+                    var.name
+                ));
+            }
+
+            if let (Some(ref pattern), None) = (&var.validation, &var.computed) {
+                // The validation of lists applies to each of their entries
+                let defaults: Vec<&str> = match var.default {
+                    Value::String(ref s) => vec![s],
+                    Value::List(ref items) if var.list => {
+                        items.iter().filter_map(|i| i.as_str()).collect()
+                    }
+                    _ => {
+                        errs.push(format!(
+                            "Variable `{}` has a validation regex but is not a string",
+                            var.name
+                        ));
+                        continue;
+                    }
+                };
+                let is_unset = |default: &str| !var.required && default.is_empty();
+                match Validation::parse(pattern) {
+                    Ok(Validation::Regex(re)) => {
+                        if defaults.iter().any(|d| !is_unset(d) && !re.is_match(d)) {
+                            errs.push(format!(
+                                "Variable `{}` has a default that doesn't pass its validation regex",
+                                var.name
+                            ));
+                        }
+                    }
+                    Ok(Validation::Rule(rule)) => {
+                        if defaults.iter().any(|d| !is_unset(d) && !rule.is_valid(d)) {
+                            errs.push(format!(
+                                "Variable `{}` has a default that doesn't pass its validation: it {}",
+                                var.name,
+                                rule.description()
+                            ));
+                        }
+                    }
+                    Err(_) if Regex::new(pattern).is_ok() => {
+                        errs.push(format!(
+                            "Variable `{}` has an unknown validation rule `{}`, expected one of {}",
+                            var.name,
+                            pattern,
+                            Rule::ALL.map(|r| r.name()).join(", ")
+                        ));
+                    }
+                    Err(_) => {
+                        errs.push(format!(
+                            "Variable `{}` has an invalid validation regex: {}",
+                            var.name, pattern
+                        ));
+                    }
+                }
+            }
+        }
+
+        // Variables are passed to hooks as env vars, whose names need to be unique
+        if self.all_hooks().next().is_some() {
+            let reserved = [
+                ("OUTPUT_DIR", "the output directory"),
+                ("DEFAULTS_FILE", "the defaults of the pre-prompt hooks"),
+                ("EXTRA_CONTEXT_FILE", "the extra context of the pre-gen hooks"),
+            ];
+            let mut env_names = HashMap::new();
+            for var in &self.variables {
+                let env_name = hook_env_var_name(&var.name);
+                let reserved_for = reserved
+                    .iter()
+                    .find(|(name, _)| env_name == format!("{HOOK_ENV_PREFIX}{name}"))
+                    .map(|(_, what)| what);
+                if let Some(what) = reserved_for {
+                    errs.push(format!(
+                        "Variable `{}` would be passed to hooks as `{}`, which is reserved for {}",
+                        var.name, env_name, what
+                    ));
+                } else if let Some(other) = env_names.insert(env_name.clone(), &var.name) {
+                    if *other != var.name {
                         errs.push(format!(
-                            "Variable `{}` has `{}` as default, which isn't in the choices",
-                            var.name, var.default
+                            "Variables `{}` and `{}` would both be passed to hooks as `{}`",
+                            other, var.name, env_name
                         ));
                     }
                 }
-                else {
+            }
+        }
+
+        if !self.reorder_variables {
+            for (i, deps) in self.variable_dependencies().into_iter().enumerate() {
+                for dep in deps.into_iter().filter(|d| *d > i) {
                     errs.push(format!(
-                        "Variable `{}` is not string but has choices defined",
-                        var.name
+                        "Variable `{}` depends on `{}`, which is declared after it. Move it before or set `reorder_variables = true`",
+                        self.variables[i].name, self.variables[dep].name
                     ));
                 }
             }
+        }
+        for cycle in self.dependency_cycles() {
+            let names = cycle.iter().map(|n| format!("`{n}`")).collect::<Vec<_>>();
+            errs.push(format!("Variables {} depend on each other", names.join(" -> ")));
+        }
 
-            // Since variables are ordered, we can detect whether the only_if is referring
-            // to an unknown variable or a variable of the wrong type
-            if let Some(ref cond) = var.only_if {
-                if let Some(ref t) = types.get(&cond.name) {
-                    if **t != cond.value.type_str() {
-                        errs.push(format!(
-                            "Variable `{}` depends on `{}={}`, but the type of `{}` is {}",
-                            var.name, cond.name, cond.value, cond.name, t
-                        ));
-                    }
-                } else {
+        // Hooks run after all the questions so they can refer to any variable, but the pre-prompt
+        // ones run before any question is answered
+        for hook in self.pre_prompt_hooks.iter().filter(|h| h.only_if.is_some()) {
+            errs.push(format!(
+                "Hook `{}` runs before the questions, it can't have an `only_if`",
+                hook.name
+            ));
+        }
+
+        for hook in self.all_hooks() {
+            if hook.interpreter.as_ref().is_some_and(|i| i.is_empty()) {
+                errs.push(format!("Hook `{}` has an empty interpreter", hook.name));
+            }
+            if hook.timeout_secs == Some(0) {
+                errs.push(format!("Hook `{}` has a timeout of 0 seconds", hook.name));
+            }
+            for os in hook.os.iter().flatten() {
+                if !HOOK_OSES.contains(&os.as_str()) {
                     errs.push(format!(
-                        "Variable `{}` depends on `{}`, which wasn't asked",
-                        var.name, cond.name
+                        "Hook `{}` has an unknown os `{}`, expected one of {}",
+                        hook.name,
+                        os,
+                        HOOK_OSES.join(", ")
                     ));
                 }
             }
 
-            if let Some(ref pattern) = var.validation {
-                if !var.default.is_str() {
+            if let Some(ref cond) = hook.only_if {
+                let subject = format!("Hook `{}`", hook.name);
+                errs.extend(cond.errors(&subject, false, &types, "which is not a variable"));
+            }
+        }
+
+        for files in &self.conditional_files {
+            let paths = files.paths.join(", ");
+            for pattern in &files.paths {
+                if let Err(e) = Pattern::new(pattern) {
                     errs.push(format!(
-                        "Variable `{}` has a validation regex but is not a string",
-                        var.name
+                        "In conditional_files, `{pattern}` is not a valid pattern: {e}"
                     ));
-                    continue;
-                }
-
-                match Regex::new(pattern) {
-                    Ok(re) => {
-                        if !re.is_match(var.default.as_str().unwrap()) {
-                            errs.push(format!(
-                                "Variable `{}` has a default that doesn't pass its validation regex",
-                                var.name
-                            ));
-                        }
-                    }
-                    Err(_) => {
-                        errs.push(format!(
-                            "Variable `{}` has an invalid validation regex: {}",
-                            var.name, pattern
-                        ));
-                    }
                 }
             }
+
+            let subject = format!("Conditional files `{paths}`");
+            errs.extend(files.only_if.errors(&subject, true, &types, "which is not a variable"));
         }
 
         errs
@@ -209,46 +1746,105 @@ impl TemplateDefinition {
 
     /// Takes a path to a `template.toml` file and validates it.
     /// An Error is only returned if we couldn't load the file or the TOML wasn't valid.
+    /// The template.toml of the addons is validated as well and the hook files need to exist,
+    /// relative to the template.toml.
     pub fn validate_file<T: AsRef<Path>>(path: T) -> Result<Vec<String>> {
-        let definition: TemplateDefinition = toml::from_str(&read_file(path.as_ref())?)
-            .map_err(|err| new_error(ErrorKind::Toml { err }))?;
+        let definition = TemplateDefinition::parse(&read_file(path.as_ref())?)?;
+
+        let mut errs = definition.validate();
+        let dir = path.as_ref().parent().unwrap_or(Path::new(""));
+        // Relative to the template, not to wherever it is validated from
+        for hook in definition.all_hooks_paths() {
+            if !dir.join(&hook).exists() {
+                errs.push(format!("Hook file `{}` was not found", hook));
+            }
+        }
+        for addon in &definition.addons {
+            let addon_path = dir.join(&addon.directory).join("template.toml");
+            match TemplateDefinition::validate_file(&addon_path) {
+                Ok(addon_errs) => errs
+                    .extend(addon_errs.into_iter().map(|e| format!("Addon `{}`: {e}", addon.name))),
+                Err(e) => errs.push(format!("Addon `{}`: {e}", addon.name)),
+            }
+        }
+        Ok(errs)
+    }
+
+    /// Parse the content of a template.toml.
+    /// If its `kickstart_version` is a requirement, it is checked before anything else so
+    /// templates using features of later versions error with the version they need rather
+    /// than with what this version can't parse.
+    pub fn parse(content: &str) -> Result<TemplateDefinition> {
+        let table: toml::Table =
+            toml::from_str(content).map_err(|err| new_error(ErrorKind::Toml { err }))?;
+        if let Some(toml::Value::String(requirement)) = table.get("kickstart_version") {
+            let invalid = |reason| {
+                new_error(ErrorKind::InvalidKickstartVersion {
+                    requirement: requirement.clone(),
+                    reason,
+                })
+            };
+            let parsed = VersionReq::parse(requirement).map_err(|e| invalid(e.to_string()))?;
+            let current = env!("CARGO_PKG_VERSION");
+            let mut version = Version::parse(current).expect("the version of kickstart is valid");
+            // A pre-release of kickstart has what the templates requiring that version use
+            version.pre = Prerelease::EMPTY;
+            if !parsed.matches(&version) {
+                return Err(new_error(ErrorKind::UnsupportedKickstartVersion {
+                    required: parsed.to_string(),
+                    current: current.to_string(),
+                }));
+            }
+        }
+
+        toml::from_str(content).map_err(|err| new_error(ErrorKind::Toml { err }))
+    }
 
-        Ok(definition.validate())
+    /// Whether the variable with that name is a secret that should not be persisted anywhere
+    pub(crate) fn is_secret(&self, name: &str) -> bool {
+        self.variables.iter().any(|v| v.name == name && v.secret)
     }
 
     /// Returns the default values for all the variables that have one while following conditions
     /// TODO: probably remove that fn? see how to test things
     pub fn default_values(&self) -> Result<HashMap<String, Value>> {
+        self.default_values_with(&BuiltinValues::now(), |name| env::var(name).ok())
+    }
+
+    /// [TemplateDefinition::default_values] with the `kickstart` values of `builtins` and `env`
+    /// giving the value of an environment variable
+    pub(crate) fn default_values_with(
+        &self,
+        builtins: &BuiltinValues,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<HashMap<String, Value>> {
         let mut vals = HashMap::new();
         for var in &self.variables {
             // Skip the question if the value is different from the condition
-            if let Some(ref cond) = var.only_if {
-                if let Some(val) = vals.get(&cond.name) {
-                    if *val != cond.value {
-                        continue;
-                    }
-                } else {
-                    // Not having it means we didn't even ask the question
-                    continue;
-                }
+            if self.variable_conditions(var).any(|cond| !cond.is_met(&vals))
+                || var.missing_choices(&vals).is_some()
+            {
+                continue;
             }
 
-            match &var.default {
-                Value::Boolean(b) => {
-                    vals.insert(var.name.clone(), Value::Boolean(*b));
-                }
+            if let Some(val) = var.computed_value(&vals, builtins)? {
+                vals.insert(var.name.clone(), val);
+                continue;
+            }
+
+            if let Some(val) = var.env_default(&env).or_else(|| var.builtin_default(builtins)) {
+                vals.insert(var.name.clone(), val);
+                continue;
+            }
+
+            let default = match &var.default {
                 Value::String(s) => {
-                    let mut context = Context::new();
-                    for (key, val) in &vals {
-                        context.insert(key, val);
-                    }
-                    let rendered_default = render_one_off_template(s, &context, None)?;
-                    vals.insert(var.name.clone(), Value::String(rendered_default));
+                    let context = build_context(&vals, builtins);
+                    Value::String(render_one_off_template(s, &context, None)?)
                 }
-                Value::Integer(i) => {
-                    vals.insert(var.name.clone(), Value::Integer(*i));
-                }
-            }
+                default => default.clone(),
+            };
+            vals.insert(var.name.clone(), default);
         }
 
         Ok(vals)
@@ -257,14 +1853,26 @@ impl TemplateDefinition {
 
 #[cfg(test)]
 mod tests {
-    use toml;
-
     use super::*;
 
+    #[test]
+    fn can_convert_line_endings() {
+        let mixed = "a\r\nb\nc\r\n";
+        assert_eq!(LineEndings::Keep.apply(mixed), mixed);
+        assert_eq!(LineEndings::Lf.apply(mixed), "a\nb\nc\n");
+        assert_eq!(LineEndings::Crlf.apply(mixed), "a\r\nb\r\nc\r\n");
+        let native = if cfg!(windows) { "a\r\nb\r\nc\r\n" } else { "a\nb\nc\n" };
+        assert_eq!(LineEndings::Native.apply(mixed), native);
+        // Lone `\r` are not line endings
+        assert_eq!(LineEndings::Crlf.apply("a\rb\n"), "a\rb\r\n");
+        assert!(matches!(LineEndings::Lf.apply("a\nb"), Cow::Borrowed(_)));
+        assert!(matches!(LineEndings::Crlf.apply("a\r\nb"), Cow::Borrowed(_)));
+    }
+
     #[test]
     fn can_validate_definition() {
         insta::glob!("snapshots/validation/*.toml", |path| {
-            match TemplateDefinition::validate_file(&path) {
+            match TemplateDefinition::validate_file(path) {
                 Ok(errs) => insta::assert_debug_snapshot!(&errs),
                 Err(e) => insta::assert_snapshot!(&e),
             }
@@ -429,4 +2037,478 @@ mod tests {
 
         assert_eq!(got_value, &Value::String(expected_value))
     }
+
+    #[test]
+    fn defaults_can_come_from_env() {
+        let tpl: TemplateDefinition = toml::from_str(
+            r#"
+            name = "Test template"
+            kickstart_version = 1
+
+            [[variables]]
+            name = "project_name"
+            default = "My project"
+            default_from_env = "KICKSTART_TEST_PROJECT_NAME"
+            prompt = "What's the name of your project?"
+
+            [[variables]]
+            name = "ci"
+            default = false
+            default_from_env = "KICKSTART_TEST_CI"
+            prompt = "Running in CI?"
+
+            [[variables]]
+            name = "port"
+            default = 8080
+            default_from_env = "KICKSTART_TEST_PORT"
+            prompt = "Which port?"
+        "#,
+        )
+        .unwrap();
+        let env = HashMap::from([
+            ("KICKSTART_TEST_PROJECT_NAME", "from-env"),
+            ("KICKSTART_TEST_CI", "yes"),
+            // Not an integer so the static default is used
+            ("KICKSTART_TEST_PORT", "http"),
+        ]);
+
+        let vals = tpl
+            .default_values_with(&BuiltinValues::now(), |name| env.get(name).map(|v| v.to_string()))
+            .unwrap();
+        assert_eq!(vals["project_name"], Value::String("from-env".to_string()));
+        assert_eq!(vals["ci"], Value::Boolean(true));
+        assert_eq!(vals["port"], Value::Integer(8080));
+    }
+
+    #[test]
+    fn can_compute_variables() {
+        let tpl: TemplateDefinition = toml::from_str(
+            r#"
+            name = "Test template"
+            kickstart_version = 1
+
+            [[variables]]
+            name = "project_name"
+            default = "My Project"
+            prompt = "What's the name of your project?"
+
+            [[variables]]
+            name = "project_slug"
+            computed = "{{ project_name | slugify }}"
+
+            [[variables]]
+            name = "name_length"
+            computed = " {{ project_name | length }} "
+            type = "int"
+
+            [[variables]]
+            name = "is_long"
+            computed = "{{ name_length > 5 }}"
+            type = "bool"
+
+            [[variables]]
+            name = "short_name"
+            default = "{{ project_slug | truncate(length=2, end='') }}"
+            prompt = "Short name?"
+            only_if = { name = "is_long", value = true }
+        "#,
+        )
+        .unwrap();
+        assert!(tpl.validate().is_empty());
+
+        let vals = tpl.default_values().unwrap();
+        assert_eq!(vals["project_slug"], Value::String("my-project".to_string()));
+        assert_eq!(vals["name_length"], Value::Integer(10));
+        assert_eq!(vals["is_long"], Value::Boolean(true));
+        assert_eq!(vals["short_name"], Value::String("my".to_string()));
+    }
+
+    #[test]
+    fn computed_variables_need_a_value_of_their_type() {
+        let tpl: TemplateDefinition = toml::from_str(
+            r#"
+            name = "Test template"
+            kickstart_version = 1
+
+            [[variables]]
+            name = "port"
+            computed = "port-{{ 8000 + 80 }}"
+            type = "int"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            tpl.default_values().unwrap_err().to_string(),
+            "Value `port-8080` for variable port is not a valid integer"
+        );
+
+        for (variable, error) in [
+            (
+                "name = 'a'\ncomputed = 'b'\ndefault = 'c'",
+                "variable `a` is computed, it can't have a `default` or a `prompt`",
+            ),
+            (
+                "name = 'a'\ndefault = 'b'\nprompt = 'c'\ntype = 'string'",
+                "variable `a` has a `type` but only computed variables and lists can have one",
+            ),
+            ("name = 'a'\nprompt = 'b'", "variable `a` needs a `default`"),
+            ("name = 'a'\ncomputed = 'b'\ntype = 'float'", "unknown variant `float`"),
+        ] {
+            let content = format!("name = 't'\nkickstart_version = 1\n[[variables]]\n{variable}");
+            let err = toml::from_str::<TemplateDefinition>(&content).unwrap_err();
+            assert!(err.message().contains(error), "{}", err.message());
+        }
+    }
+
+    #[test]
+    fn checks_the_kickstart_version_required_first() {
+        let parse = |version: &str| {
+            TemplateDefinition::parse(&format!(
+                "name = 't'\nkickstart_version = '{version}'\nfrom_the_future = {{ a = 1 }}\n\
+                 variables = []\n"
+            ))
+        };
+        let tpl = parse(">=0.5").unwrap();
+        assert_eq!(tpl.kickstart_version, KickstartVersion::Requirement(">=0.5".to_string()));
+        assert_eq!(tpl.unknown_keys.keys().collect::<Vec<_>>(), ["from_the_future"]);
+
+        let err = parse(">=99").unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::UnsupportedKickstartVersion { .. }));
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "This template requires kickstart >=99, you have {}: please upgrade",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+        let err = parse("latest").unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::InvalidKickstartVersion { .. }));
+    }
+
+    #[test]
+    fn can_evaluate_conditions() {
+        #[derive(Deserialize)]
+        struct Conditions {
+            conditions: Vec<Condition>,
+        }
+        let Conditions { conditions } = toml::from_str(
+            r#"
+            conditions = [
+                { name = "database", value = "postgres" },
+                { not = { name = "database", value = "postgres" } },
+                { name = "database", value_in = ["mysql", "postgres"] },
+                { name = "workers", gt = 1, lte = 8 },
+                { name = "workers", lt = 4 },
+                { all_of = [{ name = "ci", value = true }, { name = "workers", gte = 4 }] },
+                { any_of = [{ name = "ci", value = false }, { name = "missing", value = 1 }] },
+                { not = { name = "missing", value = 1 } },
+                { name = "database", gt = 1 },
+            ]
+        "#,
+        )
+        .unwrap();
+        let vals: HashMap<_, _> = [
+            ("database", Value::String("postgres".to_string())),
+            ("workers", Value::Integer(4)),
+            ("ci", Value::Boolean(true)),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+
+        let results: Vec<_> = conditions.iter().map(|c| c.is_met(&vals)).collect();
+        assert_eq!(results, [true, false, true, true, false, true, false, true, false]);
+        assert_eq!(conditions[3].to_string(), "`workers` > 1 and `workers` <= 8");
+        assert_eq!(conditions[5].to_string(), "`ci` is true and `workers` >= 4");
+        assert_eq!(conditions[1].to_string(), "not (`database` is postgres)");
+    }
+
+    #[test]
+    fn groups_share_their_condition() {
+        let definition: TemplateDefinition = toml::from_str(
+            r#"
+            name = "Test template"
+            kickstart_version = 1
+
+            [groups.database]
+            title = "Database"
+            only_if = { name = "use_db", value = true }
+
+            [[variables]]
+            name = "use_db"
+            default = false
+            prompt = "Use a database?"
+
+            [[variables]]
+            name = "database"
+            default = "postgres"
+            prompt = "Which database?"
+            group = "database"
+
+            [[variables]]
+            name = "pool_size"
+            default = 5
+            prompt = "Pool size?"
+            group = "database"
+            only_if = { name = "database", value = "postgres" }
+
+            [[variables]]
+            name = "name"
+            default = "hello"
+            prompt = "Name?"
+        "#,
+        )
+        .unwrap();
+        assert!(definition.validate().is_empty());
+        let vals = definition.default_values().unwrap();
+        assert_eq!(vals.len(), 2);
+        assert!(vals.contains_key("use_db") && vals.contains_key("name"));
+        let pool_size = &definition.variables[2];
+        assert_eq!(definition.variable_conditions(pool_size).count(), 2);
+
+        let definition: TemplateDefinition = toml::from_str(
+            r#"
+            name = "Test template"
+            kickstart_version = 1
+
+            [groups.unused]
+            title = "Unused"
+
+            [groups.database]
+            title = "Database"
+            only_if = { name = "database", value = "postgres" }
+
+            [[variables]]
+            name = "database"
+            default = "postgres"
+            prompt = "Which database?"
+            group = "database"
+
+            [[variables]]
+            name = "port"
+            default = 5432
+            prompt = "Port?"
+            group = "db"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            definition.validate(),
+            [
+                "Group `unused` has no variables",
+                "Variable `port` is in the group `db`, which is not in `groups`",
+                "Variables `database` -> `database` depend on each other",
+            ]
+        );
+    }
+
+    #[test]
+    fn choices_can_depend_on_another_answer() {
+        let definition: TemplateDefinition = toml::from_str(
+            r#"
+            name = "Test template"
+            kickstart_version = 1
+
+            [[variables]]
+            name = "provider"
+            default = "aws"
+            prompt = "Cloud provider?"
+            choices = ["aws", "gcp", "none"]
+
+            [[variables]]
+            name = "region"
+            default = "{% if provider == 'aws' %}us-east-1{% else %}europe-west1{% endif %}"
+            prompt = "Region?"
+            choices_from = { name = "provider", choices = { aws = ["us-east-1", "eu-west-1"], gcp = ["us-east1", "europe-west1"] } }
+        "#,
+        )
+        .unwrap();
+        assert!(definition.validate().is_empty());
+        let vals = definition.default_values().unwrap();
+        assert_eq!(vals["region"], Value::String("us-east-1".to_string()));
+
+        let region = &definition.variables[1];
+        let mut vals = HashMap::from([("provider".to_string(), Value::String("gcp".into()))]);
+        assert_eq!(region.choices_for(&vals).map(|c| c.len()), Some(2));
+        assert!(region.check_answer(&Value::String("europe-west1".into()), &vals).is_ok());
+        assert!(region.check_answer(&Value::String("us-east-1".into()), &vals).is_err());
+        assert!(region.missing_choices(&vals).is_none());
+        vals.insert("provider".to_string(), Value::String("none".into()));
+        assert!(region.missing_choices(&vals).is_some());
+
+        let definition: TemplateDefinition = toml::from_str(
+            r#"
+            name = "Test template"
+            kickstart_version = 1
+
+            [[variables]]
+            name = "provider"
+            default = "aws"
+            prompt = "Cloud provider?"
+            choices = ["aws", "gcp"]
+
+            [[variables]]
+            name = "region"
+            default = "eu-central-1"
+            prompt = "Region?"
+            choices_from = { name = "provider", choices = { aws = ["us-east-1", 1], azure = [], gcp = ["eu-central-1"] } }
+
+            [[variables]]
+            name = "zone"
+            default = "a"
+            prompt = "Zone?"
+            choices_from = { name = "size", choices = { small = ["a"] } }
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            definition.validate(),
+            [
+                "Variable `region` has choices for `azure`, which is not a value of `provider`",
+                "Variable `region` has `1` in its choices for `aws`, which is of type integer instead of string",
+                "Variable `region` has no choices for `provider` = azure",
+                "Variable `region` has `eu-central-1` as default for `provider` = aws, which isn't in its choices",
+                "Variable `zone` takes its choices from `size`, which is not a variable",
+            ]
+        );
+    }
+
+    #[test]
+    fn can_have_list_variables() {
+        let definition: TemplateDefinition = toml::from_str(
+            r#"
+            name = "Test template"
+            kickstart_version = 1
+
+            [[variables]]
+            name = "members"
+            type = "list"
+            default = ["core", "cli"]
+            prompt = "Members?"
+            validation = "^[a-z]+$"
+            min_items = 1
+            max_items = 3
+        "#,
+        )
+        .unwrap();
+        assert!(definition.validate().is_empty());
+        let members = &definition.variables[0];
+        assert!(members.list);
+        let list = |items: &[&str]| {
+            Value::List(items.iter().map(|i| Value::String(i.to_string())).collect())
+        };
+        assert!(members.check_value(&list(&["a", "b"])).is_ok());
+        assert_eq!(
+            members.check_value(&list(&[])).unwrap_err(),
+            "The list must have between 1 and 3 entries"
+        );
+        assert!(members.check_value(&list(&["Nope"])).unwrap_err().starts_with("`Nope`: "));
+        assert!(members.check_value(&Value::List(vec![Value::Integer(1)])).is_err());
+
+        let definition: TemplateDefinition = toml::from_str(
+            r#"
+            name = "Test template"
+            kickstart_version = 1
+
+            [[variables]]
+            name = "members"
+            type = "list"
+            default = ["core", 1]
+            prompt = "Members?"
+            choices = ["core"]
+
+            [[variables]]
+            name = "crates"
+            type = "list"
+            default = ["Core"]
+            prompt = "Crates?"
+            validation = "^[a-z]+$"
+            max_items = 0
+
+            [[variables]]
+            name = "name"
+            default = "hello"
+            prompt = "Name?"
+            min_items = 1
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            definition.validate(),
+            [
+                "Variable `members` is a list but its default is not a list of strings",
+                "Variable `members` is a list so it can't have choices, use `multi` instead",
+                "Variable `crates` has a default that doesn't have at most 0 entries",
+                "Variable `crates` has a default that doesn't pass its validation regex",
+                "Variable `name` has a `min_items` or a `max_items` but is not a list",
+            ]
+        );
+    }
+
+    #[test]
+    fn can_find_the_variables_used_in_templates() {
+        let names = ["name", "length", "slug", "other"];
+        assert_eq!(
+            template_references(
+                "{{ name | length }}-{{ slug ~ 'other' }}{% if other == name %}{% endif %}",
+                &names
+            ),
+            ["name", "slug", "other"]
+        );
+        assert_eq!(
+            template_references(
+                "{{ kickstart.name }} {{ truncate(length=2) }} {{ 'slug' }} name",
+                &names
+            ),
+            Vec::<&str>::new()
+        );
+    }
+
+    #[test]
+    fn can_find_the_identifiers_of_templates() {
+        let template = r#"
+{% for dep, version in dependencies %}{{ dep }} = "{{ version }}"{% endfor %}
+{% set full = name ~ suffix %}{{ full }} {{ loop.index }}
+{% if license is defined and license is not starting_with("MIT") %}{{ macros::badge(x=1) }}{% endif %}
+{% macro row(cell, width=2) %}{{ cell }}{{ width }}{% endmacro %}
+{{ kickstart.year }}"#;
+        assert_eq!(
+            template_identifiers(template),
+            ["dependencies", "name", "suffix", "license", "kickstart"]
+        );
+    }
+
+    #[test]
+    fn can_reorder_variables() {
+        let mut tpl: TemplateDefinition = toml::from_str(
+            r#"
+            name = "Test template"
+            kickstart_version = 1
+            reorder_variables = true
+
+            [[variables]]
+            name = "pg_version"
+            default = "16"
+            prompt = "Which version of Postgres?"
+            only_if = { name = "database", value = "postgres" }
+
+            [[variables]]
+            name = "project_name"
+            default = "My project"
+            prompt = "What's the name of your project?"
+
+            [[variables]]
+            name = "database"
+            default = "postgres"
+            prompt = "Which database?"
+        "#,
+        )
+        .unwrap();
+        assert!(tpl.validate().is_empty());
+
+        tpl.sort_variables();
+        let names: Vec<_> = tpl.variables.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["project_name", "database", "pg_version"]);
+        assert_eq!(tpl.default_values().unwrap()["pg_version"], Value::String("16".to_string()));
+    }
 }
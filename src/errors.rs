@@ -1,8 +1,21 @@
+//! The errors of the library: a single [Error] type whose [ErrorKind] can be matched on to
+//! tell failures apart, eg a missing template.toml from a cloning or rendering failure.
+//!
+//! ```
+//! use std::path::Path;
+//! use kickstart::errors::ErrorKind;
+//! use kickstart::Template;
+//!
+//! let err = Template::from_local(Path::new("does-not-exist"), None).unwrap_err();
+//! assert!(matches!(err.kind, ErrorKind::MissingTemplateDefinition));
+//! ```
 use std::fmt;
 use std::io;
 use std::path::{Path, PathBuf};
 use std::result;
 
+use crate::{GitRef, TemplateCandidate};
+
 /// A crate private constructor for `Error`.
 pub(crate) fn new_error(kind: ErrorKind) -> Error {
     Error { kind, source: None }
@@ -29,13 +42,166 @@ pub struct Error {
 #[non_exhaustive]
 pub enum ErrorKind {
     MissingTemplateDefinition,
+    /// There is no template.toml but several templates were found in subdirectories
+    MultipleTemplates(Vec<TemplateCandidate>),
     InvalidTemplate,
     UnreadableStdin,
+    /// A question needs to be asked but stdin is not a terminal
+    NotInteractive,
     InvalidVariableName(String),
+    /// A value given for a variable couldn't be converted to that variable type
+    InvalidVariableValue {
+        name: String,
+        value: String,
+        expected: &'static str,
+    },
+    /// A variable that needs a value didn't get one from a non-interactive source
+    MissingVariable(String),
+    /// Values that were not asked interactively don't pass the constraints of their variable,
+    /// eg its `choices` or `validation`, as the name of the variable and what is wrong
+    ConstraintViolations(Vec<(String, String)>),
+    /// A value was given for a variable that is computed from the others
+    ComputedVariable(String),
+    /// A file of the template uses a variable that doesn't exist.
+    /// `path` is relative to the template and the position is only there if we could find it.
+    UndefinedVariable {
+        name: String,
+        path: PathBuf,
+        position: Option<(usize, usize)>,
+    },
+    /// A file containing the answers to the questions couldn't be used
+    InvalidInputFile {
+        format: &'static str,
+        path: PathBuf,
+        reason: String,
+    },
+    /// The generation was stopped on request
+    Aborted,
+    /// The user gave that many invalid answers in a row to a question
+    TooManyAttempts(usize),
+    /// Generating would overwrite those files in the output directory
+    ExistingFiles(Vec<PathBuf>),
+    /// A lockfile couldn't be parsed
+    InvalidLockfile {
+        path: PathBuf,
+        reason: String,
+    },
     /// An error while cloning a repository
     Git {
         err: io::Error,
     },
+    /// The repository could be reached but doesn't have that branch, tag or commit
+    GitRefNotFound {
+        remote: String,
+        git_ref: GitRef,
+    },
+    /// `git clone` failed, eg because of the network
+    GitClone {
+        remote: String,
+        reason: String,
+    },
+    /// Fetching the latest commit of a cached template failed
+    GitFetch {
+        remote: String,
+        reason: String,
+    },
+    /// The template is used offline but was never cloned
+    NotCached {
+        remote: String,
+    },
+    /// The config file of the user couldn't be parsed
+    InvalidConfig {
+        path: PathBuf,
+        reason: String,
+    },
+    /// The `output_dir` of the template doesn't render to a single directory name
+    InvalidOutputDir {
+        rendered: String,
+        reason: &'static str,
+    },
+    /// An archive template couldn't be downloaded
+    Download {
+        url: String,
+        reason: String,
+    },
+    /// An archive template couldn't be extracted or had entries outside of it
+    InvalidArchive {
+        archive: String,
+        reason: String,
+    },
+    /// The generated project couldn't be written to an archive
+    WriteArchive {
+        archive: PathBuf,
+        reason: String,
+    },
+    /// There is no template.toml in the extracted archive, or in its `directory`
+    NoTemplateInArchive {
+        archive: String,
+        directory: Option<String>,
+    },
+    /// A cookiecutter template uses something kickstart can't translate, eg Jinja that Tera
+    /// can't parse. `path` is relative to the template.
+    UnsupportedCookiecutter {
+        path: PathBuf,
+        reason: String,
+    },
+    /// A pattern of a `.kickstartignore` file, or given to [crate::Template::set_excludes] if
+    /// there is no `path`, is not valid. `path` is relative to the template.
+    InvalidIgnorePattern {
+        path: Option<PathBuf>,
+        reason: String,
+    },
+    /// None of the files of the template match the globs given to
+    /// [crate::Template::set_only], with the paths closest to them
+    NoMatchingPaths {
+        patterns: Vec<String>,
+        closest: Vec<String>,
+    },
+    /// The `kickstart_version` of a template is not a valid version requirement
+    InvalidKickstartVersion {
+        requirement: String,
+        reason: String,
+    },
+    /// The template requires other versions of kickstart than the one running
+    UnsupportedKickstartVersion {
+        required: String,
+        current: String,
+    },
+    /// An addon given to [crate::Template::select_addons] doesn't exist or couldn't be loaded
+    Addon {
+        name: String,
+        reason: String,
+    },
+    /// The `choices_cmd` of a variable couldn't give its choices and it has no `choices` to use
+    /// instead, or has `choices_cmd_required`
+    ChoicesCommand {
+        name: String,
+        reason: String,
+    },
+    /// A pre-prompt hook wrote defaults that are not a JSON object of values of the type of the
+    /// variables
+    HookDefaults {
+        hook: String,
+        reason: String,
+    },
+    /// A pre-gen hook wrote extra context that is not a JSON object of values, or while the
+    /// template has a variable named `extra`
+    HookExtraContext {
+        hook: String,
+        reason: String,
+    },
+    /// An argument of a hook couldn't be rendered, `index` starting at 0
+    HookArgument {
+        hook: String,
+        index: usize,
+        arg: String,
+        reason: String,
+    },
+    /// A git command failed while creating the repository of the generated project
+    GitInit {
+        command: String,
+        reason: String,
+    },
     /// An error while doing IO (reading/writing files)
     Io {
         err: io::Error,
@@ -51,6 +217,14 @@ pub enum ErrorKind {
     Toml {
         err: toml::de::Error,
     },
+    /// A path of the template renders to something that can't be used, eg because it is
+    /// outside of the output directory
+    InvalidRenderedPath {
+        /// The path in the template, before rendering
+        path: PathBuf,
+        rendered: String,
+        reason: &'static str,
+    },
     /// A glob pattern couldn't be built from the input
     InvalidGlobPattern {
         pattern_before_rendering: String,
@@ -59,6 +233,57 @@ pub enum ErrorKind {
     },
 }
 
+impl ErrorKind {
+    /// The name of the variant, eg `InvalidVariableName`, to tell errors apart outside of Rust
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::MissingTemplateDefinition => "MissingTemplateDefinition",
+            ErrorKind::MultipleTemplates(..) => "MultipleTemplates",
+            ErrorKind::InvalidTemplate => "InvalidTemplate",
+            ErrorKind::UnreadableStdin => "UnreadableStdin",
+            ErrorKind::NotInteractive => "NotInteractive",
+            ErrorKind::InvalidVariableName(..) => "InvalidVariableName",
+            ErrorKind::InvalidVariableValue { .. } => "InvalidVariableValue",
+            ErrorKind::MissingVariable(..) => "MissingVariable",
+            ErrorKind::ConstraintViolations(..) => "ConstraintViolations",
+            ErrorKind::ComputedVariable(..) => "ComputedVariable",
+            ErrorKind::UndefinedVariable { .. } => "UndefinedVariable",
+            ErrorKind::InvalidInputFile { .. } => "InvalidInputFile",
+            ErrorKind::Aborted => "Aborted",
+            ErrorKind::TooManyAttempts(..) => "TooManyAttempts",
+            ErrorKind::ExistingFiles(..) => "ExistingFiles",
+            ErrorKind::InvalidLockfile { .. } => "InvalidLockfile",
+            ErrorKind::Git { .. } => "Git",
+            ErrorKind::GitRefNotFound { .. } => "GitRefNotFound",
+            ErrorKind::GitClone { .. } => "GitClone",
+            ErrorKind::GitFetch { .. } => "GitFetch",
+            ErrorKind::NotCached { .. } => "NotCached",
+            ErrorKind::InvalidConfig { .. } => "InvalidConfig",
+            ErrorKind::InvalidOutputDir { .. } => "InvalidOutputDir",
+            ErrorKind::Download { .. } => "Download",
+            ErrorKind::InvalidArchive { .. } => "InvalidArchive",
+            ErrorKind::WriteArchive { .. } => "WriteArchive",
+            ErrorKind::NoTemplateInArchive { .. } => "NoTemplateInArchive",
+            ErrorKind::UnsupportedCookiecutter { .. } => "UnsupportedCookiecutter",
+            ErrorKind::InvalidIgnorePattern { .. } => "InvalidIgnorePattern",
+            ErrorKind::NoMatchingPaths { .. } => "NoMatchingPaths",
+            ErrorKind::InvalidKickstartVersion { .. } => "InvalidKickstartVersion",
+            ErrorKind::UnsupportedKickstartVersion { .. } => "UnsupportedKickstartVersion",
+            ErrorKind::Addon { .. } => "Addon",
+            ErrorKind::ChoicesCommand { .. } => "ChoicesCommand",
+            ErrorKind::HookDefaults { .. } => "HookDefaults",
+            ErrorKind::HookExtraContext { .. } => "HookExtraContext",
+            ErrorKind::HookArgument { .. } => "HookArgument",
+            ErrorKind::GitInit { .. } => "GitInit",
+            ErrorKind::Io { .. } => "Io",
+            ErrorKind::Tera { .. } => "Tera",
+            ErrorKind::Toml { .. } => "Toml",
+            ErrorKind::InvalidRenderedPath { .. } => "InvalidRenderedPath",
+            ErrorKind::InvalidGlobPattern { .. } => "InvalidGlobPattern",
+        }
+    }
+}
+
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Error {
         new_error(ErrorKind::Io { err, path: PathBuf::new() })
@@ -76,13 +301,110 @@ impl fmt::Display for Error {
         match self.kind {
             ErrorKind::Io { ref err, ref path } => write!(f, "{}: {:?}", err, path),
             ErrorKind::Tera { ref err, ref path } => {
-                if let Some(p) = path {
-                    write!(f, "{}: {:?}", err, p)
-                } else {
-                    write!(f, "{}: rendering a one-off template", err)
+                let name = match path {
+                    Some(p) => format!("`{}`", p.display()),
+                    None => "a one-off template".to_string(),
+                };
+                let name_template =
+                    |e: &dyn std::error::Error| e.to_string().replace("'__tera_one_off'", &name);
+                write!(f, "{}", name_template(err))?;
+                // The details, eg the position of a syntax error, are in the causes
+                let mut cause = std::error::Error::source(err);
+                while let Some(e) = cause {
+                    write!(f, "\n{}", name_template(e))?;
+                    cause = e.source();
                 }
+                Ok(())
             }
             ErrorKind::Git { ref err } => write!(f, "Could not clone the repository: {}", err),
+            ErrorKind::GitRefNotFound { ref remote, ref git_ref } => {
+                write!(f, "The {} was not found in {}", git_ref, remote)
+            }
+            ErrorKind::GitClone { ref remote, ref reason } => {
+                write!(f, "Could not clone {}: {}", remote, reason)
+            }
+            ErrorKind::GitFetch { ref remote, ref reason } => {
+                write!(f, "Could not update the cached clone of {}: {}", remote, reason)
+            }
+            ErrorKind::NotCached { ref remote } => {
+                write!(f, "{} was never cloned so it can't be used offline", remote)
+            }
+            ErrorKind::InvalidConfig { ref path, ref reason } => {
+                write!(f, "Invalid config file {}: {}", path.display(), reason)
+            }
+            ErrorKind::InvalidOutputDir { ref rendered, reason } => {
+                write!(
+                    f,
+                    "The `output_dir` of the template renders to `{}`, which {}",
+                    rendered, reason
+                )
+            }
+            ErrorKind::Download { ref url, ref reason } => {
+                write!(f, "Could not download {}: {}", url, reason)
+            }
+            ErrorKind::InvalidArchive { ref archive, ref reason } => {
+                write!(f, "Could not extract {}: {}", archive, reason)
+            }
+            ErrorKind::WriteArchive { ref archive, ref reason } => {
+                write!(f, "Could not write the archive {}: {}", archive.display(), reason)
+            }
+            ErrorKind::NoTemplateInArchive { ref archive, directory: Some(ref directory) } => {
+                write!(f, "No template.toml found in the `{}` directory of {}", directory, archive)
+            }
+            ErrorKind::NoTemplateInArchive { ref archive, directory: None } => {
+                write!(f, "No template.toml found in {}", archive)
+            }
+            ErrorKind::UnsupportedCookiecutter { ref path, ref reason } => {
+                write!(f, "Unsupported cookiecutter feature in `{}`: {}", path.display(), reason)
+            }
+            ErrorKind::InvalidIgnorePattern { path: Some(ref path), ref reason } => {
+                write!(f, "Invalid pattern in `{}`: {}", path.display(), reason)
+            }
+            ErrorKind::InvalidIgnorePattern { path: None, ref reason } => {
+                write!(f, "Invalid exclusion: {}", reason)
+            }
+            ErrorKind::InvalidKickstartVersion { ref requirement, ref reason } => {
+                write!(
+                    f,
+                    "The `kickstart_version` `{}` is not a valid version requirement: {}",
+                    requirement, reason
+                )
+            }
+            ErrorKind::UnsupportedKickstartVersion { ref required, ref current } => {
+                write!(
+                    f,
+                    "This template requires kickstart {}, you have {}: please upgrade",
+                    required, current
+                )
+            }
+            ErrorKind::Addon { ref name, ref reason } => write!(f, "Addon `{}`: {}", name, reason),
+            ErrorKind::ChoicesCommand { ref name, ref reason } => {
+                write!(f, "Could not get the choices of `{}`: its `choices_cmd` {}", name, reason)
+            }
+            ErrorKind::HookDefaults { ref hook, ref reason } => {
+                write!(f, "The hook `{}` gave invalid defaults: {}", hook, reason)
+            }
+            ErrorKind::HookExtraContext { ref hook, ref reason } => {
+                write!(f, "The hook `{}` gave invalid extra context: {}", hook, reason)
+            }
+            ErrorKind::HookArgument { ref hook, index, ref arg, ref reason } => write!(
+                f,
+                "Could not render the argument {} of the hook `{}`, `{}`: {}",
+                index, hook, arg, reason
+            ),
+            ErrorKind::GitInit { ref command, ref reason } => {
+                write!(f, "Could not create the git repository, `{}` failed: {}", command, reason)
+            }
+            ErrorKind::NoMatchingPaths { ref patterns, ref closest } => {
+                write!(f, "No file of the template matches `{}`", patterns.join("`, `"))?;
+                if !closest.is_empty() {
+                    write!(f, ", the closest ones are:")?;
+                    for path in closest {
+                        write!(f, "\n- {path}")?;
+                    }
+                }
+                Ok(())
+            }
             ErrorKind::Toml { ref err } => write!(f, "Invalid TOML: {}", err),
             ErrorKind::InvalidGlobPattern {
                 ref err,
@@ -99,11 +421,88 @@ impl fmt::Display for Error {
                     write!(f, "Invalid glob pattern `{}`: {}", pattern_before_rendering, err)
                 }
             }
+            ErrorKind::InvalidRenderedPath { ref path, ref rendered, reason } => {
+                write!(
+                    f,
+                    "The template path `{}` renders to `{}`, which {}",
+                    path.display(),
+                    rendered,
+                    reason
+                )
+            }
             ErrorKind::InvalidVariableName(ref name) => {
                 write!(f, "Variable {name} not found in the template definition")
             }
+            ErrorKind::InvalidVariableValue { ref name, ref value, expected } => {
+                write!(f, "Value `{value}` for variable {name} is not a valid {expected}")
+            }
+            ErrorKind::ConstraintViolations(ref violations) => {
+                write!(f, "Some values are not allowed by the template:")?;
+                for (name, reason) in violations {
+                    write!(f, "\n- {name}: {reason}")?;
+                }
+                Ok(())
+            }
+            ErrorKind::MissingVariable(ref name) => {
+                write!(f, "Variable {name} is required but no value was given for it")
+            }
+            ErrorKind::UndefinedVariable { ref name, ref path, position } => {
+                write!(f, "{}", path.display())?;
+                if let Some((line, column)) = position {
+                    write!(f, ":{line}:{column}")?;
+                }
+                write!(
+                    f,
+                    ": the variable `{name}` is not defined. Set `lenient_rendering = true` in the template.toml if it is on purpose"
+                )
+            }
+            ErrorKind::ComputedVariable(ref name) => {
+                write!(f, "Variable {name} is computed from the other variables, it can't be given a value")
+            }
+            ErrorKind::InvalidInputFile { format, ref path, ref reason } => {
+                write!(f, "Invalid {format} input file {path:?}: {reason}")
+            }
+            ErrorKind::Aborted => write!(f, "Aborted"),
+            ErrorKind::TooManyAttempts(max) => {
+                write!(f, "Too many invalid answers ({max} in a row)")
+            }
+            ErrorKind::ExistingFiles(ref paths) => {
+                write!(
+                    f,
+                    "The following files already exist in the output directory:\n{}",
+                    paths
+                        .iter()
+                        .map(|p| format!("- {}", p.display()))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                )
+            }
+            ErrorKind::InvalidLockfile { ref path, ref reason } => {
+                write!(f, "Invalid lockfile {path:?}: {reason}")
+            }
             ErrorKind::MissingTemplateDefinition => write!(f, "The template.toml is missing"),
+            ErrorKind::MultipleTemplates(ref candidates) => {
+                write!(
+                    f,
+                    "There is no template.toml but several templates, choose one with --directory:"
+                )?;
+                for c in candidates {
+                    match c.description {
+                        Some(ref description) => {
+                            write!(f, "\n  - {}: {}", c.directory, description)?
+                        }
+                        None => write!(f, "\n  - {}", c.directory)?,
+                    }
+                }
+                Ok(())
+            }
             ErrorKind::UnreadableStdin => write!(f, "Unable to read from stdin"),
+            ErrorKind::NotInteractive => write!(
+                f,
+                "Questions need to be asked but stdin is not a terminal: use --no-input to use \
+                the defaults, --input-file or --define to give the answers, or --assume-tty if \
+                it is a terminal"
+            ),
             ErrorKind::InvalidTemplate => write!(f, "The template.toml is invalid"),
         }
     }
@@ -3,8 +3,11 @@ use std::collections::HashMap;
 use heck::*;
 use tera::{to_value, try_get_value, Result, Tera, Value};
 
+/// Register the case conversion filters on `tera`.
+/// `slugify` is not in there as it is one of the Tera built-in filters.
 pub fn register_all_filters(tera: &mut Tera) {
     tera.register_filter("upper_camel_case", upper_camel_case);
+    tera.register_filter("pascal_case", upper_camel_case);
     tera.register_filter("camel_case", camel_case);
     tera.register_filter("snake_case", snake_case);
     tera.register_filter("kebab_case", kebab_case);
@@ -47,3 +50,47 @@ pub fn shouty_kebab_case(value: &Value, _: &HashMap<String, Value>) -> Result<Va
     let s = try_get_value!("shouty_kebab_case", "value", String, value);
     Ok(to_value(s.to_shouty_kebab_case()).unwrap())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tera::Context;
+
+    fn render(filter: &str, value: &str) -> String {
+        let mut tera = Tera::default();
+        register_all_filters(&mut tera);
+        let mut context = Context::new();
+        context.insert("value", value);
+        tera.render_str(&format!("{{{{ value | {filter} }}}}"), &context).unwrap()
+    }
+
+    #[test]
+    fn can_convert_cases() {
+        let cases = [
+            ("snake_case", "my_new_project"),
+            ("kebab_case", "my-new-project"),
+            ("camel_case", "myNewProject"),
+            ("pascal_case", "MyNewProject"),
+            ("upper_camel_case", "MyNewProject"),
+            ("shouty_snake_case", "MY_NEW_PROJECT"),
+            ("shouty_kebab_case", "MY-NEW-PROJECT"),
+            ("title_case", "My New Project"),
+        ];
+        for (filter, expected) in cases {
+            for input in ["my new project", "MyNewProject", "my-new_project"] {
+                assert_eq!(render(filter, input), expected, "{filter} of {input}");
+            }
+        }
+    }
+
+    #[test]
+    fn can_convert_unicode() {
+        assert_eq!(render("snake_case", "Crème Brûlée"), "crème_brûlée");
+        assert_eq!(render("pascal_case", "straße bahn"), "StraßeBahn");
+        assert_eq!(render("kebab_case", "日本語 テンプレート"), "日本語-テンプレート");
+        assert_eq!(render("slugify", "Crème Brûlée"), "creme-brulee");
+        assert_eq!(render("slugify", "Ünïcödé ✨ Project"), "unicode-sparkles-project");
+        assert_eq!(render("slugify", "my new_project"), "my-new-project");
+        assert_eq!(render("title_case", ""), "");
+    }
+}
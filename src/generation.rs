@@ -1,24 +1,52 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::fmt;
 use std::fs::{self, File};
-use std::io::{Read, Write};
+use std::io::{Read, Seek, Write};
+use std::iter;
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
-use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::result;
+use std::slice;
 use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
 
 use glob::Pattern;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use log::{debug, warn};
+use regex::Regex;
+use serde::{Serialize, Serializer};
 use tempfile::{tempdir, TempDir};
 use tera::Context;
 use walkdir::WalkDir;
 
-use crate::definition::{Hook, TemplateDefinition};
-use crate::errors::{map_io_err, new_error, ErrorKind, Result};
+use crate::archive;
+use crate::cache;
+use crate::cookiecutter;
+use crate::definition::{template_identifiers, Condition, Hook, LineEndings, TemplateDefinition};
+use crate::errors::{map_io_err, new_error, Error, ErrorKind, Result};
+use crate::lockfile::Lockfile;
 use crate::utils::{
-    create_directory, get_source, is_binary, read_file, render_one_off_template, write_file, Source,
+    build_context, command_line, could_need_rendering, create_directory, edit_distance,
+    find_variable_use, get_source, hash_content, hash_file, hook_env_var_name, is_binary,
+    read_file, render_one_off_template, repo_name, undefined_variable, write_file, BuiltinValues,
+    Source, BUILTIN_CONTEXT_KEY, EXTRA_CONTEXT_KEY, HOOK_ENV_PREFIX,
 };
-use crate::{Value, Variable};
+use crate::{Choice, Value, Variable};
+
+/// A file marking a directory of the template that should be created even if it is empty,
+/// as git doesn't keep empty directories. It is not copied itself.
+pub const KEEP_FILE_NAME: &str = ".kickstart-keep";
+
+/// A file listing paths of the template that are never generated, with the syntax of a
+/// `.gitignore`. It applies to the directory it is in, like git, and is not copied itself.
+pub const IGNORE_FILE_NAME: &str = ".kickstartignore";
 
 /// Contains information about a given hook: what's the original path and what's the path
 /// to the templated version
@@ -42,8 +70,347 @@ impl HookFile {
     pub fn path(&self) -> &Path {
         &self.path
     }
+
+    /// How long the hook can run for, if it has a timeout
+    pub fn timeout(&self) -> Option<Duration> {
+        self.hook.timeout_secs.map(Duration::from_secs)
+    }
+
+    /// Whether the output of the hook should only be shown if it fails
+    pub fn is_quiet(&self) -> bool {
+        self.hook.quiet
+    }
+
+    /// Whether failing should only be a warning rather than stop everything
+    pub fn allows_failure(&self) -> bool {
+        self.hook.allow_failure
+    }
+
+    /// Whether it can run at the same time as the other `parallel` hooks next to it
+    pub fn is_parallel(&self) -> bool {
+        self.hook.parallel
+    }
+
+    /// The file a pre-prompt hook can write the defaults of some variables to, as a JSON
+    /// object. It is given to it as `KICKSTART_DEFAULTS_FILE`, see
+    /// [Template::load_hook_defaults].
+    pub fn defaults_file(&self) -> PathBuf {
+        PathBuf::from(format!("{}.defaults.json", self.path.display()))
+    }
+
+    /// The file a pre-gen hook can write values the templates need but the user isn't asked for
+    /// to, as a JSON object. It is given to it as `KICKSTART_EXTRA_CONTEXT_FILE`, see
+    /// [Template::load_extra_context].
+    pub fn extra_context_file(&self) -> PathBuf {
+        PathBuf::from(format!("{}.extra-context.json", self.path.display()))
+    }
+
+    /// The program and its arguments to run the hook with, if any. The hook path needs to be
+    /// appended to it.
+    pub fn interpreter(&self) -> Option<&[String]> {
+        self.hook.interpreter.as_deref().filter(|i| !i.is_empty())
+    }
+}
+
+/// What [Template::git_init] did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitInit {
+    /// The repository was created, with the hash of its first commit
+    Committed(String),
+    /// Nothing was done, for that reason, eg git not being installed
+    Skipped(String),
+}
+
+/// What to check out when cloning a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitRef {
+    Branch(String),
+    Tag(String),
+    /// Anything `git checkout` accepts, eg a commit hash
+    Rev(String),
+}
+
+impl fmt::Display for GitRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GitRef::Branch(name) => write!(f, "branch `{name}`"),
+            GitRef::Tag(name) => write!(f, "tag `{name}`"),
+            GitRef::Rev(rev) => write!(f, "`{rev}`"),
+        }
+    }
+}
+
+/// How to clone a template from a git repository
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CloneOptions {
+    /// The branch, tag or commit to check out, the default branch otherwise
+    pub git_ref: Option<GitRef>,
+    /// Clone the whole history instead of only the last commit and check out everything
+    /// rather than only the directory of the template, eg for hooks that need them
+    pub full_clone: bool,
+    /// Where to keep the clones to reuse them next time, see [cache::default_dir].
+    /// Templates are cloned in the temporary directory every time otherwise.
+    pub cache_dir: Option<PathBuf>,
+    /// Use the cached clone as is instead of fetching the latest commit, failing if the
+    /// template was never cloned
+    pub offline: bool,
+    /// Clone again even if the template is cached
+    pub refresh: bool,
+}
+
+/// Use git command rather than git2 as it seems there are some issues building it
+/// on some platforms:
+/// https://www.reddit.com/r/rust/comments/92mbk5/kickstart_a_scaffolding_tool_to_get_new_projects/e3ahegw
+fn git(repo: &Path, args: &[&str]) -> Result<Output> {
+    debug!("Running `git {}` in {}", args.join(" "), repo.display());
+    Command::new("git")
+        .args(args)
+        .current_dir(repo)
+        .output()
+        .map_err(|err| new_error(ErrorKind::Git { err }))
+}
+
+/// Clone `remote` in `dest`, only getting the last commit unless `full` and only checking out
+/// the top-level files if `sparse`
+fn git_clone(
+    remote: &str,
+    dest: &Path,
+    git_ref: Option<&GitRef>,
+    full: bool,
+    sparse: bool,
+) -> Result<Output> {
+    let mut clone = Command::new("git");
+    // A repository that doesn't exist would otherwise ask for credentials
+    clone.env("GIT_TERMINAL_PROMPT", "0").args(["clone", "--recurse-submodules"]);
+    if !full {
+        clone.args(["--depth", "1", "--shallow-submodules"]);
+    }
+    if sparse {
+        // Files outside of the sparse checkout are not downloaded at all
+        clone.args(["--sparse", "--filter=blob:none"]);
+    }
+    if let Some(GitRef::Branch(name) | GitRef::Tag(name)) = git_ref {
+        clone.args(["--branch", name]);
+    }
+    clone.arg(remote).arg(dest);
+    debug!("Running `{}`", command_line(&clone));
+    clone.output().map_err(|err| new_error(ErrorKind::Git { err }))
+}
+
+/// A template found in a subdirectory of a source without a template.toml at its root
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateCandidate {
+    /// The directory to give to use that template, relative to the root of the source
+    pub directory: String,
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// Picks which of the templates found in the subdirectories of a source to use, returning its
+/// [TemplateCandidate::directory]. Loading errors with [ErrorKind::MultipleTemplates] if it
+/// returns `None`, so one can be picked with `directory` instead.
+pub type TemplatePicker<'a> = dyn FnMut(&[TemplateCandidate]) -> Result<Option<String>> + 'a;
+
+/// The [TemplatePicker] used when there is no way to pick
+fn refuse_to_pick(_: &[TemplateCandidate]) -> Result<Option<String>> {
+    Ok(None)
+}
+
+/// Look for templates one level below `dir`, which is `directory` in the source
+fn find_templates(dir: &Path, directory: Option<&str>) -> Result<Vec<TemplateCandidate>> {
+    let mut candidates = Vec::new();
+    if !dir.is_dir() {
+        return Ok(candidates);
+    }
+
+    for entry in map_io_err(fs::read_dir(dir), dir)? {
+        let path = map_io_err(entry, dir)?.path();
+        let conf_path = path.join("template.toml");
+        if !conf_path.is_file() {
+            continue;
+        }
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        // Broken templates are still listed, they will error once picked
+        let definition = toml::from_str::<TemplateDefinition>(&read_file(&conf_path)?).ok();
+        candidates.push(TemplateCandidate {
+            directory: match directory {
+                Some(d) => format!("{}/{name}", d.trim_end_matches(['/', '\\'])),
+                None => name.clone(),
+            },
+            name: definition.as_ref().map(|d| d.name.clone()).unwrap_or(name),
+            description: definition.and_then(|d| d.description),
+        });
+    }
+    candidates.sort_by(|a, b| a.directory.cmp(&b.directory));
+
+    Ok(candidates)
+}
+
+/// Clone `remote` in `dest` and check out `git_ref`.
+/// Only the last commit is cloned unless `full` and only `directory` is checked out if given.
+fn clone_repo(
+    remote: &str,
+    dest: &Path,
+    git_ref: Option<&GitRef>,
+    full: bool,
+    directory: Option<&str>,
+) -> Result<()> {
+    let mut sparse = directory.is_some() && !full;
+    let mut output = git_clone(remote, dest, git_ref, full, sparse)?;
+    if sparse && String::from_utf8_lossy(&output.stderr).contains("unknown option") {
+        // `--sparse` needs git 2.25, check out everything with older versions
+        sparse = false;
+        if dest.exists() {
+            map_io_err(fs::remove_dir_all(dest), dest)?;
+        }
+        output = git_clone(remote, dest, git_ref, full, false)?;
+    }
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match git_ref {
+            Some(r) if stderr.contains("not found in upstream") => {
+                new_error(ErrorKind::GitRefNotFound {
+                    remote: remote.to_string(),
+                    git_ref: r.clone(),
+                })
+            }
+            _ => new_error(ErrorKind::GitClone {
+                remote: remote.to_string(),
+                reason: stderr.trim().to_string(),
+            }),
+        });
+    }
+
+    if let Some(directory) = directory.filter(|_| sparse) {
+        // Only the top-level files are checked out so far. Rather than missing files,
+        // check out everything if the directory can't be added.
+        let set = git(dest, &["sparse-checkout", "set", directory])?;
+        if !set.status.success() {
+            git(dest, &["sparse-checkout", "disable"])?;
+        }
+    }
+
+    if let Some(r @ GitRef::Rev(rev)) = git_ref {
+        let mut checkout = git(dest, &["checkout", "--quiet", rev])?;
+        if !checkout.status.success() && !full {
+            // Only the last commit of the default branch was cloned, the rev might be
+            // anywhere else
+            git(
+                dest,
+                &[
+                    "fetch",
+                    "--quiet",
+                    "--unshallow",
+                    "--tags",
+                    "origin",
+                    "+refs/heads/*:refs/remotes/origin/*",
+                ],
+            )?;
+            checkout = git(dest, &["checkout", "--quiet", rev])?;
+        }
+        if !checkout.status.success() {
+            return Err(new_error(ErrorKind::GitRefNotFound {
+                remote: remote.to_string(),
+                git_ref: r.clone(),
+            }));
+        }
+        // The submodules were cloned for the default branch
+        git(dest, &["submodule", "update", "--init", "--recursive"])?;
+    }
+
+    Ok(())
+}
+
+/// Update a cached clone to the latest commit of what it was cloned for.
+/// Commits given with [GitRef::Rev] are considered pinned and are not fetched again.
+fn fetch_cached(repo: &Path, remote: &str, git_ref: Option<&GitRef>, full: bool) -> Result<()> {
+    let refspec = match git_ref {
+        Some(GitRef::Branch(name)) => format!("refs/heads/{name}"),
+        Some(GitRef::Tag(name)) => format!("refs/tags/{name}"),
+        Some(GitRef::Rev(_)) => return Ok(()),
+        None => "HEAD".to_string(),
+    };
+    let mut args = vec!["fetch", "--quiet"];
+    if !full {
+        args.extend(["--depth", "1"]);
+    }
+    args.extend(["origin", &refspec]);
+
+    for args in [&args[..], &["checkout", "--quiet", "--force", "FETCH_HEAD"]] {
+        let output = git(repo, args)?;
+        if !output.status.success() {
+            return Err(new_error(ErrorKind::GitFetch {
+                remote: remote.to_string(),
+                reason: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            }));
+        }
+    }
+    git(repo, &["submodule", "update", "--init", "--recursive"])?;
+
+    Ok(())
+}
+
+/// What to do when a file to generate already exists in the output directory
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum OverwritePolicy {
+    /// Refuse to generate anything if a non-empty file would be overwritten, unless it is
+    /// generated with [Template::generate_with] which asks what to do with each of them
+    #[default]
+    Abort,
+    /// Overwrite existing files
+    Overwrite,
+    /// Keep the existing files and only write the new ones
+    Skip,
+}
+
+/// A file to generate that already exists in the output directory
+#[derive(Debug)]
+pub struct Conflict<'a> {
+    /// The path relative to the output directory
+    pub path: &'a Path,
+    /// The content of the existing file
+    pub existing: &'a [u8],
+    /// The content that would be written
+    pub generated: &'a [u8],
+}
+
+/// What to do with a [Conflict]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// Replace the existing file
+    Overwrite,
+    /// Keep the existing file
+    Skip,
+    /// Stop the generation without writing anything
+    Abort,
+}
+
+/// Run the `choices_cmd` of a variable in `dir`, returning its non-empty lines or why it failed
+fn run_choices_cmd(cmd: &[String], dir: &Path) -> result::Result<Vec<String>, String> {
+    let mut command = Command::new(&cmd[0]);
+    command.args(&cmd[1..]).current_dir(dir).stdin(Stdio::null());
+    debug!("Running `{}` in {}", command_line(&command), dir.display());
+    let output = command.output().map_err(|e| format!("could not be run: {e}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("failed with {}: {}", output.status, stderr.trim()));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines: Vec<String> = Vec::new();
+    for line in stdout.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if !lines.iter().any(|l| l == line) {
+            lines.push(line.to_string());
+        }
+    }
+    if lines.is_empty() {
+        return Err("printed nothing".to_string());
+    }
+    Ok(lines)
 }
 
+/// Decides what to do with each [Conflict] when generating
+pub type ConflictResolver<'a> = dyn FnMut(&Conflict) -> Result<ConflictResolution> + 'a;
+
 /// The current template being generated.
 /// This is what you will end up interacting the most as a library.
 #[derive(Debug)]
@@ -52,61 +419,337 @@ pub struct Template {
     pub definition: TemplateDefinition,
     /// The variables set by the user, either interactively or through the library
     variables: HashMap<String, Value>,
+    /// The defaults given by the pre-prompt hooks, see [Template::load_hook_defaults]
+    hook_defaults: HashMap<String, Value>,
+    /// The values given by the pre-gen hooks, under `extra` in the context, see
+    /// [Template::load_extra_context]
+    extra_context: BTreeMap<String, Value>,
     /// Local path to the template folder
-    path: PathBuf,
+    pub(crate) path: PathBuf,
+    /// Where the template came from: a local path, a git URL or an archive
+    source: String,
+    /// The commit checked out if the template was cloned
+    commit: Option<String>,
+    /// Whether the template was cloned from a git repository or downloaded rather than loaded
+    /// from disk
+    remote: bool,
+    /// The directory inside the source where the template is
+    directory: Option<String>,
     /// Temp dir created to store the hooks after templating
     tmp_dir: TempDir,
+    /// What to do with files that already exist in the output directory
+    overwrite_policy: OverwritePolicy,
+    /// The operating system hooks are selected for
+    hook_os: String,
+    /// Whether to render everything in a staging directory before moving it to the output
+    /// directory
+    atomic: bool,
+    /// The staging directory, if it was created before generating
+    staging: Option<TempDir>,
+    /// Where the template was extracted if it came from an archive
+    extracted: Option<TempDir>,
+    /// The values available under `kickstart` in the context
+    builtins: BuiltinValues,
+    /// Called before rendering each file
+    progress: Option<ProgressFn>,
+    /// How many files are rendered at the same time
+    jobs: usize,
+    /// The paths never to generate on top of the `.kickstartignore` files
+    excludes: Gitignore,
+    /// If not empty, only the files matching one of those are generated
+    only: Vec<Pattern>,
+    /// Whether the template is a cookiecutter one, whose Jinja is translated when rendering
+    cookiecutter: bool,
+    /// The addons selected with [Template::select_addons] by name, in the order of the
+    /// `[[addons]]`
+    addons: Vec<(String, Template)>,
 }
 
 impl Template {
     /// Load a template from a string.
-    /// It will try to detect whether this is a local folder or whether
+    /// It will try to detect whether this is a local folder, an archive or whether
     /// it should try to clone it.
     pub fn from_input(input: &str, directory: Option<&str>) -> Result<Template> {
+        Template::from_input_with(input, &CloneOptions::default(), directory)
+    }
+
+    /// Same as [Template::from_input] but with options on how to clone the template.
+    /// They are ignored for local templates.
+    pub fn from_input_with(
+        input: &str,
+        options: &CloneOptions,
+        directory: Option<&str>,
+    ) -> Result<Template> {
+        Template::from_input_picking(input, options, directory, &mut refuse_to_pick)
+    }
+
+    /// Same as [Template::from_input_with] but calling `pick` to choose one of the templates
+    /// found in the subdirectories of the source if there are several, which is then loaded
+    /// without cloning or extracting it again.
+    pub fn from_input_picking(
+        input: &str,
+        options: &CloneOptions,
+        directory: Option<&str>,
+        pick: &mut TemplatePicker,
+    ) -> Result<Template> {
         match get_source(input) {
-            Source::Git(remote) => Template::from_git(&remote, directory),
-            Source::Local(path) => Template::from_local(&path, directory),
+            Source::Git(remote) => {
+                debug!("Loading `{input}` as the git repository {remote}");
+                Template::from_git_picking(&remote, options, directory, pick)
+            }
+            Source::Local(path) => {
+                debug!("Loading `{input}` as the local directory {}", path.display());
+                Template::from_local_picking(&path, directory, pick)
+            }
+            Source::Archive(archive) => {
+                debug!("Loading `{input}` as an archive");
+                Template::from_archive_picking(&archive, directory, pick)
+            }
         }
     }
 
     /// Load a template from git.
-    /// This will clone the repository if possible in the temporary directory of the user
+    /// This will clone the repository if possible in the temporary directory of the user.
+    /// Any URL git understands can be used, including SSH ones: authentication is left to git,
+    /// eg using the keys of the ssh-agent.
+    /// A ref to check out can be given after a `#`, eg `https://github.com/Keats/kickstart#v0.5.0`.
+    /// Only the last commit is cloned and only `directory` is checked out if it is given,
+    /// see [CloneOptions] to get everything.
     pub fn from_git(remote: &str, directory: Option<&str>) -> Result<Template> {
-        // Clone the remote in git first in /tmp
-        let mut tmp = env::temp_dir();
-        tmp.push(remote.split('/').last().unwrap_or("kickstart"));
-        if tmp.exists() {
-            fs::remove_dir_all(&tmp)?;
+        Template::from_git_with(remote, &CloneOptions::default(), directory)
+    }
+
+    /// Same as [Template::from_git] but with options on how to clone the template.
+    /// The `git_ref` of the options takes precedence over one given in the URL.
+    pub fn from_git_with(
+        remote: &str,
+        options: &CloneOptions,
+        directory: Option<&str>,
+    ) -> Result<Template> {
+        Template::from_git_picking(remote, options, directory, &mut refuse_to_pick)
+    }
+
+    /// Same as [Template::from_git_with] but calling `pick` to choose one of the templates
+    /// found in the subdirectories of the repository if there are several, which is then
+    /// loaded from the same clone.
+    pub fn from_git_picking(
+        remote: &str,
+        options: &CloneOptions,
+        directory: Option<&str>,
+        pick: &mut TemplatePicker,
+    ) -> Result<Template> {
+        let (remote, git_ref) = match remote.rsplit_once('#') {
+            Some((url, fragment)) if !fragment.is_empty() => {
+                (url, options.git_ref.clone().or_else(|| Some(GitRef::Rev(fragment.to_string()))))
+            }
+            _ => (remote, options.git_ref.clone()),
+        };
+
+        let full = options.full_clone;
+        let dir = match &options.cache_dir {
+            Some(cache_dir) => {
+                cache::entry_dir(cache_dir, remote, git_ref.as_ref(), full, directory)
+            }
+            // Clone the remote in git first in /tmp
+            None => env::temp_dir().join(repo_name(remote)),
+        };
+        let cached = options.cache_dir.is_some() && dir.exists();
+
+        if options.offline {
+            if !cached {
+                return Err(new_error(ErrorKind::NotCached { remote: remote.to_string() }));
+            }
+            debug!("Using the cached clone in {} as is", dir.display());
+        } else if cached && !options.refresh {
+            debug!("Updating the cached clone in {}", dir.display());
+            fetch_cached(&dir, remote, git_ref.as_ref(), full)?;
+        } else {
+            // With a cache, clone next to the entry first so an interrupted clone is not
+            // mistaken for a cached one later on
+            let dest = match &options.cache_dir {
+                Some(cache_dir) => {
+                    create_directory(cache_dir)?;
+                    dir.with_extension("partial")
+                }
+                None => dir.clone(),
+            };
+            if dest.exists() {
+                map_io_err(fs::remove_dir_all(&dest), &dest)?;
+            }
+            if let Err(e) = clone_repo(remote, &dest, git_ref.as_ref(), full, directory) {
+                let _ = fs::remove_dir_all(&dest);
+                return Err(e);
+            }
+            if dest != dir {
+                if dir.exists() {
+                    map_io_err(fs::remove_dir_all(&dir), &dir)?;
+                }
+                map_io_err(fs::rename(&dest, &dir), &dir)?;
+            }
         }
 
-        // Use git command rather than git2 as it seems there are some issues building it
-        // on some platforms:
-        // https://www.reddit.com/r/rust/comments/92mbk5/kickstart_a_scaffolding_tool_to_get_new_projects/e3ahegw
-        Command::new("git")
-            .args(["clone", "--recurse-submodules", remote, &format!("{}", tmp.display())])
-            .output()
-            .map_err(|err| new_error(ErrorKind::Git { err }))?;
-        Template::from_local(&tmp, directory)
+        let commit = git(&dir, &["rev-parse", "HEAD"])
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+
+        debug!("Using commit {} of {remote}", commit.as_deref().unwrap_or("unknown"));
+
+        let mut template = Template::from_local_picking(&dir, directory, pick)?;
+        // The clone is in a temporary or cache directory, record where it actually came from
+        template.source = remote.to_string();
+        template.commit = commit;
+        template.remote = true;
+        Ok(template)
     }
 
-    /// Load a template from a local path
+    /// Load a template from a local path.
+    /// If there is no template.toml in it but there are templates in its subdirectories, the
+    /// only one is used or it errors with [ErrorKind::MultipleTemplates] so one can be picked
+    /// with `directory`.
     pub fn from_local(path: &Path, directory: Option<&str>) -> Result<Template> {
+        Template::from_local_picking(path, directory, &mut refuse_to_pick)
+    }
+
+    fn from_local_picking(
+        path: &Path,
+        directory: Option<&str>,
+        pick: &mut TemplatePicker,
+    ) -> Result<Template> {
         let mut buf = path.to_path_buf();
         if let Some(dir) = directory {
             buf.push(dir);
         }
         let conf_path = buf.join("template.toml");
-        if !conf_path.exists() {
-            return Err(new_error(ErrorKind::MissingTemplateDefinition));
+        let cookiecutter = !conf_path.exists() && cookiecutter::is_cookiecutter(&buf);
+        if !conf_path.exists() && !cookiecutter {
+            let candidates = find_templates(&buf, directory)?;
+            debug!(
+                "No template.toml in {}, found {} template(s) in its subdirectories",
+                buf.display(),
+                candidates.len()
+            );
+            return match &candidates[..] {
+                [] => Err(new_error(ErrorKind::MissingTemplateDefinition)),
+                [single] => Template::from_local(path, Some(&single.directory)),
+                _ => match pick(&candidates)? {
+                    Some(picked) => Template::from_local_picking(path, Some(&picked), pick),
+                    None => Err(new_error(ErrorKind::MultipleTemplates(candidates))),
+                },
+            };
         }
 
-        let definition: TemplateDefinition = toml::from_str(&read_file(&conf_path)?)
-            .map_err(|err| new_error(ErrorKind::Toml { err }))?;
+        let mut definition: TemplateDefinition = if cookiecutter {
+            debug!(
+                "No template.toml in {}, reading its {}",
+                buf.display(),
+                cookiecutter::CONFIG_FILE
+            );
+            cookiecutter::definition(&buf)?
+        } else {
+            TemplateDefinition::parse(&read_file(&conf_path)?)?
+        };
+        for key in definition.unknown_keys.keys() {
+            warn!(
+                "Unknown key `{key}` in the template.toml, it may be meant for a later version of \
+                 kickstart"
+            );
+        }
+        if definition.reorder_variables {
+            definition.sort_variables();
+        }
+
+        debug!("Using the template in {}", buf.display());
+        Ok(Template {
+            path: buf,
+            source: format!(
+                "{}",
+                path.canonicalize().unwrap_or_else(|_| path.to_path_buf()).display()
+            ),
+            commit: None,
+            remote: false,
+            directory: directory.map(|d| d.to_string()),
+            definition,
+            variables: HashMap::new(),
+            hook_defaults: HashMap::new(),
+            extra_context: BTreeMap::new(),
+            tmp_dir: tempdir()?,
+            overwrite_policy: OverwritePolicy::default(),
+            hook_os: env::consts::OS.to_string(),
+            builtins: BuiltinValues::now(),
+            atomic: true,
+            staging: None,
+            extracted: None,
+            progress: None,
+            jobs: default_jobs(),
+            excludes: Gitignore::empty(),
+            only: Vec::new(),
+            cookiecutter,
+            addons: Vec::new(),
+        })
+    }
+
+    /// Load a template from a `.tar.gz`, `.tgz` or `.zip` archive: a local file or a HTTP(S)
+    /// URL to download it from. It is extracted in a temporary directory and, if everything is
+    /// in a single top-level directory, that directory is used as the root of the template.
+    pub fn from_archive(archive: &str, directory: Option<&str>) -> Result<Template> {
+        Template::from_archive_picking(archive, directory, &mut refuse_to_pick)
+    }
+
+    fn from_archive_picking(
+        archive: &str,
+        directory: Option<&str>,
+        pick: &mut TemplatePicker,
+    ) -> Result<Template> {
+        let tmp = tempdir()?;
+        let (path, source) = if archive::is_url(archive) {
+            let path = tmp.path().join("archive");
+            archive::download(archive, &path)?;
+            (path, archive.to_string())
+        } else {
+            let path = PathBuf::from(archive);
+            map_io_err(fs::metadata(&path), &path)?;
+            let source = path.canonicalize().unwrap_or_else(|_| path.clone());
+            (path, source.display().to_string())
+        };
+
+        let extracted = tmp.path().join("extracted");
+        create_directory(&extracted)?;
+        archive::extract(archive, &path, &extracted)?;
+
+        let root = archive::root(&extracted)?;
+        let mut template = match Template::from_local_picking(&root, directory, pick) {
+            Err(e) if matches!(e.kind, ErrorKind::MissingTemplateDefinition) => {
+                return Err(new_error(ErrorKind::NoTemplateInArchive {
+                    archive: archive.to_string(),
+                    directory: directory.map(|d| d.to_string()),
+                }));
+            }
+            res => res?,
+        };
+        template.source = source;
+        template.remote = archive::is_url(archive);
+        template.extracted = Some(tmp);
+        Ok(template)
+    }
+
+    /// Where the template comes from, as given by the user
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The commit checked out if the template was cloned
+    pub fn commit(&self) -> Option<&str> {
+        self.commit.as_deref()
+    }
 
-        Ok(Template { path: buf, definition, variables: HashMap::new(), tmp_dir: tempdir()? })
+    /// Whether the template was cloned from a git repository or downloaded, in which case its
+    /// hooks might not be trustworthy
+    pub fn is_remote(&self) -> bool {
+        self.remote
     }
 
-    fn get_variable_by_name(&self, name: &str) -> Result<&Variable> {
+    pub(crate) fn get_variable_by_name(&self, name: &str) -> Result<&Variable> {
         if let Some(var) = self.definition.variables.iter().find(|v| v.name == name) {
             Ok(var)
         } else {
@@ -114,24 +757,91 @@ impl Template {
         }
     }
 
+    /// [TemplateDefinition::default_values] with the `kickstart` values of the template, frozen
+    /// by [Template::set_deterministic]
+    pub fn default_values(&self) -> Result<HashMap<String, Value>> {
+        self.definition.default_values_with(&self.builtins, |name| env::var(name).ok())
+    }
+
     /// Use this to get the default of the given variable.
     /// You have to pass a hashmap of previous values set because some default variables
     /// can use previous variables.
+    /// A default given by a pre-prompt hook is used as is instead. Then, if the variable has a
+    /// `default_from_env` that is set to a valid value, that value is used as is, and then the
+    /// value of its `default_from` if it is not empty.
+    /// For computed variables, this is their value.
     /// Will error if the template doesn't know that variable name.
     pub fn get_default_for(&self, name: &str, vals: &HashMap<String, Value>) -> Result<Value> {
         let var = self.get_variable_by_name(name)?;
-        match &var.default {
-            Value::Integer(i) => Ok(Value::Integer(*i)),
-            Value::Boolean(i) => Ok(Value::Boolean(*i)),
+        if let Some(val) = var.computed_value(vals, &self.builtins)? {
+            return Ok(val);
+        }
+        let hook_default = self.hook_defaults.get(name).cloned();
+        if let Some(val) = hook_default
+            .or_else(|| var.env_default(|name| env::var(name).ok()))
+            .or_else(|| var.builtin_default(&self.builtins))
+        {
+            return Ok(val);
+        }
+        let default = match &var.default {
+            Value::Integer(i) => Value::Integer(*i),
+            Value::Float(i) => Value::Float(*i),
+            Value::Boolean(i) => Value::Boolean(*i),
+            Value::List(i) => Value::List(i.clone()),
             Value::String(i) => {
                 // TODO: Very inefficient but might be ok?
-                let mut context = Context::new();
-                for (key, val) in vals {
-                    context.insert(key, val);
-                }
-                let rendered_default = render_one_off_template(i, &context, None)?;
-                Ok(Value::String(rendered_default))
+                let context = build_context(vals, &self.builtins);
+                Value::String(render_one_off_template(i, &context, None)?)
             }
+        };
+        Ok(default)
+    }
+
+    /// Use this to get the prompt of the given variable, rendered with the previous values
+    /// like string defaults are, eg `Which database should {{ project_name }} use?`.
+    /// Will error if the template doesn't know that variable name or if the prompt can't be
+    /// rendered, eg because it uses a variable that was not asked.
+    pub fn get_prompt_for(&self, name: &str, vals: &HashMap<String, Value>) -> Result<String> {
+        let var = self.get_variable_by_name(name)?;
+        let context = build_context(vals, &self.builtins);
+        render_one_off_template(&var.prompt, &context, None)
+    }
+
+    /// Parse a raw string, for example coming from the command line, into a value matching the
+    /// type of the given variable default.
+    /// Will error if the template doesn't know that variable name, if the variable is computed
+    /// or if the string cannot be converted to that type.
+    pub fn parse_value_for(&self, name: &str, input: &str) -> Result<Value> {
+        let var = self.get_variable_by_name(name)?;
+        if var.computed.is_some() {
+            return Err(new_error(ErrorKind::ComputedVariable(name.to_string())));
+        }
+        var.default.parse_same_type(input).ok_or_else(|| {
+            new_error(ErrorKind::InvalidVariableValue {
+                name: name.to_string(),
+                value: input.to_string(),
+                expected: var.default.type_str(),
+            })
+        })
+    }
+
+    /// Check the values against the constraints of their variable, see [Variable::check_answer],
+    /// for the ones that were not asked with a prompt checking them already.
+    /// Errors with all the values that don't pass, in the order of the variables.
+    pub fn check_values(&self, vals: &HashMap<String, Value>) -> Result<()> {
+        let violations: Vec<_> = self
+            .definition
+            .variables
+            .iter()
+            .filter_map(|var| {
+                let reason = var.check_answer(vals.get(&var.name)?, vals).err()?;
+                Some((var.name.clone(), reason))
+            })
+            .collect();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(new_error(ErrorKind::ConstraintViolations(violations)))
         }
     }
 
@@ -139,6 +849,10 @@ impl Template {
     /// Will error if the template doesn't know that variable name.
     pub fn insert_variable(&mut self, name: &str, value: Value) -> Result<()> {
         self.get_variable_by_name(name)?;
+        // The addons see all the variables, including the ones they don't declare
+        for (_, addon) in &mut self.addons {
+            addon.variables.insert(name.to_string(), value.clone());
+        }
         self.variables.insert(name.to_string(), value);
 
         Ok(())
@@ -148,36 +862,259 @@ impl Template {
     /// Will error if the template doesn't know one of the variables name.
     pub fn set_variables(&mut self, variables: HashMap<String, Value>) -> Result<()> {
         self.variables.clear();
+        for (_, addon) in &mut self.addons {
+            addon.variables.clear();
+        }
         for (name, val) in variables {
             self.insert_variable(&name, val)?;
         }
         Ok(())
     }
 
-    fn get_hooks(&self, hooks: &[Hook]) -> Result<Vec<HookFile>> {
-        let mut context = Context::new();
-        for (key, val) in &self.variables {
-            context.insert(key, val);
+    /// The variables that have been set so far
+    pub fn variables(&self) -> &HashMap<String, Value> {
+        &self.variables
+    }
+
+    /// Select the addons to generate along with the template, by their name in its
+    /// `[[addons]]`. Each is loaded from its directory and generated in the same output
+    /// directory after the template, in the order of the `[[addons]]`, their files overwriting
+    /// the ones generated before them. Their hooks run after the ones of the template in each
+    /// stage.
+    /// Their variables are added to the ones of the template, except those it already has, so
+    /// this needs to be called once, before asking the questions. The addons can use all the
+    /// variables in their files.
+    /// Errors with [ErrorKind::Addon] if the template has no such addon or if it can't be
+    /// loaded.
+    pub fn select_addons(&mut self, names: &[String]) -> Result<()> {
+        let addon_error = |name: &str, reason: String| {
+            new_error(ErrorKind::Addon { name: name.to_string(), reason })
+        };
+        let declared = &self.definition.addons;
+        if let Some(name) = names.iter().find(|n| !declared.iter().any(|a| &a.name == *n)) {
+            let reason = match &declared[..] {
+                [] => "the template has no addons".to_string(),
+                _ => format!(
+                    "the template has no such addon, only {}",
+                    declared.iter().map(|a| format!("`{}`", a.name)).collect::<Vec<_>>().join(", ")
+                ),
+            };
+            return Err(addon_error(name, reason));
+        }
+
+        let mut addons = Vec::new();
+        for addon in declared.iter().filter(|a| names.contains(&a.name)) {
+            debug!("Loading the addon `{}` from {}", addon.name, addon.directory);
+            let mut template = Template::from_local(&self.path.join(&addon.directory), None)
+                .map_err(|e| addon_error(&addon.name, e.to_string()))?;
+            if !template.definition.addons.is_empty() {
+                return Err(addon_error(
+                    &addon.name,
+                    "addons can't have addons of their own".to_string(),
+                ));
+            }
+            template.hook_os = self.hook_os.clone();
+            template.builtins = self.builtins.clone();
+            template.excludes = self.excludes.clone();
+            addons.push((addon.name.clone(), template));
+        }
+        for (_, addon) in &addons {
+            for var in &addon.definition.variables {
+                if !self.definition.variables.iter().any(|v| v.name == var.name) {
+                    self.definition.variables.push(var.clone());
+                }
+            }
+            for (key, group) in &addon.definition.groups {
+                self.definition.groups.entry(key.clone()).or_insert_with(|| group.clone());
+            }
+        }
+        self.addons = addons;
+        Ok(())
+    }
+
+    /// The commands the variables with a `choices_cmd` run to get their choices, by variable
+    pub fn choices_cmds(&self) -> Vec<(&str, &[String])> {
+        self.definition
+            .variables
+            .iter()
+            .filter_map(|v| Some((v.name.as_str(), v.choices_cmd.as_deref()?)))
+            .collect()
+    }
+
+    /// Set the choices of the variables with a `choices_cmd` to the lines it prints, running it
+    /// in the template directory. If its output doesn't have the default, its first line
+    /// becomes the default.
+    /// If it fails or `run` is `false`, eg because the template is remote and not trusted,
+    /// their `choices` are used instead and why is returned to warn about it. Variables without
+    /// `choices` or with `choices_cmd_required` error with [ErrorKind::ChoicesCommand] instead.
+    pub fn run_choices_cmds(&mut self, run: bool) -> Result<Vec<String>> {
+        let mut fallbacks = Vec::new();
+        for var in &mut self.definition.variables {
+            let Some(cmd) = var.choices_cmd.as_ref().filter(|c| !c.is_empty()) else {
+                continue;
+            };
+            let lines = match run {
+                true => run_choices_cmd(cmd, &self.path),
+                false => Err("was not allowed to run".to_string()),
+            };
+            match lines {
+                Ok(lines) => {
+                    debug!("`{}` has {} choices from its `choices_cmd`", var.name, lines.len());
+                    if !lines.iter().any(|l| Some(l.as_str()) == var.default.as_str()) {
+                        var.default = Value::String(lines[0].clone());
+                    }
+                    var.choices =
+                        Some(lines.into_iter().map(|l| Choice::Value(Value::String(l))).collect());
+                }
+                Err(reason) if var.choices.is_some() && !var.choices_cmd_required => {
+                    fallbacks.push(format!(
+                        "The `choices_cmd` of `{}` {reason}, using its `choices` instead",
+                        var.name
+                    ));
+                }
+                Err(reason) => {
+                    return Err(new_error(ErrorKind::ChoicesCommand {
+                        name: var.name.clone(),
+                        reason,
+                    }))
+                }
+            }
+        }
+        Ok(fallbacks)
+    }
+
+    /// The local directory of the template, where it was cloned or extracted if it is remote
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The addons selected with [Template::select_addons] and their name, in the order they
+    /// are generated in
+    pub fn addons(&self) -> impl Iterator<Item = (&str, &Template)> {
+        self.addons.iter().map(|(name, addon)| (name.as_str(), addon))
+    }
+
+    /// Sets what to do when generating files that already exist in the output directory.
+    /// By default, nothing will be generated if any non-empty file would be overwritten.
+    pub fn set_overwrite_policy(&mut self, policy: OverwritePolicy) {
+        self.overwrite_policy = policy;
+    }
+
+    /// Select the hooks as if running on that operating system instead of the current one,
+    /// eg to check the hooks of another platform. See [crate::HOOK_OSES] for the names.
+    pub fn set_hook_os(&mut self, os: &str) {
+        self.hook_os = os.to_string();
+        for (_, addon) in &mut self.addons {
+            addon.set_hook_os(os);
+        }
+    }
+
+    /// Freezes the `kickstart.date`, `kickstart.year` and `kickstart.timestamp` values of the
+    /// context to 2000-01-01 UTC so generating twice gives the same output, eg in tests.
+    /// The entries of archives written by [Template::generate_archive] get that date too.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.builtins =
+            if deterministic { BuiltinValues::deterministic() } else { BuiltinValues::now() };
+        for (_, addon) in &mut self.addons {
+            addon.set_deterministic(deterministic);
         }
+    }
+
+    /// Sets whether to render everything in a staging directory next to the output directory
+    /// and only move it there once everything succeeded, which is the default.
+    /// Without it, files are written directly in the output directory, which avoids writing
+    /// them twice for huge templates.
+    pub fn set_atomic(&mut self, atomic: bool) {
+        self.atomic = atomic;
+    }
+
+    /// Never generate the paths matching those patterns, written like in a `.gitignore` and
+    /// matched against the rendered paths relative to the output directory, eg `*.png` or
+    /// `/docs/`. They come on top of the `.kickstartignore` files of the template.
+    pub fn set_excludes(&mut self, patterns: &[String]) -> Result<()> {
+        let invalid = |err: ignore::Error| {
+            new_error(ErrorKind::InvalidIgnorePattern { path: None, reason: err.to_string() })
+        };
+        let mut builder = GitignoreBuilder::new("");
+        for pattern in patterns {
+            builder.add_line(None, pattern).map_err(invalid)?;
+        }
+        self.excludes = builder.build().map_err(invalid)?;
+        for (_, addon) in &mut self.addons {
+            addon.excludes = self.excludes.clone();
+        }
+        Ok(())
+    }
+
+    /// Only generate the files matching one of those globs, and the directories containing
+    /// them, eg to render a few files again in an existing project. They are matched against
+    /// the paths in the template as well as the rendered ones, a directory matching standing
+    /// for everything inside it.
+    /// Generating fails if they don't match anything, see [ErrorKind::NoMatchingPaths].
+    pub fn set_only(&mut self, globs: &[String]) -> Result<()> {
+        self.only = globs
+            .iter()
+            .map(|glob| {
+                Pattern::new(glob).map_err(|err| {
+                    new_error(ErrorKind::InvalidGlobPattern {
+                        err,
+                        pattern_before_rendering: glob.clone(),
+                        pattern_after_rendering: None,
+                    })
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(())
+    }
+
+    /// Sets how many files are rendered at the same time when generating, as many as there are
+    /// CPUs by default. 1 renders them one after the other.
+    pub fn set_jobs(&mut self, jobs: usize) {
+        self.jobs = jobs.max(1);
+    }
+
+    /// Sets a function called before writing each file when generating, eg to show a progress
+    /// bar. The files that already exist and are kept as they are don't count.
+    pub fn set_progress(&mut self, progress: impl Fn(&Progress) + Send + Sync + 'static) {
+        self.progress = Some(ProgressFn(Box::new(progress)));
+    }
+
+    /// Creates the staging directory the template will be rendered in by [Template::generate]
+    /// for that output directory and returns its path, eg to run the pre-gen hooks in it.
+    /// Returns the output directory itself if generation is not atomic.
+    pub fn prepare_staging(&mut self, output_dir: &Path) -> Result<PathBuf> {
+        if !self.atomic {
+            return Ok(output_dir.to_path_buf());
+        }
+        let staging = staging_dir_for(output_dir)?;
+        let path = staging.path().to_path_buf();
+        self.staging = Some(staging);
+        Ok(path)
+    }
+
+    /// Whether the hook should run on the operating system hooks are selected for, according
+    /// to its `os` field
+    pub fn is_hook_for_os(&self, hook: &Hook) -> bool {
+        match &hook.os {
+            Some(oses) => oses.contains(&self.hook_os),
+            None => true,
+        }
+    }
+
+    fn get_hooks(&self, hooks: &[Hook]) -> Result<Vec<HookFile>> {
+        let context = self.context();
 
         let mut hooks_files = Vec::new();
 
         for hook in hooks {
             // First we check whether we need to run it or not
-            if let Some(cond) = &hook.only_if {
-                if let Some(val) = self.variables.get(&cond.name) {
-                    if *val != cond.value {
-                        continue;
-                    }
-                } else {
-                    // Not having it means we didn't even ask the question
-                    continue;
-                }
+            if !self.is_hook_for_os(hook) || !self.should_run_hook(hook) {
+                continue;
             }
 
             // Then we will read the content of the file and run it through Tera
             let content = read_file(&self.path.join(&hook.path))?;
-            let rendered = render_one_off_template(&content, &context, Some(hook.path.clone()))?;
+            let rendered = self.render_text(&content, &context, Some(hook.path.clone()))?;
 
             // Then we save it in a temporary file
             let out_hook_path =
@@ -195,65 +1132,426 @@ impl Template {
         Ok(hooks_files)
     }
 
-    /// Returns the paths of the hooks that need to be ran in the pre-gen step.
-    /// The path will point to a temporary file and not the path of the template as it will
-    /// be templated.
-    pub fn get_pre_gen_hooks(&self) -> Result<Vec<HookFile>> {
-        self.get_hooks(&self.definition.pre_gen_hooks)
+    fn hook_args(&self, hook: &Hook, context: &Context) -> Result<Vec<String>> {
+        let mut args = Vec::new();
+        for (index, arg) in hook.args.iter().enumerate() {
+            let rendered = self.render_text(arg, context, None).map_err(|err| {
+                new_error(ErrorKind::HookArgument {
+                    hook: hook.name.clone(),
+                    index,
+                    arg: arg.clone(),
+                    reason: err.to_string(),
+                })
+            })?;
+            args.push(rendered);
+        }
+        Ok(args)
     }
 
-    /// Returns the paths of the hooks that need to be ran in the post-gen step.
-    /// The path will point to a temporary file and not the path of the template as it will
-    /// be templated.
-    pub fn get_post_gen_hooks(&self) -> Result<Vec<HookFile>> {
-        self.get_hooks(&self.definition.post_gen_hooks)
+    /// The `args` of a hook, each of them rendered with the current variables, to give to it
+    /// after its path.
+    /// Errors with [ErrorKind::HookArgument] for the first one that can't be rendered.
+    pub fn render_hook_args(&self, hook: &HookFile) -> Result<Vec<String>> {
+        self.hook_args(&hook.hook, &self.context())
     }
 
-    /// Checks whether the variable should be asked at all.
-    /// This will evaluate whatever condition if it has one.
-    /// Use that rather than accessing the variable.default as the value might be templated.
-    /// Will error if the template doesn't know that variable name.
-    pub fn should_ask_variable(&self, name: &str, vals: &HashMap<String, Value>) -> Result<bool> {
-        let var = self.get_variable_by_name(name)?;
-        if let Some(ref cond) = var.only_if {
-            if let Some(val) = vals.get(&cond.name) {
-                Ok(val == &cond.value)
-            } else {
-                // This means we never even asked the question
-                Ok(false)
+    /// The `args` of a hook as [Template::render_hook_args] renders them, with `********`
+    /// instead of the values of the secret variables so they can be shown
+    pub fn render_masked_hook_args(&self, hook: &HookFile) -> Result<Vec<String>> {
+        let mut context = self.context();
+        for var in self.definition.variables.iter().filter(|v| v.secret) {
+            if context.contains_key(&var.name) {
+                context.insert(&var.name, "********");
             }
-        } else {
-            Ok(true)
         }
+        self.hook_args(&hook.hook, &context)
     }
 
-    /// Generate the template at the given output directory
-    pub fn generate(&self, output_dir: &Path) -> Result<()> {
-        let mut context = Context::new();
-        for (key, val) in &self.variables {
-            context.insert(key, val);
+    /// Returns the paths of the hooks that need to be ran before asking the questions.
+    /// The path will point to a temporary file and not the path of the template as it will
+    /// be templated, without any variable.
+    /// The hooks of the selected addons come after the ones of the template.
+    pub fn get_pre_prompt_hooks(&self) -> Result<Vec<HookFile>> {
+        let mut hooks = self.get_hooks(&self.definition.pre_prompt_hooks)?;
+        for (_, addon) in &self.addons {
+            hooks.extend(addon.get_pre_prompt_hooks()?);
         }
+        Ok(hooks)
+    }
 
-        if !output_dir.exists() {
-            create_directory(output_dir)?;
+    /// Read the defaults a pre-prompt hook wrote to [HookFile::defaults_file], if it wrote any,
+    /// and remove the file. They are used instead of the defaults of the template.toml, the ones
+    /// of the later hooks winning, see [Template::get_default_for].
+    /// Errors with [ErrorKind::HookDefaults] if they are not a JSON object or if a value is
+    /// not of the type of its variable.
+    pub fn load_hook_defaults(&mut self, hook: &HookFile) -> Result<()> {
+        let hook_name = hook.name();
+        let path = hook.defaults_file();
+        if !path.exists() {
+            return Ok(());
         }
-        let output_dir = output_dir.canonicalize()?;
+        let content = read_file(&path)?;
+        map_io_err(fs::remove_file(&path), &path)?;
+        let invalid = |reason: String| {
+            new_error(ErrorKind::HookDefaults { hook: hook_name.to_string(), reason })
+        };
 
-        // Create the glob patterns of files to copy without rendering first, only once
-        let mut patterns = Vec::with_capacity(self.definition.copy_without_render.len());
-        for s in &self.definition.copy_without_render {
-            let rendered = render_one_off_template(s, &context, None)?;
-            match Pattern::new(&rendered) {
-                Ok(p) => patterns.push(p),
-                Err(err) => {
-                    return Err(new_error(ErrorKind::InvalidGlobPattern {
-                        err,
-                        pattern_before_rendering: s.clone(),
-                        pattern_after_rendering: if s == &rendered { None } else { Some(rendered) },
-                    }));
-                }
+        let defaults: BTreeMap<String, Value> = serde_json::from_str(&content)
+            .map_err(|e| invalid(format!("they are not a JSON object of values: {e}")))?;
+        for (name, value) in defaults {
+            let Some(var) = self.definition.variables.iter().find(|v| v.name == name) else {
+                return Err(invalid(format!("`{name}` is not a variable")));
+            };
+            if var.computed.is_some() {
+                return Err(invalid(format!("`{name}` is computed, it can't have a default")));
+            }
+            // `2` is a fine value for a float variable
+            let value = match (value, var.raw_default()) {
+                (Value::Integer(i), Value::Float(..)) => Value::Float(i as f64),
+                (value, _) => value,
             };
+            let expected = var.raw_default().type_str();
+            if value.type_str() != expected {
+                return Err(invalid(format!(
+                    "`{name}` is of type {expected} but got `{value}` ({})",
+                    value.type_str()
+                )));
+            }
+            self.hook_defaults.insert(name, value);
         }
+        Ok(())
+    }
+
+    /// Read the values a pre-gen hook wrote to [HookFile::extra_context_file], if it wrote any,
+    /// and remove the file. They are available under `extra` when rendering, eg
+    /// `{{ extra.port }}`, the ones of the later hooks winning.
+    /// Errors with [ErrorKind::HookExtraContext] if they are not a JSON object of strings,
+    /// booleans, numbers and arrays of those, or if the template has a variable named `extra`.
+    pub fn load_extra_context(&mut self, hook: &HookFile) -> Result<()> {
+        let hook_name = hook.name();
+        let path = hook.extra_context_file();
+        if !path.exists() {
+            return Ok(());
+        }
+        let content = read_file(&path)?;
+        map_io_err(fs::remove_file(&path), &path)?;
+        let invalid = |reason: String| {
+            new_error(ErrorKind::HookExtraContext { hook: hook_name.to_string(), reason })
+        };
+
+        if self.definition.variables.iter().any(|v| v.name == EXTRA_CONTEXT_KEY) {
+            return Err(invalid(format!(
+                "the template has a variable named `{EXTRA_CONTEXT_KEY}`, which is where they go"
+            )));
+        }
+        let extra: BTreeMap<String, Value> = serde_json::from_str(&content)
+            .map_err(|e| invalid(format!("it is not a JSON object of values: {e}")))?;
+        self.extra_context.extend(extra);
+        Ok(())
+    }
+
+    /// The values given by the pre-gen hooks so far, see [Template::load_extra_context]
+    pub fn extra_context(&self) -> &BTreeMap<String, Value> {
+        &self.extra_context
+    }
+
+    /// Returns the paths of the hooks that need to be ran in the pre-gen step.
+    /// The path will point to a temporary file and not the path of the template as it will
+    /// be templated.
+    /// The hooks of the selected addons come after the ones of the template.
+    pub fn get_pre_gen_hooks(&self) -> Result<Vec<HookFile>> {
+        let mut hooks = self.get_hooks(&self.definition.pre_gen_hooks)?;
+        for (_, addon) in &self.addons {
+            hooks.extend(addon.get_pre_gen_hooks()?);
+        }
+        Ok(hooks)
+    }
+
+    /// Returns the paths of the hooks that need to be ran in the post-gen step.
+    /// The path will point to a temporary file and not the path of the template as it will
+    /// be templated.
+    /// The hooks of the selected addons come after the ones of the template.
+    pub fn get_post_gen_hooks(&self) -> Result<Vec<HookFile>> {
+        let mut hooks = self.get_hooks(&self.definition.post_gen_hooks)?;
+        for (_, addon) in &self.addons {
+            hooks.extend(addon.get_post_gen_hooks()?);
+        }
+        Ok(hooks)
+    }
+
+    /// The environment variables to set when running hooks: every variable as
+    /// `KICKSTART_<NAME>` and the absolute output directory as `KICKSTART_OUTPUT_DIR`.
+    /// Lists are joined with commas.
+    pub fn hook_env(&self, output_dir: &Path) -> Result<Vec<(String, String)>> {
+        let output_dir = if output_dir.exists() {
+            output_dir.canonicalize()?
+        } else {
+            env::current_dir()?.join(output_dir)
+        };
+        let mut vars = vec![(
+            format!("{HOOK_ENV_PREFIX}OUTPUT_DIR"),
+            output_dir.to_string_lossy().into_owned(),
+        )];
+
+        for (name, val) in &self.variables {
+            let val = match val {
+                Value::List(items) => {
+                    items.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(",")
+                }
+                _ => val.to_string(),
+            };
+            vars.push((hook_env_var_name(name), val));
+        }
+
+        Ok(vars)
+    }
+
+    /// The directory to run the hooks in: the output directory, or the generated project for
+    /// cookiecutter templates as cookiecutter does
+    pub fn hook_dir(&self, output_dir: &Path) -> Result<PathBuf> {
+        match self.definition.directory {
+            Some(ref directory) if self.cookiecutter => {
+                Ok(output_dir.join(self.render_text(directory, &self.context(), None)?))
+            }
+            _ => Ok(output_dir.to_path_buf()),
+        }
+    }
+
+    /// Checks whether the `only_if` condition of the hook, if any, is met by the variables
+    /// that have been set.
+    pub fn should_run_hook(&self, hook: &Hook) -> bool {
+        match &hook.only_if {
+            Some(cond) => {
+                let met = self.is_condition_met(cond);
+                match met {
+                    true => debug!("The hook `{}` runs, its condition is met: {cond}", hook.name),
+                    false => debug!(
+                        "The hook `{}` doesn't run, its condition is not met: {cond}",
+                        hook.name
+                    ),
+                }
+                met
+            }
+            None => true,
+        }
+    }
+
+    /// Whether the variable of the condition has that value.
+    /// Not having the variable means we didn't even ask the question so it is not met.
+    fn is_condition_met(&self, cond: &Condition) -> bool {
+        cond.is_met(&self.variables)
+    }
+
+    /// Checks whether the variable should be asked at all.
+    /// This will evaluate whatever condition if it has one, and whether it has choices if they
+    /// come from another variable.
+    /// Use that rather than accessing the variable.default as the value might be templated.
+    /// Will error if the template doesn't know that variable name.
+    pub fn should_ask_variable(&self, name: &str, vals: &HashMap<String, Value>) -> Result<bool> {
+        let var = self.get_variable_by_name(name)?;
+        for cond in self.definition.variable_conditions(var) {
+            if !cond.is_met(vals) {
+                debug!("`{name}` is not asked, its condition is not met: {cond}");
+                return Ok(false);
+            }
+            debug!("`{name}` is asked, its condition is met: {cond}");
+        }
+        if let Some(reason) = var.missing_choices(vals) {
+            warn!("`{name}` is not asked as {reason}, it has no choices");
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// The directory to generate in if none is given: the `output_dir` of the template rendered
+    /// with the variables, which needs to be a single directory name.
+    /// Returns `None` if the template doesn't have one.
+    pub fn default_output_dir(&self) -> Result<Option<String>> {
+        let Some(ref output_dir) = self.definition.output_dir else {
+            return Ok(None);
+        };
+        let rendered = render_one_off_template(output_dir, &self.context(), None)?;
+        let rendered = rendered.trim();
+
+        let reason = match rendered {
+            "" => Some("is empty"),
+            "." | ".." => Some("is not a directory name"),
+            _ if rendered.contains(['/', '\\']) || rendered.contains(std::path::is_separator) => {
+                Some("is not a single directory name")
+            }
+            _ if rendered.contains('\0') => Some("contains a NUL character"),
+            _ if cfg!(windows) => windows_name_error(rendered),
+            _ => None,
+        };
+        if let Some(reason) = reason {
+            return Err(new_error(ErrorKind::InvalidOutputDir {
+                rendered: rendered.to_string(),
+                reason,
+            }));
+        }
+        // Eg `C:` on Windows
+        if !matches!(Path::new(rendered).components().next(), Some(Component::Normal(_))) {
+            return Err(new_error(ErrorKind::InvalidOutputDir {
+                rendered: rendered.to_string(),
+                reason: "is not a directory name",
+            }));
+        }
+
+        Ok(Some(rendered.to_string()))
+    }
+
+    /// The `post_gen_message` of the template rendered with the variables, `None` if the
+    /// template doesn't have one.
+    pub fn post_gen_message(&self) -> Result<Option<String>> {
+        let Some(ref message) = self.definition.post_gen_message else {
+            return Ok(None);
+        };
+        self.render_text(message, &self.context(), None).map(Some)
+    }
+
+    /// The `git_init_message` of the template rendered with the variables
+    pub fn git_init_message(&self) -> Result<String> {
+        self.render_text(&self.definition.git_init_message, &self.context(), None)
+    }
+
+    /// Create a git repository in `output_dir` with everything in it in a first commit, as the
+    /// `git_init` of the template asks. It shells out to git so the config of the user, eg its
+    /// hooks or signing, applies.
+    /// Nothing is done if git is not installed or if `output_dir` is already in a repository.
+    /// Errors with [ErrorKind::GitInit] if a git command fails, eg without `user.email`.
+    pub fn git_init(&self, output_dir: &Path) -> Result<GitInit> {
+        let message = self.git_init_message()?;
+        if Command::new("git").arg("--version").output().is_err() {
+            return Ok(GitInit::Skipped("git is not installed".to_string()));
+        }
+        let inside = git(output_dir, &["rev-parse", "--is-inside-work-tree"])?;
+        if inside.status.success() {
+            return Ok(GitInit::Skipped(format!(
+                "{} is already in a git repository",
+                output_dir.display()
+            )));
+        }
+
+        for args in [&["init"][..], &["add", "--all"], &["commit", "--message", &message]] {
+            let output = git(output_dir, args)?;
+            if !output.status.success() {
+                return Err(new_error(ErrorKind::GitInit {
+                    command: format!("git {}", args.join(" ")),
+                    reason: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                }));
+            }
+        }
+        let head = git(output_dir, &["rev-parse", "HEAD"])?;
+        Ok(GitInit::Committed(String::from_utf8_lossy(&head.stdout).trim().to_string()))
+    }
+
+    /// The variables and built-in values, with `has_<name>` for optional variables
+    fn context(&self) -> Context {
+        let mut context = build_context(&self.variables, &self.builtins);
+        for var in self.definition.variables.iter().filter(|v| !v.required) {
+            let has_value = self.variables.get(&var.name).is_some_and(|v| v.as_str() != Some(""));
+            context.insert(format!("has_{}", var.name), &has_value);
+        }
+        if self.cookiecutter {
+            context.insert("cookiecutter", &self.variables);
+        }
+        if !self.extra_context.is_empty() {
+            context.insert(EXTRA_CONTEXT_KEY, &self.extra_context);
+        }
+        context
+    }
+
+    /// Render a file, path or pattern of the template.
+    /// The Jinja of cookiecutter templates is translated first and what Tera still can't render,
+    /// other than variables that don't exist, is reported as an unsupported cookiecutter feature.
+    fn render_text(&self, text: &str, context: &Context, path: Option<PathBuf>) -> Result<String> {
+        if !self.cookiecutter {
+            return render_one_off_template(text, context, path);
+        }
+        render_one_off_template(&cookiecutter::translate(text, false), context, path.clone())
+            .map_err(|err| match err.kind {
+                ErrorKind::Tera { err: ref tera_err, .. }
+                    if undefined_variable(tera_err).is_none() =>
+                {
+                    let cause = std::error::Error::source(tera_err).unwrap_or(tera_err);
+                    let reason = format!("Tera can't render it\n{}", cause.to_string().trim_end());
+                    new_error(ErrorKind::UnsupportedCookiecutter {
+                        path: path.unwrap_or_else(|| PathBuf::from(cookiecutter::CONFIG_FILE)),
+                        reason,
+                    })
+                }
+                _ => err,
+            })
+    }
+
+    /// Walks the template and returns every path that should end up in the output directory,
+    /// in walking order.
+    /// `output_dir` and `staging` should be canonicalized if they exist so they can be excluded
+    /// from the walk when they live inside the template.
+    /// `errors` collects the rendering errors of paths and patterns, which are then skipped,
+    /// instead of returning the first one.
+    fn plan(
+        &self,
+        output_dir: Option<&Path>,
+        staging: Option<&Path>,
+        context: &Context,
+        mut errors: Option<&mut Vec<Error>>,
+    ) -> Result<Vec<PlannedEntry>> {
+        let mut collect = |err: Error| match errors.as_deref_mut() {
+            Some(errors) => {
+                errors.push(err);
+                Ok(())
+            }
+            None => Err(err),
+        };
+
+        // Create the glob patterns of files to copy without rendering first, only once
+        let patterns = match self.render_patterns(&self.definition.copy_without_render, context) {
+            Ok(patterns) => patterns,
+            Err(err) => {
+                collect(err)?;
+                Vec::new()
+            }
+        };
+        let mut line_endings = Vec::new();
+        for files in &self.definition.line_endings_overrides {
+            match self.render_patterns(&files.paths, context) {
+                Ok(patterns) => {
+                    line_endings.extend(patterns.into_iter().map(|p| (p, files.line_endings)))
+                }
+                Err(err) => collect(err)?,
+            }
+        }
+        // Same for the conditional files that should not be generated
+        let mut excluded = Vec::new();
+        for (index, files) in self.definition.conditional_files.iter().enumerate() {
+            let met = self.is_condition_met(&files.only_if);
+            match met {
+                true => debug!(
+                    "{:?} are generated, their condition is met: {}",
+                    files.paths, files.only_if
+                ),
+                false => debug!(
+                    "{:?} are not generated, their condition is not met: {}",
+                    files.paths, files.only_if
+                ),
+            }
+            if !met {
+                match self.render_patterns(&files.paths, context) {
+                    Ok(patterns) => excluded.extend(patterns.into_iter().map(|p| (p, index))),
+                    Err(err) => collect(err)?,
+                }
+            }
+        }
+        let is_excluded = |path: &Path| {
+            excluded.iter().find_map(|(p, index)| {
+                // `dir/**` excludes the directory itself as well
+                let is_dir_glob =
+                    p.as_str().strip_suffix("/**").is_some_and(|dir| Path::new(dir) == path);
+                (p.matches_path(path) || is_dir_glob).then_some(*index)
+            })
+        };
+        let mut excluded_dirs: Vec<PathBuf> = Vec::new();
 
         let start_path = if let Some(ref directory) = self.definition.directory {
             self.path.join(directory)
@@ -261,8 +1559,11 @@ impl Template {
             self.path.clone()
         };
 
-        // And now generate the files in the output dir given
-        let walker = WalkDir::new(&start_path)
+        let in_output_dir = |p: &Path| {
+            let p = p.canonicalize().expect("to canonicalize");
+            output_dir.into_iter().chain(staging).any(|o| p.starts_with(o))
+        };
+        let mut walker = WalkDir::new(&start_path)
             .follow_links(self.definition.follow_symlinks)
             .into_iter()
             .filter_entry(|e| {
@@ -270,110 +1571,2464 @@ impl Template {
                 let relative_path = e.path().strip_prefix(&start_path).expect("Stripping prefix");
                 if relative_path.starts_with(".git/")
                     || (relative_path.is_dir() && relative_path.starts_with(".git"))
-                    || e.path().canonicalize().expect("to canonicalize").starts_with(&output_dir)
+                    || in_output_dir(e.path())
                 {
                     return false;
                 }
-                true
+                true
+            });
+
+        let hooks_paths = self.definition.all_hooks_paths();
+        let addon_dirs: Vec<PathBuf> = self
+            .definition
+            .addons
+            .iter()
+            .map(|a| {
+                Path::new(&a.directory).components().filter(|c| *c != Component::CurDir).collect()
+            })
+            .collect();
+        let mut entries = Vec::new();
+        // The `.kickstartignore` files of the directories walked so far, deepest last
+        let mut ignore_files = Vec::new();
+        if start_path != self.path {
+            ignore_files.extend(load_ignore_file(&self.path, &self.path)?);
+        }
+
+        'outer: while let Some(entry) = walker.next() {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            if entry.file_type().is_dir() {
+                ignore_files.extend(load_ignore_file(&self.path, entry.path())?);
+            }
+            // Skip root folder, the template.toml and the ignore files
+            if entry.path() == self.path
+                || entry.path() == self.path.join("template.toml")
+                || (entry.file_name() == IGNORE_FILE_NAME && !entry.file_type().is_dir())
+            {
+                continue;
+            }
+            // Nor the test cases of the template
+            if entry.file_type().is_dir()
+                && entry.path() == self.path.join(&self.definition.tests_dir)
+            {
+                walker.skip_current_dir();
+                continue;
+            }
+            let is_dir = entry.path().is_dir();
+            if let Some(reason) = is_ignored(&ignore_files, &self.path, entry.path(), is_dir) {
+                let path = entry.path().strip_prefix(&self.path).unwrap();
+                debug!("Skipping {}: it is {reason}", path.display());
+                entries.push(PlannedEntry {
+                    source: entry.path().to_path_buf(),
+                    path: path.to_path_buf(),
+                    is_dir,
+                    no_render: true,
+                    line_endings: LineEndings::Keep,
+                    excluded_by: None,
+                    ignored_because: Some(reason),
+                    addon: None,
+                });
+                if is_dir {
+                    walker.skip_current_dir();
+                }
+                continue;
+            }
+
+            let path = entry.path().strip_prefix(&self.path).unwrap();
+            if output_dir.is_some_and(|o| path.starts_with(o)) {
+                continue;
+            }
+            let path_str = format!("{}", path.display());
+            for ignored in &self.definition.ignore {
+                if ignored == &path_str || path_str.starts_with(ignored) {
+                    debug!("Skipping {path_str}: it is ignored by `{ignored}`");
+                    continue 'outer;
+                }
+            }
+
+            // We automatically ignore hooks file
+            if hooks_paths.contains(&path_str) {
+                debug!("Skipping {path_str}: it is a hook");
+                continue 'outer;
+            }
+            // And the addons, which are generated on their own
+            if addon_dirs.iter().any(|d| path.starts_with(d)) {
+                debug!("Skipping {path_str}: it is an addon");
+                if is_dir {
+                    walker.skip_current_dir();
+                }
+                continue 'outer;
+            }
+
+            // Only there so the directory exists in git
+            if entry.file_name() == KEEP_FILE_NAME && !entry.file_type().is_dir() {
+                continue;
+            }
+
+            let path_str = path_str.replace("$$", "|");
+            let rendered = self
+                .render_text(&path_str, context, Some(path.to_path_buf()))
+                .and_then(|rendered| check_rendered_path(path, &rendered).map(|_| rendered));
+            let rendered_path = match rendered {
+                Ok(rendered) => PathBuf::from(rendered),
+                Err(err) => {
+                    collect(err)?;
+                    continue;
+                }
+            };
+            if let Some(reason) =
+                is_ignored(slice::from_ref(&self.excludes), &self.path, &rendered_path, is_dir)
+            {
+                debug!("Skipping {}: it is {reason}", rendered_path.display());
+                entries.push(PlannedEntry {
+                    source: entry.path().to_path_buf(),
+                    path: rendered_path,
+                    is_dir,
+                    no_render: true,
+                    line_endings: LineEndings::Keep,
+                    excluded_by: None,
+                    ignored_because: Some(reason),
+                    addon: None,
+                });
+                if is_dir {
+                    walker.skip_current_dir();
+                }
+                continue;
+            }
+
+            // Only the excluded path itself is kept, not what's inside an excluded directory
+            if excluded_dirs.iter().any(|d| rendered_path.starts_with(d)) {
+                continue;
+            }
+            let excluded_by = is_excluded(&rendered_path);
+            if excluded_by.is_some() && is_dir {
+                excluded_dirs.push(rendered_path.clone());
+            }
+            // For patterns, we do not want the output directory to be included
+            let no_render = !is_dir && patterns.iter().any(|p| p.matches_path(&rendered_path));
+            let line_endings = line_endings
+                .iter()
+                .find(|(p, _)| p.matches_path(&rendered_path))
+                .map_or(self.definition.line_endings, |(_, l)| *l);
+
+            entries.push(PlannedEntry {
+                source: entry.path().to_path_buf(),
+                path: rendered_path,
+                is_dir,
+                no_render,
+                line_endings,
+                excluded_by,
+                ignored_because: None,
+                addon: None,
+            });
+        }
+
+        // Nor the directories only there to hold addons, deepest first as they can be nested
+        let mut removed = vec![false; entries.len()];
+        for i in (0..entries.len()).rev() {
+            let entry = &entries[i];
+            let source = entry.source.strip_prefix(&self.path).unwrap_or(&entry.source);
+            if !entry.is_dir || !addon_dirs.iter().any(|d| d.starts_with(source)) {
+                continue;
+            }
+            removed[i] = !entries
+                .iter()
+                .enumerate()
+                .any(|(j, e)| !removed[j] && j != i && e.path.starts_with(&entry.path));
+        }
+        let mut removed = removed.into_iter();
+        entries.retain(|_| !removed.next().unwrap_or_default());
+
+        Ok(entries)
+    }
+
+    /// Render the glob patterns, eg of `copy_without_render`, keeping them in the same order
+    fn render_patterns(&self, globs: &[String], context: &Context) -> Result<Vec<Pattern>> {
+        let mut patterns = Vec::with_capacity(globs.len());
+        for s in globs {
+            let rendered = self.render_text(s, context, None)?;
+            match Pattern::new(&rendered) {
+                Ok(p) => patterns.push(p),
+                Err(err) => {
+                    return Err(new_error(ErrorKind::InvalidGlobPattern {
+                        err,
+                        pattern_before_rendering: s.clone(),
+                        pattern_after_rendering: if s == &rendered { None } else { Some(rendered) },
+                    }));
+                }
+            };
+        }
+        Ok(patterns)
+    }
+
+    /// The `copy_without_render` globs that don't match any file of the template with the
+    /// current variables, which is likely a mistake
+    pub fn unmatched_copy_without_render(&self) -> Result<Vec<String>> {
+        let context = self.context();
+        let patterns = self.render_patterns(&self.definition.copy_without_render, &context)?;
+        let mut entries = self.plan(None, None, &context, None)?;
+        entries.retain(|e| e.ignored_because.is_none());
+
+        Ok(self
+            .definition
+            .copy_without_render
+            .iter()
+            .zip(patterns)
+            .filter(|(_, p)| !entries.iter().any(|e| !e.is_dir && p.matches_path(&e.path)))
+            .map(|(s, _)| s.clone())
+            .collect())
+    }
+
+    /// Read the file and render it unless it is binary or should be copied as is
+    fn render_entry(&self, entry: &PlannedEntry, context: &Context) -> Result<RenderedFile> {
+        if entry.is_dir {
+            return Ok(RenderedFile::Directory);
+        }
+        let verbatim = RenderedFile::Verbatim { source: entry.source.clone() };
+        if entry.no_render {
+            return Ok(verbatim);
+        }
+        let mut f = map_io_err(File::open(&entry.source), &entry.source)?;
+        let size = map_io_err(f.metadata(), &entry.source)?.len();
+        let mut needs_rendering = true;
+        if size > LARGE_FILE_SIZE {
+            needs_rendering = map_io_err(could_need_rendering(&mut f), &entry.source)?;
+            // Its line endings might still need to be converted
+            if !needs_rendering && entry.line_endings == LineEndings::Keep {
+                debug!("{} is large and has nothing to render", entry.path.display());
+                return Ok(verbatim);
+            }
+            map_io_err(f.rewind(), &entry.source)?;
+        }
+        let mut buffer = Vec::with_capacity(size as usize);
+        map_io_err(f.read_to_end(&mut buffer), &entry.source)?;
+
+        // Only pass non-binary files or the files not matching the copy_without_render patterns through Tera
+        if is_binary(&buffer) {
+            return Ok(verbatim);
+        }
+        // Binary files without null bytes, eg some images
+        let Ok(text) = str::from_utf8(&buffer) else {
+            return Ok(verbatim);
+        };
+
+        let mut content = match needs_rendering {
+            true => self.render_file(text, context, &entry.source)?,
+            false => text.to_string(),
+        };
+        if let Cow::Owned(converted) = entry.line_endings.apply(&content) {
+            content = converted;
+        }
+        Ok(RenderedFile::Rendered { source: entry.source.clone(), content })
+    }
+
+    /// Renders what [Template::generate] writes, the rendered paths relative to the output
+    /// directory, see [Template::render_to_map].
+    /// `output_dir` and `staging` are excluded from the walk as in [Template::plan].
+    fn render_map(
+        &self,
+        output_dir: Option<&Path>,
+        staging: Option<&Path>,
+        context: &Context,
+    ) -> Result<BTreeMap<PathBuf, RenderedFile>> {
+        let mut entries = self.generated_entries(output_dir, staging, context)?;
+        let addon_contexts: Vec<_> = self.addons.iter().map(|(_, a)| a.context()).collect();
+        for (index, ((_, addon), context)) in self.addons.iter().zip(&addon_contexts).enumerate() {
+            let addon_entries = addon.generated_entries(output_dir, staging, context)?;
+            entries.extend(
+                addon_entries.into_iter().map(|entry| PlannedEntry { addon: Some(index), ..entry }),
+            );
+        }
+
+        if !self.only.is_empty() {
+            entries = self.select_only(entries)?;
+        }
+
+        let rendered = self.render_entries(&entries, context, &addon_contexts);
+        // The addons overwrite what was generated before them
+        let mut files = BTreeMap::new();
+        for (entry, file) in entries.into_iter().zip(rendered) {
+            files.insert(entry.path, file);
+        }
+        // Whatever the threads did first, the error is the one of the first path
+        files.into_iter().map(|(path, file)| file.map(|file| (path, file))).collect()
+    }
+
+    /// The entries of the template alone that [Template::render_map] renders: the ones that
+    /// are not ignored, excluded by `conditional_files` or removed by `cleanup`
+    fn generated_entries(
+        &self,
+        output_dir: Option<&Path>,
+        staging: Option<&Path>,
+        context: &Context,
+    ) -> Result<Vec<PlannedEntry>> {
+        let cleanup_paths = self.cleanup_paths(context)?;
+        let mut entries = self.plan(output_dir, staging, context, None)?;
+        entries.retain(|entry| {
+            if entry.ignored_because.is_some() {
+                return false;
+            }
+            if entry.excluded_by.is_some() {
+                debug!(
+                    "Skipping {}: its `conditional_files` condition is not met",
+                    entry.path.display()
+                );
+                return false;
+            }
+            if cleanup_paths.iter().any(|p| entry.path.starts_with(p)) {
+                debug!("Skipping {}: it is removed by `cleanup`", entry.path.display());
+                return false;
+            }
+            true
+        });
+        Ok(entries)
+    }
+
+    /// The files matching [Template::set_only] and the directories containing them
+    fn select_only(&self, entries: Vec<PlannedEntry>) -> Result<Vec<PlannedEntry>> {
+        let is_selected = |entry: &PlannedEntry| {
+            let in_template = entry.source.strip_prefix(&self.path).unwrap_or(&entry.source);
+            in_template.ancestors().chain(entry.path.ancestors()).any(|path| {
+                !path.as_os_str().is_empty() && self.only.iter().any(|p| p.matches_path(path))
+            })
+        };
+        let selected: Vec<_> = entries
+            .iter()
+            .filter(|e| !e.is_dir && is_selected(e))
+            .map(|e| e.path.clone())
+            .collect();
+
+        if selected.is_empty() {
+            let patterns: Vec<_> = self.only.iter().map(|p| p.as_str().to_string()).collect();
+            let mut candidates: Vec<_> = entries
+                .iter()
+                .filter(|e| !e.is_dir)
+                .map(|e| {
+                    let path = e.path.to_string_lossy().replace('\\', "/");
+                    let distance =
+                        patterns.iter().map(|p| edit_distance(p, &path)).min().unwrap_or_default();
+                    (distance, path)
+                })
+                .collect();
+            candidates.sort();
+            let closest = candidates.into_iter().take(5).map(|(_, path)| path).collect();
+            return Err(new_error(ErrorKind::NoMatchingPaths { patterns, closest }));
+        }
+
+        Ok(entries
+            .into_iter()
+            .filter(|e| match e.is_dir {
+                true => selected.iter().any(|p| p.starts_with(&e.path)),
+                false => selected.contains(&e.path),
+            })
+            .collect())
+    }
+
+    /// Renders the entries on up to [Template::set_jobs] threads, returning the results in the
+    /// same order as the entries.
+    /// The ones of the addons are rendered by them, with their context in `addon_contexts`.
+    fn render_entries(
+        &self,
+        entries: &[PlannedEntry],
+        context: &Context,
+        addon_contexts: &[Context],
+    ) -> Vec<Result<RenderedFile>> {
+        let total = entries.iter().filter(|e| !e.is_dir).count();
+        let render = |entry: &PlannedEntry| match entry.addon {
+            Some(index) => self.addons[index].1.render_entry(entry, &addon_contexts[index]),
+            None => self.render_entry(entry, context),
+        };
+
+        let jobs = self.jobs.min(total);
+        if jobs <= 1 {
+            return entries.iter().map(render).collect();
+        }
+        debug!("Rendering {total} files on {jobs} threads");
+        let next = AtomicUsize::new(0);
+        let mut results: Vec<_> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..jobs)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(entry) = entries.get(index) else {
+                                break done;
+                            };
+                            done.push((index, render(entry)));
+                        }
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|w| w.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect()
+        });
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+
+    /// Renders the template with the current variables without writing anything: every file
+    /// and directory [Template::generate] would write, by their rendered path relative to the
+    /// output directory.
+    /// The files copied as is, eg binary ones, are not read, see [RenderedFile::Verbatim].
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use kickstart::{RenderedFile, Template};
+    ///
+    /// let mut template = Template::from_local(Path::new("examples/super-basic"), None).unwrap();
+    /// template.set_variables(template.definition.default_values().unwrap()).unwrap();
+    /// let files = template.render_to_map().unwrap();
+    /// assert!(files[Path::new("Hello")].is_dir());
+    /// match &files[Path::new("Hello/Howdy.py")] {
+    ///     RenderedFile::Rendered { content, .. } => assert_eq!(content, "print(\"Hello, Vincent!\")\n"),
+    ///     RenderedFile::Directory | RenderedFile::Verbatim { .. } => unreachable!(),
+    /// }
+    /// ```
+    pub fn render_to_map(&self) -> Result<BTreeMap<PathBuf, RenderedFile>> {
+        self.render_map(None, None, &self.context())
+    }
+
+    /// Render the files coming from one of `sources`, paths relative to the template, and all
+    /// the directories, without stopping at the first file that fails to render. `None` renders
+    /// everything. Returns the path of every entry in the output directory and in the template
+    /// along with how it rendered, see [crate::preview::Preview].
+    pub(crate) fn render_sources(
+        &self,
+        sources: Option<&[PathBuf]>,
+    ) -> Result<Vec<(PathBuf, PathBuf, Result<RenderedFile>)>> {
+        let context = self.context();
+        let mut entries = self.generated_entries(None, None, &context)?;
+        let addon_contexts: Vec<_> = self.addons.iter().map(|(_, a)| a.context()).collect();
+        for (index, ((_, addon), context)) in self.addons.iter().zip(&addon_contexts).enumerate() {
+            let addon_entries = addon.generated_entries(None, None, context)?;
+            entries.extend(
+                addon_entries.into_iter().map(|entry| PlannedEntry { addon: Some(index), ..entry }),
+            );
+        }
+        let relative = |entry: &PlannedEntry| {
+            entry.source.strip_prefix(&self.path).unwrap_or(&entry.source).to_path_buf()
+        };
+        if let Some(sources) = sources {
+            entries.retain(|entry| entry.is_dir || sources.contains(&relative(entry)));
+        }
+
+        let rendered = self.render_entries(&entries, &context, &addon_contexts);
+        Ok(entries
+            .iter()
+            .zip(rendered)
+            .map(|(entry, file)| (entry.path.clone(), relative(entry), file))
+            .collect())
+    }
+
+    /// Render the content of a file of the template.
+    /// Variables that don't exist are errors pointing at where they are used, unless the
+    /// template has `lenient_rendering`, in which case they are rendered as empty strings.
+    fn render_file(&self, text: &str, context: &Context, path: &Path) -> Result<String> {
+        let path = path.strip_prefix(&self.path).unwrap_or(path);
+        let mut context = Cow::Borrowed(context);
+        // All the missing variables at once, rendering again below is only for the ones that
+        // can't be found without parsing the template. The ones given a `default` or tested with
+        // `defined` have to stay undefined.
+        if self.definition.lenient_rendering {
+            let checked =
+                Regex::new(r"\b(\w+)\s*(?:\|\s*default\b|is\s+(?:not\s+)?(?:un)?defined\b)")
+                    .unwrap();
+            let checked: Vec<_> =
+                checked.captures_iter(text).map(|caps| caps[1].to_string()).collect();
+            for name in template_identifiers(text) {
+                if !context.contains_key(&name) && !checked.contains(&name) {
+                    context.to_mut().insert(&name, "");
+                }
+            }
+        }
+        loop {
+            let err = match self.render_text(text, &context, Some(path.to_path_buf())) {
+                Ok(rendered) => return Ok(rendered),
+                Err(err) => err,
+            };
+            let ErrorKind::Tera { err: ref tera_err, .. } = err.kind else {
+                return Err(err);
+            };
+            let Some(name) = undefined_variable(tera_err) else {
+                return Err(err);
+            };
+
+            // Only top-level variables can be added, `{{ a.b }}` can't be fixed that way
+            if self.definition.lenient_rendering
+                && !name.contains('.')
+                && !context.contains_key(&name)
+            {
+                context.to_mut().insert(&name, "");
+                continue;
+            }
+            return Err(new_error(ErrorKind::UndefinedVariable {
+                position: find_variable_use(text, &name),
+                name,
+                path: path.to_path_buf(),
+            }));
+        }
+    }
+
+    /// The rendered paths of the cleanup actions that apply, relative to the output directory
+    fn cleanup_paths(&self, context: &Context) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for cleanup in &self.definition.cleanup {
+            if let Some(val) = self.variables.get(&cleanup.name) {
+                if *val == cleanup.value {
+                    for p in &cleanup.paths {
+                        paths.push(PathBuf::from(self.render_text(p, context, None)?));
+                    }
+                }
+            }
+        }
+        Ok(paths)
+    }
+
+    /// Generate the template at the given output directory.
+    /// Unless disabled with [Template::set_atomic], everything is rendered in a staging
+    /// directory first and only moved to the output directory once that succeeded.
+    /// Files already existing in the output directory are handled according to the policy set
+    /// with [Template::set_overwrite_policy].
+    /// If it fails, everything it created is removed.
+    pub fn generate(&self, output_dir: &Path) -> Result<Generation> {
+        self.generate_inner(output_dir, None)
+    }
+
+    /// Generate the template at the given output directory, calling `resolve` for every
+    /// non-empty file that already exists to decide what to do with it, unless the policy set
+    /// with [Template::set_overwrite_policy] already says to overwrite or skip them.
+    /// All the conflicts are resolved before writing anything.
+    /// If it fails, everything it created is removed.
+    pub fn generate_with(
+        &self,
+        output_dir: &Path,
+        resolve: &mut ConflictResolver,
+    ) -> Result<Generation> {
+        self.generate_inner(output_dir, Some(resolve))
+    }
+
+    /// Generate the template in a `.tar.gz` or `.zip` archive at `archive` rather than in a
+    /// directory, eg to offer it as a download. Everything is rendered in memory, as with
+    /// [Template::render_to_map], except the files copied verbatim which are streamed from the
+    /// template into the archive. The entries are dated from the generation, see
+    /// [Template::set_deterministic] for archives that are the same every time. Nothing is left
+    /// at `archive` if it fails.
+    /// The paths of the returned [Generation] are relative to the root of the archive and
+    /// `created` only has the archive.
+    pub fn generate_archive(&self, archive: &Path) -> Result<Generation> {
+        let files = self.render_to_map()?;
+        let mut generation = Generation::default();
+        let mut entries = BTreeMap::new();
+        for (path, file) in &files {
+            let entry = match file {
+                RenderedFile::Directory => {
+                    generation.directories.push(path.clone());
+                    entries.insert(path.clone(), archive::Entry::Directory);
+                    continue;
+                }
+                RenderedFile::Rendered { content, source } => {
+                    archive::Entry::File(Cow::Borrowed(content.as_bytes()), unix_mode(source)?)
+                }
+                RenderedFile::Verbatim { source } => {
+                    archive::Entry::Copy(source.clone(), unix_mode(source)?)
+                }
+            };
+            entries.insert(path.clone(), entry);
+            generation.files.push(GeneratedFile::new(path.clone(), file)?);
+        }
+        if self.definition.write_lockfile {
+            let content = self.lockfile(&generation.files, None).to_json();
+            entries.insert(
+                PathBuf::from(&self.definition.lockfile_name),
+                archive::Entry::File(Cow::Owned(content.into_bytes()), 0o644),
+            );
+        }
+        archive::create(&entries, archive, self.builtins.timestamp)?;
+        generation.created = vec![archive.canonicalize()?];
+        Ok(generation)
+    }
+
+    fn generate_inner(
+        &self,
+        output_dir: &Path,
+        resolve: Option<&mut ConflictResolver>,
+    ) -> Result<Generation> {
+        let mut generation = Generation::default();
+
+        // The output directory might be nested in several directories that don't exist yet
+        let missing =
+            output_dir.ancestors().take_while(|p| !p.as_os_str().is_empty() && !p.exists()).count();
+        create_directory(output_dir)?;
+        let output_dir = output_dir.canonicalize()?;
+        if let Some(top) = missing.checked_sub(1).and_then(|n| output_dir.ancestors().nth(n)) {
+            generation.created.push(top.to_path_buf());
+        }
+
+        let own_staging;
+        let staging = match (&self.staging, self.atomic) {
+            (_, false) => None,
+            (Some(staging), true) => Some(staging.path()),
+            (None, true) => {
+                own_staging = staging_dir_for(&output_dir)?;
+                Some(own_staging.path())
+            }
+        };
+        let write_dir = match staging {
+            // It might have been removed since it was prepared
+            Some(staging) => {
+                create_directory(staging)?;
+                staging.canonicalize()?
+            }
+            None => output_dir.clone(),
+        };
+
+        let res = self.write_output(&output_dir, &write_dir, resolve, &mut generation);
+        match res.and_then(|()| match staging {
+            Some(_) => move_staged(&write_dir, &output_dir, &mut generation),
+            None => Ok(()),
+        }) {
+            Ok(()) => Ok(generation),
+            Err(e) => {
+                // The error is more useful than one happening while cleaning up
+                let _ = generation.rollback();
+                Err(e)
+            }
+        }
+    }
+
+    /// Does the actual generation for [Template::generate_inner], writing the files in
+    /// `write_dir`, which is either the output directory or the staging directory.
+    /// Keeps track of what it writes and creates in the output directory in `generation`.
+    fn write_output(
+        &self,
+        output_dir: &Path,
+        write_dir: &Path,
+        mut resolve: Option<&mut ConflictResolver>,
+        generation: &mut Generation,
+    ) -> Result<()> {
+        let context = self.context();
+        let files = self.render_map(Some(output_dir), Some(write_dir), &context)?;
+        // What is created is tracked when moving it out of the staging directory otherwise
+        let track_created = write_dir == output_dir;
+
+        // Look for existing files before writing anything so we don't end up with a half
+        // overwritten directory
+        let mut existing = Vec::new();
+        for (path, file) in files.iter().filter(|(_, f)| !f.is_dir()) {
+            let real_path = output_dir.join(path);
+            if !fs::metadata(&real_path).is_ok_and(|m| m.len() > 0) {
+                continue;
+            }
+            let resolution = match (self.overwrite_policy, resolve.as_mut()) {
+                (OverwritePolicy::Overwrite, _) => ConflictResolution::Overwrite,
+                (OverwritePolicy::Skip, _) => ConflictResolution::Skip,
+                (OverwritePolicy::Abort, Some(resolve)) => {
+                    let existing = map_io_err(fs::read(&real_path), &real_path)?;
+                    resolve(&Conflict { path, existing: &existing, generated: &file.bytes()? })?
+                }
+                (OverwritePolicy::Abort, None) => {
+                    existing.push(path.clone());
+                    continue;
+                }
+            };
+            match resolution {
+                ConflictResolution::Overwrite => generation.overwritten.push(path.clone()),
+                ConflictResolution::Skip => generation.skipped.push(path.clone()),
+                ConflictResolution::Abort => return Err(new_error(ErrorKind::Aborted)),
+            }
+        }
+        if !existing.is_empty() {
+            return Err(new_error(ErrorKind::ExistingFiles(existing)));
+        }
+
+        // The relative path and hash of every file written
+        let mut written = Vec::new();
+        // The files kept as they are don't count
+        let total = files
+            .iter()
+            .filter(|(path, f)| !f.is_dir() && !generation.skipped.contains(path))
+            .count();
+
+        for (path, file) in files {
+            let real_path = write_dir.join(&path);
+            if generation.skipped.contains(&path) {
+                debug!("Skipping {}: it already exists", path.display());
+                continue;
+            }
+            ensure_inside(write_dir, &real_path, &path)?;
+            if track_created && !real_path.exists() {
+                generation.created.push(real_path.clone());
+            }
+
+            if file.is_dir() {
+                create_directory(&real_path)?;
+                generation.directories.push(path);
+                continue;
+            }
+
+            if let Some(ProgressFn(ref progress)) = self.progress {
+                progress(&Progress { path: &path, current: written.len() + 1, total });
+            }
+            match file {
+                RenderedFile::Verbatim { ref source } => {
+                    debug!("Copying {} as is", path.display());
+                    map_io_err(fs::copy(source, &real_path), source)?;
+                }
+                RenderedFile::Rendered { ref source, ref content } => {
+                    debug!("Rendering {}", path.display());
+                    write_file(&real_path, content)?;
+                    copy_permissions(source, &real_path)?;
+                }
+                RenderedFile::Directory => unreachable!("directories are created above"),
+            }
+            written.push(GeneratedFile::new(path, &file)?);
+        }
+
+        if self.definition.write_lockfile {
+            let lockfile_path = write_dir.join(&self.definition.lockfile_name);
+            if track_created && !lockfile_path.exists() {
+                generation.created.push(lockfile_path.clone());
+            }
+            self.lockfile(&written, Some(output_dir)).write(&lockfile_path)?;
+        }
+
+        generation.files = written;
+        Ok(())
+    }
+
+    /// The lockfile of a generation that wrote `written`. When only some files are generated
+    /// again, the other ones are taken from the lockfile already in `output_dir`, if any.
+    fn lockfile(&self, written: &[GeneratedFile], output_dir: Option<&Path>) -> Lockfile {
+        let mut files: BTreeMap<_, _> = written
+            .iter()
+            .map(|f| (f.path.to_string_lossy().replace('\\', "/"), f.sha256.clone()))
+            .collect();
+        // The other files are still the ones of the previous generation, if any
+        if let Some(output_dir) = output_dir.filter(|_| !self.only.is_empty()) {
+            let previous = output_dir.join(&self.definition.lockfile_name);
+            if let Ok(previous) = Lockfile::load(&previous) {
+                for (path, hash) in previous.files {
+                    files.entry(path).or_insert(hash);
+                }
+            }
+        }
+        let variables: HashMap<_, _> = self
+            .variables
+            .iter()
+            .filter(|(name, _)| !self.definition.is_secret(name))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        let mut lockfile = Lockfile::new(
+            &self.source,
+            self.commit.as_deref(),
+            self.directory.as_deref(),
+            &variables,
+            files,
+        );
+        lockfile.addons = self.addons.iter().map(|(name, _)| name.clone()).collect();
+        lockfile
+    }
+
+    /// Goes through the template like [Template::generate] would, rendering all the paths,
+    /// patterns and files, but without writing anything.
+    /// Returns what would be created, as well as all the rendering errors encountered rather
+    /// than stopping at the first one.
+    pub fn dry_run(&self, output_dir: &Path) -> Result<(Vec<DryRunEntry>, Vec<Error>)> {
+        let output_dir = output_dir.canonicalize().ok();
+        let mut errors = Vec::new();
+        let templates: Vec<_> =
+            iter::once(self).chain(self.addons.iter().map(|(_, a)| a)).collect();
+        let contexts: Vec<_> = templates.iter().map(|t| t.context()).collect();
+
+        let mut planned = Vec::new();
+        for (index, (template, context)) in templates.iter().zip(&contexts).enumerate() {
+            let cleanup_paths = template.cleanup_paths(context).unwrap_or_else(|err| {
+                errors.push(err);
+                Vec::new()
+            });
+            let entries = template.plan(output_dir.as_deref(), None, context, Some(&mut errors))?;
+            planned.extend(
+                entries
+                    .into_iter()
+                    .filter(|entry| !cleanup_paths.iter().any(|p| entry.path.starts_with(p)))
+                    .map(|entry| PlannedEntry { addon: index.checked_sub(1), ..entry }),
+            );
+        }
+        if !self.only.is_empty() {
+            planned = self.select_only(planned)?;
+        }
+        let mut entries = Vec::new();
+        for entry in planned {
+            let index = entry.addon.map_or(0, |i| i + 1);
+            let (template, context) = (templates[index], &contexts[index]);
+            if let Some(reason) = entry.ignored_because {
+                entries.push(DryRunEntry {
+                    path: entry.path,
+                    is_dir: entry.is_dir,
+                    size: 0,
+                    skipped_because: Some(reason),
+                    ignored: true,
+                });
+                continue;
+            }
+            if let Some(index) = entry.excluded_by {
+                let cond = &template.definition.conditional_files[index].only_if;
+                entries.push(DryRunEntry {
+                    path: entry.path,
+                    is_dir: entry.is_dir,
+                    size: 0,
+                    skipped_because: Some(format!("only if {cond}")),
+                    ignored: false,
+                });
+                continue;
+            }
+
+            let size = match template.render_entry(&entry, context).and_then(|f| f.size()) {
+                Ok(size) => size,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+            entries.push(DryRunEntry {
+                path: entry.path,
+                is_dir: entry.is_dir,
+                size,
+                skipped_because: None,
+                ignored: false,
+            });
+        }
+
+        Ok((entries, errors))
+    }
+
+    /// Renders everything in the template with the current variables, without writing anything
+    /// and whatever the conditions say: the files and their paths like [Template::dry_run], as
+    /// well as the patterns of the conditions that are not met, the `output_dir`, the
+    /// `post_gen_message`, the `git_init_message` and all the hooks. Used to check a template
+    /// before publishing it.
+    /// Returns all the errors encountered rather than stopping at the first one.
+    pub fn render_errors(&self) -> Vec<Error> {
+        let context = self.context();
+        let mut errors = match self.dry_run(self.tmp_dir.path()) {
+            Ok((_, errors)) => errors,
+            Err(err) => vec![err],
+        };
+
+        let unused_patterns = self
+            .definition
+            .conditional_files
+            .iter()
+            .filter(|files| self.is_condition_met(&files.only_if))
+            .map(|files| &files.paths)
+            .chain(
+                self.definition
+                    .cleanup
+                    .iter()
+                    .filter(|c| self.variables.get(&c.name) != Some(&c.value))
+                    .map(|c| &c.paths),
+            );
+        for patterns in unused_patterns {
+            for pattern in patterns {
+                if let Err(err) = self.render_text(pattern, &context, None) {
+                    errors.push(err);
+                }
+            }
+        }
+
+        if let Err(err) = self.default_output_dir() {
+            errors.push(err);
+        }
+        if let Err(err) = self.post_gen_message() {
+            errors.push(err);
+        }
+        if let Err(err) = self.git_init_message() {
+            errors.push(err);
+        }
+
+        for hook in self.definition.all_hooks() {
+            let rendered = read_file(&self.path.join(&hook.path))
+                .and_then(|content| self.render_text(&content, &context, Some(hook.path.clone())));
+            if let Err(err) = rendered {
+                errors.push(err);
+            }
+            if let Err(err) = self.hook_args(hook, &context) {
+                errors.push(err);
+            }
+        }
+
+        errors
+    }
+
+    /// Which variables are not used by any file, path or hook of the template, nor by the
+    /// definition itself, and which identifiers used in those are not variables.
+    /// Tera is not really parsed to find them, eg variables set in an included file are not
+    /// known, so both can be wrong.
+    pub fn variable_usage(&self) -> Result<VariableUsage> {
+        let context = self.context();
+        let names: Vec<&str> = self.definition.variables.iter().map(|v| v.name.as_str()).collect();
+        let optional: Vec<&str> = self
+            .definition
+            .variables
+            .iter()
+            .filter(|v| !v.required)
+            .map(|v| v.name.as_str())
+            .collect();
+        let mut used = self.definition.referenced_variables();
+        let mut undeclared: Vec<(String, PathBuf)> = Vec::new();
+        let mut found_in = |text: &str, path: &Path| {
+            for ident in template_identifiers(text) {
+                // `has_<name>` of optional variables is a use of the variable
+                let presence_of = ident.strip_prefix("has_").filter(|n| optional.contains(n));
+                if let Some(name) = presence_of {
+                    used.push(name.to_string());
+                } else if names.contains(&ident.as_str()) {
+                    used.push(ident);
+                } else if ident != BUILTIN_CONTEXT_KEY
+                    && ident != EXTRA_CONTEXT_KEY
+                    && !undeclared.iter().any(|(i, _)| i == &ident)
+                {
+                    undeclared.push((ident, path.to_path_buf()));
+                }
+            }
+        };
+        let read_text = |path: &Path| -> Result<Option<String>> {
+            let buffer = map_io_err(fs::read(path), path)?;
+            Ok(if is_binary(&buffer) { None } else { String::from_utf8(buffer).ok() })
+        };
+
+        // The paths that don't render are reported by `validate --render`
+        let mut files = Vec::new();
+        for entry in self.plan(None, None, &context, Some(&mut Vec::new()))? {
+            if entry.ignored_because.is_some() {
+                continue;
+            }
+            // What's in an excluded directory is not planned but would be with other answers
+            if entry.is_dir && entry.excluded_by.is_some() {
+                for e in WalkDir::new(&entry.source).min_depth(1).into_iter().filter_map(|e| e.ok())
+                {
+                    files.push((e.path().to_path_buf(), !e.file_type().is_dir()));
+                }
+            }
+            files.push((entry.source, !entry.is_dir && !entry.no_render));
+        }
+        for (source, render) in files {
+            let path = source.strip_prefix(&self.path).unwrap_or(&source);
+            found_in(&path.to_string_lossy().replace("$$", "|"), path);
+            if render {
+                if let Some(text) = read_text(&source)? {
+                    found_in(&text, path);
+                }
+            }
+        }
+
+        let mut hook_env_vars = Vec::new();
+        for hook in self.definition.all_hooks() {
+            for arg in &hook.args {
+                found_in(arg, &hook.path);
+            }
+            if let Some(text) = read_text(&self.path.join(&hook.path))? {
+                found_in(&text, &hook.path);
+                hook_env_vars.extend(
+                    names
+                        .iter()
+                        .filter(|n| text.contains(&hook_env_var_name(n)))
+                        .map(|n| n.to_string()),
+                );
+            }
+        }
+        used.extend(hook_env_vars);
+
+        undeclared.sort();
+        Ok(VariableUsage {
+            unused: names
+                .into_iter()
+                .filter(|n| !used.iter().any(|u| u == n))
+                .map(String::from)
+                .collect(),
+            undeclared,
+        })
+    }
+}
+
+/// Names that can't be used for files on Windows, with or without extension
+const WINDOWS_RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Why that file name can't be used on Windows, if it can't
+fn windows_name_error(name: &str) -> Option<&'static str> {
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    if WINDOWS_RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        Some("is a reserved name on Windows")
+    } else if name.contains(|c: char| "<>:\"|?*".contains(c) || c.is_ascii_control()) {
+        Some("contains a character that is not allowed on Windows")
+    } else {
+        None
+    }
+}
+
+/// Make sure a rendered path of the template stays in the output directory and only has
+/// valid names, as variables could contain anything, eg `../`
+fn check_rendered_path(path: &Path, rendered: &str) -> Result<()> {
+    let err = |reason| {
+        new_error(ErrorKind::InvalidRenderedPath {
+            path: path.to_path_buf(),
+            rendered: rendered.to_string(),
+            reason,
+        })
+    };
+
+    // Checked first as the root would otherwise be reported as an empty name
+    if Path::new(rendered).has_root() {
+        return Err(err("is outside of the output directory"));
+    }
+    // Splitting the string rather than using `Path::components` as it hides empty names
+    for name in rendered.split(std::path::is_separator) {
+        let reason = match name {
+            "" => Some("has an empty file or directory name"),
+            "." | ".." => Some("is outside of the output directory"),
+            _ if name.contains('\0') => Some("contains a NUL character"),
+            _ if cfg!(windows) => windows_name_error(name),
+            _ => None,
+        };
+        if let Some(reason) = reason {
+            return Err(err(reason));
+        }
+    }
+    // Eg `C:` on Windows, which doesn't contain a separator
+    if Path::new(rendered).components().any(|c| !matches!(c, Component::Normal(_))) {
+        return Err(err("is outside of the output directory"));
+    }
+    Ok(())
+}
+
+/// Symlinks already in the output directory could still make a file end up outside of it.
+/// `dir` needs to be canonicalized and the parent of `path` to exist.
+fn ensure_inside(dir: &Path, path: &Path, relative_path: &Path) -> Result<()> {
+    let parent = path.parent().expect("to have a parent");
+    if map_io_err(parent.canonicalize(), parent)?.starts_with(dir) {
+        return Ok(());
+    }
+    Err(new_error(ErrorKind::InvalidRenderedPath {
+        path: relative_path.to_path_buf(),
+        rendered: relative_path.display().to_string(),
+        reason: "is outside of the output directory because of a symlink",
+    }))
+}
+
+/// Give a rendered file the permissions of its template file, eg to keep scripts executable.
+/// `fs::copy` already takes care of it for the files copied as is.
+/// Nothing is done on Windows, where the only permission is the read-only flag.
+pub(crate) fn copy_permissions(from: &Path, to: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        let mode = map_io_err(fs::metadata(from), from)?.permissions().mode();
+        map_io_err(fs::set_permissions(to, fs::Permissions::from_mode(mode & 0o7777)), to)?;
+    }
+    #[cfg(not(unix))]
+    let _ = (from, to);
+    Ok(())
+}
+
+/// The Unix permissions of the file at `path`, the usual ones of files on other platforms
+fn unix_mode(path: &Path) -> Result<u32> {
+    #[cfg(unix)]
+    return Ok(map_io_err(fs::metadata(path), path)?.permissions().mode());
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        Ok(0o644)
+    }
+}
+
+/// The `.kickstartignore` file of that directory of the template, if it has one
+fn load_ignore_file(template: &Path, dir: &Path) -> Result<Option<Gitignore>> {
+    let path = dir.join(IGNORE_FILE_NAME);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let invalid = |err: ignore::Error| {
+        let path = path.strip_prefix(template).unwrap_or(&path).to_path_buf();
+        new_error(ErrorKind::InvalidIgnorePattern { path: Some(path), reason: err.to_string() })
+    };
+    let mut builder = GitignoreBuilder::new(dir);
+    if let Some(err) = builder.add(&path) {
+        return Err(invalid(err));
+    }
+    builder.build().map(Some).map_err(invalid)
+}
+
+/// Why the path is ignored by those ignore files, if it is: the deepest one applying to the
+/// path decides, as with git. The ignore files are named relative to `template`.
+fn is_ignored(
+    ignore_files: &[Gitignore],
+    template: &Path,
+    path: &Path,
+    is_dir: bool,
+) -> Option<String> {
+    for ignore_file in ignore_files.iter().rev().filter(|i| path.starts_with(i.path())) {
+        match ignore_file.matched(path, is_dir) {
+            Match::None => continue,
+            Match::Whitelist(_) => return None,
+            Match::Ignore(glob) => {
+                return Some(match glob.from() {
+                    Some(file) => {
+                        let file = file.strip_prefix(template).unwrap_or(file);
+                        format!("ignored by `{}` in {}", glob.original(), file.display())
+                    }
+                    None => format!("excluded by `{}`", glob.original()),
+                });
+            }
+        }
+    }
+    None
+}
+
+/// As many threads as there are CPUs, or 1 if we can't know
+fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Create the directory to render the template in before moving it to `output_dir`.
+/// It is created next to it if possible so moving files is only renaming them.
+fn staging_dir_for(output_dir: &Path) -> Result<TempDir> {
+    let output_dir = match output_dir.canonicalize() {
+        Ok(p) => p,
+        Err(_) => env::current_dir()?.join(output_dir),
+    };
+    let name = output_dir.file_name().unwrap_or_default().to_string_lossy();
+    let prefix = format!(".{name}.kickstart-");
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(&prefix);
+    // The parent might not be writable, eg when generating in an existing directory
+    let sibling = output_dir
+        .ancestors()
+        .skip(1)
+        .find(|p| p.is_dir())
+        .and_then(|parent| builder.tempdir_in(parent).ok());
+    match sibling {
+        Some(staging) => Ok(staging),
+        None => Ok(builder.tempdir()?),
+    }
+}
+
+/// Move everything rendered in the staging directory to the output directory, merging it with
+/// what is already there. What didn't exist before is added to `generation`.
+fn move_staged(staging: &Path, output_dir: &Path, generation: &mut Generation) -> Result<()> {
+    let entries = WalkDir::new(staging)
+        .min_depth(1)
+        .into_iter()
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(std::io::Error::from)?;
+
+    for entry in entries {
+        let relative_path = entry.path().strip_prefix(staging).expect("Stripping prefix");
+        let dest = output_dir.join(relative_path);
+        ensure_inside(output_dir, &dest, relative_path)?;
+        if !dest.exists() {
+            generation.created.push(dest.clone());
+        }
+        if entry.file_type().is_dir() {
+            create_directory(&dest)?;
+            continue;
+        }
+        // Renaming doesn't work across filesystems, eg if the staging directory had to be
+        // created in the system temp directory. It is deleted when dropped in that case.
+        if fs::rename(entry.path(), &dest).is_err() {
+            map_io_err(fs::copy(entry.path(), &dest), &dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Files bigger than that are copied as is without being read whole if they have nothing to
+/// render, see [could_need_rendering]
+const LARGE_FILE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// A path of the template that will end up in the output directory
+#[derive(Debug)]
+struct PlannedEntry {
+    /// The path in the template
+    source: PathBuf,
+    /// The rendered path, relative to the output directory
+    path: PathBuf,
+    is_dir: bool,
+    /// Whether it matched one of the `copy_without_render` patterns
+    no_render: bool,
+    /// How to write its line endings if it is rendered
+    line_endings: LineEndings,
+    /// The index of the `conditional_files` excluding it, if any
+    excluded_by: Option<usize>,
+    /// Why it is never generated if it is ignored, by a `.kickstartignore` file or an
+    /// exclusion. What's inside an ignored directory is not planned at all.
+    ignored_because: Option<String>,
+    /// The index of the selected addon it comes from, `None` if it comes from the template
+    addon: Option<usize>,
+}
+
+/// What ends up at a path of the output directory, see [Template::render_to_map]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderedFile {
+    /// A directory of the template, listed so the empty ones are created too
+    Directory,
+    /// A file that went through Tera, or only had its line endings converted if it is large
+    /// and has nothing to render
+    Rendered {
+        /// The path of the file in the template
+        source: PathBuf,
+        content: String,
+    },
+    /// A file copied as is, because it is binary, matches `copy_without_render` or is large
+    /// and has nothing to render.
+    /// Its content is not kept in memory, it is read from `source` when needed.
+    Verbatim {
+        /// The path of the file in the template
+        source: PathBuf,
+    },
+}
+
+impl RenderedFile {
+    pub fn is_dir(&self) -> bool {
+        matches!(self, RenderedFile::Directory)
+    }
+
+    /// The content that would be written, empty for directories
+    pub fn bytes(&self) -> Result<Cow<'_, [u8]>> {
+        match self {
+            RenderedFile::Directory => Ok(Cow::Borrowed(&[])),
+            RenderedFile::Rendered { content, .. } => Ok(Cow::Borrowed(content.as_bytes())),
+            RenderedFile::Verbatim { source } => {
+                map_io_err(fs::read(source), source).map(Cow::Owned)
+            }
+        }
+    }
+
+    /// The size in bytes of the content, 0 for directories
+    pub fn size(&self) -> Result<u64> {
+        match self {
+            RenderedFile::Directory => Ok(0),
+            RenderedFile::Rendered { content, .. } => Ok(content.len() as u64),
+            RenderedFile::Verbatim { source } => {
+                map_io_err(fs::metadata(source), source).map(|m| m.len())
+            }
+        }
+    }
+}
+
+/// Paths are written with `/` whatever the platform, like in the lockfile
+fn serialize_path<S: Serializer>(path: &Path, serializer: S) -> result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.to_string_lossy().replace('\\', "/"))
+}
+
+/// A file written by [Template::generate]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GeneratedFile {
+    /// Relative to the output directory
+    #[serde(serialize_with = "serialize_path")]
+    pub path: PathBuf,
+    /// In bytes
+    pub size: u64,
+    /// Whether it was rendered with Tera rather than copied as is
+    pub rendered: bool,
+    /// The SHA-256 of its content, as in the lockfile
+    pub sha256: String,
+}
+
+impl GeneratedFile {
+    /// What writing `file` at `path`, relative to the output directory, gives
+    pub fn new(path: PathBuf, file: &RenderedFile) -> Result<GeneratedFile> {
+        let (rendered, sha256) = match file {
+            RenderedFile::Directory => (false, hash_content(&[])),
+            RenderedFile::Rendered { content, .. } => (true, hash_content(content.as_bytes())),
+            RenderedFile::Verbatim { source } => (false, hash_file(source)?),
+        };
+        Ok(GeneratedFile { path, size: file.size()?, rendered, sha256 })
+    }
+}
+
+/// What [Template::generate] did in the output directory
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Generation {
+    /// The files written, in the order they were written
+    pub files: Vec<GeneratedFile>,
+    /// The directories of the template written, relative to the output directory.
+    /// They might have existed before.
+    pub directories: Vec<PathBuf>,
+    /// The absolute paths of the files and directories that did not exist before, in the order
+    /// they were created
+    pub created: Vec<PathBuf>,
+    /// The files that already existed and were overwritten, relative to the output directory
+    pub overwritten: Vec<PathBuf>,
+    /// The files that already existed and were kept as is, relative to the output directory
+    pub skipped: Vec<PathBuf>,
+}
+
+impl Generation {
+    /// Remove everything that was created by the generation, eg after a post-gen hook failed.
+    /// Files that existed before are left untouched, even if they were overwritten.
+    pub fn rollback(&self) -> Result<()> {
+        for path in self.created.iter().rev() {
+            // Already removed by the cleanup or along with its directory
+            let Ok(metadata) = fs::symlink_metadata(path) else {
+                continue;
+            };
+            if metadata.is_dir() {
+                map_io_err(fs::remove_dir_all(path), path)?;
+            } else {
+                map_io_err(fs::remove_file(path), path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How the variables are used by the template, see [Template::variable_usage]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VariableUsage {
+    /// The variables that are never used, in the order they are declared
+    pub unused: Vec<String>,
+    /// The identifiers the template uses that are not variables, with the path relative to the
+    /// template of a file using it
+    pub undeclared: Vec<(String, PathBuf)>,
+}
+
+/// Where the generation is, given to the function set with [Template::set_progress]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress<'a> {
+    /// The file about to be written, relative to the output directory
+    pub path: &'a Path,
+    /// Its position among the files to render, starting at 1
+    pub current: usize,
+    pub total: usize,
+}
+
+/// The function set with [Template::set_progress]
+struct ProgressFn(Box<dyn Fn(&Progress) + Send + Sync>);
+
+impl fmt::Debug for ProgressFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressFn")
+    }
+}
+
+/// A file or directory that would be created when generating the template
+#[derive(Debug, Clone, PartialEq)]
+pub struct DryRunEntry {
+    /// The rendered path, relative to the output directory
+    pub path: PathBuf,
+    pub is_dir: bool,
+    /// The size in bytes of the file, 0 for directories
+    pub size: u64,
+    /// Why it would not be generated, if it is excluded by `conditional_files` or ignored.
+    /// Everything inside a skipped directory is left out.
+    pub skipped_because: Option<String>,
+    /// Whether it is skipped because of a `.kickstartignore` file or [Template::set_excludes]
+    /// rather than because of the answers
+    pub ignored: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn can_generate_from_local_path() {
+        let dir = tempdir().unwrap();
+        let mut tpl = Template::from_input("examples/complex", None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        let res = tpl.generate(dir.path());
+
+        assert!(res.is_ok());
+        assert!(!dir.path().join("some-project").join("template.toml").exists());
+        assert!(dir.path().join("some-project").join("logo.png").exists());
+    }
+
+    #[test]
+    fn lists_what_was_generated() {
+        let dir = tempdir().unwrap();
+        let mut tpl = Template::from_input("examples/complex", None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        let generation = tpl.generate(dir.path()).unwrap();
+
+        let project = Path::new("some-project");
+        let file = |name: &str| generation.files.iter().find(|f| f.path == project.join(name));
+        let logo = file("logo.png").unwrap();
+        assert!(!logo.rendered);
+        assert_eq!(logo.size, fs::metadata(dir.path().join(&logo.path)).unwrap().len());
+        let readme = file("README.md").unwrap();
+        let content = fs::read(dir.path().join(&readme.path)).unwrap();
+        assert!(readme.rendered);
+        assert_eq!(readme.size, content.len() as u64);
+        assert_eq!(readme.sha256, hash_content(&content));
+        assert!(generation.directories.contains(&project.join("docs")));
+        assert!(!generation
+            .directories
+            .iter()
+            .any(|d| generation.files.iter().any(|f| &f.path == d)));
+    }
+
+    #[test]
+    fn reports_the_progress_of_the_generation() {
+        let dir = tempdir().unwrap();
+        let mut tpl = Template::from_input("examples/complex", None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        tpl.set_progress({
+            let seen = seen.clone();
+            move |p| seen.lock().unwrap().push((p.current, p.total, p.path.to_path_buf()))
+        });
+        let generation = tpl.generate(dir.path()).unwrap();
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), generation.files.len());
+        assert!(seen
+            .iter()
+            .enumerate()
+            .all(|(i, (current, total, _))| { *current == i + 1 && *total == seen.len() }));
+        assert!(seen.iter().any(|(_, _, path)| path.ends_with("logo.png")));
+        drop(seen);
+
+        // Only what is written again counts
+        let readme = generation.files.iter().find(|f| f.path.ends_with("README.md")).unwrap();
+        fs::remove_file(dir.path().join(&readme.path)).unwrap();
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        tpl.set_progress({
+            let seen = seen.clone();
+            move |p| seen.lock().unwrap().push((p.current, p.total, p.path.to_path_buf()))
+        });
+        tpl.set_overwrite_policy(OverwritePolicy::Skip);
+        tpl.generate(dir.path()).unwrap();
+        assert_eq!(*seen.lock().unwrap(), [(1, 1, readme.path.clone())]);
+    }
+
+    #[test]
+    fn finds_templates_in_subdirectories() {
+        let err = Template::from_input("examples", None).unwrap_err();
+        let ErrorKind::MultipleTemplates(candidates) = err.kind else {
+            panic!("expected several templates, got {err}");
+        };
+        let complex = candidates.iter().find(|c| c.directory == "complex").unwrap();
+        assert_eq!(complex.name, "Django");
+        assert_eq!(complex.description.as_deref(), Some("A fully-featured Django template"));
+        assert!(candidates.iter().any(|c| c.directory == "super-basic"));
+
+        // A single template is used directly
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("templates").join("only")).unwrap();
+        fs::copy(
+            "examples/super-basic/template.toml",
+            dir.path().join("templates").join("only").join("template.toml"),
+        )
+        .unwrap();
+        let tpl = Template::from_local(dir.path(), Some("templates")).unwrap();
+        assert_eq!(tpl.directory.as_deref(), Some("templates/only"));
+
+        let err = Template::from_local(&dir.path().join("templates").join("only"), Some("nope"));
+        assert!(matches!(err.unwrap_err().kind, ErrorKind::MissingTemplateDefinition));
+    }
+
+    #[test]
+    fn picks_one_of_the_templates_of_a_repository_from_the_same_clone() {
+        let dir = tempdir().unwrap();
+        let repo = dir.path().join("templates");
+        for name in ["a", "b"] {
+            fs::create_dir_all(repo.join(name)).unwrap();
+            fs::copy("examples/super-basic/template.toml", repo.join(name).join("template.toml"))
+                .unwrap();
+        }
+        for args in [
+            &["init"][..],
+            &["add", "--all"],
+            &["-c", "user.name=a", "-c", "user.email=a@a", "commit", "-m", "Add"],
+        ] {
+            assert!(git(&repo, args).unwrap().status.success());
+        }
+
+        let options =
+            CloneOptions { cache_dir: Some(dir.path().join("cache")), ..Default::default() };
+        let remote = format!("file://{}", repo.display());
+        let mut seen = Vec::new();
+        let tpl = Template::from_git_picking(&remote, &options, None, &mut |candidates| {
+            seen.extend(candidates.iter().map(|c| c.directory.clone()));
+            // Cloning it again would fail
+            fs::remove_dir_all(&repo).unwrap();
+            Ok(Some("b".to_string()))
+        })
+        .unwrap();
+        assert_eq!(seen, ["a", "b"]);
+        assert_eq!(tpl.directory.as_deref(), Some("b"));
+        assert_eq!(tpl.source(), remote);
+    }
+
+    #[test]
+    fn can_render_the_default_output_dir() {
+        let mut tpl = Template::from_input("examples/super-basic", None).unwrap();
+        assert_eq!(tpl.default_output_dir().unwrap(), None);
+
+        tpl.definition.output_dir = Some("{{ directory_name | slugify }}".to_string());
+        for (name, expected) in [("My Project", "my-project"), ("a/b", "a-b")] {
+            tpl.insert_variable("directory_name", Value::String(name.to_string())).unwrap();
+            assert_eq!(tpl.default_output_dir().unwrap().as_deref(), Some(expected));
+        }
+
+        for output_dir in ["", "..", "{{ directory_name }}/sub", "a\\b", "/tmp"] {
+            tpl.definition.output_dir = Some(output_dir.to_string());
+            let err = tpl.default_output_dir().unwrap_err();
+            assert!(matches!(err.kind, ErrorKind::InvalidOutputDir { .. }), "{output_dir}");
+        }
+    }
+
+    #[test]
+    fn can_render_the_post_gen_message() {
+        let mut tpl = Template::from_input("examples/super-basic", None).unwrap();
+        assert_eq!(tpl.post_gen_message().unwrap(), None);
+
+        tpl.definition.post_gen_message = Some("cd {{ directory_name }}".to_string());
+        tpl.insert_variable("directory_name", Value::String("Hello".to_string())).unwrap();
+        assert_eq!(tpl.post_gen_message().unwrap().as_deref(), Some("cd Hello"));
+        tpl.definition.post_gen_message = Some("cd {{ nope }}".to_string());
+        assert!(matches!(tpl.post_gen_message().unwrap_err().kind, ErrorKind::Tera { .. }));
+    }
+
+    #[test]
+    fn git_init_is_skipped_in_an_existing_repository() {
+        let mut tpl = Template::from_input("examples/super-basic", None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        tpl.definition.git_init_message = "Start {{ directory_name }}".to_string();
+        assert_eq!(tpl.git_init_message().unwrap(), "Start Hello");
+
+        let dir = tempdir().unwrap();
+        assert!(git(dir.path(), &["init"]).unwrap().status.success());
+        let output_dir = dir.path().join("project");
+        tpl.generate(&output_dir).unwrap();
+        let res = tpl.git_init(&output_dir).unwrap();
+        assert!(
+            matches!(res, GitInit::Skipped(ref reason) if reason.ends_with("is already in a git repository"))
+        );
+        assert!(!output_dir.join(".git").exists());
+    }
+
+    #[test]
+    fn multi_choices_are_rendered_as_arrays() {
+        let dir = tempdir().unwrap();
+        let mut tpl = Template::from_input("examples/complex", None).unwrap();
+        let mut vals = tpl.definition.default_values().unwrap();
+        assert_eq!(vals["features"], Value::List(vec![Value::String("ci".to_string())]));
+        tpl.set_variables(vals.clone()).unwrap();
+        tpl.generate(dir.path()).unwrap();
+        let readme = dir.path().join("some-project").join("README.md");
+        assert!(!read_file(&readme).unwrap().contains("## Docker"));
+
+        let dir = tempdir().unwrap();
+        vals.insert("features".to_string(), tpl.parse_value_for("features", "ci,docker").unwrap());
+        tpl.set_variables(vals).unwrap();
+        tpl.generate(dir.path()).unwrap();
+        let readme = dir.path().join("some-project").join("README.md");
+        assert!(read_file(&readme).unwrap().contains("## Docker"));
+    }
+
+    #[test]
+    fn writes_lockfile_unless_disabled() {
+        let dir = tempdir().unwrap();
+        let mut tpl = Template::from_input("examples/super-basic", None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        tpl.generate(dir.path()).unwrap();
+
+        let content = read_file(&dir.path().join(".kickstart.json")).unwrap();
+        let lockfile: Lockfile = serde_json::from_str(&content).unwrap();
+        assert_eq!(lockfile.version, 1);
+        assert!(lockfile.source.ends_with("super-basic"));
+        assert_eq!(lockfile.commit, None);
+        assert!(lockfile.files.contains_key("Hello/Howdy.py"));
+        assert_eq!(lockfile.variables["directory_name"], Value::String("Hello".to_string()));
+
+        let dir = tempdir().unwrap();
+        tpl.definition.write_lockfile = false;
+        tpl.generate(dir.path()).unwrap();
+        assert!(!dir.path().join(".kickstart.json").exists());
+    }
+
+    #[test]
+    fn secrets_are_not_written_to_lockfile() {
+        let tpl_dir = tempdir().unwrap();
+        fs::write(
+            tpl_dir.path().join("template.toml"),
+            "name = 'Secret'\nkickstart_version = 1\n\n[[variables]]\nname = 'token'\n\
+            default = ''\nprompt = 'Token?'\nsecret = true\n",
+        )
+        .unwrap();
+        fs::write(tpl_dir.path().join("config.txt"), "token={{ token }}").unwrap();
+
+        let dir = tempdir().unwrap();
+        let mut tpl = Template::from_input(&tpl_dir.path().to_string_lossy(), None).unwrap();
+        let vals = [("token".to_string(), Value::String("s3cr3t".to_string()))].into();
+        tpl.set_variables(vals).unwrap();
+        tpl.generate(dir.path()).unwrap();
+
+        assert_eq!(read_file(&dir.path().join("config.txt")).unwrap(), "token=s3cr3t");
+        let lockfile = read_file(&dir.path().join(".kickstart.json")).unwrap();
+        assert!(!lockfile.contains("s3cr3t"));
+    }
+
+    #[test]
+    fn hooks_are_selected_by_os() {
+        let tpl_dir = tempdir().unwrap();
+        fs::write(
+            tpl_dir.path().join("template.toml"),
+            r#"
+name = "Hooks"
+kickstart_version = 1
+post_gen_hooks = [
+    { name = "windows", path = "setup.ps1", os = ["windows"] },
+    { name = "unix", path = "setup.sh", os = ["linux", "macos"] },
+    { name = "everywhere", path = "setup.py" },
+]
+variables = []
+"#,
+        )
+        .unwrap();
+        for file in ["setup.ps1", "setup.sh", "setup.py"] {
+            fs::write(tpl_dir.path().join(file), "").unwrap();
+        }
+        let mut tpl = Template::from_input(&tpl_dir.path().to_string_lossy(), None).unwrap();
+
+        for (os, expected) in [
+            ("windows", vec!["windows", "everywhere"]),
+            ("linux", vec!["unix", "everywhere"]),
+            ("macos", vec!["unix", "everywhere"]),
+        ] {
+            tpl.set_hook_os(os);
+            let hooks = tpl.get_post_gen_hooks().unwrap();
+            assert_eq!(hooks.iter().map(|h| h.name()).collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn hook_args_are_rendered() {
+        let tpl_dir = tempdir().unwrap();
+        fs::write(
+            tpl_dir.path().join("template.toml"),
+            r#"
+name = "Hooks"
+kickstart_version = 1
+post_gen_hooks = [
+    { name = "setup", path = "setup.sh", args = ["--name", "{{ project_name | upper }}"] },
+    { name = "broken", path = "setup.sh", args = ["ok", "{{ nope }}"] },
+]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#,
+        )
+        .unwrap();
+        fs::write(tpl_dir.path().join("setup.sh"), "").unwrap();
+        let mut tpl = Template::from_local(tpl_dir.path(), None).unwrap();
+        tpl.set_variables(HashMap::from([(
+            "project_name".to_string(),
+            Value::String("app".to_string()),
+        )]))
+        .unwrap();
+
+        let hooks = tpl.get_post_gen_hooks().unwrap();
+        assert_eq!(tpl.render_hook_args(&hooks[0]).unwrap(), ["--name", "APP"]);
+        let err = tpl.render_hook_args(&hooks[1]).unwrap_err();
+        assert!(
+            matches!(err.kind, ErrorKind::HookArgument { ref hook, index: 1, .. } if hook == "broken"),
+            "{err}"
+        );
+        assert_eq!(tpl.render_errors().len(), 1);
+    }
+
+    #[test]
+    fn respects_overwrite_policy_for_existing_files() {
+        let dir = tempdir().unwrap();
+        let existing = dir.path().join("some-project").join("README.md");
+        fs::create_dir_all(existing.parent().unwrap()).unwrap();
+        fs::write(&existing, "mine").unwrap();
+        let logo = dir.path().join("some-project").join("logo.png");
+
+        let mut tpl = Template::from_input("examples/complex", None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+
+        let err = tpl.generate(dir.path()).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::ExistingFiles(ref p) if p.len() == 1));
+        assert!(!logo.exists());
+
+        tpl.set_overwrite_policy(OverwritePolicy::Skip);
+        tpl.generate(dir.path()).unwrap();
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "mine");
+        assert!(logo.exists());
+
+        tpl.set_overwrite_policy(OverwritePolicy::Overwrite);
+        tpl.generate(dir.path()).unwrap();
+        assert_ne!(fs::read_to_string(&existing).unwrap(), "mine");
+    }
+
+    #[test]
+    fn can_resolve_conflicts_one_by_one() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("some-project");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("README.md"), "mine").unwrap();
+        fs::write(project.join("logo.png"), "mine").unwrap();
+
+        let mut tpl = Template::from_input("examples/complex", None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+
+        let res = tpl.generate_with(dir.path(), &mut |_| Ok(ConflictResolution::Abort));
+        assert!(matches!(res.unwrap_err().kind, ErrorKind::Aborted));
+        assert!(!project.join("docs").exists());
+
+        let mut seen = Vec::new();
+        let written = tpl
+            .generate_with(dir.path(), &mut |c| {
+                assert_eq!(c.existing, b"mine");
+                seen.push(c.path.to_path_buf());
+                if c.path.ends_with("README.md") {
+                    Ok(ConflictResolution::Overwrite)
+                } else {
+                    Ok(ConflictResolution::Skip)
+                }
             })
-            .filter_map(|e| e.ok());
+            .unwrap()
+            .files
+            .into_iter()
+            .map(|f| f.path)
+            .collect::<Vec<_>>();
+        assert_eq!(seen.len(), 2);
+        assert!(written.contains(&Path::new("some-project").join("README.md")));
+        assert!(!written.contains(&Path::new("some-project").join("logo.png")));
+        assert_eq!(fs::read_to_string(project.join("logo.png")).unwrap(), "mine");
+        assert_ne!(fs::read_to_string(project.join("README.md")).unwrap(), "mine");
 
-        let hooks_paths = self.definition.all_hooks_paths();
+        // The policy wins over asking
+        fs::write(project.join("README.md"), "mine").unwrap();
+        tpl.set_overwrite_policy(OverwritePolicy::Skip);
+        let generation =
+            tpl.generate_with(dir.path(), &mut |_| panic!("the policy says to skip")).unwrap();
+        assert!(generation.skipped.contains(&Path::new("some-project").join("README.md")));
+        assert!(generation.overwritten.is_empty());
+        assert_eq!(fs::read_to_string(project.join("README.md")).unwrap(), "mine");
+    }
 
-        'outer: for entry in walker {
-            // Skip root folder and the template.toml
-            if entry.path() == self.path || entry.path() == self.path.join("template.toml") {
-                continue;
-            }
+    #[test]
+    fn dry_run_lists_files_without_writing() {
+        let dir = tempdir().unwrap();
+        let mut tpl = Template::from_input("examples/complex", None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        let out = dir.path().join("out");
+        let (entries, errors) = tpl.dry_run(&out).unwrap();
 
-            let path = entry.path().strip_prefix(&self.path).unwrap();
-            if path.starts_with(&output_dir) {
-                continue;
-            }
-            let path_str = format!("{}", path.display());
-            for ignored in &self.definition.ignore {
-                if ignored == &path_str || path_str.starts_with(ignored) {
-                    continue 'outer;
-                }
-            }
+        assert!(errors.is_empty());
+        assert!(!out.exists());
+        let paths: Vec<_> = entries.iter().map(|e| e.path.clone()).collect();
+        assert!(paths.contains(&Path::new("some-project").join("logo.png")));
+        assert!(!paths.iter().any(|p| p.ends_with("template.toml")));
+        let readme = entries.iter().find(|e| e.path.ends_with("README.md")).unwrap();
+        assert!(!readme.is_dir && readme.size > 0);
 
-            // We automatically ignore hooks file
-            if hooks_paths.contains(&path_str) {
-                continue 'outer;
-            }
+        // Cleaned up files are not listed
+        tpl.insert_variable("auth_method", Value::String("none".to_string())).unwrap();
+        let (entries, _) = tpl.dry_run(&out).unwrap();
+        assert!(!entries.iter().any(|e| e.path.ends_with("auth.md")));
+    }
 
-            let path_str = path_str.replace("$$", "|");
-            let tpl = render_one_off_template(&path_str, &context, None)?;
-            let real_path = output_dir.join(Path::new(&tpl));
+    #[test]
+    fn failed_generation_removes_what_it_created() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            "name = 'test'\nkickstart_version = 1\n[[variables]]\nname = 'a'\ndefault = 'b'\nprompt = 'a?'\n",
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("docs").join("index.md"), "{{ a }}").unwrap();
+        fs::write(dir.path().join("README.md"), "{{ a }}").unwrap();
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
 
-            if entry.path().is_dir() {
-                create_directory(&real_path)?;
-                continue;
-            }
+        let out = tempdir().unwrap();
+        fs::write(out.path().join("mine.txt"), "mine").unwrap();
+        fs::write(out.path().join("README.md"), "").unwrap();
+        let generation = tpl.generate(out.path()).unwrap();
+        assert_eq!(fs::read_to_string(out.path().join("README.md")).unwrap(), "b");
+        generation.rollback().unwrap();
+        let mut left: Vec<_> =
+            fs::read_dir(out.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        left.sort();
+        assert_eq!(left, ["README.md", "mine.txt"]);
 
-            // Only pass non-binary files or the files not matching the copy_without_render patterns through Tera
-            let mut f = File::open(entry.path())?;
-            let mut buffer = Vec::new();
-            f.read_to_end(&mut buffer)?;
+        // Now failing partway, into an output directory that doesn't exist yet
+        fs::write(dir.path().join("docs").join("broken.md"), "{{ nope }}").unwrap();
+        let nested = out.path().join("new").join("project");
+        assert!(tpl.generate(&nested).is_err());
+        assert!(!out.path().join("new").exists());
+    }
 
-            // For patterns, we do not want the output directory to be included
-            let glob_real_path = real_path.strip_prefix(&output_dir).expect("valid path");
-            let no_render = patterns.iter().map(|p| p.matches_path(glob_real_path)).any(|x| x);
+    #[test]
+    fn generates_through_a_staging_directory() {
+        let dir = tempdir().unwrap();
+        let output = dir.path().join("project");
+        let mut tpl = Template::from_input("examples/super-basic", None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
 
-            if no_render || is_binary(&buffer) {
-                map_io_err(fs::copy(entry.path(), &real_path), entry.path())?;
-                continue;
-            }
+        // Eg a file written by a pre-gen hook
+        let staging = tpl.prepare_staging(&output).unwrap();
+        assert_eq!(staging.parent(), Some(dir.path()));
+        fs::write(staging.join("from-hook.txt"), "").unwrap();
+        let generation = tpl.generate(&output).unwrap();
+        assert!(output.join("from-hook.txt").exists());
+        assert!(generation.files.iter().all(|f| output.join(&f.path).exists()));
+        drop(tpl);
+        // Only the output directory is left
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
 
-            let contents = render_one_off_template(
-                str::from_utf8(&buffer).unwrap(),
-                &context,
-                Some(entry.path().to_path_buf()),
-            )?;
+        let mut tpl = Template::from_input("examples/super-basic", None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        tpl.set_atomic(false);
+        assert_eq!(tpl.prepare_staging(&output).unwrap(), output);
+        tpl.set_overwrite_policy(OverwritePolicy::Overwrite);
+        assert!(tpl.generate(&output).unwrap().created.is_empty());
+    }
 
-            write_file(&real_path, &contents)?;
+    #[cfg(unix)]
+    #[test]
+    fn keeps_the_permissions_of_template_files() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            "name = 'test'\nkickstart_version = 1\ncopy_without_render = ['*.bin']\n[[variables]]\nname = 'a'\ndefault = 'b'\nprompt = 'a?'\n",
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("scripts")).unwrap();
+        let files = [
+            ("scripts/run.sh", 0o755),
+            ("{{ a }}.sh", 0o750),
+            ("tool.bin", 0o711),
+            ("secret.txt", 0o600),
+        ];
+        for (name, mode) in files {
+            let path = dir.path().join(name);
+            fs::write(&path, "#!/bin/sh\necho {{ a }}\n").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
         }
 
-        for cleanup in &self.definition.cleanup {
-            if let Some(val) = self.variables.get(&cleanup.name) {
-                if *val == cleanup.value {
-                    for p in &cleanup.paths {
-                        let actual_path = render_one_off_template(p, &context, None)?;
-                        let path_to_delete = output_dir.join(actual_path).canonicalize()?;
-                        // Avoid path traversals
-                        if !path_to_delete.starts_with(&output_dir) || !path_to_delete.exists() {
-                            continue;
-                        }
-                        if path_to_delete.is_dir() {
-                            map_io_err(fs::remove_dir_all(&path_to_delete), &path_to_delete)?;
-                        } else {
-                            map_io_err(fs::remove_file(&path_to_delete), &path_to_delete)?;
-                        }
-                    }
-                }
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        let out = tempdir().unwrap();
+        tpl.generate(out.path()).unwrap();
+
+        let mode = |p: &str| fs::metadata(out.path().join(p)).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode("scripts/run.sh"), 0o755);
+        assert_eq!(mode("b.sh"), 0o750);
+        assert_eq!(mode("tool.bin"), 0o711);
+        assert_eq!(mode("secret.txt"), 0o600);
+        assert_eq!(fs::read_to_string(out.path().join("b.sh")).unwrap(), "#!/bin/sh\necho b\n");
+    }
+
+    #[test]
+    fn binary_files_are_copied_as_is() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            "name = 'test'\nkickstart_version = 1\ncopy_without_render = ['*.html', 'docs/*']\n[[variables]]\nname = 'a'\ndefault = 'b'\nprompt = 'a?'\n",
+        )
+        .unwrap();
+        let with_null = b"\x89PNG\x00{{ a }}".to_vec();
+        let not_utf8 = b"\xff\xfe{{ a }}".to_vec();
+        fs::write(dir.path().join("{{ a }}.png"), &with_null).unwrap();
+        fs::write(dir.path().join("font.ttf"), &not_utf8).unwrap();
+        fs::write(dir.path().join("index.html"), "{{ a }}").unwrap();
+
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        let out = tempdir().unwrap();
+        tpl.generate(out.path()).unwrap();
+        assert_eq!(fs::read(out.path().join("b.png")).unwrap(), with_null);
+        assert_eq!(fs::read(out.path().join("font.ttf")).unwrap(), not_utf8);
+        assert_eq!(fs::read_to_string(out.path().join("index.html")).unwrap(), "{{ a }}");
+
+        assert_eq!(tpl.unmatched_copy_without_render().unwrap(), ["docs/*"]);
+    }
+
+    #[test]
+    fn can_render_in_memory_what_would_be_generated() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            r#"
+name = "test"
+kickstart_version = 1
+copy_without_render = ["*.html"]
+conditional_files = [{ only_if = { name = "docs", value = true }, paths = ["docs/**"] }]
+cleanup = [{ name = "docs", value = false, paths = ["CHANGELOG.md"] }]
+
+[[variables]]
+name = "a"
+default = "b"
+prompt = "a?"
+
+[[variables]]
+name = "docs"
+default = false
+prompt = "docs?"
+"#,
+        )
+        .unwrap();
+        fs::create_dir_all(dir.path().join("docs")).unwrap();
+        fs::create_dir_all(dir.path().join("{{ a }}")).unwrap();
+        fs::write(dir.path().join("docs/index.md"), "{{ a }}").unwrap();
+        fs::write(dir.path().join("CHANGELOG.md"), "{{ a }}").unwrap();
+        fs::write(dir.path().join("index.html"), "{{ a }}").unwrap();
+        fs::write(dir.path().join("{{ a }}/README.md"), "Hello {{ a }}").unwrap();
+
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        let files = tpl.render_to_map().unwrap();
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            [Path::new("b"), Path::new("b/README.md"), Path::new("index.html")]
+        );
+        assert_eq!(
+            files[Path::new("b/README.md")],
+            RenderedFile::Rendered {
+                source: dir.path().join("{{ a }}/README.md"),
+                content: "Hello b".to_string()
             }
+        );
+        assert_eq!(files[Path::new("index.html")].bytes().unwrap().as_ref(), b"{{ a }}");
+
+        // And it is what gets generated
+        let out = tempdir().unwrap();
+        let generation = tpl.generate(out.path()).unwrap();
+        let written: Vec<_> = generation.files.iter().map(|f| f.path.as_path()).collect();
+        assert_eq!(written, [Path::new("b/README.md"), Path::new("index.html")]);
+        assert!(!out.path().join("CHANGELOG.md").exists());
+    }
+
+    #[test]
+    fn parallel_rendering_reports_errors_deterministically() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            "name = 'test'\nkickstart_version = 1\n[[variables]]\nname = 'a'\ndefault = 'b'\nprompt = 'a?'\n",
+        )
+        .unwrap();
+        for i in 0..50 {
+            fs::write(dir.path().join(format!("{i:02}.txt")), "{{ a }}").unwrap();
         }
+        fs::write(dir.path().join("30.txt"), "{{ nope }}").unwrap();
+        fs::write(dir.path().join("40.txt"), "{{ nope }}").unwrap();
 
-        Ok(())
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        for jobs in [1, 4, 16] {
+            tpl.set_jobs(jobs);
+            let err = tpl.render_to_map().unwrap_err();
+            assert!(
+                matches!(err.kind, ErrorKind::UndefinedVariable { ref path, .. } if path == Path::new("30.txt")),
+                "{jobs}: {err}"
+            );
+        }
+        fs::remove_file(dir.path().join("30.txt")).unwrap();
+        fs::remove_file(dir.path().join("40.txt")).unwrap();
+        tpl.set_jobs(1);
+        let sequential = tpl.render_to_map().unwrap();
+        tpl.set_jobs(8);
+        assert_eq!(tpl.render_to_map().unwrap(), sequential);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use tempfile::tempdir;
+    /// Run with `cargo test --release -- --ignored --nocapture rendering_benchmark`
+    #[test]
+    #[ignore = "benchmark"]
+    fn rendering_benchmark() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            "name = 'test'\nkickstart_version = 1\n[[variables]]\nname = 'name'\ndefault = 'service'\nprompt = 'name?'\n",
+        )
+        .unwrap();
+        let content = "{% for i in range(end=200) %}{{ name | upper }} {{ i }}\n{% endfor %}";
+        for i in 0..900 {
+            let module = dir.path().join(format!("src/module_{}", i / 30));
+            fs::create_dir_all(&module).unwrap();
+            fs::write(module.join(format!("file_{i}.rs")), content).unwrap();
+        }
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
 
-    use super::*;
+        let mut timings = Vec::new();
+        for jobs in [1, default_jobs().max(2)] {
+            tpl.set_jobs(jobs);
+            let out = tempdir().unwrap();
+            let start = std::time::Instant::now();
+            let generation = tpl.generate(out.path()).unwrap();
+            assert_eq!(generation.files.len(), 900);
+            timings.push((jobs, start.elapsed()));
+        }
+        for (jobs, elapsed) in &timings {
+            println!("{jobs} job(s): {elapsed:?}");
+        }
+        if default_jobs() > 1 {
+            assert!(timings[1].1 < timings[0].1);
+        }
+    }
 
     #[test]
-    fn can_generate_from_local_path() {
+    fn large_files_are_streamed_when_there_is_nothing_to_render() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            "name = 'test'\nkickstart_version = 1\n[[variables]]\nname = 'a'\ndefault = 'b'\nprompt = 'a?'\n",
+        )
+        .unwrap();
+        let size = LARGE_FILE_SIZE as usize + 1024 * 1024;
+        // A null byte far from the start, and text with braces but no tag
+        let mut dataset = vec![b'x'; size];
+        dataset[size - 10] = 0;
+        let csv = "{id},{name}\n".repeat(size / 12);
+        fs::write(dir.path().join("dataset.bin"), &dataset).unwrap();
+        fs::write(dir.path().join("data.csv"), &csv).unwrap();
+        fs::write(dir.path().join("{{ a }}.txt"), "{{ a }}").unwrap();
+
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        let out = tempdir().unwrap();
+        let start = std::time::Instant::now();
+        let generation = tpl.generate(out.path()).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(30), "{:?}", start.elapsed());
+
+        assert_eq!(fs::read(out.path().join("dataset.bin")).unwrap(), dataset);
+        assert_eq!(fs::read_to_string(out.path().join("data.csv")).unwrap(), csv);
+        assert_eq!(fs::read_to_string(out.path().join("b.txt")).unwrap(), "b");
+        let file =
+            |name: &str| generation.files.iter().find(|f| f.path == Path::new(name)).unwrap();
+        assert!(!file("dataset.bin").rendered);
+        assert!(!file("data.csv").rendered);
+        assert_eq!(file("data.csv").size, csv.len() as u64);
+        assert_eq!(file("data.csv").sha256, hash_content(csv.as_bytes()));
+    }
+
+    #[test]
+    fn line_endings_are_converted_in_rendered_files() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            r#"
+name = "test"
+kickstart_version = 1
+line_endings = "lf"
+copy_without_render = ["*.txt"]
+
+[[line_endings_overrides]]
+paths = ["*.bat"]
+line_endings = "crlf"
+
+[[line_endings_overrides]]
+paths = ["{{ a }}/*"]
+line_endings = "keep"
+
+[[variables]]
+name = "a"
+default = "b"
+prompt = "a?"
+"#,
+        )
+        .unwrap();
+        let mixed = "#!/bin/sh\r\necho {{ a }}\nexit\r\n";
+        fs::create_dir_all(dir.path().join("{{ a }}")).unwrap();
+        fs::write(dir.path().join("run.sh"), mixed).unwrap();
+        fs::write(dir.path().join("run.bat"), mixed).unwrap();
+        fs::write(dir.path().join("{{ a }}/kept.sh"), mixed).unwrap();
+        fs::write(dir.path().join("as-is.txt"), mixed).unwrap();
+        let binary = b"\x00\r\n\n".to_vec();
+        fs::write(dir.path().join("data.bin"), &binary).unwrap();
+        // Too large to be read if it didn't need converting, and without any tag
+        let rows = LARGE_FILE_SIZE as usize / 5 + 1;
+        fs::write(dir.path().join("data.csv"), "a,b\r\n".repeat(rows)).unwrap();
+
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        let out = tempdir().unwrap();
+        tpl.generate(out.path()).unwrap();
+        let read = |path: &str| fs::read(out.path().join(path)).unwrap();
+        assert_eq!(read("run.sh"), b"#!/bin/sh\necho b\nexit\n");
+        assert_eq!(read("run.bat"), b"#!/bin/sh\r\necho b\r\nexit\r\n");
+        assert_eq!(read("b/kept.sh"), b"#!/bin/sh\r\necho b\nexit\r\n");
+        assert_eq!(read("as-is.txt"), mixed.as_bytes());
+        assert_eq!(read("data.bin"), binary);
+        assert!(read("data.csv") == "a,b\n".repeat(rows).as_bytes());
+    }
+
+    #[test]
+    fn ignore_files_and_excludes_are_honoured() {
+        let dir = tempdir().unwrap();
+        let files = [
+            ("template.toml", "name = 'test'\nkickstart_version = 1\n[[variables]]\nname = 'a'\ndefault = 'b'\nprompt = 'a?'\n"),
+            (".kickstartignore", "*.swp\ntarget/\n/screenshots\n"),
+            ("README.md", "{{ a }}"),
+            ("README.md.swp", ""),
+            ("target/debug/app", ""),
+            ("screenshots/home.png", ""),
+            ("{{ a }}/screenshots/kept.png", ""),
+            ("{{ a }}/.kickstartignore", "*.log\n!keep.log\n"),
+            ("{{ a }}/debug.log", ""),
+            ("{{ a }}/keep.log", ""),
+            ("{{ a }}/docs/index.md", ""),
+        ];
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        tpl.set_excludes(&["/b/docs/".to_string()]).unwrap();
+        let generated: Vec<_> = tpl.render_to_map().unwrap().into_keys().collect();
+        assert_eq!(
+            generated,
+            ["README.md", "b", "b/keep.log", "b/screenshots", "b/screenshots/kept.png"]
+                .map(PathBuf::from)
+        );
+
+        let out = tempdir().unwrap();
+        let (entries, _) = tpl.dry_run(out.path()).unwrap();
+        let mut ignored: Vec<_> = entries
+            .iter()
+            .filter(|e| e.ignored)
+            .map(|e| (e.path.to_string_lossy().into_owned(), e.skipped_because.clone().unwrap()))
+            .collect();
+        ignored.sort();
+        assert_eq!(
+            ignored,
+            [
+                ("README.md.swp", "ignored by `*.swp` in .kickstartignore"),
+                ("b/docs", "excluded by `/b/docs/`"),
+                ("screenshots", "ignored by `/screenshots` in .kickstartignore"),
+                ("target", "ignored by `target/` in .kickstartignore"),
+                ("{{ a }}/debug.log", "ignored by `*.log` in {{ a }}/.kickstartignore"),
+            ]
+            .map(|(p, r)| (p.to_string(), r.to_string()))
+        );
+
+        let err = tpl.set_excludes(&["a[".to_string()]).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::InvalidIgnorePattern { path: None, .. }));
+    }
+
+    #[test]
+    fn can_generate_only_some_files() {
+        let dir = tempdir().unwrap();
+        let files = [
+            ("template.toml", "name = 'test'\nkickstart_version = 1\n[[variables]]\nname = 'a'\ndefault = 'b'\nprompt = 'a?'\n"),
+            ("{{ a }}/src/main.rs", ""),
+            ("{{ a }}/src/lib.rs", ""),
+            ("{{ a }}/docs/index.md", ""),
+            ("README.md", ""),
+        ];
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        let paths =
+            |tpl: &Template| -> Vec<PathBuf> { tpl.render_to_map().unwrap().into_keys().collect() };
+
+        // In the template, and a directory for everything inside it
+        tpl.set_only(&["{{ a }}/src/main.rs".to_string(), "b/docs".to_string()]).unwrap();
+        assert_eq!(
+            paths(&tpl),
+            ["b", "b/docs", "b/docs/index.md", "b/src", "b/src/main.rs"].map(PathBuf::from)
+        );
+        // Like `copy_without_render`, `*` matches across directories
+        tpl.set_only(&["*.md".to_string()]).unwrap();
+        assert_eq!(paths(&tpl), ["README.md", "b", "b/docs", "b/docs/index.md"].map(PathBuf::from));
+
+        tpl.set_only(&["b/src/lin.rs".to_string()]).unwrap();
+        let err = tpl.render_to_map().unwrap_err();
+        let ErrorKind::NoMatchingPaths { ref closest, .. } = err.kind else {
+            panic!("{err}");
+        };
+        assert_eq!(closest[0], "b/src/lib.rs");
+        assert_eq!(closest.len(), 4);
+    }
+
+    #[test]
+    fn can_generate_addons_along_with_the_template() {
+        let dir = tempdir().unwrap();
+        let files = [
+            (
+                "template.toml",
+                r#"
+name = "service"
+kickstart_version = 1
+
+[[addons]]
+name = "grpc"
+directory = "addons/grpc"
+prompt = "Add a gRPC server?"
+
+[[addons]]
+name = "cron"
+directory = "./addons/cron"
+prompt = "Add a cron job?"
+
+[[variables]]
+name = "project_name"
+default = "svc"
+prompt = "Name?"
+"#,
+            ),
+            ("README.md", "# {{ project_name }}\n"),
+            ("src/main.rs", "fn main() {}\n"),
+            (
+                "addons/grpc/template.toml",
+                r#"
+name = "grpc"
+kickstart_version = 1
+
+[[variables]]
+name = "project_name"
+default = "unused"
+prompt = "Name?"
+
+[[variables]]
+name = "grpc_port"
+default = 50051
+prompt = "Port?"
+"#,
+            ),
+            ("addons/grpc/README.md", "# {{ project_name }} on {{ grpc_port }}\n"),
+            ("addons/grpc/src/grpc.rs", "// {{ project_name }}\n"),
+            ("addons/cron/template.toml", "name = 'cron'\nkickstart_version = 1\nvariables = []\n"),
+            ("addons/cron/src/cron.rs", ""),
+        ];
+        for (path, content) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        let err = tpl.select_addons(&["kafka".to_string()]).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Addon { ref name, .. } if name == "kafka"));
+
+        tpl.select_addons(&["grpc".to_string()]).unwrap();
+        // Only the variables the template doesn't have already
+        let names: Vec<_> = tpl.definition.variables.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, ["project_name", "grpc_port"]);
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+
+        let out = tempdir().unwrap();
+        let generation = tpl.generate(out.path()).unwrap();
+        let mut paths: Vec<_> = generation.files.iter().map(|f| f.path.clone()).collect();
+        paths.sort();
+        assert_eq!(paths, ["README.md", "src/grpc.rs", "src/main.rs"].map(PathBuf::from));
+        // The addon comes last
+        assert_eq!(read_file(&out.path().join("README.md")).unwrap(), "# svc on 50051\n");
+        assert_eq!(read_file(&out.path().join("src/grpc.rs")).unwrap(), "// svc\n");
+        assert!(!out.path().join("addons").exists());
+        let lockfile = Lockfile::load(&out.path().join(".kickstart.json")).unwrap();
+        assert_eq!(lockfile.addons, ["grpc"]);
+    }
+
+    #[test]
+    fn conditional_files_are_not_generated_if_their_condition_is_false() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            r#"
+name = "test"
+kickstart_version = 1
+
+[[conditional_files]]
+paths = ["docker", "*.dockerignore"]
+only_if = { name = "use_docker", value = true }
+
+[[conditional_files]]
+paths = ["{{ project_name }}/ci/**"]
+only_if = { name = "ci", value = "github" }
+
+[[variables]]
+name = "project_name"
+default = "app"
+prompt = "Name?"
+
+[[variables]]
+name = "use_docker"
+default = false
+prompt = "Docker?"
+
+[[variables]]
+name = "ci"
+default = "none"
+prompt = "CI?"
+"#,
+        )
+        .unwrap();
+        for path in [
+            "docker/compose/dev.yml",
+            "{{ project_name }}/ci/main.yml",
+            "{{ project_name }}/main.py",
+        ] {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        fs::write(dir.path().join(".dockerignore"), "").unwrap();
+
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        let out = tempdir().unwrap();
+        tpl.generate(out.path()).unwrap();
+        assert!(out.path().join("app").join("main.py").exists());
+        assert!(!out.path().join("app").join("ci").exists());
+        assert!(!out.path().join("docker").exists());
+        assert!(!out.path().join(".dockerignore").exists());
+
+        let (entries, _) = tpl.dry_run(&out.path().join("other")).unwrap();
+        let mut skipped: Vec<_> = entries
+            .iter()
+            .filter_map(|e| Some((e.path.to_str().unwrap(), e.skipped_because.as_deref()?)))
+            .collect();
+        skipped.sort();
+        assert_eq!(
+            skipped,
+            [
+                (".dockerignore", "only if `use_docker` is true"),
+                ("app/ci", "only if `ci` is github"),
+                ("docker", "only if `use_docker` is true"),
+            ]
+        );
+
+        tpl.insert_variable("use_docker", Value::Boolean(true)).unwrap();
+        tpl.set_overwrite_policy(OverwritePolicy::Overwrite);
+        tpl.generate(out.path()).unwrap();
+        assert!(out.path().join("docker").join("compose").join("dev.yml").exists());
+        assert!(!out.path().join("app").join("ci").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn directories_are_not_created_through_symlinks() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            "name = 'a'\nkickstart_version = 1\nvariables = []\n",
+        )
+        .unwrap();
+        fs::create_dir_all(dir.path().join("link").join("sub")).unwrap();
+        let elsewhere = tempdir().unwrap();
+        let out = tempdir().unwrap();
+        std::os::unix::fs::symlink(elsewhere.path(), out.path().join("link")).unwrap();
+
+        for atomic in [true, false] {
+            let mut tpl = Template::from_local(dir.path(), None).unwrap();
+            tpl.set_atomic(atomic);
+            let err = tpl.generate(out.path()).unwrap_err();
+            assert!(matches!(err.kind, ErrorKind::InvalidRenderedPath { .. }), "{err}");
+            assert!(!elsewhere.path().join("sub").exists());
+        }
+    }
+
+    #[test]
+    fn rendered_paths_are_checked() {
+        let path = Path::new("{{ a }}");
+        for ok in ["src", "src/mod.rs", ".gitignore", "my..file"] {
+            assert!(check_rendered_path(path, ok).is_ok(), "{ok}");
+        }
+        for invalid in ["", "../x", "src/../../x", "/etc/passwd", "a//b", "a/", "./a", "a\0b"] {
+            assert!(check_rendered_path(path, invalid).is_err(), "{invalid}");
+        }
+        let err = check_rendered_path(path, "/etc/passwd").unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ErrorKind::InvalidRenderedPath { reason: "is outside of the output directory", .. }
+        ));
+
+        for reserved in ["CON", "nul", "com1.txt", "Aux .rs", "a:b", "what?"] {
+            assert!(windows_name_error(reserved).is_some(), "{reserved}");
+        }
+        for ok in ["console", "null.rs", "COM10", "readme.md"] {
+            assert_eq!(windows_name_error(ok), None, "{ok}");
+        }
+    }
+
+    #[test]
+    fn empty_directories_are_created() {
         let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            r#"
+name = "test"
+kickstart_version = 1
+
+[[conditional_files]]
+paths = ["logs"]
+only_if = { name = "logging", value = true }
+
+[[variables]]
+name = "a"
+default = "app"
+prompt = "a?"
+
+[[variables]]
+name = "logging"
+default = false
+prompt = "Logs?"
+"#,
+        )
+        .unwrap();
+        fs::create_dir_all(dir.path().join("{{ a }}-data").join("{{ a }}-cache")).unwrap();
+        fs::create_dir(dir.path().join("logs")).unwrap();
+        fs::write(dir.path().join("logs").join(KEEP_FILE_NAME), "").unwrap();
+
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        let out = tempdir().unwrap();
+        tpl.generate(out.path()).unwrap();
+        assert!(out.path().join("app-data").join("app-cache").is_dir());
+        assert!(!out.path().join("logs").exists());
+
+        tpl.insert_variable("logging", Value::Boolean(true)).unwrap();
+        tpl.generate(out.path()).unwrap();
+        assert_eq!(fs::read_dir(out.path().join("logs")).unwrap().count(), 0);
+
         let mut tpl = Template::from_input("examples/complex", None).unwrap();
         tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
-        let res = tpl.generate(&dir.path().to_path_buf());
+        tpl.generate(out.path()).unwrap();
+        let migrations = out.path().join("some-project").join("migrations");
+        assert_eq!(fs::read_dir(migrations).unwrap().count(), 0);
+    }
 
-        assert!(res.is_ok());
-        assert!(!dir.path().join("some-project").join("template.toml").exists());
-        assert!(dir.path().join("some-project").join("logo.png").exists());
+    #[test]
+    fn dry_run_collects_rendering_errors() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            "name = 'test'\nkickstart_version = 1\n[[variables]]\nname = 'a'\ndefault = 'b'\nprompt = 'a?'\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("one.txt"), "{{ nope }}").unwrap();
+        fs::write(dir.path().join("two.txt"), "{% if %}").unwrap();
+        fs::write(dir.path().join("three.txt"), "{{ a }}").unwrap();
+
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        let (entries, errors) = tpl.dry_run(&dir.path().join("out")).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(errors.len(), 2);
     }
 
     #[test]
@@ -381,7 +4036,7 @@ mod tests {
         let dir = tempdir().unwrap();
         let mut tpl = Template::from_input("examples/with-directory", None).unwrap();
         tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
-        let res = tpl.generate(&dir.path().to_path_buf());
+        let res = tpl.generate(dir.path());
         assert!(res.is_ok());
         assert!(dir.path().join("template_root").join("Howdy.py").exists());
     }
@@ -391,7 +4046,7 @@ mod tests {
         let dir = tempdir().unwrap();
         let mut tpl = Template::from_input("./", Some("examples/complex")).unwrap();
         tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
-        let res = tpl.generate(&dir.path().to_path_buf());
+        let res = tpl.generate(dir.path());
         assert!(res.is_ok());
         assert!(!dir.path().join("some-project").join("template.toml").exists());
         assert!(dir.path().join("some-project").join("logo.png").exists());
@@ -403,7 +4058,7 @@ mod tests {
         let mut tpl =
             Template::from_input("https://github.com/Keats/rust-cli-template", None).unwrap();
         tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
-        let res = tpl.generate(&dir.path().to_path_buf());
+        let res = tpl.generate(dir.path());
 
         assert!(res.is_ok());
         assert!(!dir.path().join("My-CLI").join("template.toml").exists());
@@ -417,7 +4072,7 @@ mod tests {
             Template::from_input("https://github.com/Keats/kickstart", Some("examples/complex"))
                 .unwrap();
         tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
-        let res = tpl.generate(&dir.path().to_path_buf());
+        let res = tpl.generate(dir.path());
 
         assert!(res.is_ok());
         assert!(!dir.path().join("some-project").join("template.toml").exists());
@@ -429,9 +4084,391 @@ mod tests {
         let dir = tempdir().unwrap();
         let mut tpl = Template::from_input("examples/slugify", None).unwrap();
         tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
-        let res = tpl.generate(&dir.path().to_path_buf());
+        let res = tpl.generate(dir.path());
         assert!(res.is_ok());
         assert!(!dir.path().join("template.toml").exists());
         assert!(dir.path().join("hello.md").exists());
     }
+
+    #[test]
+    fn can_render_prompts_with_previous_values() {
+        let mut tpl = Template::from_input("examples/super-basic", None).unwrap();
+        tpl.definition.variables[1].prompt = "File name in {{ directory_name }}?".to_string();
+        let mut vals = HashMap::new();
+        assert!(tpl.get_prompt_for("file_name", &vals).is_err());
+        vals.insert("directory_name".to_string(), Value::String("docs".to_string()));
+        assert_eq!(tpl.get_prompt_for("file_name", &vals).unwrap(), "File name in docs?");
+        assert_eq!(tpl.get_prompt_for("directory_name", &vals).unwrap(), "Directory name?");
+    }
+
+    #[test]
+    fn can_use_case_conversion_filters_in_defaults() {
+        let dir = tempdir().unwrap();
+        let mut tpl = Template::from_input("examples/case-conversion", None).unwrap();
+        let vals = tpl.definition.default_values().unwrap();
+        assert_eq!(vals["crate_name"], Value::String("my_crème_project".to_string()));
+        assert_eq!(vals["env_prefix"], Value::String("MY_CRÈME_PROJECT".to_string()));
+        assert_eq!(vals["slug"], Value::String("my-creme-project".to_string()));
+        assert_eq!(
+            tpl.get_default_for("struct_name", &vals).unwrap(),
+            Value::String("MyCrèmeProject".to_string())
+        );
+
+        tpl.set_variables(vals).unwrap();
+        tpl.generate(dir.path()).unwrap();
+        let main =
+            read_file(&dir.path().join("my_crème_project").join("src").join("main.rs")).unwrap();
+        assert!(main.contains("//! My Crème Project, served at /my-creme-project"));
+        assert!(main.contains("struct MyCrèmeProject {"));
+        assert!(main.contains("    myCrèmeProject: String,"));
+        assert!(main.contains("`MY_CRÈME_PROJECT_CONFIG`"));
+    }
+
+    #[test]
+    fn undefined_variables_are_errors_unless_lenient() {
+        let dir = tempdir().unwrap();
+        let tpl_dir = tempdir().unwrap();
+        fs::create_dir(tpl_dir.path().join("docs")).unwrap();
+        fs::write(
+            tpl_dir.path().join("template.toml"),
+            "name = 'Typo'\nkickstart_version = 1\n\n[[variables]]\nname = 'project_name'\ndefault = 'hello'\nprompt = 'Name?'\n",
+        )
+        .unwrap();
+        fs::write(
+            tpl_dir.path().join("docs").join("README.md"),
+            "# {{ project_name }}\n\nBy {{ author | default(value='me') }}, {{ projetc_name }}\n",
+        )
+        .unwrap();
+
+        let mut tpl = Template::from_local(tpl_dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        let err = tpl.generate(dir.path()).unwrap_err();
+        match err.kind {
+            ErrorKind::UndefinedVariable { ref name, ref path, position } => {
+                assert_eq!(name, "projetc_name");
+                assert_eq!(path, &Path::new("docs").join("README.md"));
+                assert_eq!(position, Some((3, 43)));
+            }
+            _ => panic!("unexpected error {err}"),
+        }
+
+        tpl.definition.lenient_rendering = true;
+        tpl.generate(dir.path()).unwrap();
+        let readme = read_file(&dir.path().join("docs").join("README.md")).unwrap();
+        assert_eq!(readme, "# hello\n\nBy me, \n");
+
+        // Several missing variables, some of them checked
+        fs::write(
+            tpl_dir.path().join("docs").join("README.md"),
+            "{{ a }}-{{ b | upper }}-{% if c is defined %}c{% endif %}-{{ d | default(value='d') }}",
+        )
+        .unwrap();
+        let dir = tempdir().unwrap();
+        tpl.generate(dir.path()).unwrap();
+        let readme = read_file(&dir.path().join("docs").join("README.md")).unwrap();
+        assert_eq!(readme, "---d");
+    }
+
+    #[test]
+    fn can_collect_all_the_render_errors() {
+        let tpl_dir = tempdir().unwrap();
+        fs::write(
+            tpl_dir.path().join("template.toml"),
+            r#"
+name = "Broken"
+kickstart_version = 1
+output_dir = "{{ nope }}"
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+
+[[cleanup]]
+name = "project_name"
+value = "bye"
+paths = ["{{ project_name | nope }}"]
+
+[[pre_gen_hooks]]
+name = "check"
+path = "check.sh"
+os = ["windows"]
+"#,
+        )
+        .unwrap();
+        fs::write(tpl_dir.path().join("README.md"), "{% if %}").unwrap();
+        fs::write(tpl_dir.path().join("{{ project_name | upper }}.md"), "{{ project_name }}")
+            .unwrap();
+
+        let mut tpl = Template::from_local(tpl_dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        let errors: Vec<_> = tpl.render_errors().iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors.len(), 4, "{errors:?}");
+        assert!(errors[0].starts_with("Failed to parse `README.md`\n --> 1:7"), "{errors:?}");
+        assert!(errors[1].contains("Filter 'nope' not found"), "{errors:?}");
+        assert!(errors[2].contains("Variable `nope` not found"), "{errors:?}");
+        assert!(errors[3].contains("check.sh"), "{errors:?}");
+    }
+
+    #[test]
+    fn can_find_unused_and_undeclared_variables() {
+        let tpl_dir = tempdir().unwrap();
+        fs::write(
+            tpl_dir.path().join("template.toml"),
+            r#"
+name = "Usage"
+kickstart_version = 1
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+
+[[variables]]
+name = "unused"
+default = "x"
+prompt = "Unused?"
+
+[[variables]]
+name = "ci"
+default = false
+prompt = "CI?"
+
+[[variables]]
+name = "workflow"
+default = "ci"
+prompt = "Workflow?"
+only_if = { name = "ci", value = true }
+
+[[variables]]
+name = "in_hook"
+default = "x"
+prompt = "Hook?"
+
+[[conditional_files]]
+paths = [".github/**"]
+only_if = { name = "ci", value = true }
+
+[[post_gen_hooks]]
+name = "hook"
+path = "hook.sh"
+"#,
+        )
+        .unwrap();
+        fs::create_dir_all(tpl_dir.path().join(".github").join("workflows")).unwrap();
+        fs::write(
+            tpl_dir.path().join(".github").join("workflows").join("{{ workflow }}.yml"),
+            "{{ runner }}",
+        )
+        .unwrap();
+        fs::write(tpl_dir.path().join("README.md"), "{{ project_name }} {{ kickstart.year }}")
+            .unwrap();
+        fs::write(tpl_dir.path().join("hook.sh"), "echo $KICKSTART_IN_HOOK {{ autor }}").unwrap();
+
+        let mut tpl = Template::from_local(tpl_dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        let usage = tpl.variable_usage().unwrap();
+        assert_eq!(usage.unused, ["unused"]);
+        assert_eq!(
+            usage.undeclared,
+            [
+                ("autor".to_string(), PathBuf::from("hook.sh")),
+                (
+                    "runner".to_string(),
+                    Path::new(".github").join("workflows").join("{{ workflow }}.yml")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn templates_know_if_optional_variables_are_set() {
+        let dir = tempdir().unwrap();
+        let tpl_dir = tempdir().unwrap();
+        fs::write(
+            tpl_dir.path().join("template.toml"),
+            "name = 'Optional'\nkickstart_version = 1\n\n[[variables]]\nname = 'homepage'\n\
+            default = ''\nprompt = 'Homepage?'\nrequired = false\n",
+        )
+        .unwrap();
+        fs::write(
+            tpl_dir.path().join("README.md"),
+            "{% if has_homepage %}See {{ homepage }}{% else %}No homepage{% endif %}",
+        )
+        .unwrap();
+
+        let mut tpl = Template::from_local(tpl_dir.path(), None).unwrap();
+        tpl.set_variables(tpl.definition.default_values().unwrap()).unwrap();
+        tpl.generate(dir.path()).unwrap();
+        assert_eq!(read_file(&dir.path().join("README.md")).unwrap(), "No homepage");
+
+        let dir = tempdir().unwrap();
+        tpl.insert_variable("homepage", Value::String("https://example.com".to_string())).unwrap();
+        tpl.generate(dir.path()).unwrap();
+        assert_eq!(read_file(&dir.path().join("README.md")).unwrap(), "See https://example.com");
+        assert!(tpl.variable_usage().unwrap().undeclared.is_empty());
+    }
+
+    #[test]
+    fn default_values_use_the_frozen_builtins() {
+        let tpl_dir = tempdir().unwrap();
+        fs::write(
+            tpl_dir.path().join("template.toml"),
+            "name = 'Dated'\nkickstart_version = 1\n\n[[variables]]\nname = 'license_year'\n\
+            default = '{{ kickstart.year }}'\nprompt = 'Year?'\n",
+        )
+        .unwrap();
+
+        let mut tpl = Template::from_local(tpl_dir.path(), None).unwrap();
+        tpl.set_deterministic(true);
+        let vals = tpl.default_values().unwrap();
+        assert_eq!(vals["license_year"], Value::String("2000".to_string()));
+        tpl.set_deterministic(false);
+        assert_ne!(tpl.default_values().unwrap()["license_year"], vals["license_year"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn can_get_choices_from_a_command() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("tags.txt"), "v1.1\n\nv1.0\nv1.1\n").unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            r#"
+name = "Choices"
+kickstart_version = 1
+
+[[variables]]
+name = "tag"
+default = "v0.9"
+prompt = "Tag?"
+choices_cmd = ["cat", "tags.txt"]
+
+[[variables]]
+name = "context"
+default = "local"
+prompt = "Context?"
+choices = ["local", "staging"]
+choices_cmd = ["sh", "-c", "exit 3"]
+"#,
+        )
+        .unwrap();
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        assert_eq!(tpl.choices_cmds().len(), 2);
+        let fallbacks = tpl.run_choices_cmds(true).unwrap();
+        assert_eq!(fallbacks.len(), 1);
+        assert!(fallbacks[0].starts_with("The `choices_cmd` of `context` failed"), "{fallbacks:?}");
+        let tag = tpl.get_variable_by_name("tag").unwrap();
+        let choices: Vec<_> = tag.choices.iter().flatten().map(|c| c.to_string()).collect();
+        assert_eq!(choices, ["v1.1", "v1.0"]);
+        // The default is not one of them
+        assert_eq!(
+            tpl.get_default_for("tag", &HashMap::new()).unwrap(),
+            Value::String("v1.1".to_string())
+        );
+        assert_eq!(tpl.get_variable_by_name("context").unwrap().choices.as_ref().unwrap().len(), 2);
+
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        let err = tpl.run_choices_cmds(false).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::ChoicesCommand { ref name, .. } if name == "tag"));
+    }
+
+    #[test]
+    fn pre_prompt_hooks_can_give_defaults() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            r#"
+name = "Defaults"
+kickstart_version = 1
+pre_prompt_hooks = [{ name = "detect", path = "detect.sh" }]
+
+[[variables]]
+name = "author"
+default = "someone"
+prompt = "Author?"
+
+[[variables]]
+name = "ratio"
+default = 0.5
+prompt = "Ratio?"
+"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("detect.sh"), "").unwrap();
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        let hook = tpl.get_pre_prompt_hooks().unwrap().remove(0);
+        // Nothing written, nothing changes
+        tpl.load_hook_defaults(&hook).unwrap();
+        fs::write(hook.defaults_file(), r#"{"author": "Vincent", "ratio": 1}"#).unwrap();
+        tpl.load_hook_defaults(&hook).unwrap();
+        assert!(!hook.defaults_file().exists());
+        let vals = HashMap::new();
+        assert_eq!(tpl.get_default_for("author", &vals).unwrap(), Value::String("Vincent".into()));
+        assert_eq!(tpl.get_default_for("ratio", &vals).unwrap(), Value::Float(1.0));
+        for (content, reason) in [
+            (r#"{"author": 1}"#, "`author` is of type string but got `1` (integer)"),
+            (r#"{"nope": "x"}"#, "`nope` is not a variable"),
+            ("[1]", "they are not a JSON object of values"),
+        ] {
+            fs::write(hook.defaults_file(), content).unwrap();
+            let err = tpl.load_hook_defaults(&hook).unwrap_err();
+            assert!(
+                matches!(err.kind, ErrorKind::HookDefaults { ref hook, reason: ref r } if hook == "detect" && r.starts_with(reason)),
+                "{err}"
+            );
+        }
+    }
+
+    #[test]
+    fn pre_gen_hooks_can_give_extra_context() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            r#"
+name = "Extra"
+kickstart_version = 1
+pre_gen_hooks = [
+    { name = "versions", path = "versions.sh" },
+    { name = "tags", path = "tags.sh" },
+]
+
+[[variables]]
+name = "name"
+default = "app"
+prompt = "Name?"
+"#,
+        )
+        .unwrap();
+        for hook in ["versions.sh", "tags.sh"] {
+            fs::write(dir.path().join(hook), "").unwrap();
+        }
+        fs::write(
+            dir.path().join("config"),
+            "{{ name }}:{{ extra.port }} {{ extra.tags | join(sep=\",\") }}",
+        )
+        .unwrap();
+        let mut tpl = Template::from_local(dir.path(), None).unwrap();
+        tpl.set_variables(HashMap::from([("name".to_string(), Value::String("app".into()))]))
+            .unwrap();
+        let hooks = tpl.get_pre_gen_hooks().unwrap();
+        // Nothing written, nothing changes
+        tpl.load_extra_context(&hooks[0]).unwrap();
+        assert!(tpl.extra_context().is_empty());
+        fs::write(hooks[0].extra_context_file(), r#"{"port": 8080, "tags": ["a"]}"#).unwrap();
+        fs::write(hooks[1].extra_context_file(), r#"{"tags": ["a", "b"]}"#).unwrap();
+        tpl.load_extra_context(&hooks[0]).unwrap();
+        tpl.load_extra_context(&hooks[1]).unwrap();
+        assert!(!hooks[0].extra_context_file().exists());
+
+        let out = tempdir().unwrap();
+        tpl.generate(out.path()).unwrap();
+        assert_eq!(fs::read_to_string(out.path().join("config")).unwrap(), "app:8080 a,b");
+
+        fs::write(hooks[0].extra_context_file(), r#"{"nested": {"a": 1}}"#).unwrap();
+        let err = tpl.load_extra_context(&hooks[0]).unwrap_err();
+        assert!(
+            matches!(err.kind, ErrorKind::HookExtraContext { ref hook, .. } if hook == "versions")
+        );
+    }
 }
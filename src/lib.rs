@@ -4,15 +4,37 @@
 //! See the [kickstart binary](https://github.com/Keats/kickstart/blob/master/src/main.rs)
 //! for an example on how to use the library.
 
+mod archive;
+pub mod cache;
 #[cfg(feature = "cli")]
 pub mod cli;
+pub mod cookiecutter;
 mod definition;
 pub mod errors;
 mod filters;
 mod generation;
+mod lockfile;
+pub mod preview;
+mod questions;
+mod schema;
+pub mod skeleton;
+pub mod testing;
+pub mod update;
 mod utils;
+pub mod validation;
 mod value;
 
-pub use definition::{Cleanup, Condition, Hook, TemplateDefinition, Variable};
-pub use generation::{HookFile, Template};
+pub use definition::{
+    Addon, Choice, ChoicesFrom, Cleanup, Condition, ConditionalFiles, Group, Hook,
+    KickstartVersion, LineEndings, LineEndingsOverride, TemplateDefinition, Variable, HOOK_OSES,
+};
+pub use generation::{
+    CloneOptions, Conflict, ConflictResolution, ConflictResolver, DryRunEntry, GeneratedFile,
+    Generation, GitInit, GitRef, HookFile, OverwritePolicy, Progress, RenderedFile, Template,
+    TemplateCandidate, TemplatePicker, VariableUsage, IGNORE_FILE_NAME, KEEP_FILE_NAME,
+};
+pub use lockfile::{Lockfile, LOCKFILE_VERSION};
+pub use questions::{Question, Questions};
+pub use schema::JSON_SCHEMA_DIALECT;
+pub use utils::{command_line, expand_shorthand, BuiltinValues, GitIdentity, DEFAULT_FROM_SOURCES};
 pub use value::Value;
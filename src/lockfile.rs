@@ -0,0 +1,76 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{new_error, ErrorKind, Result};
+use crate::utils::{read_file, write_file};
+use crate::Value;
+
+/// The current version of the lockfile format
+pub const LOCKFILE_VERSION: u8 = 1;
+
+/// The default name of the lockfile written in the generated project
+pub const DEFAULT_LOCKFILE_NAME: &str = ".kickstart.json";
+
+/// Records how a project was generated so it can be re-generated later on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// Version of the lockfile format
+    pub version: u8,
+    /// The template source as given by the user: a local path or a git URL
+    pub source: String,
+    /// The commit of the template that was used, if it was cloned
+    pub commit: Option<String>,
+    /// The directory inside the source that was used, if any
+    pub directory: Option<String>,
+    /// All the resolved variables
+    pub variables: BTreeMap<String, Value>,
+    /// The SHA-256 of every generated file, keyed by their path relative to the project root
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+    /// The addons generated along with the template
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addons: Vec<String>,
+}
+
+impl Lockfile {
+    pub(crate) fn new(
+        source: &str,
+        commit: Option<&str>,
+        directory: Option<&str>,
+        variables: &HashMap<String, Value>,
+        files: BTreeMap<String, String>,
+    ) -> Lockfile {
+        Lockfile {
+            version: LOCKFILE_VERSION,
+            source: source.to_string(),
+            commit: commit.map(|c| c.to_string()),
+            directory: directory.map(|d| d.to_string()),
+            variables: variables.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            files,
+            addons: Vec::new(),
+        }
+    }
+
+    /// Load a lockfile previously written by kickstart
+    pub fn load(path: &Path) -> Result<Lockfile> {
+        serde_json::from_str(&read_file(path)?).map_err(|e| {
+            new_error(ErrorKind::InvalidLockfile {
+                path: path.to_path_buf(),
+                reason: e.to_string(),
+            })
+        })
+    }
+
+    /// The lockfile as it is written, in JSON
+    pub(crate) fn to_json(&self) -> String {
+        let content = serde_json::to_string_pretty(self).expect("lockfile to serialize");
+        format!("{content}\n")
+    }
+
+    /// Write the lockfile as JSON at the given path
+    pub fn write(&self, path: &Path) -> Result<()> {
+        write_file(path, &self.to_json())
+    }
+}
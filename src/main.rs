@@ -1,24 +1,57 @@
-use std::collections::HashMap;
-use std::path::PathBuf;
-use std::process::Command as StdCommand;
+use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::iter;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command as StdCommand, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use anyhow::{bail, Result};
-use clap::{Parser, Subcommand};
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use log::{debug, trace, LevelFilter};
+use notify::{RecursiveMode, Watcher};
 
-use kickstart::cli::prompt::{ask_bool, ask_choices, ask_integer, ask_string};
-use kickstart::cli::terminal;
-use kickstart::{HookFile, Template, TemplateDefinition, Value};
+use kickstart::cache::{self, CACHE_DIR_ENV_VAR};
+use kickstart::cli::config::{Alias, Config, CONFIG_ENV_VAR};
+use kickstart::cli::input::{is_stdin, load_values_from_file, save_answers, InputFormat};
+use kickstart::cli::interrupt::{self, KillOnInterrupt, ListOnInterrupt};
+use kickstart::cli::logger;
+use kickstart::cli::prompt::{
+    ensure_interactive, is_interactive, set_assume_tty, set_simple_prompts, Prompter,
+    DEFAULT_MAX_ATTEMPTS,
+};
+use kickstart::cli::report::{self, HookRun, ReportedError};
+use kickstart::cli::terminal::{self, set_color_choice, ColorChoice, Spinner};
+use kickstart::cookiecutter::{convert, plan_conversion, Conversion};
+use kickstart::errors::{Error, ErrorKind};
+use kickstart::preview::{Preview, PreviewUpdate};
+use kickstart::skeleton::create_skeleton;
+use kickstart::testing::Failure;
+use kickstart::update::{check_project, previous_answers, update_project, ConflictPolicy};
+use kickstart::{
+    command_line, expand_shorthand, Choice, CloneOptions, DryRunEntry, GeneratedFile, Generation,
+    GitInit, GitRef, Hook, HookFile, Lockfile, OverwritePolicy, Template, TemplateCandidate,
+    TemplateDefinition, Value, Variable, HOOK_OSES, IGNORE_FILE_NAME,
+};
 
 #[derive(Parser)]
 #[clap(version, author, about, subcommand_negates_reqs = true)]
 pub struct Cli {
-    /// Template to use: a local path or a HTTP url pointing to a Git repository
+    /// Template to use: a local path, the HTTP(S) or SSH URL of a Git repository, eg
+    /// `git@host:owner/repo.git`, a `owner/repo` shorthand for GitHub, which can be prefixed
+    /// by `gitlab:` or `bitbucket:`, or the path or URL of a `.tar.gz`, `.tgz` or `.zip` archive
     #[clap(required = true)]
     pub template: Option<String>,
 
-    /// Where to output the project: defaults to the current directory
-    #[clap(short = 'o', long, default_value_os_t = PathBuf::from("."))]
-    pub output_dir: PathBuf,
+    /// Where to output the project: defaults to the `output_dir` of the template, in the current
+    /// directory, or to the current directory itself
+    #[clap(short = 'o', long)]
+    pub output_dir: Option<PathBuf>,
 
     /// The directory of the given folder/repository to use, which needs to be a template.
     /// Only really useful if you are loading a template from a repository. If you are loading
@@ -26,89 +59,1690 @@ pub struct Cli {
     #[clap(short = 'd', long)]
     pub directory: Option<String>,
 
+    /// The branch to clone if the template is a git repository
+    #[clap(long, conflicts_with_all = ["tag", "rev"])]
+    pub branch: Option<String>,
+
+    /// The tag to clone if the template is a git repository
+    #[clap(long, conflicts_with = "rev")]
+    pub tag: Option<String>,
+
+    /// The commit, or anything `git checkout` accepts, to use if the template is a git
+    /// repository. It can also be given at the end of the URL after a `#`
+    #[clap(long)]
+    pub rev: Option<String>,
+
+    /// Always treat the template as a git repository, even if a local directory of the same
+    /// name exists
+    #[clap(long)]
+    pub remote: bool,
+
+    /// Clone the whole history of git repositories and check out all their files, rather than
+    /// only the last commit and the `--directory`
+    #[clap(long)]
+    pub full_clone: bool,
+
+    /// Use the cached clone of remote templates without fetching their latest commit
+    #[clap(long)]
+    pub offline: bool,
+
+    /// Clone remote templates again even if they are cached
+    #[clap(long, conflicts_with = "offline")]
+    pub refresh: bool,
+
     /// Do not prompt for variables and only use the defaults from template.toml
-    #[clap(long, default_value_t = false)]
+    #[clap(long, default_value_t = false, global = true)]
     pub no_input: bool,
 
-    /// Whether to run all the hooks
-    #[clap(long, default_value_t = true)]
+    /// A JSON, YAML or TOML file containing the answers to the template questions.
+    /// No questions will be asked and every variable needs to have a value.
+    /// Use `-` to read it from stdin.
+    #[clap(short = 'i', long)]
+    pub input_file: Option<PathBuf>,
+
+    /// The format of the input file, guessed from its extension if not given
+    #[clap(long, value_enum, requires = "input_file")]
+    pub input_format: Option<InputFormat>,
+
+    /// Save the answers to that path as JSON, to be used with `--input-file` later on
+    #[clap(long, value_name = "PATH")]
+    pub save_answers: Option<PathBuf>,
+
+    /// Write the list of the generated files to that path as JSON, with their size, whether
+    /// they were rendered and their SHA-256
+    #[clap(long, value_name = "PATH")]
+    pub manifest: Option<PathBuf>,
+
+    /// Write the generated project to that `.tar.gz` or `.zip` archive instead of a directory.
+    /// Hooks are not run and an existing archive is only overwritten with `--force`
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["output_dir", "dry_run", "only", "run_hooks", "git_init", "skip_existing"]
+    )]
+    pub archive: Option<PathBuf>,
+
+    /// Show what would be generated without writing anything or running hooks
+    #[clap(long, default_value_t = false)]
+    pub dry_run: bool,
+
+    /// Do not generate the paths matching that pattern, written like in a `.gitignore` and
+    /// matched against the paths in the output directory. Can be repeated
+    #[clap(long, value_name = "PATTERN")]
+    pub exclude: Vec<String>,
+
+    /// Only generate the files matching that glob, in the template or in the output directory,
+    /// eg to render a file again in an existing project with `--force`. Hooks are not run unless
+    /// `--run-hooks` is given. Can be repeated
+    #[clap(long, value_name = "GLOB")]
+    pub only: Vec<String>,
+
+    /// Generate that addon of the template along with it, without asking which ones to add.
+    /// Otherwise they are asked about, or their `default` is used without questions. Can be
+    /// repeated
+    #[clap(long = "addon", value_name = "NAME")]
+    pub addons: Vec<String>,
+
+    /// How many files to render at the same time, as many as there are CPUs by default. Also the
+    /// most `parallel` hooks to run at the same time, all of them by default
+    #[clap(short = 'j', long, value_name = "N")]
+    pub jobs: Option<NonZeroUsize>,
+
+    /// With `--dry-run`, print the rendered content of that file instead, the path being
+    /// relative to the output directory
+    #[clap(long, value_name = "PATH", requires = "dry_run", conflicts_with = "format")]
+    pub show: Option<PathBuf>,
+
+    /// Overwrite files that already exist in the output directory
+    #[clap(long, default_value_t = false)]
+    pub force: bool,
+
+    /// Keep files that already exist in the output directory and only write the new ones
+    #[clap(long, default_value_t = false, conflicts_with = "force")]
+    pub skip_existing: bool,
+
+    /// Run the hooks even with `--only`, they are always run otherwise
+    #[clap(long)]
     pub run_hooks: bool,
 
+    /// Select the hooks for that operating system instead of the current one
+    #[clap(long, value_name = "OS", value_parser = clap::builder::PossibleValuesParser::new(HOOK_OSES))]
+    pub hook_os: Option<String>,
+
+    /// Kill hooks still running after that many seconds, unless they set their own `timeout_secs`
+    #[clap(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub hook_timeout: Option<u64>,
+
+    /// Only show the output of hooks that fail, as if they all had `quiet = true`
+    #[clap(long)]
+    pub quiet_hooks: bool,
+
+    /// Stop at the first hook that fails, even if it has `allow_failure = true`
+    #[clap(long)]
+    pub strict_hooks: bool,
+
+    /// Create a git repository with the generated files in a first commit, even if the template
+    /// doesn't set `git_init`
+    #[clap(long, overrides_with = "no_git_init")]
+    pub git_init: bool,
+
+    /// Don't create a git repository, even if the template sets `git_init`
+    #[clap(long, overrides_with = "git_init")]
+    pub no_git_init: bool,
+
+    /// Remove what was generated if a post-gen hook fails, without asking
+    #[clap(long)]
+    pub clean_on_failure: bool,
+
+    /// Write the files directly in the output directory instead of moving them there once
+    /// everything is rendered. Avoids writing huge templates twice
+    #[clap(long)]
+    pub no_atomic: bool,
+
+    /// Freeze `kickstart.date`, `kickstart.year` and `kickstart.timestamp` to 2000-01-01 UTC
+    /// so generating twice gives the same files
+    #[clap(long, global = true)]
+    pub deterministic: bool,
+
+    /// Run the hooks of templates cloned from a git repository without asking first
+    #[clap(long)]
+    pub trust: bool,
+
+    /// Do not run the hook with that name. Can be repeated
+    #[clap(long, value_name = "NAME", conflicts_with = "only_hooks")]
+    pub skip_hook: Vec<String>,
+
+    /// Only run the hook with that name. Can be repeated
+    #[clap(long = "only-hook", value_name = "NAME")]
+    pub only_hooks: Vec<String>,
+
+    /// Set the value of a variable directly, eg `-D project_name=hello`.
+    /// Can be repeated and takes precedence over defaults and the input file.
+    #[clap(short = 'D', long = "define", value_name = "NAME=VALUE", value_parser = parse_define)]
+    pub defines: Vec<(String, String)>,
+
+    /// Do not ask for confirmation before generating
+    #[clap(short = 'y', long, default_value_t = false)]
+    pub yes: bool,
+
+    /// How many invalid answers in a row to accept for a question before aborting
+    #[clap(long, default_value_t = DEFAULT_MAX_ATTEMPTS, global = true)]
+    pub max_attempts: usize,
+
+    /// Ask for the number of a choice instead of moving through them with the arrows
+    #[clap(long, default_value_t = false, global = true)]
+    pub simple_prompts: bool,
+
+    /// Ask questions even if stdin doesn't look like a terminal
+    #[clap(long, default_value_t = false, global = true)]
+    pub assume_tty: bool,
+
+    /// When to use colors in the output. `NO_COLOR` disables them in `auto`
+    #[clap(long, value_enum, value_name = "WHEN", default_value_t = ColorChoice::Auto, global = true)]
+    pub color: ColorChoice,
+
+    /// Same as `--color never`
+    #[clap(long, default_value_t = false, global = true)]
+    pub no_color: bool,
+
+    /// Also show what kickstart does, eg the git commands it runs and why files are skipped.
+    /// Repeat it to show even more
+    #[clap(short = 'v', long, action = clap::ArgAction::Count, global = true, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Only show the warnings, errors and questions when generating
+    #[clap(short = 'q', long, default_value_t = false, global = true)]
+    pub quiet: bool,
+
+    /// Also write what kickstart does to that file, as with `-v`, eg for a bug report
+    #[clap(long, value_name = "PATH", global = true)]
+    pub log_file: Option<PathBuf>,
+
+    /// How to show what happens: as it goes, or as a single JSON document once done for other
+    /// tools to read. Only generating and `info` support `json`
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text, global = true)]
+    pub format: OutputFormat,
+
     #[clap(subcommand)]
     pub command: Option<Command>,
 }
 
+impl Cli {
+    /// What asks the questions, giving up after `--max-attempts` invalid answers
+    fn prompter(&self) -> Prompter {
+        Prompter::new(self.max_attempts)
+    }
+}
+
 #[derive(Debug, Subcommand)]
 pub enum Command {
     /// Validates that a template.toml is valid
     Validate {
         /// The path to the template.toml
         path: PathBuf,
+        /// Also render all the files, paths and hooks with the default values, to find the
+        /// ones that don't
+        #[clap(long)]
+        render: bool,
+        /// Also warn about the variables that no file uses and the files using variables that
+        /// don't exist
+        #[clap(long)]
+        check_usage: bool,
+    },
+    /// Re-applies the template of a generated project using the answers from its lockfile.
+    /// Only variables that are new in the template are asked and hooks are not run.
+    Update {
+        /// The project to update: defaults to the current directory
+        #[clap(default_value_os_t = PathBuf::from("."))]
+        path: PathBuf,
+
+        /// The name of the lockfile in the project
+        #[clap(long, default_value = ".kickstart.json")]
+        lockfile: String,
+
+        /// Overwrite the files modified since the generation instead of writing the new
+        /// version next to them as `.new` files
+        #[clap(long)]
+        force: bool,
+
+        /// Keep the files modified since the generation without writing `.new` files
+        #[clap(long, conflicts_with = "force")]
+        skip_modified: bool,
+    },
+    /// Lists the files of a generated project that changed since the generation, according to
+    /// its lockfile. Exits with an error if any did. Nothing is written.
+    Check {
+        /// The project to check: defaults to the current directory
+        #[clap(default_value_os_t = PathBuf::from("."))]
+        path: PathBuf,
+
+        /// The name of the lockfile in the project
+        #[clap(long, default_value = ".kickstart.json")]
+        lockfile: String,
+
+        /// Only compare with the lockfile, without loading the template to find the files it
+        /// generates that the lockfile doesn't list
+        #[clap(long)]
+        lockfile_only: bool,
+    },
+    /// Shows what a template asks and which hooks it has, without asking or running anything
+    Info {
+        /// The template, as given when generating: a local path, a URL or a shorthand
+        template: String,
+
+        /// A directory in the template to use as its root
+        #[clap(short = 'd', long)]
+        directory: Option<String>,
+    },
+    /// Prints the JSON Schema of the input files of a template, to check answers before
+    /// generating
+    Schema {
+        /// The template, as given when generating: a local path, a URL or a shorthand
+        template: String,
+
+        /// A directory in the template to use as its root
+        #[clap(short = 'd', long)]
+        directory: Option<String>,
+
+        /// Write the schema to that file instead of printing it
+        #[clap(long, value_name = "PATH")]
+        out: Option<PathBuf>,
+    },
+    /// Converts a cookiecutter template to a kickstart one and lists what it could not
+    /// translate, to be fixed by hand
+    Convert {
+        /// The directory of the cookiecutter template
+        template: PathBuf,
+
+        /// Where to write the kickstart template, which needs to be empty if it exists
+        output: PathBuf,
+
+        /// Show what would be written without writing anything
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Creates the skeleton of a new template to start from: a commented template.toml with a
+    /// variable of each kind, a file using them and example hooks
+    Init {
+        /// The directory of the template, created if needed: defaults to the current directory
+        #[clap(default_value_os_t = PathBuf::from("."))]
+        path: PathBuf,
+
+        /// Overwrite the files of an existing template
+        #[clap(long)]
+        force: bool,
+    },
+    /// Renders a local template in a scratch directory, then renders again the files that
+    /// change as they are saved, showing what doesn't render without stopping, until Ctrl-C.
+    /// Hooks are never run
+    Dev {
+        /// The directory of the template
+        template: PathBuf,
+
+        /// The answers to render it with, as given to `--input-file`. The defaults are used
+        /// otherwise
+        #[clap(long, value_name = "PATH")]
+        answers: Option<PathBuf>,
+
+        /// Where to render it: defaults to a new temporary directory
+        #[clap(short = 'o', long)]
+        output_dir: Option<PathBuf>,
+
+        /// Remove what was rendered when stopping: the output directory if it didn't exist,
+        /// only the files and directories created in it otherwise
+        #[clap(long)]
+        clean: bool,
+    },
+    /// Runs the test cases of a template, the files of its `tests_dir`, rendering it with
+    /// their answers and checking the files it generates. Nothing is written and hooks are
+    /// never run. Exits with an error if any case fails
+    Test {
+        /// The template, as given when generating: a local path, a URL or a shorthand
+        template: String,
+
+        /// A directory in the template to use as its root
+        #[clap(short = 'd', long)]
+        directory: Option<String>,
+    },
+    /// Manages the aliases of templates, which can be used instead of their source
+    Alias {
+        #[clap(subcommand)]
+        command: AliasCommand,
     },
+    /// Manages the cache of remote templates
+    Cache {
+        #[clap(subcommand)]
+        command: CacheCommand,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AliasCommand {
+    /// Adds an alias, replacing any existing one of the same name
+    Add {
+        /// The name to use instead of the source
+        name: String,
+        /// The template, as given when generating
+        source: String,
+        /// A directory in the template to use as its root
+        #[clap(short = 'd', long)]
+        directory: Option<String>,
+        /// The branch to use if the template is a git repository, updated every time
+        #[clap(long, conflicts_with_all = ["tag", "rev"])]
+        branch: Option<String>,
+        /// The tag to use if the template is a git repository
+        #[clap(long, conflicts_with = "rev")]
+        tag: Option<String>,
+        /// The commit, or anything `git checkout` accepts, to use if the template is a git
+        /// repository
+        #[clap(long, alias = "ref")]
+        rev: Option<String>,
+    },
+    /// Lists the aliases
+    List,
+    /// Removes an alias
+    Remove { name: String },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CacheCommand {
+    /// Removes all the cached templates
+    Clear,
+}
+
+/// Split a `NAME=VALUE` define on the first `=`
+fn parse_define(input: &str) -> Result<(String, String), String> {
+    match input.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("`{input}` is not of the form NAME=VALUE")),
+    }
+}
+
+/// Convert the raw defines into values of the type of each variable.
+/// Unlike other sources, defining a variable unknown to the template is an error.
+fn parse_defines(
+    template: &Template,
+    defines: &[(String, String)],
+) -> Result<HashMap<String, Value>> {
+    let mut vals = HashMap::new();
+    for (name, raw) in defines {
+        vals.insert(name.clone(), template.parse_value_for(name, raw)?);
+    }
+    Ok(vals)
+}
+
+/// Prefix of the environment variables that can set the value of a variable, eg
+/// `KICKSTART_VAR_PROJECT_NAME` for `project_name`
+const ENV_VAR_PREFIX: &str = "KICKSTART_VAR_";
+
+/// Read the values of the template variables set in the environment, converted to the type of
+/// each variable
+fn parse_env_overrides(template: &Template) -> Result<HashMap<String, Value>> {
+    let mut vals = HashMap::new();
+    for var in &template.definition.variables {
+        let key = format!("{ENV_VAR_PREFIX}{}", var.name.to_uppercase());
+        let Some(raw) = env::var_os(&key) else {
+            continue;
+        };
+        let Some(raw) = raw.to_str() else {
+            bail!("The `{key}` environment variable is not valid unicode");
+        };
+        let val = template
+            .parse_value_for(&var.name, raw)
+            .with_context(|| format!("Invalid value in the `{key}` environment variable"))?;
+        vals.insert(var.name.clone(), val);
+    }
+    Ok(vals)
+}
+
+/// Load the template a project was generated from according to its lockfile, with the same
+/// addons, at `commit` if given
+fn load_locked_template(lockfile: &Lockfile, commit: Option<&str>) -> Result<Template> {
+    let options = CloneOptions {
+        git_ref: commit.map(|c| GitRef::Rev(c.to_string())),
+        // Both versions of the template can be needed at once, each has its own entry
+        cache_dir: cache::default_dir(),
+        ..Default::default()
+    };
+    let mut template =
+        Template::from_input_with(&lockfile.source, &options, lockfile.directory.as_deref())?;
+    template.select_addons(&lockfile.addons)?;
+    Ok(template)
 }
 
 /// Ask all the questions of that template and return the answers.
+/// Variables present in `overrides` are not asked and take the given value instead.
 /// If `no_input` is `true`, it will automatically pick the defaults without
 /// prompting the user
-fn ask_questions(template: &Template, no_input: bool) -> Result<HashMap<String, Value>> {
-    let mut vals = HashMap::new();
+fn ask_questions(
+    template: &Template,
+    prompter: Prompter,
+    no_input: bool,
+    overrides: &HashMap<String, Value>,
+) -> Result<HashMap<String, Value>> {
+    let mut questions = template.questions().with_answers(overrides.clone());
+    let mut shown_groups = HashSet::new();
 
-    for var in &template.definition.variables {
-        if !template.should_ask_variable(&var.name, &vals)? {
+    while let Some(question) = questions.next_question()? {
+        let var = question.variable;
+        let default = question.default;
+        if no_input {
+            questions.answer(default);
             continue;
         }
-        let default = template.get_default_for(&var.name, &vals)?;
+        if let Some(key) = question.group.filter(|key| shown_groups.insert(*key)) {
+            if let Some(group) = template.definition.groups.get(key) {
+                terminal::bold(&format!("\n{}\n", group.title));
+            }
+        }
+        if let Some(ref err) = question.prompt_error {
+            terminal::warning(&format!(
+                "Could not render the prompt of `{}`, showing it as is: {err}\n",
+                var.name
+            ));
+        }
+        let prompt = question.prompt;
+        let prompter = prompter.with_help(var.help.as_deref());
 
-        if matches!(default, Value::String(..)) {
-            if let Some(ref choices) = var.choices {
-                let res = if no_input { default } else { ask_choices(&var.prompt, &default, choices)? };
-                vals.insert(var.name.clone(), res);
-                continue;
+        let answer = match (default, question.choices) {
+            (Value::List(defaults), Some(choices)) => {
+                Value::List(prompter.ask_multi_choices(&prompt, &defaults, choices)?)
+            }
+            // Whatever their type, the value is the one of the choice picked
+            (default, Some(choices)) => prompter.ask_choices(&prompt, &default, choices)?,
+            (Value::Boolean(b), None) => Value::Boolean(prompter.ask_bool(&prompt, b)?),
+            (Value::String(_), None) if var.secret => {
+                Value::String(prompter.ask_secret(&prompt, var)?)
             }
+            (Value::String(s), None) => Value::String(prompter.ask_string(&prompt, var, &s)?),
+            (Value::Integer(i), None) => {
+                Value::Integer(prompter.ask_integer(&prompt, i, var.min, var.max)?)
+            }
+            (Value::Float(f), None) => Value::Float(prompter.ask_float(&prompt, f)?),
+            (Value::List(defaults), None) if var.list => {
+                Value::List(prompter.ask_list(&prompt, var, &defaults)?)
+            }
+            // Only multi choices and lists have a list default, which validation enforces
+            (Value::List(l), None) => Value::List(l),
+        };
+        questions.answer(answer);
+    }
+
+    Ok(questions.into_answers())
+}
+
+/// Print the answers in the order they were asked so the user can review them.
+/// Secrets are masked.
+fn print_answers(template: &Template, vals: &HashMap<String, Value>) {
+    let answered: Vec<_> = template
+        .definition
+        .variables
+        .iter()
+        .filter_map(|var| vals.get(&var.name).map(|val| (var, val)))
+        .collect();
+    let width = answered.iter().map(|(var, _)| var.name.len()).max().unwrap_or(0);
+
+    terminal::bold("\nYour answers:\n");
+    for (var, val) in answered {
+        let val = if var.secret { "********".to_string() } else { val.to_string() };
+        println!("  {:width$}  {}", var.name, val, width = width);
+    }
+}
+
+/// A size in bytes for humans, eg `128 KiB`
+fn human_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    for unit in ["B", "KiB", "MiB"] {
+        if size < 1024.0 {
+            return if unit == "B" { format!("{bytes} B") } else { format!("{size:.0} {unit}") };
         }
+        size /= 1024.0;
+    }
+    format!("{size:.1} GiB")
+}
 
-        match default {
-            Value::Boolean(b) => {
-                let res = if no_input { b } else { ask_bool(&var.prompt, b)? };
-                vals.insert(var.name.clone(), Value::Boolean(res));
-                continue;
+/// Eg `Created 34 files, 6 directories, 128 KiB`
+fn summary(generation: &Generation) -> String {
+    let plural =
+        |n: usize, one: &str, many: &str| format!("{n} {}", if n == 1 { one } else { many });
+    format!(
+        "Created {}, {}, {}",
+        plural(generation.files.len(), "file", "files"),
+        plural(generation.directories.len(), "directory", "directories"),
+        human_size(generation.files.iter().map(|f| f.size).sum())
+    )
+}
+
+/// Print the files/directories that would be created as a tree along with the hooks that
+/// would run
+/// The ignored paths are only listed if `verbose`
+fn print_dry_run(
+    entries: &[DryRunEntry],
+    pre_gen_hooks: &[HookFile],
+    post_gen_hooks: &[HookFile],
+    verbose: bool,
+) {
+    terminal::bold("Would generate:\n");
+    for entry in entries.iter().filter(|e| verbose || !e.ignored) {
+        let depth = entry.path.components().count();
+        let name = entry.path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        let slash = if entry.is_dir { "/" } else { "" };
+        if let Some(ref reason) = entry.skipped_because {
+            println!("{:indent$}{}{} (skipped, {})", "", name, slash, reason, indent = depth * 2);
+        } else if entry.is_dir {
+            println!("{:indent$}{}/", "", name, indent = depth * 2);
+        } else {
+            println!("{:indent$}{} ({} B)", "", name, entry.size, indent = depth * 2);
+        }
+    }
+
+    for (stage, hooks) in [("pre-gen", pre_gen_hooks), ("post-gen", post_gen_hooks)] {
+        if !hooks.is_empty() {
+            terminal::bold(&format!("Would run {stage} hooks:\n"));
+            for hook in hooks {
+                println!("  - {}", hook.name());
             }
-            Value::String(s) => {
-                let res = if no_input { s } else { ask_string(&var.prompt, &s, &var.validation)? };
-                vals.insert(var.name.clone(), Value::String(res));
+        }
+    }
+}
+
+/// Load the template being worked on by `kickstart dev` with the answers of that file, the
+/// defaults being used without it
+fn load_dev_template(path: &Path, answers: Option<&Path>, deterministic: bool) -> Result<Template> {
+    let mut template = Template::from_local(path, None)?;
+    template.set_deterministic(deterministic);
+    let vals = match answers {
+        Some(answers) => load_values_from_file(&template, answers, None, &HashMap::new())?,
+        None => template.default_values()?,
+    };
+    template.set_variables(vals)?;
+    Ok(template)
+}
+
+fn print_preview_update(update: &PreviewUpdate) {
+    for path in &update.written {
+        terminal::file_status("rendered", &path.to_string_lossy());
+    }
+    for path in &update.removed {
+        terminal::file_status("deleted", &path.to_string_lossy());
+    }
+    for err in &update.errors {
+        terminal::error(&format!("Error: {err}\n"));
+    }
+}
+
+fn print_test_failures(failures: &[Failure]) {
+    for failure in failures {
+        println!("  - {failure}");
+        if let Failure::MissingContent { expected, closest: Some(closest), .. } = failure {
+            // Both end with a newline so the diff is only about the content
+            let expected = format!("{}\n", expected.trim_end_matches('\n'));
+            terminal::diff(&format!("{closest}\n"), &expected, "generated", "expected");
+        }
+    }
+}
+
+/// Render the template in the output directory of `preview` and render again what changed every
+/// time a file of the template, or the answers, are saved. Errors are shown and it keeps watching, until Ctrl-C.
+fn watch_template(
+    path: &Path,
+    answers: Option<&Path>,
+    preview: &mut Preview,
+    deterministic: bool,
+) -> Result<()> {
+    let path = path.canonicalize().with_context(|| format!("Could not find {}", path.display()))?;
+    let answers = answers.map(|a| a.canonicalize().unwrap_or_else(|_| a.to_path_buf()));
+    let output_dir = preview.output_dir().canonicalize()?;
+    if output_dir.starts_with(&path) {
+        bail!("The output directory can't be in the template, it would render itself");
+    }
+
+    let mut template = load_dev_template(&path, answers.as_deref(), deterministic)?;
+    print_preview_update(&preview.render_all(&template)?);
+
+    // `None` once interrupted, which is how it stops
+    let (tx, rx) = std::sync::mpsc::channel();
+    let interrupted = tx.clone();
+    thread::spawn(move || {
+        interrupt::wait_for_interrupt();
+        let _ = interrupted.send(None);
+    });
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = tx.send(Some(event));
+    })?;
+    watcher.watch(&path, RecursiveMode::Recursive)?;
+    if let Some(ref answers) = answers {
+        watcher.watch(answers, RecursiveMode::NonRecursive)?;
+    }
+    terminal::success(&format!(
+        "Rendered in {}, watching {} for changes. Press Ctrl-C to stop\n",
+        output_dir.display(),
+        path.display()
+    ));
+    while let Ok(Some(event)) = rx.recv() {
+        // Editors often save in several steps, they are handled together
+        let mut events = vec![event];
+        while let Ok(Some(event)) = rx.recv_timeout(Duration::from_millis(100)) {
+            events.push(event);
+        }
+        if interrupt::is_interrupted() {
+            break;
+        }
+        let mut changed = Vec::new();
+        let mut reload = false;
+        for event in events {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    terminal::error(&format!("Error: {e}\n"));
+                    continue;
+                }
+            };
+            if event.kind.is_access() {
                 continue;
             }
-            Value::Integer(i) => {
-                let res = if no_input { i } else { ask_integer(&var.prompt, i)? };
-                vals.insert(var.name.clone(), Value::Integer(res));
-                continue;
+            for p in event.paths {
+                if answers.as_ref() == Some(&p) {
+                    reload = true;
+                    continue;
+                }
+                let Ok(relative) = p.strip_prefix(&path) else { continue };
+                if relative.starts_with(".git") {
+                    continue;
+                }
+                // Anything in them can change what is rendered and where
+                let name = relative.file_name();
+                reload |= name == Some(OsStr::new("template.toml"))
+                    || name == Some(OsStr::new(IGNORE_FILE_NAME));
+                if !changed.contains(&relative.to_path_buf()) {
+                    changed.push(relative.to_path_buf());
+                }
             }
         }
+        if changed.is_empty() && !reload {
+            continue;
+        }
+
+        let update = if reload {
+            match load_dev_template(&path, answers.as_deref(), deterministic) {
+                Ok(reloaded) => template = reloaded,
+                Err(e) => {
+                    terminal::error(&format!("Error: {e:#}\n"));
+                    continue;
+                }
+            }
+            preview.render_all(&template)
+        } else {
+            preview.render_changed(&template, &changed)
+        };
+        match update {
+            Ok(update) => print_preview_update(&update),
+            Err(e) => terminal::error(&format!("Error: {e}\n")),
+        }
     }
+    // The interruption was probably in the middle of a line
+    terminal::bold("\nStopped watching\n");
+    Ok(())
+}
 
-    Ok(vals)
+fn print_conversion(conversion: &Conversion, dry_run: bool) {
+    if dry_run {
+        terminal::bold("Would write:\n");
+    }
+    terminal::file_status(if dry_run { "create" } else { "created" }, "template.toml");
+    for file in &conversion.files {
+        let status = match (file.rewritten, dry_run) {
+            (true, true) => "rewrite",
+            (true, false) => "rewritten",
+            (false, true) => "copy",
+            (false, false) => "copied",
+        };
+        if file.source == file.path {
+            terminal::file_status(status, &file.path.display().to_string());
+        } else {
+            let paths = format!("{} -> {}", file.source.display(), file.path.display());
+            terminal::file_status(status, &paths);
+        }
+    }
+    if dry_run {
+        terminal::bold("\nThe template.toml would be:\n");
+        print!("{}", conversion.definition);
+    }
+    if !conversion.notes.is_empty() {
+        terminal::warning("\nTo fix by hand:\n");
+        for note in &conversion.notes {
+            terminal::warning(&format!("  - {}\n", note.replace('\n', "\n    ")));
+        }
+    }
 }
 
-fn execute_hook(hook: &HookFile, output_dir: &PathBuf) -> Result<()> {
-    terminal::bold(&format!("  - {}\n", hook.name()));
-    let mut command = StdCommand::new(hook.path());
-    if output_dir.exists() {
-        command.current_dir(output_dir);
+/// The alias of that name in the config file, unless a local path has the same name
+fn find_alias(name: &str) -> Result<Option<Alias>> {
+    if Path::new(name).exists() {
+        return Ok(None);
     }
-    let code = command.status()?;
-    if code.success() {
-        Ok(())
+    match Config::default_path() {
+        Some(path) => Ok(Config::load(&path)?.templates.remove(name)),
+        None => Ok(None),
+    }
+}
+
+/// Load the template, which can be an alias, asking which one to use with `prompter` if there
+/// are several in the source.
+/// The directory and ref of an alias are only used if they are not given on the command line.
+fn load_template(
+    template: &str,
+    directory: Option<&str>,
+    remote: bool,
+    options: &CloneOptions,
+    prompter: Option<Prompter>,
+    progress: bool,
+) -> Result<Template> {
+    let alias = find_alias(template)?;
+    let mut options = options.clone();
+    let (input, directory) = match alias {
+        Some(ref alias) => {
+            if options.git_ref.is_none() {
+                options.git_ref = alias.git_ref();
+            }
+            (alias.source.as_str(), directory.or(alias.directory.as_deref()))
+        }
+        None => (template, directory),
+    };
+    // Local templates are loaded right away
+    let mut spinner = (progress && (remote || !Path::new(input).exists()))
+        .then(|| Spinner::start(&format!("Fetching {input}")));
+    let mut pick = |candidates: &[TemplateCandidate]| {
+        let Some(ref prompter) = prompter else {
+            return Ok(None);
+        };
+        spinner.take();
+        let choices: Vec<_> = candidates
+            .iter()
+            .map(|c| Choice::Table {
+                value: Value::String(c.directory.clone()),
+                label: None,
+                description: c.description.clone(),
+            })
+            .collect();
+        let choice = prompter.ask_choices(
+            "Which template do you want to use?",
+            choices[0].value(),
+            &choices,
+        )?;
+        Ok(Some(choice.to_string()))
+    };
+    if remote {
+        let remote = expand_shorthand(input).unwrap_or_else(|| input.to_string());
+        Ok(Template::from_git_picking(&remote, &options, directory, &mut pick)?)
     } else {
-        bail!("Hook `{}` exited with a non 0 code\n", hook.name())
+        Ok(Template::from_input_picking(input, &options, directory, &mut pick)?)
     }
 }
 
+/// Print the variables of the template as a table, followed by their help and its hooks
+fn print_info(definition: &TemplateDefinition) {
+    terminal::bold(&format!("{}\n", definition.name));
+    if let Some(ref description) = definition.description {
+        println!("{description}");
+    }
+
+    if !definition.variables.is_empty() {
+        // The variables of a group are shown together under its title, where its first one is
+        let mut ordered: Vec<&Variable> = Vec::new();
+        for var in &definition.variables {
+            match var.group {
+                Some(ref key) if ordered.iter().any(|v| v.group.as_ref() == Some(key)) => (),
+                Some(ref key) => ordered
+                    .extend(definition.variables.iter().filter(|v| v.group.as_ref() == Some(key))),
+                None => ordered.push(var),
+            }
+        }
+
+        let mut rows =
+            vec![["Name", "Type", "Default", "Choices", "Validation", "Only if"].map(String::from)];
+        let mut headings = HashMap::new();
+        for (i, var) in ordered.iter().enumerate() {
+            let group = var.group.as_ref().and_then(|key| definition.groups.get(key));
+            let starts_group = var.group.is_some() && (i == 0 || ordered[i - 1].group != var.group);
+            if let Some(group) = group.filter(|_| starts_group) {
+                let heading = match group.only_if {
+                    Some(ref cond) => format!("{} (only if {cond})", group.title),
+                    None => group.title.clone(),
+                };
+                headings.insert(rows.len(), heading);
+            }
+            let name = match var.group {
+                Some(_) => format!("  {}", var.name),
+                None => var.name.clone(),
+            };
+            let default = match var.computed {
+                Some(ref expr) => format!("computed: {expr}"),
+                None => var.raw_default().to_string(),
+            };
+            let type_str = if var.multi { "list" } else { var.raw_default().type_str() };
+            let list = |c: &[Choice]| {
+                format!("[{}]", c.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(", "))
+            };
+            let choices = match var.choices_from {
+                Some(ref from) => {
+                    let lists: Vec<_> =
+                        from.choices.iter().map(|(k, c)| format!("{k}: {}", list(c))).collect();
+                    Some(format!("by `{}`, {}", from.name, lists.join(", ")))
+                }
+                None => var.choices.as_deref().map(list),
+            };
+            let choices = match (&var.choices_cmd, choices) {
+                (Some(cmd), Some(fallback)) => Some(format!("`{}` or {fallback}", cmd.join(" "))),
+                (Some(cmd), None) => Some(format!("`{}`", cmd.join(" "))),
+                (None, choices) => choices,
+            };
+            let only_if = var.only_if.as_ref().map(|c| c.to_string());
+            rows.push([
+                name,
+                type_str.to_string(),
+                default,
+                choices.unwrap_or_default(),
+                var.validation.clone().unwrap_or_default(),
+                only_if.unwrap_or_default(),
+            ]);
+        }
+
+        let mut widths = [0; 6];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        println!();
+        for (i, row) in rows.iter().enumerate() {
+            if let Some(heading) = headings.get(&i) {
+                terminal::bold(&format!("{heading}\n"));
+            }
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+            if i == 0 {
+                terminal::bold(&format!("{}\n", line.trim_end()));
+            } else {
+                println!("{}", line.trim_end());
+            }
+        }
+
+        let helps: Vec<_> =
+            definition.variables.iter().filter_map(|v| Some((&v.name, v.help.as_ref()?))).collect();
+        if !helps.is_empty() {
+            terminal::bold("\nHelp:\n");
+            for (name, help) in helps {
+                println!("  {name}: {help}");
+            }
+        }
+    }
+
+    for (stage, hooks) in [
+        ("Pre-prompt", &definition.pre_prompt_hooks),
+        ("Pre-gen", &definition.pre_gen_hooks),
+        ("Post-gen", &definition.post_gen_hooks),
+    ] {
+        if !hooks.is_empty() {
+            terminal::bold(&format!("\n{stage} hooks:\n"));
+            for hook in hooks {
+                match hook.only_if {
+                    Some(ref c) => {
+                        println!("  - {} ({}), only if {}", hook.name, hook.path.display(), c)
+                    }
+                    None => println!("  - {} ({})", hook.name, hook.path.display()),
+                }
+            }
+        }
+    }
+}
+
+/// Once interrupted: list the files already created in the output directory, which are
+/// kept, and exit with 130 like shells do. The temporary directories are gone by then.
+fn exit_interrupted() -> ! {
+    // The interruption was probably in the middle of a line
+    println!();
+    let created = interrupt::created();
+    if !created.is_empty() {
+        terminal::warning("These files were created in the output directory and were kept:\n");
+        for path in created {
+            terminal::warning(&format!("  - {}\n", path.display()));
+        }
+    }
+    terminal::error("Aborted.\n");
+    std::process::exit(130)
+}
+
+/// Whether starting a process failed because the file can't be executed as is
+fn is_not_executable(e: &io::Error) -> bool {
+    // ENOEXEC on Unix, eg a script without shebang, and ERROR_BAD_EXE_FORMAT on Windows, eg a
+    // shell script
+    let exec_format_error = if cfg!(windows) { 193 } else { 8 };
+    e.kind() == io::ErrorKind::PermissionDenied || e.raw_os_error() == Some(exec_format_error)
+}
+
+/// Wait for the child to exit, returning `None` if it is still running after `timeout`
+fn wait_timeout(child: &mut Child, timeout: Duration) -> Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Kill the child and everything it started
+#[cfg(unix)]
+fn kill_tree(child: &mut Child) {
+    // Only called on hooks leading their own process group, see `spawn_hook`
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    let _ = child.wait();
+}
+
+/// Kill the child and everything it started
+#[cfg(not(unix))]
+fn kill_tree(child: &mut Child) {
+    if cfg!(windows) {
+        let pid = child.id().to_string();
+        let _ = StdCommand::new("taskkill").args(["/T", "/F", "/PID", &pid]).output();
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// How many bytes of each output stream of a quiet hook are kept: only the end is shown if it
+/// writes more than that
+const MAX_CAPTURED_OUTPUT: usize = 1024 * 1024;
+
+/// Read the stream until it is closed in another thread, keeping only its last
+/// [MAX_CAPTURED_OUTPUT] bytes. Also returns whether anything was dropped.
+fn capture(mut stream: impl Read + Send + 'static) -> thread::JoinHandle<(Vec<u8>, bool)> {
+    thread::spawn(move || {
+        let mut captured = VecDeque::new();
+        let mut truncated = false;
+        let mut buf = [0; 8192];
+        while let Ok(n @ 1..) = stream.read(&mut buf) {
+            captured.extend(&buf[..n]);
+            if captured.len() > MAX_CAPTURED_OUTPUT {
+                captured.drain(..captured.len() - MAX_CAPTURED_OUTPUT);
+                truncated = true;
+            }
+        }
+        (captured.into(), truncated)
+    })
+}
+
+/// Show every line of the stream of a hook running along others as it comes, prefixed with the
+/// name of the hook, on stderr or stdout
+fn forward_lines(
+    stream: impl Read + Send + 'static,
+    hook: &str,
+    stderr: bool,
+) -> thread::JoinHandle<()> {
+    let hook = hook.to_string();
+    thread::spawn(move || {
+        for line in io::BufReader::new(stream).split(b'\n').map_while(|l| l.ok()) {
+            let line = String::from_utf8_lossy(&line);
+            if stderr {
+                eprintln!("[{hook}] {line}");
+            } else {
+                println!("[{hook}] {line}");
+            }
+        }
+    })
+}
+
+/// Show what a quiet hook wrote to one of its streams once it failed, each line prefixed with
+/// `[prefix]` if given
+fn show_captured(
+    name: &str,
+    (output, truncated): (Vec<u8>, bool),
+    prefix: Option<&str>,
+    out: &mut dyn Write,
+) {
+    if output.is_empty() {
+        return;
+    }
+    if truncated {
+        let _ = writeln!(
+            out,
+            "[{name} truncated, only the last {MAX_CAPTURED_OUTPUT} bytes are shown]"
+        );
+    }
+    match prefix {
+        Some(prefix) => {
+            for line in String::from_utf8_lossy(&output).lines() {
+                let _ = writeln!(out, "[{prefix}] {line}");
+            }
+        }
+        None => {
+            let _ = out.write_all(&output);
+        }
+    }
+    let _ = out.flush();
+}
+
+/// A hook started by [spawn_hook], to wait for with [finish_hook]
+struct RunningHook<'a> {
+    hook: &'a HookFile,
+    child: Child,
+    start: Instant,
+    timeout: Option<Duration>,
+    /// Whether its output is only shown if it fails
+    quiet: bool,
+    /// Whether it runs along other hooks, its output lines being prefixed with its name
+    parallel: bool,
+    /// What a quiet hook wrote to its stdout and stderr
+    captured: Option<(OutputCapture, OutputCapture)>,
+    /// The threads showing the output of a hook running along others as it comes
+    forwarders: Vec<thread::JoinHandle<()>>,
+    /// The thread writing the answers to its stdin
+    writer: thread::JoinHandle<()>,
+    /// Until it exited
+    _kill: KillOnInterrupt,
+}
+
+type OutputCapture = thread::JoinHandle<(Vec<u8>, bool)>;
+
+/// Start a hook, with `parallel` if it runs along other hooks.
+fn spawn_hook<'a>(
+    template: &Template,
+    stage: &str,
+    hook: &'a HookFile,
+    output_dir: &Path,
+    cli: &Cli,
+    parallel: bool,
+) -> Result<RunningHook<'a>> {
+    if interrupt::is_interrupted() {
+        bail!("Interrupted before running the hook `{}`", hook.name());
+    }
+    let timeout = hook.timeout().or(cli.hook_timeout.map(Duration::from_secs));
+    // Their output would end up in the middle of the JSON
+    let quiet = cli.quiet_hooks || hook.is_quiet() || report::is_collecting();
+    if quiet && !parallel {
+        report::bold(&format!("  - {}", hook.name()));
+    } else {
+        report::bold(&format!("  - {}\n", hook.name()));
+    }
+    let hook_args = template.render_hook_args(hook)?;
+    let mut command = match hook.interpreter() {
+        Some([program, args @ ..]) => {
+            let mut command = StdCommand::new(program);
+            command.args(args).arg(hook.path());
+            command
+        }
+        _ => StdCommand::new(hook.path()),
+    };
+    let hook_args_count = hook_args.len();
+    command.args(hook_args);
+    let mut env = template.hook_env(output_dir)?;
+    // Where they can write what they give back to kickstart
+    let output_file = match stage {
+        "pre-prompt" => Some(("KICKSTART_DEFAULTS_FILE", hook.defaults_file())),
+        "pre-gen" => Some(("KICKSTART_EXTRA_CONTEXT_FILE", hook.extra_context_file())),
+        _ => None,
+    };
+    if let Some((name, path)) = output_file {
+        env.push((name.to_string(), path.display().to_string()));
+    }
+    // Only the names, the values can come from secret variables
+    let names: Vec<_> = env.iter().map(|(name, _)| name.as_str()).collect();
+    trace!("The environment variables given to the hook `{}`: {}", hook.name(), names.join(", "));
+    command.envs(env).stdin(Stdio::piped());
+    if quiet || parallel {
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+    // Pre-gen hooks of cookiecutter templates run before their directory exists
+    let hook_dir = template.hook_dir(output_dir)?;
+    if let Some(dir) = [hook_dir.as_path(), output_dir].into_iter().find(|d| d.exists()) {
+        command.current_dir(dir);
+    }
+    // In its own process group so the whole tree can be killed on timeout, or with the other
+    // hooks running when interrupted or erroring. Only then as that takes it out of the foreground of the terminal.
+    let group = timeout.is_some() || parallel;
+    #[cfg(unix)]
+    if group {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    // The arguments rendered from secret variables are masked
+    let mut logged = StdCommand::new(command.get_program());
+    let before_args = command.get_args().count() - hook_args_count;
+    logged.args(command.get_args().take(before_args));
+    logged.args(template.render_masked_hook_args(hook)?);
+    debug!("Running the {stage} hook `{}`: `{}`", hook.name(), command_line(&logged));
+    let start = Instant::now();
+    let spawned = command.spawn();
+    if quiet && !parallel && spawned.is_err() {
+        report::plain("\n");
+    }
+    let (mut child, kill) = match spawned {
+        Ok(child) => {
+            let kill = KillOnInterrupt::new(child.id(), group);
+            (child, kill)
+        }
+        Err(e) if hook.interpreter().is_none() && is_not_executable(&e) => bail!(
+            "Hook `{}` could not be executed: make sure it has a shebang (eg `#!/bin/sh`) and the \
+            executable bit, or set an `interpreter` for it in template.toml ({e})",
+            hook.name()
+        ),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Hook `{}` could not be started", hook.name()))
+        }
+    };
+
+    // Both streams are read at the same time so the hook can't block on a full pipe
+    let (mut captured, mut forwarders) = (None, Vec::new());
+    if let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) {
+        if quiet {
+            captured = Some((capture(stdout), capture(stderr)));
+        } else {
+            forwarders.push(forward_lines(stdout, hook.name(), false));
+            forwarders.push(forward_lines(stderr, hook.name(), true));
+        }
+    }
+    // The answers are also given as JSON on stdin. That's written from another thread so a hook
+    // that doesn't read its stdin can't block us once the pipe is full, and the pipe is closed
+    // once done. Hooks exiting without reading it are fine so write errors are ignored.
+    let answers = serde_json::to_vec(template.variables())?;
+    let mut stdin = child.stdin.take().expect("stdin to be piped");
+    let writer = thread::spawn(move || {
+        let _ = stdin.write_all(&answers);
+    });
+    Ok(RunningHook {
+        hook,
+        child,
+        start,
+        timeout,
+        quiet,
+        parallel,
+        captured,
+        forwarders,
+        writer,
+        _kill: kill,
+    })
+}
+
+/// Wait for a hook started by [spawn_hook] to exit, killing it if it runs for longer than its
+/// timeout. Returns why it failed, if it did.
+fn finish_hook(running: RunningHook, stage: &str) -> Result<Option<String>> {
+    let RunningHook { hook, mut child, start, timeout, quiet, parallel, .. } = running;
+    let status = match timeout {
+        Some(timeout) => wait_timeout(&mut child, timeout.saturating_sub(start.elapsed()))?,
+        None => Some(child.wait()?),
+    };
+    if status.is_none() {
+        kill_tree(&mut child);
+    }
+    let duration = start.elapsed();
+    let _ = running.writer.join();
+    for forwarder in running.forwarders {
+        let _ = forwarder.join();
+    }
+    report::hook_ran(HookRun {
+        name: hook.name().to_string(),
+        stage: stage.to_string(),
+        exit_code: status.and_then(|s| s.code()),
+        duration_ms: duration.as_millis() as u64,
+    });
+
+    let failure = match status {
+        Some(status) if status.success() => None,
+        Some(status) => Some(match status.code() {
+            Some(code) => format!("Hook `{}` exited with code {code}", hook.name()),
+            None => format!("Hook `{}` was killed by a signal", hook.name()),
+        }),
+        None => Some(format!(
+            "Hook `{}` timed out after {}s",
+            hook.name(),
+            timeout.unwrap_or_default().as_secs()
+        )),
+    };
+
+    let prefix = if parallel { Some(hook.name()) } else { None };
+    if let Some((stdout, stderr)) = running.captured {
+        let (stdout, stderr) =
+            (stdout.join().unwrap_or_default(), stderr.join().unwrap_or_default());
+        if failure.is_none() {
+            if !parallel {
+                report::success(&format!(" ✓ ({})\n", terminal::elapsed(duration)));
+            }
+        } else if report::is_collecting() {
+            // Stdout is for the report
+            show_captured("stdout", stdout, prefix, &mut io::stderr());
+            show_captured("stderr", stderr, prefix, &mut io::stderr());
+        } else {
+            if !parallel {
+                println!();
+            }
+            show_captured("stdout", stdout, prefix, &mut io::stdout());
+            show_captured("stderr", stderr, prefix, &mut io::stderr());
+        }
+    }
+    if failure.is_none() && (!quiet || parallel) {
+        report::success(&format!("  ✓ {} ({})\n", hook.name(), terminal::elapsed(duration)));
+    }
+    Ok(failure)
+}
+
+/// Error with why the hook failed, unless it has `allow_failure` and `--strict-hooks` is not
+/// used: then it returns it after a warning.
+fn check_hook_failure(
+    hook: &HookFile,
+    failure: Option<String>,
+    cli: &Cli,
+) -> Result<Option<String>> {
+    match failure {
+        Some(message) if hook.allows_failure() && !cli.strict_hooks => {
+            report::warning(&format!("{message}, continuing as it is allowed to fail\n"));
+            Ok(Some(message))
+        }
+        Some(message) => bail!(message),
+        None => Ok(None),
+    }
+}
+
+/// Run a hook, erroring if it fails unless it has `allow_failure` and `--strict-hooks` is not
+/// used: then it returns why it failed after a warning.
+fn execute_hook(
+    template: &Template,
+    stage: &str,
+    hook: &HookFile,
+    output_dir: &Path,
+    cli: &Cli,
+) -> Result<Option<String>> {
+    let running = spawn_hook(template, stage, hook, output_dir, cli, false)?;
+    let failure = finish_hook(running, stage)?;
+    check_hook_failure(hook, failure, cli)
+}
+
+/// Whether the hook exited or ran out of time, so [finish_hook] doesn't wait
+fn is_done(running: &mut RunningHook) -> io::Result<bool> {
+    let timed_out = running.timeout.is_some_and(|t| running.start.elapsed() >= t);
+    Ok(timed_out || running.child.try_wait()?.is_some())
+}
+
+/// Run hooks at the same time, at most `--jobs` of them, waiting for all of them before
+/// erroring with every hook that failed. Returns why the ones with `allow_failure` failed, as
+/// [execute_hook] does.
+fn execute_parallel_hooks(
+    template: &Template,
+    stage: &str,
+    hooks: &[&HookFile],
+    output_dir: &Path,
+    cli: &Cli,
+) -> Result<Vec<String>> {
+    let jobs = cli.jobs.map_or(hooks.len(), |j| j.get());
+    let (mut errors, mut soft_failures) = (Vec::new(), Vec::new());
+    let mut queue = hooks.iter();
+    let mut running = Vec::new();
+    // The next hook starts as soon as one is done
+    let res = loop {
+        while running.len() < jobs {
+            let Some(hook) = queue.next() else {
+                break;
+            };
+            match spawn_hook(template, stage, hook, output_dir, cli, true) {
+                Ok(r) => running.push(r),
+                Err(e) => errors.push(format!("{e:#}")),
+            }
+        }
+        if running.is_empty() {
+            break Ok(());
+        }
+        let mut done = None;
+        for (i, r) in running.iter_mut().enumerate() {
+            match is_done(r) {
+                Ok(false) => continue,
+                Ok(true) => done = Some(Ok(i)),
+                Err(e) => done = Some(Err(e)),
+            }
+            break;
+        }
+        match done {
+            None => thread::sleep(Duration::from_millis(50)),
+            Some(Ok(i)) => {
+                let r = running.remove(i);
+                let hook = r.hook;
+                match finish_hook(r, stage) {
+                    Ok(failure) => match check_hook_failure(hook, failure, cli) {
+                        Ok(soft) => soft_failures.extend(soft),
+                        Err(e) => errors.push(e.to_string()),
+                    },
+                    Err(e) => break Err(e),
+                }
+            }
+            Some(Err(e)) => break Err(e.into()),
+        }
+    };
+    if let Err(e) = res {
+        // Not left running in the background
+        for mut r in running {
+            kill_tree(&mut r.child);
+        }
+        return Err(e);
+    }
+    match errors.as_slice() {
+        [] => Ok(soft_failures),
+        [error] => bail!("{error}"),
+        _ => bail!("{} hooks failed:\n{}", errors.len(), errors.join("\n")),
+    }
+}
+
+/// Whether we can generate in the output directory: it needs to be empty or not exist yet
+/// unless the user or the template opted in. The lockfile is ignored so a project can be
+/// generated again.
+fn can_generate_in(
+    template: &Template,
+    output_dir: &Path,
+    cli: &Cli,
+    interactive: bool,
+) -> Result<bool> {
+    let definition = &template.definition;
+    if cli.force || cli.skip_existing || definition.allow_non_empty {
+        return Ok(true);
+    }
+    let Ok(entries) = fs::read_dir(output_dir) else {
+        return Ok(true);
+    };
+    let lockfile_name = OsStr::new(&definition.lockfile_name);
+    let mut entries = entries.filter_map(|e| e.ok()).filter(|e| e.file_name() != lockfile_name);
+    if entries.next().is_none() {
+        return Ok(true);
+    }
+
+    let message = format!("The output directory `{}` is not empty", output_dir.display());
+    if !interactive {
+        bail!("{message}, use --force to generate in it anyway");
+    }
+    Ok(cli.prompter().ask_bool(&format!("{message}, generate in it anyway?"), false)?)
+}
+
+/// Remote templates can run anything with their hooks and the `choices_cmd` of their
+/// variables: list all of them, the hooks only if `with_hooks`, and ask once before running
+/// any, unless `--trust` is used. Without a way to ask, they are refused.
+/// Returns whether to run them.
+fn confirm_commands(template: &Template, cli: &Cli, with_hooks: bool) -> Result<bool> {
+    if !template.is_remote() || cli.trust {
+        return Ok(true);
+    }
+    let mut hooks = Vec::new();
+    if with_hooks {
+        for t in iter::once(template).chain(template.addons().map(|(_, addon)| addon)) {
+            let stages = [
+                ("pre-prompt", &t.definition.pre_prompt_hooks),
+                ("pre-gen", &t.definition.pre_gen_hooks),
+                ("post-gen", &t.definition.post_gen_hooks),
+            ];
+            for (stage, stage_hooks) in stages {
+                hooks.extend(
+                    stage_hooks
+                        .iter()
+                        .filter(|h| t.is_hook_for_os(h) && !is_filtered_out(&h.name, cli))
+                        .map(|h| (stage, t, h)),
+                );
+            }
+        }
+    }
+    let commands = template.choices_cmds();
+    if hooks.is_empty() && commands.is_empty() {
+        return Ok(true);
+    }
+    if cli.no_input || !is_interactive() || report::is_collecting() {
+        bail!(
+            "The template comes from a remote repository and has hooks or commands to get \
+            choices: use --trust to run them without confirmation"
+        );
+    }
+
+    terminal::warning("The template comes from a remote repository and wants to run:\n");
+    for (stage, _, hook) in &hooks {
+        let condition = hook.only_if.as_ref().map(|c| format!(", if {c}")).unwrap_or_default();
+        println!("  - the {stage} hook {} ({}{condition})", hook.name, hook.path.display());
+    }
+    for (name, cmd) in &commands {
+        println!("  - `{}` to get the choices of `{name}`", cmd.join(" "));
+    }
+    if !hooks.is_empty() && cli.prompter().ask_bool("Show the content of the hooks?", false)? {
+        for (_, t, hook) in &hooks {
+            terminal::bold(&format!("--- {} ---\n", hook.path.display()));
+            let content = fs::read(t.path().join(&hook.path))
+                .with_context(|| format!("Could not read the hook `{}`", hook.name))?;
+            println!("{}", String::from_utf8_lossy(&content));
+        }
+    }
+    if cli.prompter().ask_bool("Run them?", false)? {
+        return Ok(true);
+    }
+    terminal::warning("They will not be run\n");
+    Ok(false)
+}
+
+/// Get the choices of the variables with a `choices_cmd`, only running them if `trusted`, see
+/// [confirm_commands]. Their `choices` are used otherwise.
+fn run_choices_cmds(template: &mut Template, trusted: bool) -> Result<()> {
+    for fallback in template.run_choices_cmds(trusted)? {
+        report::warning(&format!("{fallback}\n"));
+    }
+    Ok(())
+}
+
+/// Offer to remove what was generated after a post-gen hook failed, or do it directly with
+/// `--clean-on-failure`. Files that existed before are never removed.
+fn clean_after_failure(generation: &Generation, cli: &Cli, interactive: bool) -> Result<()> {
+    if generation.created.is_empty() {
+        return Ok(());
+    }
+    let clean = cli.clean_on_failure
+        || (interactive
+            && cli
+                .prompter()
+                .ask_bool("A post-gen hook failed, remove the generated files?", false)?);
+    if clean {
+        generation.rollback().context("Could not remove the generated files")?;
+        report::bold(&format!(
+            "Removed the {} path(s) created by the generation\n",
+            generation.created.len()
+        ));
+    } else {
+        report::warning("The generated files were kept, use --clean-on-failure to remove them\n");
+    }
+    Ok(())
+}
+
+/// Pick the addons of the template to generate: the ones given with `--addon`, or asking about
+/// each of them unless there is no way to answer
+fn select_addons(template: &mut Template, cli: &Cli) -> Result<()> {
+    let names = if !cli.addons.is_empty() {
+        cli.addons.clone()
+    } else {
+        let mut names = Vec::new();
+        for addon in &template.definition.addons {
+            let selected = if cli.no_input || cli.input_file.is_some() {
+                addon.default
+            } else {
+                cli.prompter().ask_bool(&addon.prompt, addon.default)?
+            };
+            if selected {
+                names.push(addon.name.clone());
+            }
+        }
+        names
+    };
+    template.select_addons(&names)?;
+    Ok(())
+}
+
+/// The hooks of a stage of the template and of its addons, in the order they are run in
+fn hook_definitions(
+    template: &Template,
+    stage: fn(&TemplateDefinition) -> &Vec<Hook>,
+) -> Vec<Hook> {
+    let addons = template.addons().map(|(_, addon)| addon);
+    iter::once(template).chain(addons).flat_map(|t| stage(&t.definition).clone()).collect()
+}
+
+/// Make sure every hook given to `--skip-hook`/`--only-hook` exists to catch typos
+fn check_hook_filters(template: &Template, cli: &Cli) -> Result<()> {
+    let mut all_hooks = hook_definitions(template, |d| &d.pre_prompt_hooks);
+    all_hooks.extend(hook_definitions(template, |d| &d.pre_gen_hooks));
+    all_hooks.extend(hook_definitions(template, |d| &d.post_gen_hooks));
+    for name in cli.skip_hook.iter().chain(cli.only_hooks.iter()) {
+        if !all_hooks.iter().any(|h| &h.name == name) {
+            bail!("The template has no hook named `{name}`");
+        }
+    }
+    Ok(())
+}
+
+/// Whether the hook is excluded by `--skip-hook`/`--only-hook`
+fn is_filtered_out(name: &str, cli: &Cli) -> bool {
+    let name = name.to_string();
+    cli.skip_hook.contains(&name) || (!cli.only_hooks.is_empty() && !cli.only_hooks.contains(&name))
+}
+
+/// What happened to the hooks that didn't simply run, to mention once everything is done
+#[derive(Debug, Default)]
+struct HookSummary {
+    /// The hooks not run because of `--skip-hook`/`--only-hook`
+    filtered: Vec<String>,
+    /// Why the hooks with `allow_failure` that failed did
+    soft_failures: Vec<String>,
+}
+
+impl HookSummary {
+    fn extend(&mut self, other: HookSummary) {
+        self.filtered.extend(other.filtered);
+        self.soft_failures.extend(other.soft_failures);
+    }
+
+    fn report(&self) {
+        if !self.filtered.is_empty() {
+            report::bold(&format!(
+                "Hooks not run because of --skip-hook/--only-hook: {}\n",
+                self.filtered.join(", ")
+            ));
+        }
+        if !self.soft_failures.is_empty() {
+            let failures: Vec<_> =
+                self.soft_failures.iter().map(|f| format!("  - {f}\n")).collect();
+            report::warning(&format!(
+                "Hooks that failed but are allowed to:\n{}",
+                failures.join("")
+            ));
+        }
+    }
+}
+
+/// Run the hooks of a stage in `output_dir`, mentioning the ones whose `only_if` condition is
+/// not met. What the pre-gen hooks write to `KICKSTART_EXTRA_CONTEXT_FILE` is added to the
+/// context of the template.
+fn run_hooks(
+    template: &mut Template,
+    stage: &str,
+    output_dir: &Path,
+    definitions: &[Hook],
+    hooks: &[HookFile],
+    cli: &Cli,
+) -> Result<HookSummary> {
+    let hooks_for_os = definitions.iter().filter(|h| template.is_hook_for_os(h));
+    for hook in hooks_for_os.filter(|h| !template.should_run_hook(h)) {
+        report::warning(&format!("Skipping hook {} (condition not met)\n", hook.name));
+    }
+    let (filtered, hooks): (Vec<_>, Vec<_>) =
+        hooks.iter().partition(|h| is_filtered_out(h.name(), cli));
+    let mut summary = HookSummary {
+        filtered: filtered.into_iter().map(|h| h.name().to_string()).collect(),
+        soft_failures: Vec::new(),
+    };
+    if hooks.is_empty() {
+        return Ok(summary);
+    }
+
+    // The `parallel` hooks next to each other run at the same time
+    let mut batches: Vec<Vec<&HookFile>> = Vec::new();
+    for hook in hooks {
+        match batches.last_mut() {
+            Some(batch) if hook.is_parallel() && batch[0].is_parallel() => batch.push(hook),
+            _ => batches.push(vec![hook]),
+        }
+    }
+
+    report::bold(&format!("Running {stage} hooks...\n"));
+    for batch in batches {
+        let soft_failures = match batch.as_slice() {
+            [hook] => execute_hook(template, stage, hook, output_dir, cli)?.into_iter().collect(),
+            _ => execute_parallel_hooks(template, stage, &batch, output_dir, cli)?,
+        };
+        summary.soft_failures.extend(soft_failures);
+        for hook in batch {
+            match stage {
+                "pre-prompt" => template.load_hook_defaults(hook)?,
+                "pre-gen" => template.load_extra_context(hook)?,
+                _ => (),
+            }
+        }
+    }
+    // For spacing
+    report::plain("\n");
+    Ok(summary)
+}
+
+/// Run the pre-prompt hooks, in the current output directory if there is one, using the
+/// defaults each of them writes to `KICKSTART_DEFAULTS_FILE` for the questions.
+fn run_pre_prompt_hooks(template: &mut Template, cli: &Cli) -> Result<HookSummary> {
+    let hooks = template.get_pre_prompt_hooks()?;
+    if hooks.is_empty() {
+        return Ok(HookSummary::default());
+    }
+    let dir = cli.output_dir.clone().unwrap_or_else(|| PathBuf::from("."));
+    let definitions = hook_definitions(template, |d| &d.pre_prompt_hooks);
+    run_hooks(template, "pre-prompt", &dir, &definitions, &hooks, cli)
+}
+
 fn try_main() -> Result<()> {
     let cli = Cli::parse();
+    set_simple_prompts(cli.simple_prompts);
+    set_assume_tty(cli.assume_tty);
+    set_color_choice(if cli.no_color { ColorChoice::Never } else { cli.color });
+    // `--quiet` still shows the warnings, and can't be used along `-v`
+    let level = match cli.verbose {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    logger::init(level, cli.log_file.as_deref())?;
+    report::set_quiet(cli.quiet);
+    interrupt::handle_interrupts();
+    if cli.format == OutputFormat::Json {
+        match cli.command {
+            None => report::collect(),
+            Some(Command::Info { .. } | Command::Check { .. }) => (),
+            Some(_) => {
+                bail!("--format json can only be used when generating and with `info` and `check`")
+            }
+        }
+    }
 
     match cli.command {
-        Some(Command::Validate { path }) => {
-            let errs = TemplateDefinition::validate_file(path)?;
+        Some(Command::Validate { path, render, check_usage }) => {
+            let errs = TemplateDefinition::validate_file(&path)?;
 
             if !errs.is_empty() {
                 // We let the caller do the error handling/display
@@ -117,44 +1751,608 @@ fn try_main() -> Result<()> {
                     errs.into_iter().map(|e| format!("- {}\n", e)).collect::<Vec<_>>().join("\n"),
                 );
                 bail!(err);
+            }
+
+            // Globs can use variables, they are checked with the default values
+            let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            let mut template = Template::from_local(dir, None)?;
+            template.set_deterministic(cli.deterministic);
+            template.set_variables(template.default_values()?)?;
+            if render {
+                let errors = template.render_errors();
+                if !errors.is_empty() {
+                    bail!(
+                        "The template doesn't render with the default values:\n{}",
+                        errors.iter().map(|e| format!("- {e}\n")).collect::<Vec<_>>().join("\n")
+                    );
+                }
+            }
+            for glob in template.unmatched_copy_without_render()? {
+                terminal::warning(&format!(
+                    "Warning: the `copy_without_render` glob `{glob}` doesn't match any file\n"
+                ));
+            }
+            if check_usage {
+                let usage = template.variable_usage()?;
+                for name in usage.unused {
+                    terminal::warning(&format!(
+                        "Warning: the variable `{name}` doesn't seem to be used anywhere\n"
+                    ));
+                }
+                for (name, path) in usage.undeclared {
+                    terminal::warning(&format!(
+                        "Warning: `{}` uses `{name}`, which doesn't seem to be a variable\n",
+                        path.display()
+                    ));
+                }
+            }
+            terminal::success("The template.toml file is valid!\n");
+        }
+        Some(Command::Update { ref path, ref lockfile, force, skip_modified }) => {
+            let previous = Lockfile::load(&path.join(lockfile))?;
+            let mut template = load_locked_template(&previous, None)?;
+            // What the template changed since is what needs to be applied
+            let base = match previous.commit {
+                Some(ref commit) => {
+                    let mut base = load_locked_template(&previous, Some(commit))?;
+                    base.set_variables(previous_answers(&base, &previous))?;
+                    Some(base)
+                }
+                None => None,
+            };
+            let trusted = confirm_commands(&template, &cli, false)?;
+            run_choices_cmds(&mut template, trusted)?;
+            let mut answers = previous_answers(&template, &previous);
+            answers.extend(parse_env_overrides(&template)?);
+            let vals = ask_questions(&template, cli.prompter(), cli.no_input, &answers)?;
+            template.set_variables(vals)?;
+
+            let policy = if force {
+                ConflictPolicy::Overwrite
+            } else if skip_modified {
+                ConflictPolicy::Skip
             } else {
-                terminal::success("The template.toml file is valid!\n");
+                ConflictPolicy::WriteNew
+            };
+            let report = update_project(&template, path, &previous, base.as_ref(), policy)?;
+
+            terminal::bold(&format!(
+                "Created {} file(s), updated {} file(s), deleted {} file(s)\n",
+                report.created.len(),
+                report.updated.len(),
+                report.removed.len()
+            ));
+            if !report.conflicts.is_empty() {
+                let action = match policy {
+                    ConflictPolicy::Overwrite => "overwritten",
+                    ConflictPolicy::Skip => "kept as is",
+                    ConflictPolicy::WriteNew => "kept as is, see the .new files",
+                };
+                terminal::warning(&format!("Files modified since the generation, {action}:\n"));
+                for p in &report.conflicts {
+                    terminal::warning(&format!("  - {}\n", p.display()));
+                }
             }
+            terminal::success("\nEverything done, ready to go!\n");
         }
-        None => {
+        Some(Command::Check { path, lockfile, lockfile_only }) => {
+            let previous = Lockfile::load(&path.join(lockfile))?;
+            let template = if lockfile_only {
+                None
+            } else {
+                // As it was when the project was generated, not as it is now
+                let mut template = load_locked_template(&previous, previous.commit.as_deref())?;
+                // Variables the lockfile doesn't have have their default value
+                template.set_deterministic(cli.deterministic);
+                let mut vals = template.default_values()?;
+                vals.extend(previous_answers(&template, &previous));
+                template.set_variables(vals)?;
+                Some(template)
+            };
+            let drift = check_project(&path, &previous, template.as_ref())?;
+
+            match cli.format {
+                OutputFormat::Text => {
+                    for (status, paths) in [
+                        ("modified", &drift.modified),
+                        ("deleted", &drift.deleted),
+                        ("untracked", &drift.untracked),
+                    ] {
+                        for p in paths {
+                            terminal::file_status(status, p);
+                        }
+                    }
+                }
+                OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&drift)?),
+            }
+            if !drift.is_empty() {
+                bail!("The project has changed since it was generated");
+            }
+            if cli.format == OutputFormat::Text {
+                terminal::success("The project is as it was generated\n");
+            }
+        }
+        Some(Command::Info { template, directory }) => {
+            let options = CloneOptions { cache_dir: cache::default_dir(), ..Default::default() };
+            let progress = !cli.quiet && cli.format == OutputFormat::Text;
+            let template =
+                load_template(&template, directory.as_deref(), false, &options, None, progress)?;
+            match cli.format {
+                OutputFormat::Text => print_info(&template.definition),
+                // The template definition
+                OutputFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&template.definition)?)
+                }
+            }
+        }
+        Some(Command::Schema { template, directory, out }) => {
+            let options = CloneOptions { cache_dir: cache::default_dir(), ..Default::default() };
+            let template =
+                load_template(&template, directory.as_deref(), false, &options, None, !cli.quiet)?;
+            let schema = serde_json::to_string_pretty(&template.definition.json_schema())?;
+            match out {
+                Some(path) => {
+                    fs::write(&path, format!("{schema}\n"))
+                        .with_context(|| format!("Could not write {}", path.display()))?;
+                    terminal::success(&format!("Wrote the schema to {}\n", path.display()));
+                }
+                None => println!("{schema}"),
+            }
+        }
+        Some(Command::Convert { template, output, dry_run }) => {
+            if output.read_dir().is_ok_and(|mut entries| entries.next().is_some()) {
+                bail!("{} is not empty", output.display());
+            }
+            let conversion =
+                if dry_run { plan_conversion(&template)? } else { convert(&template, &output)? };
+            print_conversion(&conversion, dry_run);
+            if dry_run {
+                terminal::success("\nNothing was written (--dry-run)\n");
+            } else {
+                terminal::success(&format!("\nConverted to {}\n", output.display()));
+            }
+        }
+        Some(Command::Init { path, force }) => {
+            let written = match create_skeleton(&path, force) {
+                Err(e) if matches!(e.kind, ErrorKind::ExistingFiles(..)) => {
+                    bail!("{e}\nUse --force to overwrite them")
+                }
+                res => res?,
+            };
+            for file in written {
+                terminal::file_status("created", &file.to_string_lossy());
+            }
+            let definition = path.join("template.toml");
+            terminal::success(&format!(
+                "\nCreated a template in {}, see `kickstart validate {}` once it is edited\n",
+                path.display(),
+                definition.display()
+            ));
+        }
+        Some(Command::Dev { template, answers, output_dir, clean }) => {
+            let (output_dir, created) = match output_dir {
+                Some(dir) => {
+                    let created = !dir.exists();
+                    (dir, created)
+                }
+                None => {
+                    let dir = tempfile::Builder::new().prefix("kickstart-dev-").tempdir()?;
+                    (dir.into_path(), true)
+                }
+            };
+            let mut preview = Preview::new(&output_dir)?;
+            let res =
+                watch_template(&template, answers.as_deref(), &mut preview, cli.deterministic);
+            if clean && created {
+                let _ = fs::remove_dir_all(&output_dir);
+            } else if clean {
+                // What was in it before is left alone
+                preview.remove_created();
+            }
+            res?;
+        }
+        Some(Command::Test { template, directory }) => {
+            let options = CloneOptions { cache_dir: cache::default_dir(), ..Default::default() };
             let mut template =
-                Template::from_input(&cli.template.unwrap(), cli.directory.as_deref())?;
+                load_template(&template, directory.as_deref(), false, &options, None, !cli.quiet)?;
+            template.set_deterministic(cli.deterministic);
+            let cases = template.test_cases()?;
+            if cases.is_empty() {
+                bail!(
+                    "The template has no test cases in its {}/ directory",
+                    template.definition.tests_dir
+                );
+            }
+            let mut failed = 0;
+            for case in &cases {
+                terminal::bold(&format!("{} ... ", case.name));
+                let failures = case.run(&mut template);
+                if failures.is_empty() {
+                    terminal::success("ok\n");
+                } else {
+                    failed += 1;
+                    terminal::error("FAILED\n");
+                    print_test_failures(&failures);
+                }
+            }
+            if failed > 0 {
+                bail!("{failed} of the {} test cases failed", cases.len());
+            }
+            terminal::success(&format!("\nAll the {} test cases passed\n", cases.len()));
+        }
+        Some(Command::Alias { command }) => {
+            let Some(path) = Config::default_path() else {
+                bail!(
+                    "Could not find the config directory, set {} to choose a file",
+                    CONFIG_ENV_VAR
+                );
+            };
+            let mut config = Config::load(&path)?;
+            match command {
+                AliasCommand::Add { name, source, directory, branch, tag, rev } => {
+                    let alias = Alias { source, directory, branch, tag, rev };
+                    config.templates.insert(name.clone(), alias);
+                    config.save(&path)?;
+                    terminal::success(&format!("Added the `{name}` alias\n"));
+                }
+                AliasCommand::List => {
+                    for (name, alias) in &config.templates {
+                        let mut line = format!("{name}: {}", alias.source);
+                        if let Some(ref directory) = alias.directory {
+                            line.push_str(&format!(" (directory `{directory}`)"));
+                        }
+                        match alias.git_ref() {
+                            Some(GitRef::Rev(rev)) => line.push_str(&format!(" (rev `{rev}`)")),
+                            Some(git_ref) => line.push_str(&format!(" ({git_ref})")),
+                            None => (),
+                        }
+                        println!("{line}");
+                    }
+                }
+                AliasCommand::Remove { name } => {
+                    if config.templates.remove(&name).is_none() {
+                        bail!("There is no `{name}` alias in {}", path.display());
+                    }
+                    config.save(&path)?;
+                    terminal::success(&format!("Removed the `{name}` alias\n"));
+                }
+            }
+        }
+        Some(Command::Cache { command: CacheCommand::Clear }) => {
+            let Some(dir) = cache::default_dir() else {
+                bail!(
+                    "Could not find the cache directory, set {} to choose one",
+                    CACHE_DIR_ENV_VAR
+                );
+            };
+            cache::clear(&dir)?;
+            terminal::success(&format!("Cleared the cache in {}\n", dir.display()));
+        }
+        None => {
+            if report::is_collecting() && !cli.no_input && cli.input_file.is_none() {
+                bail!("--format json can't ask questions: use --no-input or --input-file");
+            }
+            if cli.input_file.as_deref().is_some_and(is_stdin) && io::stdin().is_terminal() {
+                bail!(
+                    "`--input-file -` reads the answers from stdin, which can't be used \
+                    interactively at the same time: pipe the answers instead"
+                );
+            }
+            let git_ref = match (&cli.branch, &cli.tag, &cli.rev) {
+                (Some(branch), _, _) => Some(GitRef::Branch(branch.clone())),
+                (_, Some(tag), _) => Some(GitRef::Tag(tag.clone())),
+                (_, _, Some(rev)) => Some(GitRef::Rev(rev.clone())),
+                _ => None,
+            };
+            let has_ref = git_ref.is_some();
+            let cache_dir = cache::default_dir();
+            if cli.offline && cache_dir.is_none() {
+                bail!(
+                    "Could not find the cache directory, set {} to choose one",
+                    CACHE_DIR_ENV_VAR
+                );
+            }
+            let options = CloneOptions {
+                git_ref,
+                full_clone: cli.full_clone,
+                cache_dir,
+                offline: cli.offline,
+                refresh: cli.refresh,
+            };
+            let interactive = !cli.no_input && cli.input_file.is_none() && is_interactive();
+            let show_progress = !cli.quiet && !report::is_collecting();
+            let mut template = load_template(
+                cli.template.as_deref().unwrap(),
+                cli.directory.as_deref(),
+                cli.remote,
+                &options,
+                interactive.then(|| cli.prompter()),
+                show_progress,
+            )?;
+            if has_ref && template.commit().is_none() {
+                bail!("--branch, --tag and --rev can only be used with templates from git repositories");
+            }
+            select_addons(&mut template, &cli)?;
+            check_hook_filters(&template, &cli)?;
+            if let Some(ref os) = cli.hook_os {
+                template.set_hook_os(os);
+            }
+            template.set_atomic(!cli.no_atomic);
+            if let Some(jobs) = cli.jobs {
+                template.set_jobs(jobs.get());
+            }
+            template.set_excludes(&cli.exclude)?;
+            template.set_only(&cli.only)?;
+            if show_progress {
+                template.set_progress(|p| {
+                    terminal::progress(&format!(
+                        "Writing {}/{} files: {}",
+                        p.current,
+                        p.total,
+                        p.path.display()
+                    ))
+                });
+            }
+            template.set_deterministic(cli.deterministic);
+            // Regenerating some files is not a new project, nor is an archive
+            let hooks_allowed = (cli.run_hooks || cli.only.is_empty()) && cli.archive.is_none();
+            let runs_hooks = hooks_allowed && !cli.dry_run && cli.show.is_none();
+            // Asked once for everything the template runs
+            let trusted = confirm_commands(&template, &cli, runs_hooks)?;
+            run_choices_cmds(&mut template, trusted)?;
+            let hooks_allowed = hooks_allowed && trusted;
 
-            // 1. ask questions
-            let vals = ask_questions(&template, cli.no_input)?;
+            // Defines take precedence over the environment
+            let mut defines = parse_env_overrides(&template)?;
+            defines.extend(parse_defines(&template, &cli.defines)?);
+            // The prompts check the answers, not what comes from elsewhere
+            template.check_values(&defines)?;
+            // The first question that isn't answered, or the confirmation, would fail so don't
+            // run anything
+            if !cli.no_input && cli.input_file.is_none() {
+                let mut questions = template.questions().with_answers(defines.clone());
+                if questions.next_question()?.is_some() || !cli.yes {
+                    ensure_interactive()?;
+                }
+            }
+
+            // 1. run pre-prompt hooks, which can give defaults to the questions
+            let mut hook_summary = HookSummary::default();
+            if hooks_allowed && !cli.dry_run && cli.show.is_none() {
+                hook_summary.extend(run_pre_prompt_hooks(&mut template, &cli)?);
+            }
+
+            // 2. ask questions
+            let vals = if let Some(ref path) = cli.input_file {
+                load_values_from_file(&template, path, cli.input_format, &defines)?
+            } else {
+                loop {
+                    let vals = ask_questions(&template, cli.prompter(), cli.no_input, &defines)?;
+                    // The values given for the choices of a `choices_from` could only be
+                    // checked once the variable they depend on was answered
+                    template.check_values(&vals)?;
+                    if cli.no_input || cli.yes {
+                        break vals;
+                    }
+                    print_answers(&template, &vals);
+                    if cli.prompter().ask_bool("Proceed with generation?", true)? {
+                        break vals;
+                    }
+                    if !cli.prompter().ask_bool("Answer the questions again?", true)? {
+                        report::bold("Aborted, nothing was generated.\n");
+                        return Ok(());
+                    }
+                }
+            };
+            // Saved before anything can fail so the answers are not lost, a dry run only reports it
+            if let Some(path) = cli.save_answers.as_ref().filter(|_| !cli.dry_run) {
+                save_answers(&template, path, &vals)?;
+            }
             template.set_variables(vals)?;
+            let output_dir = match cli.output_dir {
+                Some(ref dir) => dir.clone(),
+                // In the current directory, unless the template says otherwise
+                None => {
+                    template.default_output_dir()?.map_or_else(|| PathBuf::from("."), PathBuf::from)
+                }
+            };
 
-            // 2. run pre-gen hooks
-            let pre_gen_hooks = template.get_pre_gen_hooks()?;
-            if cli.run_hooks && !pre_gen_hooks.is_empty() {
-                terminal::bold("Running pre-gen hooks...\n");
-                for hook in &pre_gen_hooks {
-                    execute_hook(hook, &cli.output_dir)?;
+            report::update(|r| {
+                r.source = Some(template.source().to_string());
+                r.commit = template.commit().map(|c| c.to_string());
+                r.output_dir = cli.archive.is_none().then(|| output_dir.clone());
+                r.variables = template
+                    .variables()
+                    .iter()
+                    .filter(|(name, _)| {
+                        !template.definition.variables.iter().any(|v| &v.name == *name && v.secret)
+                    })
+                    .map(|(name, val)| (name.clone(), val.clone()))
+                    .collect();
+            });
+
+            if let Some(ref path) = cli.show {
+                let files = template.render_to_map()?;
+                let Some(file) = files.get(path) else {
+                    bail!("`{}` is not generated with these answers", path.display());
+                };
+                if file.is_dir() {
+                    bail!("`{}` is a directory", path.display());
                 }
-                // For spacing
-                println!();
+                io::stdout().write_all(&file.bytes()?)?;
+                return Ok(());
+            }
+            if cli.dry_run {
+                let (entries, errors) = template.dry_run(&output_dir)?;
+                let mut pre_gen_hooks = template.get_pre_gen_hooks()?;
+                pre_gen_hooks.retain(|h| !is_filtered_out(h.name(), &cli));
+                let mut post_gen_hooks = template.get_post_gen_hooks()?;
+                post_gen_hooks.retain(|h| !is_filtered_out(h.name(), &cli));
+                if !cli.run_hooks && !cli.only.is_empty() {
+                    pre_gen_hooks.clear();
+                    post_gen_hooks.clear();
+                }
+                if !pre_gen_hooks.is_empty() {
+                    report::warning(
+                        "The pre-gen hooks are not run in a dry run, the `extra` values they can \
+                        give are not available\n",
+                    );
+                }
+                if report::is_collecting() {
+                    // What generating would write, which fails anyway if some files don't render
+                    let mut files = Vec::new();
+                    if errors.is_empty() {
+                        for (path, file) in template.render_to_map()? {
+                            if !file.is_dir() {
+                                files.push(GeneratedFile::new(path, &file)?);
+                            }
+                        }
+                    }
+                    report::update(|r| {
+                        r.dry_run = true;
+                        r.files = files;
+                    });
+                } else {
+                    print_dry_run(&entries, &pre_gen_hooks, &post_gen_hooks, cli.verbose > 0);
+                    if let Some(ref path) = cli.save_answers {
+                        terminal::bold(&format!("Would save the answers to {}\n", path.display()));
+                    }
+                }
+                if !errors.is_empty() {
+                    bail!(
+                        "{} file(s) would fail to render:\n{}",
+                        errors.len(),
+                        errors.iter().map(|e| format!("- {e}")).collect::<Vec<_>>().join("\n")
+                    );
+                }
+                return Ok(());
+            }
+
+            if let Some(ref archive) = cli.archive {
+                if archive.exists() && !cli.force {
+                    bail!("{} already exists, use --force to overwrite it", archive.display());
+                }
+                if template.definition.all_hooks().next().is_some() {
+                    report::warning("The hooks of the template are not run with --archive\n");
+                }
+            } else if !can_generate_in(&template, &output_dir, &cli, interactive)? {
+                report::bold("Aborted, nothing was generated.\n");
+                return Ok(());
             }
 
-            // 3. generate
-            template.generate(&cli.output_dir)?;
+            // 3. run pre-gen hooks
+            if hooks_allowed {
+                // The output directory is only filled once everything is generated, they are
+                // run in the directory the template will be rendered in instead
+                let dir = template.prepare_staging(&output_dir)?;
+                let hooks = template.get_pre_gen_hooks()?;
+                let definitions = hook_definitions(&template, |d| &d.pre_gen_hooks);
+                hook_summary.extend(run_hooks(
+                    &mut template,
+                    "pre-gen",
+                    &dir,
+                    &definitions,
+                    &hooks,
+                    &cli,
+                )?);
+            }
 
-            // 4. run post-gen hooks
-            let post_gen_hooks = template.get_post_gen_hooks()?;
-            if cli.run_hooks && !post_gen_hooks.is_empty() {
-                terminal::bold("Running post-gen hooks...\n");
-                for hook in &post_gen_hooks {
-                    execute_hook(hook, &cli.output_dir)?;
+            // 4. generate
+            // Existing files are asked about one by one unless a flag says what to do with them
+            if cli.force {
+                template.set_overwrite_policy(OverwritePolicy::Overwrite);
+            } else if cli.skip_existing {
+                template.set_overwrite_policy(OverwritePolicy::Skip);
+            }
+            let generation = if let Some(ref archive) = cli.archive {
+                template.generate_archive(archive)
+            } else if interactive {
+                template.generate_with(&output_dir, &mut |c| cli.prompter().ask_conflict(c))
+            } else {
+                template.generate(&output_dir)
+            };
+            if show_progress {
+                terminal::clear_progress();
+            }
+            let generation = generation?;
+            let _created = ListOnInterrupt::new(&generation.created);
+            let (overwritten, skipped) = (generation.overwritten.len(), generation.skipped.len());
+            if overwritten + skipped > 0 {
+                report::bold(&format!(
+                    "Overwrote {overwritten} existing file(s), skipped {skipped}\n"
+                ));
+            }
+
+            // 5. create the git repository, before the post-gen hooks so they can use it
+            let git_init = !cli.no_git_init
+                && cli.archive.is_none()
+                && (cli.git_init || template.definition.git_init);
+            if git_init {
+                match template.git_init(&output_dir) {
+                    Ok(GitInit::Committed(commit)) => {
+                        report::bold(&format!("Created a git repository, first commit {commit}\n"));
+                    }
+                    Ok(GitInit::Skipped(reason)) => {
+                        report::warning(&format!("Not creating a git repository: {reason}\n"));
+                    }
+                    Err(e) => report::warning(&format!("{e}\n")),
                 }
-                // For spacing
-                println!();
             }
 
-            terminal::success("\nEverything done, ready to go!\n");
+            // 6. run post-gen hooks
+            if hooks_allowed {
+                let hooks = template.get_post_gen_hooks()?;
+                let definitions = hook_definitions(&template, |d| &d.post_gen_hooks);
+                match run_hooks(&mut template, "post-gen", &output_dir, &definitions, &hooks, &cli)
+                {
+                    Ok(summary) => hook_summary.extend(summary),
+                    Err(e) => {
+                        clean_after_failure(&generation, &cli, interactive)?;
+                        return Err(e);
+                    }
+                }
+            }
+            hook_summary.report();
+
+            if let Some(ref path) = cli.manifest {
+                let manifest = serde_json::json!({
+                    "files": generation.files,
+                    "directories": generation
+                        .directories
+                        .iter()
+                        .map(|d| d.to_string_lossy().replace('\\', "/"))
+                        .collect::<Vec<_>>(),
+                });
+                fs::write(path, format!("{}\n", serde_json::to_string_pretty(&manifest)?))
+                    .with_context(|| {
+                        format!("Could not write the manifest to {}", path.display())
+                    })?;
+            }
+
+            report::bold(&format!("\n{}", summary(&generation)));
+            if let Some(commit) = template.commit() {
+                report::bold(&format!("\nGenerated from commit {commit}"));
+            }
+            report::update(|r| r.files = generation.files.clone());
+            if let Some(ref archive) = cli.archive {
+                report::update(|r| r.archive = Some(archive.clone()));
+                report::bold(&format!("\nWritten to {}", archive.display()));
+            } else {
+                let output_dir = output_dir.canonicalize().unwrap_or(output_dir);
+                report::update(|r| r.output_dir = Some(output_dir.clone()));
+                report::bold(&format!("\nGenerated in {}", output_dir.display()));
+            }
+            report::success("\nEverything done, ready to go!\n");
+
+            // The project is there already, a broken message shouldn't fail the run
+            match template.post_gen_message() {
+                Ok(Some(message)) => {
+                    report::success(&format!("\n{}\n", message.trim_end()));
+                    report::update(|r| r.post_gen_message = Some(message));
+                }
+                Ok(None) => (),
+                Err(e) => report::warning(&format!("Could not render the post_gen_message: {e}\n")),
+            }
         }
     }
 
@@ -162,7 +2360,24 @@ fn try_main() -> Result<()> {
 }
 
 fn main() {
-    if let Err(e) = try_main() {
+    let res = try_main();
+    // Probably because of the interruption
+    if res.is_err() && interrupt::is_interrupted() {
+        exit_interrupted();
+    }
+    let error = res.as_ref().err().map(|e| {
+        let kind = e.chain().find_map(|c| c.downcast_ref::<Error>()).map(|e| &e.kind);
+        ReportedError::new(kind, format!("{e:#}"))
+    });
+    if let Some(json) = report::finish(error) {
+        println!("{json}");
+        if res.is_err() {
+            ::std::process::exit(1)
+        }
+        return;
+    }
+
+    if let Err(e) = res {
         terminal::error(&format!("Error: {}", e));
         let mut cause = e.source();
         while let Some(e) = cause {
@@ -0,0 +1,213 @@
+//! Keeping a directory in sync with a template while it is being edited, rendering again only
+//! the files that changed, see `kickstart dev`. Hooks are never run.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::errors::{map_io_err, Error, Result};
+use crate::generation::copy_permissions;
+use crate::utils::create_directory;
+use crate::{RenderedFile, Template};
+
+/// What changed in the output directory of a [Preview]
+#[derive(Debug, Default)]
+pub struct PreviewUpdate {
+    /// The files written as they are new or their content changed, relative to the output
+    /// directory
+    pub written: Vec<PathBuf>,
+    /// The files and directories removed as the template doesn't generate them anymore
+    pub removed: Vec<PathBuf>,
+    /// The files that couldn't be rendered, whose previous version is kept
+    pub errors: Vec<Error>,
+}
+
+/// A directory rendered from a template, which can be updated with only the files whose
+/// source changed
+#[derive(Debug)]
+pub struct Preview {
+    output_dir: PathBuf,
+    /// The source in the template of everything written, by path in the output directory
+    sources: BTreeMap<PathBuf, PathBuf>,
+    /// What didn't exist before being written, by path in the output directory
+    created: BTreeSet<PathBuf>,
+}
+
+impl Preview {
+    /// A preview in `output_dir`, which is created if it doesn't exist. Nothing is written until
+    /// [Preview::render_all] is called.
+    pub fn new(output_dir: &Path) -> Result<Preview> {
+        create_directory(output_dir)?;
+        Ok(Preview {
+            output_dir: output_dir.to_path_buf(),
+            sources: BTreeMap::new(),
+            created: BTreeSet::new(),
+        })
+    }
+
+    pub fn output_dir(&self) -> &Path {
+        &self.output_dir
+    }
+
+    /// Render the whole template, eg the first time or once its template.toml changed, and
+    /// remove what it doesn't generate anymore
+    pub fn render_all(&mut self, template: &Template) -> Result<PreviewUpdate> {
+        self.render(template, None)
+    }
+
+    /// Render again the files coming from the `changed` paths of the template, relative to it,
+    /// eg after they were edited, created or deleted
+    pub fn render_changed(
+        &mut self,
+        template: &Template,
+        changed: &[PathBuf],
+    ) -> Result<PreviewUpdate> {
+        self.render(template, Some(changed))
+    }
+
+    /// Remove the files and directories written that didn't exist before, eg when stopping,
+    /// leaving what was already in the output directory. Directories that now have other files
+    /// are kept.
+    pub fn remove_created(&mut self) {
+        // The deepest paths first
+        for path in std::mem::take(&mut self.created).into_iter().rev() {
+            self.sources.remove(&path);
+            let out = self.output_dir.join(&path);
+            let _ = if out.is_dir() { fs::remove_dir(&out) } else { fs::remove_file(&out) };
+        }
+    }
+
+    fn render(
+        &mut self,
+        template: &Template,
+        changed: Option<&[PathBuf]>,
+    ) -> Result<PreviewUpdate> {
+        let mut update = PreviewUpdate::default();
+        let mut generated = BTreeSet::new();
+        for (path, source, file) in template.render_sources(changed)? {
+            let out = self.output_dir.join(&path);
+            if file.is_ok() && fs::symlink_metadata(&out).is_err() {
+                self.created.insert(path.clone());
+            }
+            match file {
+                Ok(RenderedFile::Directory) => create_directory(&out)?,
+                Ok(file) => {
+                    let bytes = file.bytes()?;
+                    if fs::read(&out).ok().as_deref() != Some(&bytes[..]) {
+                        map_io_err(fs::write(&out, &bytes), &out)?;
+                        copy_permissions(&template.path.join(&source), &out)?;
+                        update.written.push(path.clone());
+                    }
+                }
+                Err(e) => update.errors.push(e),
+            }
+            generated.insert(path.clone());
+            self.sources.insert(path, source);
+        }
+
+        // Only what comes from the changed files can have disappeared, the deepest paths first
+        let stale: Vec<_> = self
+            .sources
+            .iter()
+            .filter(|(path, source)| match changed {
+                Some(changed) => !generated.contains(*path) && changed.contains(source),
+                None => !generated.contains(*path),
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in stale.into_iter().rev() {
+            self.sources.remove(&path);
+            self.created.remove(&path);
+            let out = self.output_dir.join(&path);
+            let removed = if out.is_dir() { fs::remove_dir(&out) } else { fs::remove_file(&out) };
+            // Eg a directory that still has files not coming from the template
+            if removed.is_ok() {
+                update.removed.push(path);
+            }
+        }
+        Ok(update)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn only_renders_the_changed_files_again() {
+        let dir = tempdir().unwrap();
+        let template_dir = dir.path().join("template");
+        fs::create_dir_all(template_dir.join("docs")).unwrap();
+        fs::write(
+            template_dir.join("template.toml"),
+            r#"
+name = "Preview"
+kickstart_version = 1
+
+[[variables]]
+name = "name"
+default = "hello"
+prompt = "Name?"
+"#,
+        )
+        .unwrap();
+        fs::write(template_dir.join("README.md"), "# {{ name }}").unwrap();
+        fs::write(template_dir.join("docs").join("{{ name }}.md"), "Docs").unwrap();
+        let mut template = Template::from_local(&template_dir, None).unwrap();
+        template.set_variables(template.definition.default_values().unwrap()).unwrap();
+
+        let out = dir.path().join("out");
+        let mut preview = Preview::new(&out).unwrap();
+        let update = preview.render_all(&template).unwrap();
+        assert_eq!(update.written, [PathBuf::from("README.md"), PathBuf::from("docs/hello.md")]);
+        assert_eq!(fs::read_to_string(out.join("README.md")).unwrap(), "# hello");
+
+        // Broken, the previous version is kept
+        fs::write(template_dir.join("README.md"), "# {{ nope }}").unwrap();
+        let changed = [PathBuf::from("README.md")];
+        let update = preview.render_changed(&template, &changed).unwrap();
+        assert!(update.written.is_empty());
+        assert_eq!(update.errors.len(), 1);
+        assert_eq!(fs::read_to_string(out.join("README.md")).unwrap(), "# hello");
+
+        fs::write(template_dir.join("README.md"), "# {{ name }}!").unwrap();
+        let update = preview.render_changed(&template, &changed).unwrap();
+        assert_eq!(update.written, changed);
+        assert!(update.errors.is_empty());
+
+        fs::remove_file(template_dir.join("docs").join("{{ name }}.md")).unwrap();
+        let changed = [PathBuf::from("docs/{{ name }}.md")];
+        let update = preview.render_changed(&template, &changed).unwrap();
+        assert_eq!(update.removed, [PathBuf::from("docs/hello.md")]);
+        assert!(!out.join("docs").join("hello.md").exists());
+        assert!(out.join("docs").is_dir());
+    }
+
+    #[test]
+    fn only_removes_what_it_created() {
+        let dir = tempdir().unwrap();
+        let template_dir = dir.path().join("template");
+        fs::create_dir_all(template_dir.join("docs")).unwrap();
+        fs::write(
+            template_dir.join("template.toml"),
+            "name = \"Preview\"\nkickstart_version = 1\nvariables = []\n",
+        )
+        .unwrap();
+        fs::write(template_dir.join("README.md"), "Hello").unwrap();
+        fs::write(template_dir.join("docs").join("index.md"), "Docs").unwrap();
+        let template = Template::from_local(&template_dir, None).unwrap();
+
+        let out = dir.path().join("out");
+        fs::create_dir_all(&out).unwrap();
+        fs::write(out.join("notes.txt"), "Mine").unwrap();
+        fs::write(out.join("README.md"), "Old").unwrap();
+        let mut preview = Preview::new(&out).unwrap();
+        preview.render_all(&template).unwrap();
+        assert_eq!(fs::read_to_string(out.join("docs").join("index.md")).unwrap(), "Docs");
+
+        preview.remove_created();
+        assert!(!out.join("docs").exists());
+        assert_eq!(fs::read_to_string(out.join("notes.txt")).unwrap(), "Mine");
+        assert!(out.join("README.md").exists());
+    }
+}
@@ -0,0 +1,292 @@
+//! The questions of a template, to collect the answers in any UI rather than with the prompts
+//! of the CLI.
+use std::collections::HashMap;
+
+use crate::errors::{new_error, ErrorKind, Result};
+use crate::{Choice, Template, Value, Variable};
+
+/// A question about a variable, with its prompt and default rendered with the answers given so
+/// far. See [Template::questions].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Question<'a> {
+    /// The name of the variable
+    pub name: &'a str,
+    /// The prompt rendered with the answers so far, or as written if it couldn't be rendered
+    pub prompt: String,
+    /// Why the prompt couldn't be rendered, if it couldn't
+    pub prompt_error: Option<String>,
+    /// The default rendered with the answers so far, a list of choices for multi choices
+    pub default: Value,
+    /// The values to pick from, if any, the ones for the answers so far with `choices_from`
+    pub choices: Option<&'a [Choice]>,
+    /// The regex or built-in rule string answers need to pass, if any
+    pub validation: Option<&'a str>,
+    /// The key of the group of the variable, if any, to show the title of the group before its
+    /// first question. See [crate::TemplateDefinition::groups]
+    pub group: Option<&'a str>,
+    /// The variable itself, for everything else, eg its `help` or whether it is a `secret`
+    pub variable: &'a Variable,
+}
+
+/// Asks the questions of a template one at a time, in the order of its variables.
+///
+/// ```
+/// use std::path::Path;
+/// use kickstart::{Template, Value};
+///
+/// let template = Template::from_local(Path::new("examples/super-basic"), None).unwrap();
+/// let mut questions = template.questions();
+/// while let Some(question) = questions.next_question().unwrap() {
+///     // Show `question.prompt` and read the answer in whatever UI
+///     let answer = match question.name {
+///         "directory_name" => Value::String("hello".to_string()),
+///         _ => question.default.clone(),
+///     };
+///     template.validate_answer(question.name, &answer).unwrap();
+///     questions.answer(answer);
+/// }
+/// let answers = questions.into_answers();
+/// assert_eq!(answers["directory_name"], Value::String("hello".to_string()));
+/// assert_eq!(answers["file_name"], Value::String("Howdy".to_string()));
+/// ```
+#[derive(Debug)]
+pub struct Questions<'a> {
+    template: &'a Template,
+    /// The index of the next variable to look at
+    next: usize,
+    /// The variable of the question returned last, until it is answered
+    pending: Option<usize>,
+    answers: HashMap<String, Value>,
+}
+
+impl<'a> Questions<'a> {
+    /// The next question to ask, `None` once all of them are answered.
+    /// The variables whose `only_if`, or the one of their group, is not met are skipped and the computed ones get their
+    /// value along the way, as do the ones already answered with [Questions::with_answers].
+    /// Until it is answered, the same question is returned.
+    pub fn next_question(&mut self) -> Result<Option<Question<'a>>> {
+        let variables = &self.template.definition.variables;
+        if let Some(index) = self.pending {
+            return self.question(&variables[index]).map(Some);
+        }
+        while let Some(var) = variables.get(self.next) {
+            self.next += 1;
+            if !self.template.should_ask_variable(&var.name, &self.answers)? {
+                // Given for nothing, eg from the lockfile of a previous generation
+                self.answers.remove(&var.name);
+                continue;
+            }
+            if var.computed.is_some() {
+                let value = self.template.get_default_for(&var.name, &self.answers)?;
+                self.answers.insert(var.name.clone(), value);
+                continue;
+            }
+            if self.answers.contains_key(&var.name) {
+                continue;
+            }
+            self.pending = Some(self.next - 1);
+            return self.question(var).map(Some);
+        }
+        Ok(None)
+    }
+
+    fn question(&self, var: &'a Variable) -> Result<Question<'a>> {
+        let default = self.template.get_default_for(&var.name, &self.answers)?;
+        let (prompt, prompt_error) = match self.template.get_prompt_for(&var.name, &self.answers) {
+            Ok(prompt) => (prompt, None),
+            Err(err) => (var.prompt.clone(), Some(err.to_string())),
+        };
+        Ok(Question {
+            name: &var.name,
+            prompt,
+            prompt_error,
+            default,
+            choices: var.choices_for(&self.answers),
+            validation: var.validation.as_deref(),
+            group: var.group.as_deref(),
+            variable: var,
+        })
+    }
+
+    /// Answer the question returned last by [Questions::next_question].
+    /// The value is used as is, see [Template::validate_answer] to check it first.
+    ///
+    /// # Panics
+    /// If there is no question waiting for an answer.
+    pub fn answer(&mut self, value: Value) {
+        let index = self.pending.take().expect("no question to answer");
+        let name = self.template.definition.variables[index].name.clone();
+        self.answers.insert(name, value);
+    }
+
+    /// Answer some questions before asking anything, eg with values given on the command
+    /// line. They are skipped if they are asked, and dropped if their `only_if` is not met.
+    /// The values are used as is, as for [Questions::answer].
+    pub fn with_answers(mut self, answers: HashMap<String, Value>) -> Self {
+        self.answers.extend(answers);
+        self
+    }
+
+    /// The answers so far, including the values of the computed variables
+    pub fn answers(&self) -> &HashMap<String, Value> {
+        &self.answers
+    }
+
+    /// The answers, to give to [Template::set_variables] once all the questions are answered
+    pub fn into_answers(self) -> HashMap<String, Value> {
+        self.answers
+    }
+}
+
+impl Template {
+    /// The questions to ask to get the values of the variables, see [Questions]
+    pub fn questions(&self) -> Questions<'_> {
+        Questions { template: self, next: 0, pending: None, answers: HashMap::new() }
+    }
+
+    /// Check an answer to a question against the type and constraints of its variable, as the
+    /// prompts of the CLI do: its `choices`, `validation`, length and range, see
+    /// [Variable::check_value]. The choices of a `choices_from` depend on the other answers so
+    /// they are only checked by [Template::check_values].
+    /// Errors with [ErrorKind::InvalidVariableValue] if it is not of the type of the variable
+    /// and [ErrorKind::ConstraintViolations] if it doesn't pass its constraints.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use kickstart::{Template, Value};
+    ///
+    /// let template = Template::from_local(Path::new("examples/super-basic"), None).unwrap();
+    /// assert!(template.validate_answer("file_name", &Value::String("hi".to_string())).is_ok());
+    /// assert!(template.validate_answer("file_name", &Value::Integer(1)).is_err());
+    /// ```
+    pub fn validate_answer(&self, name: &str, value: &Value) -> Result<()> {
+        let var = self.get_variable_by_name(name)?;
+        if var.computed.is_some() {
+            return Err(new_error(ErrorKind::ComputedVariable(name.to_string())));
+        }
+        // `2` is a fine value for a float variable
+        let same_type = value.type_str() == var.raw_default().type_str()
+            || matches!((value, var.raw_default()), (Value::Integer(..), Value::Float(..)));
+        if !same_type {
+            return Err(new_error(ErrorKind::InvalidVariableValue {
+                name: name.to_string(),
+                value: value.to_string(),
+                expected: var.raw_default().type_str(),
+            }));
+        }
+        var.check_value(value).map_err(|reason| {
+            new_error(ErrorKind::ConstraintViolations(vec![(name.to_string(), reason)]))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn can_ask_the_questions_one_at_a_time() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            r#"
+name = "Questions"
+kickstart_version = 1
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+
+[[variables]]
+name = "crate_name"
+computed = "{{ project_name | snake_case }}"
+
+[[variables]]
+name = "database"
+default = "postgres"
+prompt = "Which database should {{ crate_name }} use?"
+choices = ["postgres", "sqlite"]
+
+[[variables]]
+name = "pool_size"
+default = 5
+prompt = "Pool size?"
+only_if = { name = "database", value = "postgres" }
+
+[[variables]]
+name = "description"
+default = "{{ crate_name }} with {{ database }}"
+prompt = "Description?"
+"#,
+        )
+        .unwrap();
+        let template = Template::from_local(dir.path(), None).unwrap();
+        let mut questions = template.questions();
+
+        let question = questions.next_question().unwrap().unwrap();
+        assert_eq!(question.name, "project_name");
+        assert_eq!(question.default, Value::String("hello".to_string()));
+        // Until it is answered
+        assert_eq!(questions.next_question().unwrap().unwrap().name, "project_name");
+        questions.answer(Value::String("My App".to_string()));
+
+        let question = questions.next_question().unwrap().unwrap();
+        assert_eq!(question.name, "database");
+        assert_eq!(question.prompt, "Which database should my_app use?");
+        assert_eq!(question.choices.map(|c| c.len()), Some(2));
+        questions.answer(Value::String("sqlite".to_string()));
+
+        let question = questions.next_question().unwrap().unwrap();
+        assert_eq!(question.name, "description");
+        assert_eq!(question.default, Value::String("my_app with sqlite".to_string()));
+        questions.answer(question.default.clone());
+        assert!(questions.next_question().unwrap().is_none());
+        assert_eq!(questions.answers().len(), 4);
+        assert_eq!(questions.answers()["crate_name"], Value::String("my_app".to_string()));
+
+        let mut questions = template.questions().with_answers(HashMap::from([
+            ("project_name".to_string(), Value::String("given".to_string())),
+            ("pool_size".to_string(), Value::Integer(1)),
+        ]));
+        assert_eq!(questions.next_question().unwrap().unwrap().name, "database");
+        questions.answer(Value::String("sqlite".to_string()));
+        questions.next_question().unwrap();
+        assert!(!questions.answers().contains_key("pool_size"));
+    }
+
+    #[test]
+    fn can_validate_an_answer() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            r#"
+name = "Answers"
+kickstart_version = 1
+
+[[variables]]
+name = "ratio"
+default = 0.5
+prompt = "Ratio?"
+
+[[variables]]
+name = "workers"
+default = 4
+prompt = "Workers?"
+max = 8
+"#,
+        )
+        .unwrap();
+        let template = Template::from_local(dir.path(), None).unwrap();
+
+        assert!(template.validate_answer("ratio", &Value::Integer(1)).is_ok());
+        let err = template.validate_answer("workers", &Value::Integer(9)).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::ConstraintViolations(ref v) if v[0].0 == "workers"));
+        let err = template.validate_answer("workers", &Value::Boolean(true)).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::InvalidVariableValue { .. }));
+        let err = template.validate_answer("nope", &Value::Boolean(true)).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::InvalidVariableName(..)));
+    }
+}
@@ -0,0 +1,260 @@
+//! The JSON Schema of the input files of a template, so the answers can be checked before
+//! running kickstart, eg by an editor.
+use serde_json::{json, Map, Value as JsonValue};
+
+use crate::definition::{TemplateDefinition, Variable};
+use crate::validation::Validation;
+use crate::{Choice, Value};
+
+/// The version of JSON Schema the schemas are written in
+pub const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// The JSON Schema type of a value
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::String(..) => "string",
+        Value::Integer(..) => "integer",
+        Value::Float(..) => "number",
+        Value::Boolean(..) => "boolean",
+        Value::List(..) => "array",
+    }
+}
+
+/// Whether the default is rendered with the other answers rather than used as is
+fn is_templated(value: &Value) -> bool {
+    matches!(value, Value::String(s) if s.contains("{{") || s.contains("{%"))
+}
+
+/// The values a variable accepts when it has choices: an `enum`, or a `oneOf` to keep the
+/// labels and descriptions if there are any
+fn choices_schema(choices: &[Choice]) -> Map<String, JsonValue> {
+    let mut schema = Map::new();
+    if choices.iter().all(|c| matches!(c, Choice::Value(..))) {
+        schema.insert("enum".to_string(), choices.iter().map(|c| json!(c.value())).collect());
+        return schema;
+    }
+    let one_of = choices
+        .iter()
+        .map(|choice| {
+            let mut option = Map::new();
+            option.insert("const".to_string(), json!(choice.value()));
+            if let Choice::Table { label: Some(label), .. } = choice {
+                option.insert("title".to_string(), json!(label));
+            }
+            if let Choice::Table { description: Some(description), .. } = choice {
+                option.insert("description".to_string(), json!(description));
+            }
+            JsonValue::Object(option)
+        })
+        .collect();
+    schema.insert("oneOf".to_string(), one_of);
+    schema
+}
+
+/// The regex of the `validation` of a variable, if it is one JSON Schema can check
+fn validation_pattern(var: &Variable) -> Option<String> {
+    match var.validation.as_deref().map(Validation::parse) {
+        Some(Ok(Validation::Rule(rule))) => rule.pattern().map(|p| p.to_string()),
+        Some(Ok(Validation::Regex(re))) => Some(re.as_str().to_string()),
+        _ => None,
+    }
+}
+
+fn variable_schema(definition: &TemplateDefinition, var: &Variable) -> JsonValue {
+    let mut schema = Map::new();
+    let default = var.raw_default();
+    schema.insert("type".to_string(), json!(json_type(default)));
+
+    let mut description = var.prompt.clone();
+    if let Some(ref help) = var.help {
+        description.push_str(&format!("\n\n{help}"));
+    }
+    for cond in definition.variable_conditions(var) {
+        description.push_str(&format!("\n\nOnly used if {cond}."));
+    }
+    if let Some(ref from) = var.choices_from {
+        description.push_str(&format!("\n\nThe choices depend on `{}`.", from.name));
+    }
+    schema.insert("description".to_string(), json!(description));
+    if !is_templated(default) && !var.secret {
+        schema.insert("default".to_string(), json!(default));
+    }
+    if var.secret {
+        schema.insert("writeOnly".to_string(), json!(true));
+    }
+
+    match (&var.choices, var.multi) {
+        (Some(choices), true) => {
+            schema.insert("items".to_string(), JsonValue::Object(choices_schema(choices)));
+            schema.insert("uniqueItems".to_string(), json!(true));
+        }
+        (Some(choices), false) => schema.extend(choices_schema(choices)),
+        (None, _) => (),
+    }
+    // Which of them are allowed depends on the other answer, which the schema doesn't express
+    if let Some(ref from) = var.choices_from {
+        let mut choices: Vec<Choice> = Vec::new();
+        for choice in from.choices.values().flatten() {
+            if !choices.iter().any(|c| c.value() == choice.value()) {
+                choices.push(choice.clone());
+            }
+        }
+        schema.extend(choices_schema(&choices));
+    }
+
+    if var.list {
+        let mut items = Map::new();
+        items.insert("type".to_string(), json!("string"));
+        if let Some(pattern) = validation_pattern(var) {
+            items.insert("pattern".to_string(), json!(pattern));
+        }
+        schema.insert("items".to_string(), JsonValue::Object(items));
+        if let Some(min_items) = var.min_items {
+            schema.insert("minItems".to_string(), json!(min_items));
+        }
+        if let Some(max_items) = var.max_items {
+            schema.insert("maxItems".to_string(), json!(max_items));
+        }
+    }
+    if default.is_str() {
+        let pattern = validation_pattern(var);
+        // Optional variables can be empty whatever their validation says
+        match pattern {
+            Some(pattern) if var.required => {
+                schema.insert("pattern".to_string(), json!(pattern));
+            }
+            Some(pattern) => {
+                schema.insert("pattern".to_string(), json!(format!("^$|{pattern}")));
+            }
+            None => (),
+        }
+        if let Some(min_length) = var.min_length.filter(|_| var.required) {
+            schema.insert("minLength".to_string(), json!(min_length));
+        }
+        if let Some(max_length) = var.max_length {
+            schema.insert("maxLength".to_string(), json!(max_length));
+        }
+    }
+    if let Some(min) = var.min {
+        schema.insert("minimum".to_string(), json!(min));
+    }
+    if let Some(max) = var.max {
+        schema.insert("maximum".to_string(), json!(max));
+    }
+    JsonValue::Object(schema)
+}
+
+impl TemplateDefinition {
+    /// A JSON Schema, draft 2020-12, of the input files of the template: a property for every
+    /// variable that can be given a value, ie not the computed ones.
+    /// The variables with an `only_if`, or in a group with one, are never required as whether they are asked depends on
+    /// the other answers, which the schema doesn't express: their description says when they
+    /// are used instead.
+    pub fn json_schema(&self) -> JsonValue {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        for var in self.variables.iter().filter(|v| v.computed.is_none()) {
+            properties.insert(var.name.clone(), variable_schema(self, var));
+            if var.required && self.variable_conditions(var).next().is_none() {
+                required.push(var.name.clone());
+            }
+        }
+
+        let mut schema = Map::new();
+        schema.insert("$schema".to_string(), json!(JSON_SCHEMA_DIALECT));
+        schema.insert("title".to_string(), json!(self.name));
+        if let Some(ref description) = self.description {
+            schema.insert("description".to_string(), json!(description));
+        }
+        schema.insert("type".to_string(), json!("object"));
+        schema.insert("properties".to_string(), JsonValue::Object(properties));
+        schema.insert("required".to_string(), json!(required));
+        JsonValue::Object(schema)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_describe_the_input_file() {
+        let definition: TemplateDefinition = toml::from_str(
+            r#"
+name = "Schema"
+kickstart_version = 1
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+help = "The name of the crate"
+validation = "identifier"
+max_length = 20
+
+[[variables]]
+name = "crate_name"
+computed = "{{ project_name | snake_case }}"
+
+[[variables]]
+name = "license"
+default = "MIT"
+prompt = "License?"
+choices = ["MIT", { value = "Apache-2.0", label = "Apache" }]
+
+[[variables]]
+name = "features"
+default = ["cli"]
+prompt = "Features?"
+choices = ["cli", "serde"]
+multi = true
+
+[[variables]]
+name = "workers"
+default = 4
+prompt = "Workers?"
+min = 1
+max = 16
+only_if = { name = "features", value_in = ["cli"] }
+
+[[variables]]
+name = "homepage"
+default = "{{ project_name }}.example.com"
+prompt = "Homepage?"
+required = false
+validation = "^[a-z.]+$"
+"#,
+        )
+        .unwrap();
+
+        let schema = definition.json_schema();
+        assert_eq!(schema["$schema"], JSON_SCHEMA_DIALECT);
+        assert_eq!(schema["required"], json!(["project_name", "license", "features"]));
+        let properties = &schema["properties"];
+        assert!(properties.get("crate_name").is_none());
+        assert_eq!(
+            properties["project_name"],
+            json!({
+                "type": "string",
+                "description": "Name?\n\nThe name of the crate",
+                "default": "hello",
+                "pattern": "^[a-zA-Z][a-zA-Z0-9_-]*$",
+                "maxLength": 20,
+            })
+        );
+        assert_eq!(
+            properties["license"]["oneOf"],
+            json!([{ "const": "MIT" }, { "const": "Apache-2.0", "title": "Apache" }])
+        );
+        assert_eq!(properties["features"]["type"], "array");
+        assert_eq!(properties["features"]["items"], json!({ "enum": ["cli", "serde"] }));
+        assert_eq!(properties["workers"]["minimum"], 1);
+        assert_eq!(properties["workers"]["maximum"], 16);
+        assert!(properties["workers"]["description"]
+            .as_str()
+            .unwrap()
+            .ends_with("\n\nOnly used if `features` is one of cli."));
+        assert!(properties["homepage"].get("default").is_none());
+        assert_eq!(properties["homepage"]["pattern"], "^$|^[a-z.]+$");
+    }
+}
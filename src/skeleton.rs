@@ -0,0 +1,162 @@
+//! The skeleton of a new template, written by `kickstart init`: a commented template.toml with
+//! a variable of each kind, a file using them and example hooks, to start from.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::errors::{map_io_err, new_error, ErrorKind, Result};
+use crate::utils::{create_directory, write_file};
+
+const DEFINITION: &str = r#"# The name and description are shown when generating and by `kickstart info`
+name = {name}
+description = "A template made with `kickstart init`"
+# The version of the template.toml format, or the versions of kickstart it needs, eg ">=0.6"
+kickstart_version = 1
+
+# Hooks are scripts rendered with the variables like any other file, then ran: the pre-gen ones
+# after the questions, the post-gen ones in the generated project. They are not generated.
+pre_gen_hooks = [{ name = "welcome", path = "hooks/pre-gen.sh" }]
+post_gen_hooks = [{ name = "next steps", path = "hooks/post-gen.sh" }]
+
+# The variables are asked in order, their value being available in the files, their names and the
+# hooks as `{{ name }}`. The type of a variable is the one of its default.
+
+# A string, which the answer needs to match the regex of its `validation` for
+[[variables]]
+name = "project_name"
+default = "my-project"
+prompt = "What is the name of the project?"
+validation = "^[a-z][a-z0-9-]*$"
+
+# A choice between some values
+[[variables]]
+name = "license"
+default = "MIT"
+prompt = "Which license should it use?"
+choices = ["MIT", "Apache-2.0", "GPL-3.0"]
+
+# A yes or no question
+[[variables]]
+name = "with_ci"
+default = true
+prompt = "Do you want to run the tests on every push?"
+
+# A number between `min` and `max`, only asked if the one above was answered with yes
+[[variables]]
+name = "ci_jobs"
+default = 2
+prompt = "How many jobs should run them?"
+min = 1
+max = 8
+only_if = { name = "with_ci", value = true }
+"#;
+
+const README: &str = "# {{ project_name }}
+
+Licensed under {{ license }}.
+{% if with_ci %}
+The tests run on every push, in {{ ci_jobs }} jobs.
+{% endif %}";
+
+const PRE_GEN_HOOK: &str = r#"#!/bin/sh
+echo "Generating {{ project_name }}"
+"#;
+
+const POST_GEN_HOOK: &str = r#"#!/bin/sh
+echo "{{ project_name }} is ready in $(pwd)"
+"#;
+
+const HOOKS: [&str; 2] = ["hooks/pre-gen.sh", "hooks/post-gen.sh"];
+
+/// Write the skeleton of a template in `dir`, created if needed, named after the directory.
+/// Errors with [ErrorKind::ExistingFiles] if one of its files already exists, unless `force` is
+/// set, in which case they are overwritten. Returns the paths written, relative to `dir`.
+pub fn create_skeleton(dir: &Path, force: bool) -> Result<Vec<PathBuf>> {
+    let paths = ["template.toml", "README.md", HOOKS[0], HOOKS[1]];
+    let existing: Vec<_> =
+        paths.iter().map(|path| dir.join(path)).filter(|path| path.exists()).collect();
+    if !existing.is_empty() && !force {
+        return Err(new_error(ErrorKind::ExistingFiles(existing)));
+    }
+
+    create_directory(dir)?;
+    let name = dir
+        .canonicalize()
+        .ok()
+        .and_then(|d| Some(d.file_name()?.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "My template".to_string());
+    let definition = DEFINITION.replace("{name}", &toml::Value::String(name).to_string());
+    let files = [
+        (paths[0], definition.as_str()),
+        (paths[1], README),
+        (paths[2], PRE_GEN_HOOK),
+        (paths[3], POST_GEN_HOOK),
+    ];
+
+    let mut written = Vec::new();
+    for (path, content) in files {
+        let full_path = dir.join(path);
+        create_directory(full_path.parent().unwrap())?;
+        write_file(&full_path, content)?;
+        written.push(PathBuf::from(path));
+    }
+    // They are made executable when rendered anyway, this is to run them by hand
+    #[cfg(unix)]
+    for hook in HOOKS {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join(hook);
+        map_io_err(fs::set_permissions(&path, fs::Permissions::from_mode(0o755)), &path)?;
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Template, TemplateDefinition};
+    use tempfile::tempdir;
+
+    #[test]
+    fn skeleton_is_a_valid_template() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("my-template");
+        let written = create_skeleton(&path, false).unwrap();
+        assert_eq!(written.len(), 4);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(path.join(HOOKS[0])).unwrap().permissions().mode();
+            assert_eq!(mode & 0o111, 0o111);
+        }
+
+        let errors = TemplateDefinition::validate_file(path.join("template.toml")).unwrap();
+        assert!(errors.is_empty(), "{errors:?}");
+        let mut template = Template::from_local(&path, None).unwrap();
+        assert_eq!(template.definition.name, "my-template");
+        template.set_variables(template.definition.default_values().unwrap()).unwrap();
+        assert!(template.render_errors().is_empty());
+        let usage = template.variable_usage().unwrap();
+        assert!(usage.unused.is_empty() && usage.undeclared.is_empty(), "{usage:?}");
+
+        let out = dir.path().join("out");
+        let generation = template.generate(&out).unwrap();
+        let paths: Vec<_> = generation.files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(paths, [PathBuf::from("README.md")]);
+        assert!(fs::read_to_string(out.join("README.md")).unwrap().contains("in 2 jobs"));
+    }
+
+    #[test]
+    fn skeleton_does_not_overwrite_files_unless_forced() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("README.md"), "My notes").unwrap();
+
+        let err = create_skeleton(dir.path(), false).unwrap_err();
+        assert!(
+            matches!(err.kind, ErrorKind::ExistingFiles(ref p) if *p == [dir.path().join("README.md")])
+        );
+        assert!(!dir.path().join("template.toml").exists());
+        assert_eq!(fs::read_to_string(dir.path().join("README.md")).unwrap(), "My notes");
+
+        create_skeleton(dir.path(), true).unwrap();
+        assert!(fs::read_to_string(dir.path().join("README.md")).unwrap().contains("Licensed"));
+    }
+}
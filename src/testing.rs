@@ -0,0 +1,299 @@
+//! Test cases written by the authors of a template, see `kickstart test`: answers to render the
+//! template with and what the project should then contain. Nothing is written and hooks are
+//! never run.
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::errors::{map_io_err, new_error, Error, ErrorKind, Result};
+use crate::utils::{edit_distance, read_file};
+use crate::{Template, Value};
+
+/// The directory next to the template.toml where the test cases are, unless the template sets
+/// its `tests_dir`
+pub const DEFAULT_TESTS_DIR: &str = "kickstart-tests";
+
+/// A test case, a `.toml` or `.json` file of the `tests_dir` of a template.
+/// Paths are relative to the generated project, as rendered.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestCase {
+    /// The name of the file, without its extension
+    #[serde(skip)]
+    pub name: String,
+    /// The answers to the questions, the default being used for the ones not given
+    #[serde(default)]
+    pub answers: HashMap<String, Value>,
+    /// Files or directories that have to be generated
+    #[serde(default)]
+    pub expect_files: Vec<String>,
+    /// Files or directories that must not be generated, eg `docker/`
+    #[serde(default)]
+    pub expect_absent: Vec<String>,
+    /// Text each of these files has to contain
+    #[serde(default)]
+    pub expect_contains: BTreeMap<String, Vec<String>>,
+    /// Text none of these files can contain
+    #[serde(default)]
+    pub expect_not_contains: BTreeMap<String, Vec<String>>,
+}
+
+/// Why a [TestCase] failed
+#[derive(Debug)]
+pub enum Failure {
+    /// The answers are not valid for the template or it doesn't render with them
+    Generation(Error),
+    MissingFile(PathBuf),
+    UnexpectedFile(PathBuf),
+    MissingContent {
+        path: PathBuf,
+        expected: String,
+        /// The lines of the file most similar to what was expected, if it has any, to show how
+        /// they differ
+        closest: Option<String>,
+    },
+    UnexpectedContent {
+        path: PathBuf,
+        content: String,
+    },
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Generation(err) => write!(f, "{err}"),
+            Failure::MissingFile(path) => write!(f, "`{}` is not generated", path.display()),
+            Failure::UnexpectedFile(path) => {
+                write!(f, "`{}` is generated but it shouldn't be", path.display())
+            }
+            Failure::MissingContent { path, expected, .. } => {
+                write!(f, "`{}` doesn't contain {expected:?}", path.display())
+            }
+            Failure::UnexpectedContent { path, content } => {
+                write!(f, "`{}` contains {content:?}", path.display())
+            }
+        }
+    }
+}
+
+/// The `size` consecutive lines of `content` closest to `expected`
+fn closest_lines(content: &str, expected: &str) -> Option<String> {
+    let lines: Vec<_> = content.lines().collect();
+    let size = expected.lines().count().clamp(1, lines.len().max(1));
+    lines
+        .windows(size)
+        .map(|window| window.join("\n"))
+        .min_by_key(|candidate| edit_distance(candidate, expected))
+}
+
+impl TestCase {
+    /// Render `template` with the answers of the case and check what it generates.
+    /// Its variables are replaced by the answers. Returns every failure, a file that is not
+    /// generated being reported once whatever is expected of it.
+    pub fn run(&self, template: &mut Template) -> Vec<Failure> {
+        let files = match self.render(template) {
+            Ok(files) => files,
+            Err(err) => return vec![Failure::Generation(err)],
+        };
+        let mut failures = Vec::new();
+
+        for path in &self.expect_files {
+            if !files.contains_key(Path::new(path)) {
+                failures.push(Failure::MissingFile(PathBuf::from(path)));
+            }
+        }
+        for path in &self.expect_absent {
+            if files.contains_key(Path::new(path)) {
+                failures.push(Failure::UnexpectedFile(PathBuf::from(path)));
+            }
+        }
+        let expectations = self
+            .expect_contains
+            .iter()
+            .map(|(path, texts)| (path, texts, true))
+            .chain(self.expect_not_contains.iter().map(|(path, texts)| (path, texts, false)));
+        for (path, texts, contains) in expectations {
+            let path = PathBuf::from(path);
+            let Some(content) = files.get(&path) else {
+                let reported = failures
+                    .iter()
+                    .any(|f| matches!(f, Failure::MissingFile(missing) if *missing == path));
+                if !reported {
+                    failures.push(Failure::MissingFile(path));
+                }
+                continue;
+            };
+            for text in texts {
+                if content.contains(text.as_str()) == contains {
+                    continue;
+                }
+                failures.push(if contains {
+                    Failure::MissingContent {
+                        path: path.clone(),
+                        expected: text.clone(),
+                        closest: closest_lines(content, text),
+                    }
+                } else {
+                    Failure::UnexpectedContent { path: path.clone(), content: text.clone() }
+                });
+            }
+        }
+        failures
+    }
+
+    /// Everything the template generates with the answers, the directories being empty
+    fn render(&self, template: &mut Template) -> Result<BTreeMap<PathBuf, String>> {
+        // `2` is a fine value for a float variable
+        let mut answers = HashMap::new();
+        for (name, value) in &self.answers {
+            template.validate_answer(name, value)?;
+            let value = match (value, template.get_variable_by_name(name)?.raw_default()) {
+                (Value::Integer(i), Value::Float(..)) => Value::Float(*i as f64),
+                _ => value.clone(),
+            };
+            answers.insert(name.clone(), value);
+        }
+
+        let mut questions = template.questions().with_answers(answers);
+        while let Some(question) = questions.next_question()? {
+            let default = question.default;
+            questions.answer(default);
+        }
+        let answers = questions.into_answers();
+        template.check_values(&answers)?;
+        template.set_variables(answers)?;
+
+        let mut files = BTreeMap::new();
+        for (path, file) in template.render_to_map()? {
+            let content = String::from_utf8_lossy(&file.bytes()?).into_owned();
+            files.insert(path, content);
+        }
+        Ok(files)
+    }
+}
+
+impl Template {
+    /// The test cases of the template, the `.toml` and `.json` files of its `tests_dir` next to
+    /// its template.toml, [DEFAULT_TESTS_DIR] by default, sorted by name. There are none if that
+    /// directory doesn't exist. It is never generated.
+    /// Errors with [ErrorKind::InvalidInputFile] if one of them can't be parsed.
+    pub fn test_cases(&self) -> Result<Vec<TestCase>> {
+        let dir = self.path.join(&self.definition.tests_dir);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut paths = Vec::new();
+        for entry in map_io_err(fs::read_dir(&dir), &dir)? {
+            let path = map_io_err(entry, &dir)?.path();
+            if path.is_file()
+                && matches!(path.extension().and_then(|e| e.to_str()), Some("toml" | "json"))
+            {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut cases = Vec::new();
+        for path in paths {
+            let content = read_file(&path)?;
+            let invalid = |format, reason: String| {
+                new_error(ErrorKind::InvalidInputFile { format, path: path.clone(), reason })
+            };
+            let mut case: TestCase = if path.extension().is_some_and(|e| e == "json") {
+                serde_json::from_str(&content).map_err(|e| invalid("JSON", e.to_string()))?
+            } else {
+                toml::from_str(&content).map_err(|e| invalid("TOML", e.to_string()))?
+            };
+            case.name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            cases.push(case);
+        }
+        Ok(cases)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn can_run_the_test_cases_of_a_template() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("docker")).unwrap();
+        fs::create_dir_all(dir.path().join(DEFAULT_TESTS_DIR)).unwrap();
+        fs::write(
+            dir.path().join("template.toml"),
+            r#"
+name = "Tested"
+kickstart_version = 1
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+
+[[variables]]
+name = "docker"
+default = false
+prompt = "Docker?"
+
+[[cleanup]]
+name = "docker"
+value = false
+paths = ["docker"]
+"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "name = \"{{ project_name }}\"\n").unwrap();
+        fs::write(dir.path().join("docker").join("Dockerfile"), "FROM rust").unwrap();
+        fs::write(
+            dir.path().join(DEFAULT_TESTS_DIR).join("docker.json"),
+            r#"{"answers": {"docker": true}, "expect_files": ["docker/Dockerfile"]}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join(DEFAULT_TESTS_DIR).join("default.toml"),
+            r#"
+expect_absent = ["docker/"]
+expect_contains = { "Cargo.toml" = ['name = "hi"'] }
+expect_not_contains = { "Cargo.toml" = ["hello"] }
+"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join(DEFAULT_TESTS_DIR).join("notes.md"), "Not a case").unwrap();
+        // Files of the project, not cases
+        fs::create_dir_all(dir.path().join("tests")).unwrap();
+        fs::write(dir.path().join("tests").join("fixtures.json"), "[1, 2]").unwrap();
+
+        let mut template = Template::from_local(dir.path(), None).unwrap();
+        let cases = template.test_cases().unwrap();
+        assert_eq!(
+            cases.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(),
+            ["default", "docker"]
+        );
+
+        let failures = cases[1].run(&mut template);
+        assert!(failures.is_empty(), "{failures:?}");
+        let failures = cases[0].run(&mut template);
+        assert_eq!(failures.len(), 2, "{failures:?}");
+        assert!(matches!(
+            failures[0],
+            Failure::MissingContent { ref closest, .. } if closest.as_deref() == Some("name = \"hello\"")
+        ));
+        assert!(matches!(failures[1], Failure::UnexpectedContent { .. }));
+
+        let case = TestCase {
+            answers: HashMap::from([("nope".to_string(), Value::Boolean(true))]),
+            ..cases[1].clone()
+        };
+        let failures = case.run(&mut template);
+        assert!(matches!(failures[..], [Failure::Generation(..)]));
+
+        let files = template.render_to_map().unwrap();
+        assert!(files.contains_key(Path::new("tests/fixtures.json")));
+        assert!(!files.keys().any(|p| p.starts_with(DEFAULT_TESTS_DIR)));
+    }
+}
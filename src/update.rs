@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tempfile::tempdir;
+
+use crate::errors::{map_io_err, Result};
+use crate::lockfile::Lockfile;
+use crate::utils::{create_directory, hash_content, hash_file};
+use crate::{Template, Value};
+
+/// What to do with files that were modified in the project since it was generated
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep the modified file as is
+    Skip,
+    /// Replace the modified file with the newly generated one
+    Overwrite,
+    /// Keep the modified file and write the newly generated one next to it with a `.new` extension
+    WriteNew,
+}
+
+/// What happened to the files of the project during an update
+#[derive(Debug, Default)]
+pub struct UpdateReport {
+    /// Files that were not in the project before
+    pub created: Vec<PathBuf>,
+    /// Files that were not modified since the last generation and have been updated
+    pub updated: Vec<PathBuf>,
+    /// Files modified since the last generation that have been handled per the [ConflictPolicy]
+    pub conflicts: Vec<PathBuf>,
+    /// Files the template doesn't generate anymore that were not modified, and have been
+    /// deleted
+    pub removed: Vec<PathBuf>,
+}
+
+/// The answers from the lockfile that are still valid for that template.
+/// Variables that are not in the template anymore or whose type changed are dropped so they
+/// can be asked again.
+pub fn previous_answers(template: &Template, lockfile: &Lockfile) -> HashMap<String, Value> {
+    template
+        .definition
+        .variables
+        .iter()
+        .filter_map(|var| {
+            lockfile
+                .variables
+                .get(&var.name)
+                .filter(|val| val.type_str() == var.default.type_str())
+                .map(|val| (var.name.clone(), val.clone()))
+        })
+        .collect()
+}
+
+/// Re-generate the template over an existing project.
+/// The template is first generated in a temporary directory and then merged in the project: files
+/// that changed in the project since they were generated, according to the checksums of the
+/// `previous` lockfile, are handled following the `policy`.
+/// `base` is the template as it was when the project was generated, at the commit of the
+/// lockfile and with its variables set. With it, what the template changed since is applied:
+/// modified files that the template didn't change are kept without being conflicts, and the
+/// files it doesn't generate anymore are deleted if they were not modified.
+/// Hooks are not run.
+pub fn update_project(
+    template: &Template,
+    project_dir: &Path,
+    previous: &Lockfile,
+    base: Option<&Template>,
+    policy: ConflictPolicy,
+) -> Result<UpdateReport> {
+    let staging = tempdir()?;
+    let files = template.generate(staging.path())?.files;
+    let base_files = base.map(|b| b.render_to_map()).transpose()?;
+    let mut report = UpdateReport::default();
+
+    if let Some(ref base_files) = base_files {
+        for path in base_files.iter().filter(|(_, f)| !f.is_dir()).map(|(p, _)| p) {
+            let key = path.to_string_lossy().replace('\\', "/");
+            let dest = project_dir.join(path);
+            if files.iter().any(|f| f.path == *path) || !dest.is_file() {
+                continue;
+            }
+            if previous.files.get(&key) == Some(&hash_file(&dest)?) {
+                map_io_err(fs::remove_file(&dest), &dest)?;
+                report.removed.push(path.clone());
+            }
+        }
+    }
+
+    for relative_path in files.into_iter().map(|f| f.path) {
+        let generated = staging.path().join(&relative_path);
+        let dest = project_dir.join(&relative_path);
+        if let Some(parent) = dest.parent() {
+            create_directory(parent)?;
+        }
+
+        if !dest.exists() {
+            map_io_err(fs::copy(&generated, &dest), &dest)?;
+            report.created.push(relative_path);
+            continue;
+        }
+
+        let current = map_io_err(fs::read(&dest), &dest)?;
+        let content = map_io_err(fs::read(&generated), &generated)?;
+        if current == content {
+            continue;
+        }
+
+        let key = relative_path.to_string_lossy().replace('\\', "/");
+        if previous.files.get(&key) == Some(&hash_content(&current)) {
+            map_io_err(fs::copy(&generated, &dest), &dest)?;
+            report.updated.push(relative_path);
+            continue;
+        }
+        // Only modified in the project, there is nothing to apply
+        if let Some(base_file) = base_files.as_ref().and_then(|files| files.get(&relative_path)) {
+            if *base_file.bytes()? == content[..] {
+                continue;
+            }
+        }
+
+        match policy {
+            ConflictPolicy::Skip => (),
+            ConflictPolicy::Overwrite => {
+                map_io_err(fs::copy(&generated, &dest), &dest)?;
+            }
+            ConflictPolicy::WriteNew => {
+                let mut new_path = dest.into_os_string();
+                new_path.push(".new");
+                map_io_err(fs::copy(&generated, &new_path), Path::new(&new_path))?;
+            }
+        }
+        report.conflicts.push(relative_path);
+    }
+
+    if template.definition.write_lockfile {
+        let name = &template.definition.lockfile_name;
+        let dest = project_dir.join(name);
+        map_io_err(fs::copy(staging.path().join(name), &dest), &dest)?;
+    }
+
+    Ok(report)
+}
+
+/// How a project differs from what was generated, see [check_project].
+/// The paths are relative to the project and use `/` like in the lockfile.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Drift {
+    /// Files whose content changed since they were generated
+    pub modified: Vec<String>,
+    /// Files that were generated but are not in the project anymore
+    pub deleted: Vec<String>,
+    /// Files the template generates that the lockfile doesn't know about, eg because they were
+    /// added to the template since
+    pub untracked: Vec<String>,
+}
+
+impl Drift {
+    /// Whether the project is as it was generated
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_empty() && self.deleted.is_empty() && self.untracked.is_empty()
+    }
+}
+
+/// Compare the files of the project with the checksums of its lockfile. If the template is
+/// given, with its variables set, the files it would generate that are not in the lockfile are
+/// listed as well. It should be the template at the commit of the lockfile if it has one, the
+/// files added to the template since not being drift. Nothing is written.
+pub fn check_project(
+    project_dir: &Path,
+    lockfile: &Lockfile,
+    template: Option<&Template>,
+) -> Result<Drift> {
+    let mut drift = Drift::default();
+    for (path, hash) in &lockfile.files {
+        let on_disk = project_dir.join(path);
+        if !on_disk.exists() {
+            drift.deleted.push(path.clone());
+        } else if &hash_file(&on_disk)? != hash {
+            drift.modified.push(path.clone());
+        }
+    }
+
+    if let Some(template) = template {
+        let (entries, errors) = template.dry_run(project_dir)?;
+        if let Some(err) = errors.into_iter().next() {
+            return Err(err);
+        }
+        drift.untracked = entries
+            .into_iter()
+            .filter(|e| !e.is_dir && e.skipped_because.is_none())
+            .map(|e| e.path.to_string_lossy().replace('\\', "/"))
+            .filter(|p| !lockfile.files.contains_key(p))
+            .collect();
+    }
+
+    Ok(drift)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OverwritePolicy;
+
+    fn generate_basic(dir: &Path, recipient: &str) -> Template {
+        let mut tpl = Template::from_input("examples/super-basic", None).unwrap();
+        let mut vals = tpl.definition.default_values().unwrap();
+        vals.insert("greeting_recipient".to_string(), Value::String(recipient.to_string()));
+        tpl.set_variables(vals).unwrap();
+        tpl.set_overwrite_policy(OverwritePolicy::Overwrite);
+        tpl.generate(dir).unwrap();
+        tpl
+    }
+
+    #[test]
+    fn updates_unmodified_files_and_detects_conflicts() {
+        let dir = tempdir().unwrap();
+        generate_basic(dir.path(), "Vincent");
+        let file = dir.path().join("Hello").join("Howdy.py");
+
+        for (modified, policy, expected, new_file) in [
+            (false, ConflictPolicy::Skip, "Bob", None),
+            (true, ConflictPolicy::Skip, "mine", None),
+            (true, ConflictPolicy::Overwrite, "Bob", None),
+            (true, ConflictPolicy::WriteNew, "mine", Some("Bob")),
+        ] {
+            generate_basic(dir.path(), "Vincent");
+            if modified {
+                fs::write(&file, "mine").unwrap();
+            }
+            let previous = Lockfile::load(&dir.path().join(".kickstart.json")).unwrap();
+
+            let mut tpl = Template::from_input("examples/super-basic", None).unwrap();
+            let mut vals = previous_answers(&tpl, &previous);
+            vals.insert("greeting_recipient".to_string(), Value::String("Bob".to_string()));
+            tpl.set_variables(vals).unwrap();
+            let report = update_project(&tpl, dir.path(), &previous, None, policy).unwrap();
+
+            assert_eq!(report.conflicts.len(), if modified { 1 } else { 0 });
+            assert!(fs::read_to_string(&file).unwrap().contains(expected));
+            let new_path = dir.path().join("Hello").join("Howdy.py.new");
+            if let Some(new_content) = new_file {
+                assert!(fs::read_to_string(&new_path).unwrap().contains(new_content));
+                fs::remove_file(new_path).unwrap();
+            } else {
+                assert!(!new_path.exists());
+            }
+        }
+    }
+
+    #[test]
+    fn applies_what_changed_since_the_base() {
+        let write_template = |dir: &Path, files: &[(&str, &str)]| {
+            fs::create_dir_all(dir).unwrap();
+            fs::write(
+                dir.join("template.toml"),
+                "name = \"Base\"\nkickstart_version = 1\nvariables = []\n",
+            )
+            .unwrap();
+            for (path, content) in files {
+                fs::write(dir.join(path), content).unwrap();
+            }
+            let mut tpl = Template::from_local(dir, None).unwrap();
+            tpl.set_variables(HashMap::new()).unwrap();
+            tpl
+        };
+        let dir = tempdir().unwrap();
+        let files = [("a.txt", "one"), ("b.txt", "b"), ("gone.txt", "gone"), ("kept.txt", "k")];
+        let base = write_template(&dir.path().join("base"), &files);
+        let project = dir.path().join("project");
+        base.generate(&project).unwrap();
+        fs::write(project.join("b.txt"), "mine").unwrap();
+        fs::write(project.join("kept.txt"), "mine").unwrap();
+        let previous = Lockfile::load(&project.join(".kickstart.json")).unwrap();
+
+        let files = [("a.txt", "two"), ("b.txt", "b"), ("new.txt", "new")];
+        let tpl = write_template(&dir.path().join("new"), &files);
+        let report =
+            update_project(&tpl, &project, &previous, Some(&base), ConflictPolicy::WriteNew)
+                .unwrap();
+        assert_eq!(report.updated, [PathBuf::from("a.txt")]);
+        assert_eq!(report.created, [PathBuf::from("new.txt")]);
+        assert_eq!(report.removed, [PathBuf::from("gone.txt")]);
+        // The template didn't change it
+        assert!(report.conflicts.is_empty());
+        assert_eq!(fs::read_to_string(project.join("b.txt")).unwrap(), "mine");
+        assert!(!project.join("b.txt.new").exists());
+        // Modified, so not deleted
+        assert_eq!(fs::read_to_string(project.join("kept.txt")).unwrap(), "mine");
+    }
+
+    #[test]
+    fn can_detect_drift() {
+        let dir = tempdir().unwrap();
+        generate_basic(dir.path(), "Vincent");
+        let lockfile = Lockfile::load(&dir.path().join(".kickstart.json")).unwrap();
+        let tpl = generate_basic(dir.path(), "Vincent");
+        assert!(check_project(dir.path(), &lockfile, Some(&tpl)).unwrap().is_empty());
+
+        fs::write(dir.path().join("Hello").join("Howdy.py"), "mine").unwrap();
+        let mut lockfile = lockfile;
+        lockfile.files.insert("gone.txt".to_string(), hash_content(b"gone"));
+        let drift = check_project(dir.path(), &lockfile, Some(&tpl)).unwrap();
+        assert_eq!(drift.modified, ["Hello/Howdy.py"]);
+        assert_eq!(drift.deleted, ["gone.txt"]);
+        assert!(drift.untracked.is_empty());
+
+        lockfile.files.clear();
+        let drift = check_project(dir.path(), &lockfile, Some(&tpl)).unwrap();
+        assert_eq!(drift.untracked, ["Hello/Howdy.py"]);
+        assert!(check_project(dir.path(), &lockfile, None).unwrap().untracked.is_empty());
+    }
+}
@@ -1,17 +1,30 @@
+use std::collections::HashMap;
+use std::env;
 use std::fs::{create_dir_all, File};
-use std::io::prelude::*;
+use std::io::{self, prelude::*};
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str;
+use std::sync::OnceLock;
 
-use memchr::memchr;
+use chrono::{DateTime, Local, TimeZone, Utc};
+use memchr::{memchr, memchr_iter};
+use regex::Regex;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use tera::{Context, Tera};
 
+use crate::archive::is_archive;
 use crate::errors::{map_io_err, new_error, ErrorKind, Result};
 use crate::filters::register_all_filters;
+use crate::Value;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Source {
     Local(PathBuf),
     Git(String),
+    /// A local path or a URL to a `.tar.gz`, `.tgz` or `.zip` archive
+    Archive(String),
 }
 
 pub fn read_file(p: &Path) -> Result<String> {
@@ -38,14 +51,62 @@ pub fn create_directory(path: &Path) -> Result<()> {
     Ok(())
 }
 
-/// Is it a remote or a local thing
+/// The prefixes that can be used in front of a `owner/repo` shorthand, GitHub being the default
+const SHORTHAND_HOSTS: [(&str, &str); 3] = [
+    ("github:", "https://github.com"),
+    ("gitlab:", "https://gitlab.com"),
+    ("bitbucket:", "https://bitbucket.org"),
+];
+
+/// Expand a `owner/repo` shorthand to the URL of the repository on GitHub, or on GitLab and
+/// Bitbucket with a `gitlab:` or `bitbucket:` prefix. A `#ref` at the end is kept.
+/// Returns `None` if the input is not a shorthand.
+pub fn expand_shorthand(input: &str) -> Option<String> {
+    let (repo, fragment) = match input.split_once('#') {
+        Some((repo, fragment)) => (repo, Some(fragment)),
+        None => (input, None),
+    };
+    let (host, repo) = SHORTHAND_HOSTS
+        .iter()
+        .find_map(|(prefix, host)| repo.strip_prefix(prefix).map(|r| (*host, r)))
+        .unwrap_or(("https://github.com", repo));
+
+    let re = Regex::new(r"^[\w.-]+/[\w.-]+$").unwrap();
+    // `./foo` or `../foo` are paths, not repositories
+    if !re.is_match(repo) || repo.split('/').any(|part| part.chars().all(|c| c == '.')) {
+        return None;
+    }
+
+    let mut url = format!("{host}/{repo}");
+    if let Some(fragment) = fragment {
+        url = format!("{url}#{fragment}");
+    }
+    Some(url)
+}
+
+/// The name of the repository of a git URL, eg `templates` for
+/// `git@gitlab.internal:infra/templates.git`, used to name the directory it is cloned in
+pub fn repo_name(remote: &str) -> &str {
+    let name = remote.trim_end_matches('/').rsplit(['/', ':', '\\']).next().unwrap_or_default();
+    let name = name.strip_suffix(".git").unwrap_or(name);
+    if name.is_empty() {
+        "kickstart"
+    } else {
+        name
+    }
+}
+
+/// Is it a remote or a local thing.
+/// A local directory wins over a `owner/repo` shorthand of the same name.
 pub fn get_source(input: &str) -> Source {
     let path = Path::new(input);
 
     if path.is_dir() {
         Source::Local(path.to_path_buf())
+    } else if is_archive(input) {
+        Source::Archive(input.to_string())
     } else {
-        Source::Git(input.to_string())
+        Source::Git(expand_shorthand(input).unwrap_or_else(|| input.to_string()))
     }
 }
 
@@ -60,18 +121,320 @@ pub fn render_one_off_template(
     tera.render_str(content, context).map_err(|err| new_error(ErrorKind::Tera { err, path }))
 }
 
+/// The key of the values kickstart adds to the context of templates, which variables can't use
+pub const BUILTIN_CONTEXT_KEY: &str = "kickstart";
+
+/// The key of the values pre-gen hooks add to the context of templates, see
+/// [crate::Template::load_extra_context]
+pub const EXTRA_CONTEXT_KEY: &str = "extra";
+
+/// The Unix timestamp of 2000-01-01T00:00:00Z, the time of deterministic generations
+const DETERMINISTIC_TIMESTAMP: i64 = 946_684_800;
+
+/// The values available as `{{ kickstart.* }}` in templates, defaults and the output directory,
+/// without having to ask the user for them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuiltinValues {
+    pub year: i32,
+    /// The local date, eg `2024-03-25`
+    pub date: String,
+    /// The Unix timestamp, in seconds
+    pub timestamp: i64,
+    /// One of the values of [`std::env::consts::OS`], eg `linux`
+    pub os: String,
+    /// One of the values of [`std::env::consts::ARCH`], eg `x86_64`
+    pub arch: String,
+    /// The name of the user running kickstart, empty if it couldn't be found
+    pub username: String,
+    /// The version of kickstart
+    pub version: String,
+    /// The identity of the user in their git config
+    pub git: GitIdentity,
+}
+
+/// The `user.name` and `user.email` of the git config, empty if git is not installed or they
+/// are not set
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GitIdentity {
+    pub user_name: String,
+    pub user_email: String,
+}
+
+impl GitIdentity {
+    pub fn from_git_config() -> GitIdentity {
+        let get = |key: &str| {
+            Command::new("git")
+                .args(["config", "--get", key])
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+                .unwrap_or_default()
+        };
+        GitIdentity { user_name: get("user.name"), user_email: get("user.email") }
+    }
+
+    /// [GitIdentity::from_git_config] the first time only, every template and addon loaded
+    /// getting builtins would run `git` several times otherwise
+    fn cached() -> GitIdentity {
+        static IDENTITY: OnceLock<GitIdentity> = OnceLock::new();
+        IDENTITY.get_or_init(GitIdentity::from_git_config).clone()
+    }
+}
+
+/// What `default_from` can be set to on a variable
+pub const DEFAULT_FROM_SOURCES: [&str; 2] = ["git.user.name", "git.user.email"];
+
+impl BuiltinValues {
+    /// The git identity is only read from the git config once per process
+    pub fn now() -> BuiltinValues {
+        Self::at(Local::now())
+    }
+
+    /// The same values as [`BuiltinValues::now`] but with the date and timestamp frozen to
+    /// 2000-01-01 UTC, to generate the same output every time
+    pub fn deterministic() -> BuiltinValues {
+        Self::at(Utc.timestamp_opt(DETERMINISTIC_TIMESTAMP, 0).unwrap())
+    }
+
+    fn at<Tz: TimeZone>(time: DateTime<Tz>) -> BuiltinValues
+    where
+        Tz::Offset: std::fmt::Display,
+    {
+        let username = ["USER", "USERNAME", "LOGNAME"]
+            .iter()
+            .find_map(|var| env::var(var).ok().filter(|v| !v.is_empty()))
+            .unwrap_or_default();
+        BuiltinValues {
+            year: chrono::Datelike::year(&time),
+            date: time.format("%Y-%m-%d").to_string(),
+            timestamp: time.timestamp(),
+            os: env::consts::OS.to_string(),
+            arch: env::consts::ARCH.to_string(),
+            username,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git: GitIdentity::cached(),
+        }
+    }
+
+    /// The value of one of the [`DEFAULT_FROM_SOURCES`]
+    pub fn get(&self, source: &str) -> Option<&str> {
+        match source {
+            "git.user.name" => Some(&self.git.user_name),
+            "git.user.email" => Some(&self.git.user_email),
+            _ => None,
+        }
+    }
+}
+
+/// A context with the variables and the values of kickstart under `kickstart`
+pub fn build_context(variables: &HashMap<String, Value>, builtins: &BuiltinValues) -> Context {
+    let mut context = Context::new();
+    for (key, val) in variables {
+        context.insert(key, val);
+    }
+    context.insert(BUILTIN_CONTEXT_KEY, builtins);
+    context
+}
+
+/// The name of the variable a Tera error is about if it is because that variable doesn't exist
+pub fn undefined_variable(err: &tera::Error) -> Option<String> {
+    let re = Regex::new(r"^Variable `([^`]+)` not found in context").unwrap();
+    let mut cause: Option<&dyn std::error::Error> = Some(err);
+    while let Some(e) = cause {
+        if let Some(caps) = re.captures(&e.to_string()) {
+            return Some(caps[1].to_string());
+        }
+        cause = e.source();
+    }
+    None
+}
+
+/// The line and column, starting at 1, where `name` is first used in a `{{ }}` or `{% %}` block
+/// of that Tera template
+pub fn find_variable_use(template: &str, name: &str) -> Option<(usize, usize)> {
+    let blocks = Regex::new(r"(?s)\{\{.*?\}\}|\{%.*?%\}").unwrap();
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    for block in blocks.find_iter(template) {
+        for (offset, _) in block.as_str().match_indices(name) {
+            let start = block.start() + offset;
+            let end = start + name.len();
+            let before = template[..start].chars().next_back();
+            let after = template[end..].chars().next();
+            if before.is_some_and(|c| is_ident(c) || c == '.') || after.is_some_and(is_ident) {
+                continue;
+            }
+            let line = template[..start].matches('\n').count() + 1;
+            let line_start = template[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+            return Some((line, template[line_start..start].chars().count() + 1));
+        }
+    }
+    None
+}
+
 /// Is the buffer from a binary file?
 /// See <https://twitter.com/20100Prouillet/status/1022973478096527360>
 pub fn is_binary(buf: &[u8]) -> bool {
     memchr(b'\x00', buf).is_some()
 }
 
+/// The hex-encoded SHA-256 of some content, used to detect changes in generated files
+pub fn hash_content(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Same as [hash_content] for the content of a file, without reading it whole
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut f = map_io_err(File::open(path), path)?;
+    let mut hasher = Sha256::new();
+    map_io_err(io::copy(&mut f, &mut hasher), path)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Whether some content might need rendering: it is text and has the start of a Tera tag.
+/// It is read in chunks rather than whole, for the large files, eg datasets, which are copied
+/// as is otherwise. It stops as soon as it finds a null byte or something that isn't UTF-8.
+pub fn could_need_rendering(mut reader: impl Read) -> io::Result<bool> {
+    let mut chunk = vec![0; 64 * 1024];
+    // What was not checked yet, eg a character cut in half by the end of the previous chunk
+    let mut pending = Vec::new();
+    let mut has_tag = false;
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        pending.extend_from_slice(&chunk[..read]);
+        if is_binary(&pending) {
+            return Ok(false);
+        }
+        let valid = match str::from_utf8(&pending) {
+            Ok(_) => pending.len(),
+            // Only cut in half
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => return Ok(false),
+        };
+        has_tag = has_tag
+            || memchr_iter(b'{', &pending[..valid])
+                .any(|i| matches!(pending.get(i + 1), Some(b'{' | b'%' | b'#')));
+        // A `{` at the end could start a tag with the next chunk
+        let checked = if pending[..valid].ends_with(b"{") { valid - 1 } else { valid };
+        pending.drain(..checked);
+    }
+    Ok(has_tag && str::from_utf8(&pending).is_ok())
+}
+
+/// How many characters to insert, remove or replace to go from `a` to `b`, to suggest what
+/// might have been meant instead of a typo
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(ca != *cb);
+            current.push(replace.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The prefix of the environment variables passed to hooks
+pub const HOOK_ENV_PREFIX: &str = "KICKSTART_";
+
+/// The command as it would be typed, for the logs
+pub fn command_line(command: &Command) -> String {
+    let mut line = command.get_program().to_string_lossy().into_owned();
+    for arg in command.get_args() {
+        line.push(' ');
+        line.push_str(&arg.to_string_lossy());
+    }
+    line
+}
+
+/// The name of the environment variable holding the value of a variable in hooks, eg
+/// `KICKSTART_PROJECT_NAME` for `project_name`.
+/// Anything that is not a valid character in an environment variable name becomes a `_`.
+pub fn hook_env_var_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    format!("{HOOK_ENV_PREFIX}{sanitized}")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
     use tempfile::tempdir;
 
+    #[test]
+    fn can_compute_edit_distances() {
+        assert_eq!(edit_distance("src/main.rs", "src/main.rs"), 0);
+        assert_eq!(edit_distance("src/mian.rs", "src/main.rs"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn can_tell_if_large_content_could_need_rendering() {
+        // Small chunks of a larger content, cutting characters and tags in half
+        struct Chunks<'a>(&'a [u8]);
+        impl Read for Chunks<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let len = self.0.len().min(buf.len()).min(3);
+                buf[..len].copy_from_slice(&self.0[..len]);
+                self.0 = &self.0[len..];
+                Ok(len)
+            }
+        }
+
+        let cases: [(&[u8], bool); 7] = [
+            (b"just text { with braces }", false),
+            ("caf\u{e9} {{ name }}".as_bytes(), true),
+            (b"ab{% if a %}", true),
+            (b"a{# comment #}", true),
+            (b"{{ name }}\x00", false),
+            (b"{{ name }}\xff", false),
+            (b"text ending with {", false),
+        ];
+        for (content, expected) in cases {
+            assert_eq!(could_need_rendering(Chunks(content)).unwrap(), expected, "{content:?}");
+            assert_eq!(could_need_rendering(content).unwrap(), expected, "{content:?}");
+        }
+    }
+
+    #[test]
+    fn can_find_where_variables_are_used() {
+        let template =
+            "name: {{ name }}\n{% if other_name %}\n  {{ a.name | upper }} {{name}}{% endif %}";
+        assert_eq!(find_variable_use(template, "other_name"), Some((2, 7)));
+        assert_eq!(find_variable_use(template, "name"), Some((1, 10)));
+        assert_eq!(find_variable_use("{{ ünï }} {{ x }}", "x"), Some((1, 14)));
+        assert_eq!(find_variable_use("x {{ y }}", "x"), None);
+    }
+
+    #[test]
+    fn can_freeze_the_builtin_values() {
+        let builtins = BuiltinValues::deterministic();
+        assert_eq!(builtins.year, 2000);
+        assert_eq!(builtins.date, "2000-01-01");
+        assert_eq!(builtins.timestamp, 946_684_800);
+        assert_eq!(builtins.os, env::consts::OS);
+        assert!(BuiltinValues::now().year > 2000);
+
+        let mut vals = HashMap::new();
+        vals.insert("kickstart".to_string(), Value::String("shadowed".to_string()));
+        let context = build_context(&vals, &builtins);
+        let rendered =
+            render_one_off_template("{{ kickstart.date }} {{ kickstart.year }}", &context, None);
+        assert_eq!(rendered.unwrap(), "2000-01-01 2000");
+    }
+
     #[test]
     fn can_detect_sources() {
         let dir = tempdir().unwrap();
@@ -95,6 +458,12 @@ mod tests {
             ("git:git/Test".to_string(), Source::Git("git:git/Test".to_string())),
             // Non existing local -> considered as a git and will fail later on
             ("hello".to_string(), Source::Git("hello".to_string())),
+            // Archives
+            ("hello.tar.gz".to_string(), Source::Archive("hello.tar.gz".to_string())),
+            (
+                "https://example.com/hello.zip".to_string(),
+                Source::Archive("https://example.com/hello.zip".to_string()),
+            ),
         ];
         if !cfg!(windows) {
             let folder3 = dir.path().join("not:git");
@@ -108,4 +477,42 @@ mod tests {
             assert_eq!(get_source(&input), expected);
         }
     }
+
+    #[test]
+    fn can_find_repo_names() {
+        let inputs = vec![
+            ("https://github.com/Keats/kickstart", "kickstart"),
+            ("https://github.com/Keats/kickstart.git/", "kickstart"),
+            ("git@gitlab.internal:infra/templates.git", "templates"),
+            ("git@gitlab.internal:templates.git", "templates"),
+            ("ssh://git@gitlab.internal:2222/infra/templates.git", "templates"),
+            ("file:///srv/git/templates", "templates"),
+            ("https://example.com/", "example.com"),
+            ("", "kickstart"),
+        ];
+        for (input, expected) in inputs {
+            assert_eq!(repo_name(input), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn can_expand_shorthands() {
+        let inputs = vec![
+            ("Keats/kickstart", Some("https://github.com/Keats/kickstart")),
+            ("Keats/kickstart#v0.5.0", Some("https://github.com/Keats/kickstart#v0.5.0")),
+            ("github:Keats/kickstart", Some("https://github.com/Keats/kickstart")),
+            ("gitlab:some_one/my.repo", Some("https://gitlab.com/some_one/my.repo")),
+            ("bitbucket:me/tpl-1", Some("https://bitbucket.org/me/tpl-1")),
+            ("https://github.com/Keats/kickstart", None),
+            ("git@github.com:Keats/kickstart", None),
+            ("Keats", None),
+            ("a/b/c", None),
+            ("./kickstart", None),
+            ("../kickstart", None),
+            ("unknown:Keats/kickstart", None),
+        ];
+        for (input, expected) in inputs {
+            assert_eq!(expand_shorthand(input).as_deref(), expected, "{input}");
+        }
+    }
 }
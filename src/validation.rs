@@ -0,0 +1,274 @@
+//! The `validation` field of a variable can either be a regex or the name of one of the
+//! built-in rules, which are easier to get right than a handwritten regex.
+use regex::Regex;
+
+/// The built-in validation rules that can be used by name in the `validation` field
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rule {
+    /// Starts with an ASCII letter followed by letters, digits, `_` or `-`, eg a crate name
+    Identifier,
+    /// A semver version such as `1.0.0-beta.1`
+    Semver,
+    /// An absolute URL with a scheme and a host, eg `https://example.com/`
+    Url,
+    /// An email address
+    Email,
+    /// A non-empty path without control characters
+    Path,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 5] = [Rule::Identifier, Rule::Semver, Rule::Url, Rule::Email, Rule::Path];
+
+    /// Find a rule by the name used in `template.toml`
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL.into_iter().find(|r| r.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::Identifier => "identifier",
+            Rule::Semver => "semver",
+            Rule::Url => "url",
+            Rule::Email => "email",
+            Rule::Path => "path",
+        }
+    }
+
+    /// What is expected of the value, to show to the user when it doesn't pass
+    pub fn description(&self) -> &'static str {
+        match self {
+            Rule::Identifier => {
+                "must be a valid identifier: a letter followed by letters, digits, `_` or `-`"
+            }
+            Rule::Semver => "must be a valid semver version",
+            Rule::Url => "must be a valid URL",
+            Rule::Email => "must be a valid email address",
+            Rule::Path => "must be a valid path",
+        }
+    }
+
+    /// The regex the values need to match, `None` for the rules that are not checked with one
+    pub fn pattern(&self) -> Option<&'static str> {
+        match self {
+            Rule::Identifier => Some(r"^[a-zA-Z][a-zA-Z0-9_-]*$"),
+            // From https://semver.org/#is-there-a-suggested-regular-expression-regex-to-check-a-semver-string
+            Rule::Semver => Some(
+                r"^(0|[1-9]\d*)\.(0|[1-9]\d*)\.(0|[1-9]\d*)(?:-((?:0|[1-9]\d*|\d*[a-zA-Z-][0-9a-zA-Z-]*)(?:\.(?:0|[1-9]\d*|\d*[a-zA-Z-][0-9a-zA-Z-]*))*))?(?:\+([0-9a-zA-Z-]+(?:\.[0-9a-zA-Z-]+)*))?$",
+            ),
+            Rule::Url => Some(r"^[a-zA-Z][a-zA-Z0-9+.-]*://[^\s/?#@]+(?:[/?#]\S*)?$"),
+            Rule::Email => Some(r"^[^@\s]+@[^@\s.]+(?:\.[^@\s.]+)+$"),
+            Rule::Path => None,
+        }
+    }
+
+    pub fn is_valid(&self, input: &str) -> bool {
+        match self.pattern() {
+            Some(pattern) => Regex::new(pattern).unwrap().is_match(input),
+            None => !input.is_empty() && !input.chars().any(char::is_control),
+        }
+    }
+}
+
+/// A parsed `validation` field
+#[derive(Debug, Clone)]
+pub enum Validation {
+    Rule(Rule),
+    Regex(Regex),
+}
+
+impl Validation {
+    /// Parse the `validation` field of a variable.
+    /// A bare lowercase word is treated as a rule name, so a typo in a rule name is an error
+    /// rather than a regex that matches almost nothing. Wrap it in `^...$` to use it as a regex.
+    pub fn parse(validation: &str) -> Result<Validation, String> {
+        if let Some(rule) = Rule::from_name(validation) {
+            return Ok(Validation::Rule(rule));
+        }
+
+        if !validation.is_empty() && validation.chars().all(|c| c.is_ascii_lowercase() || c == '_')
+        {
+            let names: Vec<_> = Rule::ALL.iter().map(|r| format!("`{}`", r.name())).collect();
+            return Err(format!(
+                "`{validation}` is not a known validation rule, expected one of {}",
+                names.join(", ")
+            ));
+        }
+
+        Regex::new(validation).map(Validation::Regex).map_err(|e| e.to_string())
+    }
+
+    /// Returns the message to show to the user if the input is not valid
+    pub fn check(&self, input: &str) -> Result<(), String> {
+        match self {
+            Validation::Rule(rule) if !rule.is_valid(input) => {
+                Err(format!("The value {}", rule.description()))
+            }
+            Validation::Regex(re) if !re.is_match(input) => {
+                Err(format!("The value needs to pass the regex: {}", re.as_str()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// What an integer needs to be to be within those inclusive bounds, eg `between 1 and 8` or
+/// `at least 1`. `None` if there are no bounds.
+pub fn describe_range(min: Option<i64>, max: Option<i64>) -> Option<String> {
+    match (min, max) {
+        (Some(min), Some(max)) => Some(format!("between {min} and {max}")),
+        (Some(min), None) => Some(format!("at least {min}")),
+        (None, Some(max)) => Some(format!("at most {max}")),
+        (None, None) => None,
+    }
+}
+
+/// Returns the message to show to the user if the value is not within the inclusive bounds
+pub fn check_range(value: i64, min: Option<i64>, max: Option<i64>) -> Result<(), String> {
+    if min.is_some_and(|min| value < min) || max.is_some_and(|max| value > max) {
+        return Err(format!("The value needs to be {}", describe_range(min, max).unwrap()));
+    }
+    Ok(())
+}
+
+/// How many characters a string needs to have, eg `between 3 and 30 characters`, or `None` if
+/// there are no bounds
+pub fn describe_length(min: Option<usize>, max: Option<usize>) -> Option<String> {
+    let bounds = describe_range(min.map(|m| m as i64), max.map(|m| m as i64))?;
+    Some(format!("{bounds} characters"))
+}
+
+/// Returns the message to show to the user if the input doesn't have a number of characters,
+/// not bytes, within the inclusive bounds
+pub fn check_length(input: &str, min: Option<usize>, max: Option<usize>) -> Result<(), String> {
+    let length = input.chars().count();
+    if min.is_some_and(|min| length < min) || max.is_some_and(|max| length > max) {
+        return Err(format!("The value must be {}", describe_length(min, max).unwrap()));
+    }
+    Ok(())
+}
+
+/// How many entries a list needs to have, eg `at least 1 entries`, or `None` if there are no
+/// bounds
+pub fn describe_items(min: Option<usize>, max: Option<usize>) -> Option<String> {
+    let bounds = describe_range(min.map(|m| m as i64), max.map(|m| m as i64))?;
+    Some(format!("{bounds} entries"))
+}
+
+/// Returns the message to show to the user if a list doesn't have a number of entries within
+/// the inclusive bounds
+pub fn check_items(count: usize, min: Option<usize>, max: Option<usize>) -> Result<(), String> {
+    if min.is_some_and(|min| count < min) || max.is_some_and(|max| count > max) {
+        return Err(format!("The list must have {}", describe_items(min, max).unwrap()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_rule(rule: Rule, valid: &[&str], invalid: &[&str]) {
+        for input in valid {
+            assert!(rule.is_valid(input), "`{input}` should be a valid {}", rule.name());
+        }
+        for input in invalid {
+            assert!(!rule.is_valid(input), "`{input}` should not be a valid {}", rule.name());
+        }
+    }
+
+    #[test]
+    fn can_validate_identifiers() {
+        check_rule(
+            Rule::Identifier,
+            &["kickstart", "my-crate", "my_crate2", "A"],
+            &["", "2fast", "-crate", "my crate", "café"],
+        );
+    }
+
+    #[test]
+    fn can_validate_semver() {
+        check_rule(
+            Rule::Semver,
+            &["0.1.0", "1.0.0-beta.1", "1.0.0+build.5", "10.20.30-rc.1+sha.abc"],
+            &["", "1", "1.0", "01.0.0", "1.0.0-", "v1.0.0"],
+        );
+    }
+
+    #[test]
+    fn can_validate_urls() {
+        check_rule(
+            Rule::Url,
+            &["https://example.com", "http://localhost:8080/path?q=1", "git+ssh://host/repo"],
+            &["", "example.com", "https://", "https://exa mple.com", "://example.com"],
+        );
+    }
+
+    #[test]
+    fn can_validate_emails() {
+        check_rule(
+            Rule::Email,
+            &["me@example.com", "first.last+tag@sub.example.org"],
+            &["", "me", "me@example", "@example.com", "me@@example.com", "me @example.com"],
+        );
+    }
+
+    #[test]
+    fn can_validate_paths() {
+        check_rule(Rule::Path, &["src", "./docs/index.md", "/tmp/a b", r"C:\dev"], &["", "a\nb"]);
+    }
+
+    #[test]
+    fn can_parse_validation() {
+        assert!(matches!(Validation::parse("semver"), Ok(Validation::Rule(Rule::Semver))));
+        assert!(matches!(Validation::parse("^[a-z]+$"), Ok(Validation::Regex(..))));
+        assert!(Validation::parse("semvr").is_err());
+        assert!(Validation::parse("[a-z").is_err());
+    }
+
+    #[test]
+    fn error_message_names_the_rule() {
+        let validation = Validation::parse("semver").unwrap();
+        assert_eq!(validation.check("1.0.0"), Ok(()));
+        assert_eq!(validation.check("1.0"), Err("The value must be a valid semver version".into()));
+    }
+
+    #[test]
+    fn ranges_are_inclusive() {
+        for value in [1, 4, 8] {
+            assert!(check_range(value, Some(1), Some(8)).is_ok(), "{value}");
+        }
+        for value in [0, 9, i64::MIN] {
+            assert_eq!(
+                check_range(value, Some(1), Some(8)).unwrap_err(),
+                "The value needs to be between 1 and 8"
+            );
+        }
+        assert!(check_range(i64::MAX, Some(1), None).is_ok());
+        assert_eq!(check_range(0, Some(1), None).unwrap_err(), "The value needs to be at least 1");
+        assert!(check_range(-3, None, Some(-3)).is_ok());
+        assert_eq!(
+            check_range(-2, None, Some(-3)).unwrap_err(),
+            "The value needs to be at most -3"
+        );
+        assert!(check_range(-2, None, None).is_ok());
+    }
+
+    #[test]
+    fn lengths_are_counted_in_characters() {
+        assert!(check_length("abc", Some(3), Some(5)).is_ok());
+        assert!(check_length("abcde", Some(3), Some(5)).is_ok());
+        // 5 characters but 10 bytes
+        assert!(check_length("héllö", Some(3), Some(5)).is_ok());
+        assert!(check_length("日本語", Some(3), Some(3)).is_ok());
+        assert_eq!(
+            check_length("ab", Some(3), Some(30)).unwrap_err(),
+            "The value must be between 3 and 30 characters"
+        );
+        assert_eq!(
+            check_length("abcdef", None, Some(5)).unwrap_err(),
+            "The value must be at most 5 characters"
+        );
+        assert!(check_length("", Some(1), None).is_err());
+    }
+}
@@ -2,13 +2,16 @@ use serde::{de::Error, Deserialize, Deserializer, Serialize};
 use std::fmt::Formatter;
 use toml::Value as TomlValue;
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(untagged)]
 /// The possible values we can get from the default or from a user
 pub enum Value {
     String(String),
     Integer(i64),
+    Float(f64),
     Boolean(bool),
+    /// The selected choices of a `multi` question
+    List(Vec<Value>),
 }
 
 impl std::fmt::Display for Value {
@@ -17,6 +20,29 @@ impl std::fmt::Display for Value {
             Value::Boolean(v) => write!(f, "{v}"),
             Value::String(v) => write!(f, "{v}"),
             Value::Integer(v) => write!(f, "{v}"),
+            // Debug keeps the decimal point so `1.0` doesn't look like an integer
+            Value::Float(v) => write!(f, "{v:?}"),
+            Value::List(v) => {
+                let items: Vec<_> = v.iter().map(|i| i.to_string()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+        }
+    }
+}
+
+/// Floats are equal if they are within an epsilon of each other, since they might come from
+/// different sources (TOML, JSON, user input) that don't round the same way
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => {
+                (a - b).abs() <= f64::EPSILON * a.abs().max(b.abs()).max(1.0)
+            }
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            _ => false,
         }
     }
 }
@@ -26,7 +52,9 @@ impl Value {
         match self {
             Value::String(..) => "string",
             Value::Integer(..) => "integer",
+            Value::Float(..) => "float",
             Value::Boolean(..) => "bool",
+            Value::List(..) => "list",
         }
     }
 
@@ -40,6 +68,36 @@ impl Value {
             _ => None,
         }
     }
+
+    pub(crate) fn as_list(&self) -> Option<&[Value]> {
+        match *self {
+            Value::List(ref l) => Some(l),
+            _ => None,
+        }
+    }
+
+    /// Parse a raw string (eg from the command line) into a value of the same type as `self`.
+    /// Booleans accept `true`/`false`, integers need to fit in an i64 and floats need to be finite.
+    /// Lists are comma separated strings, eg `ci,docker`.
+    /// Returns `None` if the string can't be converted to that type.
+    pub fn parse_same_type(&self, input: &str) -> Option<Value> {
+        match self {
+            Value::String(..) => Some(Value::String(input.to_string())),
+            Value::Integer(..) => input.parse::<i64>().ok().map(Value::Integer),
+            Value::Float(..) => {
+                input.parse::<f64>().ok().filter(|f| f.is_finite()).map(Value::Float)
+            }
+            Value::Boolean(..) => input.parse::<bool>().ok().map(Value::Boolean),
+            Value::List(..) => Some(Value::List(
+                input
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(|s| Value::String(s.to_string()))
+                    .collect(),
+            )),
+        }
+    }
 }
 
 impl<'de> Deserialize<'de> for Value {
@@ -48,11 +106,61 @@ impl<'de> Deserialize<'de> for Value {
         D: Deserializer<'de>,
     {
         let v: TomlValue = Deserialize::deserialize(deserializer)?;
-        match v {
-            TomlValue::String(s) => Ok(Value::String(s)),
-            TomlValue::Integer(i) => Ok(Value::Integer(i)),
-            TomlValue::Boolean(b) => Ok(Value::Boolean(b)),
-            _ => Err(D::Error::custom(format!("Value {} (of type `{}`) is not allowed as a value: only strings, integers and boolean are.", v, v.type_str()))),
+        from_toml(v, true).map_err(D::Error::custom)
+    }
+}
+
+/// Arrays are only allowed at the top level: we don't support nested lists
+fn from_toml(v: TomlValue, allow_array: bool) -> Result<Value, String> {
+    match v {
+        TomlValue::String(s) => Ok(Value::String(s)),
+        TomlValue::Integer(i) => Ok(Value::Integer(i)),
+        TomlValue::Float(f) if f.is_finite() => Ok(Value::Float(f)),
+        TomlValue::Boolean(b) => Ok(Value::Boolean(b)),
+        TomlValue::Array(items) if allow_array => {
+            Ok(Value::List(items.into_iter().map(|i| from_toml(i, false)).collect::<Result<_, _>>()?))
+        }
+        _ => Err(format!("Value {} (of type `{}`) is not allowed as a value: only strings, integers, finite floats, boolean and arrays of those are.", v, v.type_str())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_values_based_on_type() {
+        let inputs = vec![
+            (Value::String("hey".to_string()), "8080", Some(Value::String("8080".to_string()))),
+            (Value::Integer(1), "8080", Some(Value::Integer(8080))),
+            (Value::Integer(1), "-3", Some(Value::Integer(-3))),
+            (Value::Integer(1), "hello", None),
+            (Value::Float(1.0), "0.5", Some(Value::Float(0.5))),
+            (Value::Float(1.0), "3", Some(Value::Float(3.0))),
+            (Value::Float(1.0), "inf", None),
+            (Value::Float(1.0), "NaN", None),
+            (Value::Boolean(false), "true", Some(Value::Boolean(true))),
+            (Value::Boolean(true), "false", Some(Value::Boolean(false))),
+            (Value::Boolean(true), "yes", None),
+            (
+                Value::List(vec![]),
+                "ci, docker",
+                Some(Value::List(vec![
+                    Value::String("ci".to_string()),
+                    Value::String("docker".to_string()),
+                ])),
+            ),
+        ];
+
+        for (ty, input, expected) in inputs {
+            assert_eq!(ty.parse_same_type(input), expected);
         }
     }
+
+    #[test]
+    fn floats_are_compared_within_epsilon() {
+        assert_eq!(Value::Float(0.1 + 0.2), Value::Float(0.3));
+        assert_ne!(Value::Float(0.3), Value::Float(0.31));
+        assert_ne!(Value::Float(1.0), Value::Integer(1));
+    }
 }
@@ -0,0 +1,3541 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use tempfile::{tempdir, TempDir};
+
+fn kickstart() -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_kickstart"));
+    // Don't fill the cache of the user with test repositories nor use their config
+    cmd.env("KICKSTART_CACHE_DIR", std::env::temp_dir().join("kickstart-tests-cache"));
+    cmd.env("KICKSTART_CONFIG", std::env::temp_dir().join("kickstart-tests-config.toml"));
+    cmd
+}
+
+/// Run the command with `input` written to its stdin, eg the answers to the prompts
+fn run_with_input(cmd: &mut Command, input: &str) -> Output {
+    let mut child =
+        cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    std::io::Write::write_all(&mut child.stdin.take().unwrap(), input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Generate the template in a new directory with kickstart given those arguments and `input`
+/// on its stdin
+fn generate_with_input(template: &Path, args: &[&str], input: &str) -> (Output, TempDir) {
+    let out = tempdir().unwrap();
+    let res = run_with_input(kickstart().arg(template).arg("-o").arg(out.path()).args(args), input);
+    (res, out)
+}
+
+/// Make git not depend on the config of whoever runs the tests, with a fixed identity
+fn without_git_config(cmd: &mut Command) -> &mut Command {
+    cmd.env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_AUTHOR_NAME", "Kickstart")
+        .env("GIT_AUTHOR_EMAIL", "kickstart@example.com")
+        .env("GIT_COMMITTER_NAME", "Kickstart")
+        .env("GIT_COMMITTER_EMAIL", "kickstart@example.com")
+}
+
+/// Write a template made of the given files, `template.toml` included, in a new directory
+fn make_template(files: &[(&str, &str)]) -> TempDir {
+    let dir = tempdir().unwrap();
+    for (path, content) in files {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    dir
+}
+
+/// Run git in the repository, returning its stdout
+#[cfg(unix)]
+fn git(repo: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(repo)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Like [make_template] but as a git repository with one commit.
+/// Returns the repository path and its `file://` URL as well.
+#[cfg(unix)]
+fn make_git_template(files: &[(&str, &str)]) -> (TempDir, PathBuf, String) {
+    let dir = tempdir().unwrap();
+    // Cloned in the temp directory under the last segment of the URL, which needs to differ
+    // from the repository itself
+    let repo =
+        dir.path().join(format!("{}-remote", dir.path().file_name().unwrap().to_string_lossy()));
+    for (path, content) in files {
+        let path = repo.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    git(&repo, &["init", "-q"]);
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "init"]);
+    let url = format!("file://{}", repo.display());
+    (dir, repo, url)
+}
+
+#[test]
+fn env_vars_override_defaults_and_input_file() {
+    let dir = tempdir().unwrap();
+    let answers = dir.path().join("answers.json");
+    fs::write(&answers, r#"{"directory_name": "FromFile", "file_name": "FromFile"}"#).unwrap();
+    let output = dir.path().join("out");
+
+    let res = kickstart()
+        .args(["examples/super-basic", "--no-input", "-o"])
+        .arg(&output)
+        .arg("-i")
+        .arg(&answers)
+        .args(["-D", "file_name=FromDefine"])
+        .env("KICKSTART_VAR_DIRECTORY_NAME", "FromEnv")
+        .env("KICKSTART_VAR_FILE_NAME", "FromEnvToo")
+        .env("KICKSTART_VAR_GREETING_RECIPIENT", "Env")
+        .output()
+        .unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+
+    let content = fs::read_to_string(output.join("FromEnv").join("FromDefine.py")).unwrap();
+    assert!(content.contains("Env"));
+}
+
+#[test]
+fn can_read_the_answers_from_stdin() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Stdin"
+kickstart_version = 1
+
+[[variables]]
+name = "name"
+default = "hello"
+prompt = "Name?"
+
+[[variables]]
+name = "port"
+default = 80
+prompt = "Port?"
+"#,
+        ),
+        ("name.txt", "{{ name }}:{{ port }}"),
+    ]);
+    let (res, out) =
+        generate_with_input(dir.path(), &["-i", "-"], r#"{"name": "demo", "port": 8080}"#);
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(fs::read_to_string(out.path().join("name.txt")).unwrap(), "demo:8080");
+
+    // Checked like a file
+    let (res, out) =
+        generate_with_input(dir.path(), &["-i", "-"], r#"{"name": "demo", "port": "80"}"#);
+    assert!(!res.status.success());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("Invalid JSON input file") && stderr.contains("`port`"), "{stderr}");
+    assert!(!out.path().join("name.txt").exists());
+    let (res, _) = generate_with_input(dir.path(), &["-i", "-"], "[1]");
+    assert!(String::from_utf8_lossy(&res.stderr).contains("needs to be an object"));
+}
+
+#[test]
+fn invalid_env_var_is_an_error() {
+    let dir = tempdir().unwrap();
+    let res = kickstart()
+        .args(["examples/complex", "--no-input", "-o"])
+        .arg(dir.path())
+        .env("KICKSTART_VAR_SENTRY", "maybe")
+        .output()
+        .unwrap();
+
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("KICKSTART_VAR_SENTRY"));
+}
+
+#[test]
+fn values_not_asked_are_checked_against_the_choices_and_validation() {
+    let dir = tempdir().unwrap();
+    let res = kickstart()
+        .args(["examples/complex", "--no-input", "-o"])
+        .arg(dir.path())
+        .args(["-D", "project_name=my project"])
+        .env("KICKSTART_VAR_DATABASE", "oracle")
+        .output()
+        .unwrap();
+
+    assert!(!res.status.success());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("- project_name: The value needs to pass the regex"), "{stderr}");
+    assert!(
+        stderr.contains("- database: The value `oracle` is not one of the choices: postgres"),
+        "{stderr}"
+    );
+    assert!(fs::read_dir(dir.path()).unwrap().next().is_none());
+}
+
+#[cfg(unix)]
+#[test]
+fn hooks_are_skipped_when_their_condition_is_not_met() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Hooks"
+kickstart_version = 1
+post_gen_hooks = [
+    { name = "setup-node", path = "node.sh", only_if = { name = "use_node", value = true } },
+    { name = "always", path = "always.sh" },
+]
+
+[[variables]]
+name = "use_node"
+default = false
+prompt = "Use node?"
+"#,
+        ),
+        ("node.sh", "#!/bin/sh\ntouch node-was-setup\n"),
+        ("always.sh", "#!/bin/sh\ntouch always-ran\n"),
+    ]);
+    let output = tempdir().unwrap();
+
+    let res =
+        kickstart().arg(tpl.path()).args(["--no-input", "-o"]).arg(output.path()).output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+
+    assert!(String::from_utf8_lossy(&res.stderr)
+        .contains("Skipping hook setup-node (condition not met)"));
+    assert!(!output.path().join("node-was-setup").exists());
+    assert!(output.path().join("always-ran").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn addons_are_generated_after_the_template() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Service"
+kickstart_version = 1
+post_gen_hooks = [{ name = "base", path = "hook.sh" }]
+
+[[addons]]
+name = "grpc"
+directory = "addons/grpc"
+prompt = "Add a gRPC server?"
+
+[[addons]]
+name = "cron"
+directory = "addons/cron"
+prompt = "Add a cron job?"
+default = true
+
+[[variables]]
+name = "project_name"
+default = "svc"
+prompt = "Name?"
+"#,
+        ),
+        ("hook.sh", "#!/bin/sh\necho base >> hooks.txt\n"),
+        ("README.md", "{{ project_name }}\n"),
+        (
+            "addons/grpc/template.toml",
+            "name = 'grpc'\nkickstart_version = 1\npost_gen_hooks = [{ name = 'grpc', path = 'hook.sh' }]\n\
+             [[variables]]\nname = 'port'\ndefault = 50051\nprompt = 'Port?'\n",
+        ),
+        ("addons/grpc/hook.sh", "#!/bin/sh\necho grpc >> hooks.txt\n"),
+        ("addons/grpc/grpc.txt", "{{ project_name }}:{{ port }}\n"),
+        ("addons/cron/template.toml", "name = 'cron'\nkickstart_version = 1\nvariables = []\n"),
+        ("addons/cron/cron.txt", ""),
+    ]);
+    let generate = |args: &[&str]| {
+        let output = tempdir().unwrap();
+        let res = kickstart()
+            .arg(tpl.path())
+            .args(["--no-input", "-o"])
+            .arg(output.path())
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+        output
+    };
+
+    // The defaults without questions
+    let output = generate(&[]);
+    assert!(output.path().join("cron.txt").exists());
+    assert!(!output.path().join("grpc.txt").exists());
+
+    let output = generate(&["--addon", "grpc", "-D", "port=1"]);
+    assert_eq!(fs::read_to_string(output.path().join("grpc.txt")).unwrap(), "svc:1\n");
+    assert!(!output.path().join("cron.txt").exists());
+    assert_eq!(fs::read_to_string(output.path().join("hooks.txt")).unwrap(), "base\ngrpc\n");
+
+    fs::write(tpl.path().join("addons/cron/template.toml"), "name = 'cron'\n").unwrap();
+    let res = kickstart().arg("validate").arg(tpl.path().join("template.toml")).output().unwrap();
+    assert!(!res.status.success());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("Addon `cron`: Invalid TOML"), "{stderr}");
+}
+
+#[cfg(unix)]
+#[test]
+fn hooks_get_variables_as_env_vars() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Hooks"
+kickstart_version = 1
+post_gen_hooks = [{ name = "env", path = "env.sh" }]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+
+[[variables]]
+name = "use_docker"
+default = true
+prompt = "Docker?"
+
+[[variables]]
+name = "port"
+default = 8080
+prompt = "Port?"
+"#,
+        ),
+        ("env.sh", "#!/bin/sh\nenv | grep ^KICKSTART_ > env.txt\n"),
+    ]);
+    let output = tempdir().unwrap();
+
+    let res =
+        kickstart().arg(tpl.path()).args(["--no-input", "-o"]).arg(output.path()).output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+
+    let env = fs::read_to_string(output.path().join("env.txt")).unwrap();
+    let output_dir = output.path().canonicalize().unwrap();
+    for expected in [
+        "KICKSTART_PROJECT_NAME=hello".to_string(),
+        "KICKSTART_USE_DOCKER=true".to_string(),
+        "KICKSTART_PORT=8080".to_string(),
+        format!("KICKSTART_OUTPUT_DIR={}", output_dir.display()),
+    ] {
+        assert!(env.lines().any(|l| l == expected), "{expected} not found in:\n{env}");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn hooks_get_answers_as_json_on_stdin() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Hooks"
+kickstart_version = 1
+post_gen_hooks = [
+    { name = "python", path = "answers.py" },
+    { name = "does not read stdin", path = "ignore.sh" },
+]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+
+[[variables]]
+name = "port"
+default = 8080
+prompt = "Port?"
+
+[[variables]]
+name = "features"
+default = ["ci", "docs"]
+choices = ["ci", "docker", "docs"]
+multi = true
+prompt = "Features?"
+"#,
+        ),
+        (
+            "answers.py",
+            "#!/usr/bin/env python3\nimport json, sys\nanswers = json.load(sys.stdin)\n\
+            with open('answers.txt', 'w') as f:\n\
+            \x20   f.write(f\"{answers['project_name']} {answers['port'] + 1} {answers['features']}\")\n",
+        ),
+        ("ignore.sh", "#!/bin/sh\nexit 0\n"),
+    ]);
+    let output = tempdir().unwrap();
+
+    let res =
+        kickstart().arg(tpl.path()).args(["--no-input", "-o"]).arg(output.path()).output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+
+    let answers = fs::read_to_string(output.path().join("answers.txt")).unwrap();
+    assert_eq!(answers, "hello 8081 ['ci', 'docs']");
+}
+
+#[cfg(unix)]
+#[test]
+fn hooks_can_be_filtered_by_name() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Hooks"
+kickstart_version = 1
+post_gen_hooks = [
+    { name = "first", path = "first.sh" },
+    { name = "second", path = "second.sh" },
+]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        ("first.sh", "#!/bin/sh\ntouch first-ran\n"),
+        ("second.sh", "#!/bin/sh\ntouch second-ran\n"),
+    ]);
+
+    for (flags, first, second) in [
+        (vec!["--skip-hook", "first"], false, true),
+        (vec!["--only-hook", "first"], true, false),
+        (vec!["--skip-hook", "first", "--skip-hook", "second"], false, false),
+    ] {
+        let output = tempdir().unwrap();
+        let res = kickstart()
+            .arg(tpl.path())
+            .args(["--no-input", "-o"])
+            .arg(output.path())
+            .args(&flags)
+            .output()
+            .unwrap();
+        assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+        assert_eq!(output.path().join("first-ran").exists(), first, "{flags:?}");
+        assert_eq!(output.path().join("second-ran").exists(), second, "{flags:?}");
+    }
+
+    let output = tempdir().unwrap();
+    let res = kickstart()
+        .arg(tpl.path())
+        .args(["--no-input", "--skip-hook", "frist", "-o"])
+        .arg(output.path())
+        .output()
+        .unwrap();
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("no hook named `frist`"));
+}
+
+/// A template with a single post-gen hook `hook_file` that should create `ran.txt`
+fn interpreter_template(hook_file: &str, content: &str, interpreter: Option<&str>) -> TempDir {
+    let interpreter = interpreter.map(|i| format!(", interpreter = {i}")).unwrap_or_default();
+    make_template(&[
+        (
+            "template.toml",
+            &format!(
+                r#"
+name = "Hooks"
+kickstart_version = 1
+post_gen_hooks = [{{ name = "hook", path = "{hook_file}"{interpreter} }}]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#
+            ),
+        ),
+        (hook_file, content),
+    ])
+}
+
+#[cfg(unix)]
+#[test]
+fn hooks_can_use_an_interpreter() {
+    // No shebang so it can't be executed directly
+    let tpl = interpreter_template("hook.sh", "echo ran > ran.txt\n", None);
+    let output = tempdir().unwrap();
+    let res = kickstart().arg(tpl.path()).args(["--no-input", "-o"]).arg(output.path()).output();
+    let res = res.unwrap();
+    assert!(!res.status.success());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("executable bit") && stderr.contains("`interpreter`"), "{stderr}");
+
+    let tpl = interpreter_template("hook.sh", "echo ran > ran.txt\n", Some(r#"["sh"]"#));
+    let output = tempdir().unwrap();
+    let res = kickstart().arg(tpl.path()).args(["--no-input", "-o"]).arg(output.path()).output();
+    let res = res.unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert!(output.path().join("ran.txt").exists());
+}
+
+#[cfg(windows)]
+#[test]
+fn hooks_can_use_an_interpreter() {
+    let tpl = interpreter_template(
+        "hook.ps1",
+        "Set-Content -Path ran.txt -Value ran\n",
+        Some(r#"["powershell", "-ExecutionPolicy", "Bypass", "-File"]"#),
+    );
+    let output = tempdir().unwrap();
+    let res = kickstart().arg(tpl.path()).args(["--no-input", "-o"]).arg(output.path()).output();
+    let res = res.unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert!(output.path().join("ran.txt").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn hooks_are_killed_after_their_timeout() {
+    use std::time::{Duration, Instant};
+
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Hooks"
+kickstart_version = 1
+post_gen_hooks = [{ name = "slow", path = "slow.sh", timeout_secs = 1 }]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        // `sleep` is a child of the hook: it needs to be killed too or it would keep the output
+        // pipes open for the whole sleep
+        ("slow.sh", "#!/bin/sh\necho started\nsleep 30\n"),
+    ]);
+    let output = tempdir().unwrap();
+
+    let start = Instant::now();
+    let res =
+        kickstart().arg(tpl.path()).args(["--no-input", "-o"]).arg(output.path()).output().unwrap();
+    assert!(start.elapsed() < Duration::from_secs(10));
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("Hook `slow` timed out after 1s"));
+    assert!(String::from_utf8_lossy(&res.stdout).contains("started"));
+}
+
+#[cfg(unix)]
+#[test]
+fn quiet_hooks_only_show_their_output_on_failure() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Hooks"
+kickstart_version = 1
+post_gen_hooks = [
+    { name = "install", path = "install.sh", quiet = true },
+    { name = "check", path = "check.sh", quiet = true },
+]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        ("install.sh", "#!/bin/sh\necho lots of noise\n"),
+        ("check.sh", "#!/bin/sh\necho checking\necho broken >&2\nexit 3\n"),
+    ]);
+    let output = tempdir().unwrap();
+
+    let res =
+        kickstart().arg(tpl.path()).args(["--no-input", "-o"]).arg(output.path()).output().unwrap();
+    assert!(!res.status.success());
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    // Only the hook that succeeded gets a check mark
+    assert_eq!(stdout.matches('✓').count(), 1);
+    assert!(!stdout.contains("lots of noise"));
+    assert!(stdout.contains("checking"));
+    assert!(stderr.contains("broken"));
+    assert!(stderr.contains("Hook `check` exited with code 3"));
+}
+
+#[cfg(unix)]
+#[test]
+fn generated_files_are_removed_when_a_post_gen_hook_fails() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Hooks"
+kickstart_version = 1
+post_gen_hooks = [{ name = "fail", path = "fail.sh" }]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        ("README.md", "# {{ project_name }}"),
+        ("src/main.rs", "fn main() {}"),
+        ("fail.sh", "#!/bin/sh\nexit 1\n"),
+    ]);
+
+    // Kept by default when it can't ask
+    let kept = tempdir().unwrap();
+    let res =
+        kickstart().arg(tpl.path()).args(["--no-input", "-o"]).arg(kept.path()).output().unwrap();
+    assert!(!res.status.success());
+    assert!(kept.path().join("README.md").exists());
+
+    let output = tempdir().unwrap();
+    fs::write(output.path().join("notes.txt"), "mine").unwrap();
+    let res = kickstart()
+        .arg(tpl.path())
+        .args(["--no-input", "--force", "--clean-on-failure", "-o"])
+        .arg(output.path())
+        .output()
+        .unwrap();
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("Hook `fail` exited with code 1"));
+    let left: Vec<_> =
+        fs::read_dir(output.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+    assert_eq!(left, ["notes.txt"]);
+    assert_eq!(fs::read_to_string(output.path().join("notes.txt")).unwrap(), "mine");
+}
+
+#[test]
+fn non_empty_output_dir_requires_opt_in() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Overlay"
+kickstart_version = 1
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        ("README.md", "# {{ project_name }}"),
+    ]);
+    let output = tempdir().unwrap();
+
+    // Empty, and then only containing the lockfile
+    for _ in 0..2 {
+        let res = kickstart()
+            .arg(tpl.path())
+            .args(["--no-input", "-o"])
+            .arg(output.path())
+            .output()
+            .unwrap();
+        assert!(res.status.success());
+        fs::remove_file(output.path().join("README.md")).unwrap();
+    }
+
+    fs::write(output.path().join("notes.txt"), "mine").unwrap();
+    let res =
+        kickstart().arg(tpl.path()).args(["--no-input", "-o"]).arg(output.path()).output().unwrap();
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("is not empty, use --force"));
+    assert!(!output.path().join("README.md").exists());
+
+    let toml = fs::read_to_string(tpl.path().join("template.toml")).unwrap();
+    fs::write(tpl.path().join("template.toml"), format!("allow_non_empty = true\n{toml}")).unwrap();
+    let res =
+        kickstart().arg(tpl.path()).args(["--no-input", "-o"]).arg(output.path()).output().unwrap();
+    assert!(res.status.success());
+    assert!(output.path().join("README.md").exists());
+}
+
+#[test]
+fn copy_without_render_files_are_copied_verbatim() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Docs"
+kickstart_version = 1
+copy_without_render = ["docs/examples/**", "*.min.js"]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        ("README.md", "# {{ project_name }}"),
+        ("docs/examples/nested/{{ project_name }}.md", "{{ not_a_variable }}"),
+        ("app.min.js", "var a = '{% raw'"),
+    ]);
+    let output = tempdir().unwrap();
+
+    let res =
+        kickstart().arg(tpl.path()).args(["--no-input", "-o"]).arg(output.path()).output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let read = |p: &str| fs::read_to_string(output.path().join(p)).unwrap();
+    assert_eq!(read("README.md"), "# hello");
+    assert_eq!(read("docs/examples/nested/hello.md"), "{{ not_a_variable }}");
+    assert_eq!(read("app.min.js"), "var a = '{% raw'");
+}
+
+#[test]
+fn rendered_paths_cannot_escape_the_output_dir() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Module"
+kickstart_version = 1
+
+[[variables]]
+name = "module_path"
+default = "src"
+prompt = "Where?"
+"#,
+        ),
+        ("{{ module_path }}/mod.rs", "// generated"),
+    ]);
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("out");
+    let answers = dir.path().join("answers.json");
+
+    for (module_path, reason) in [
+        ("../../escaped", "is outside of the output directory"),
+        ("/tmp/escaped", "is outside of the output directory"),
+        ("a//b", "has an empty file or directory name"),
+        ("a/\u{0}", "contains a NUL character"),
+    ] {
+        fs::write(&answers, serde_json::json!({ "module_path": module_path }).to_string()).unwrap();
+        let res = kickstart()
+            .arg(tpl.path())
+            .args(["--no-input", "-o"])
+            .arg(&output)
+            .arg("-i")
+            .arg(&answers)
+            .output()
+            .unwrap();
+        assert!(!res.status.success());
+        let stderr = String::from_utf8_lossy(&res.stderr);
+        assert!(stderr.contains("The template path `{{ module_path }}` renders to"), "{stderr}");
+        assert!(stderr.contains(reason), "{stderr}");
+    }
+    assert!(!dir.path().join("escaped").exists());
+    assert!(fs::read_dir(dir.path()).unwrap().all(|e| e.unwrap().file_name() == "answers.json"));
+}
+
+#[cfg(unix)]
+#[test]
+fn hooks_of_remote_templates_need_to_be_trusted() {
+    let (_dir, _, url) = make_git_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Remote"
+kickstart_version = 1
+pre_prompt_hooks = [{ name = "greet", path = "greet.sh" }]
+post_gen_hooks = [{ name = "touch", path = "touch.sh" }]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+
+[[variables]]
+name = "tool"
+default = "fallback"
+prompt = "Tool?"
+choices = ["fallback"]
+choices_cmd = ["echo", "picked"]
+"#,
+        ),
+        ("README.md", "# {{ project_name }} with {{ tool }}"),
+        ("greet.sh", "#!/bin/sh\necho greeted\n"),
+        ("touch.sh", "#!/bin/sh\ntouch hook-ran\n"),
+    ]);
+
+    let output = tempdir().unwrap();
+    let res = kickstart().arg(&url).args(["--no-input", "-o"]).arg(output.path()).output().unwrap();
+    assert!(!res.status.success());
+    assert!(
+        String::from_utf8_lossy(&res.stderr).contains("use --trust"),
+        "{}",
+        String::from_utf8_lossy(&res.stderr)
+    );
+    assert!(!output.path().join("README.md").exists());
+
+    let res = kickstart()
+        .arg(&url)
+        .args(["--no-input", "--trust", "-o"])
+        .arg(output.path())
+        .output()
+        .unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert!(output.path().join("hook-ran").exists());
+
+    // Everything is asked about once, before running anything
+    let generate = |input: &str| {
+        let output = tempdir().unwrap();
+        let mut command = kickstart();
+        command.arg(&url).args(["--assume-tty", "--yes", "-D", "project_name=hi", "-o"]);
+        let res = run_with_input(command.arg(output.path()), input);
+        let stdout = String::from_utf8_lossy(&res.stdout).to_string();
+        let readme = fs::read_to_string(output.path().join("README.md")).ok();
+        (stdout, readme, output.path().join("hook-ran").exists())
+    };
+    let (stdout, readme, ran) = generate("n\ny\n1\n");
+    assert_eq!(stdout.matches("Run them?").count(), 1, "{stdout}");
+    assert!(stdout.contains("the pre-prompt hook greet (greet.sh)"), "{stdout}");
+    assert!(stdout.contains("the post-gen hook touch (touch.sh)"), "{stdout}");
+    assert!(stdout.contains("`echo picked` to get the choices of `tool`"), "{stdout}");
+    assert!(stdout.contains("greeted"), "{stdout}");
+    assert_eq!(readme.as_deref(), Some("# hi with picked"));
+    assert!(ran);
+
+    let (stdout, readme, ran) = generate("n\nn\n1\n");
+    assert!(!stdout.contains("greeted"), "{stdout}");
+    assert_eq!(readme.as_deref(), Some("# hi with fallback"));
+    assert!(!ran);
+}
+
+#[cfg(unix)]
+#[test]
+fn can_choose_the_ref_of_remote_templates() {
+    let (_dir, repo, url) = make_git_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Remote"
+kickstart_version = 1
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        ("README.md", "one"),
+    ]);
+    let first = git(&repo, &["rev-parse", "HEAD"]);
+    git(&repo, &["tag", "v1"]);
+    let default_branch = git(&repo, &["branch", "--show-current"]);
+    fs::write(repo.join("README.md"), "two").unwrap();
+    git(&repo, &["commit", "-q", "-am", "two"]);
+    git(&repo, &["checkout", "-q", "-b", "feature"]);
+    fs::write(repo.join("README.md"), "three").unwrap();
+    git(&repo, &["commit", "-q", "-am", "three"]);
+    git(&repo, &["checkout", "-q", &default_branch]);
+
+    let generate = |url: &str, args: &[&str]| {
+        let output = tempdir().unwrap();
+        let res = kickstart()
+            .arg(url)
+            .args(["--no-input", "-o"])
+            .arg(output.path())
+            .args(args)
+            .output()
+            .unwrap();
+        let readme = fs::read_to_string(output.path().join("README.md")).ok();
+        let lockfile = fs::read_to_string(output.path().join(".kickstart.json")).ok();
+        (res, readme, lockfile)
+    };
+
+    assert_eq!(generate(&url, &[]).1.as_deref(), Some("two"));
+    assert_eq!(generate(&url, &["--branch", "feature"]).1.as_deref(), Some("three"));
+    assert_eq!(generate(&url, &["--tag", "v1"]).1.as_deref(), Some("one"));
+    let (res, readme, lockfile) = generate(&format!("{url}#v1"), &[]);
+    assert_eq!(readme.as_deref(), Some("one"));
+    assert!(lockfile.unwrap().contains(&first));
+    assert!(String::from_utf8_lossy(&res.stdout).contains(&first));
+
+    let (res, _, _) = generate(&url, &["--tag", "nope"]);
+    assert!(String::from_utf8_lossy(&res.stderr).contains("The tag `nope` was not found"));
+    let (res, _, _) = generate(&url, &["--rev", "deadbeef"]);
+    assert!(String::from_utf8_lossy(&res.stderr).contains("`deadbeef` was not found"));
+    let (res, _, _) = generate("file:///does/not/exist", &[]);
+    assert!(String::from_utf8_lossy(&res.stderr).contains("Could not clone"));
+    let (res, _, _) = generate(&url, &["--branch", "feature", "--tag", "v1"]);
+    assert!(!res.status.success());
+}
+
+#[test]
+fn local_directories_win_over_github_shorthands() {
+    let dir = make_template(&[
+        ("owner/repo/template.toml", "name = \"Local\"\nkickstart_version = 1\nvariables = []\n"),
+        ("owner/repo/README.md", "local"),
+    ]);
+    let output = tempdir().unwrap();
+    let res = kickstart()
+        .current_dir(dir.path())
+        .args(["owner/repo", "--no-input", "-o"])
+        .arg(output.path())
+        .output()
+        .unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(fs::read_to_string(output.path().join("README.md")).unwrap(), "local");
+}
+
+#[cfg(unix)]
+#[test]
+fn remote_forces_shorthands_to_be_cloned() {
+    // Exists locally but --remote is given
+    let dir = make_template(&[(
+        "owner/repo/template.toml",
+        "name = \"Local\"\nkickstart_version = 1\nvariables = []\n",
+    )]);
+    let res = kickstart()
+        .current_dir(dir.path())
+        .args(["owner/repo", "--remote", "--no-input", "-o"])
+        .arg(dir.path().join("out"))
+        // Make sure it doesn't reach GitHub
+        .env("GIT_CONFIG_COUNT", "1")
+        .env("GIT_CONFIG_KEY_0", "url.file:///does/not/exist/.insteadOf")
+        .env("GIT_CONFIG_VALUE_0", "https://github.com/")
+        .output()
+        .unwrap();
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr)
+        .contains("Could not clone https://github.com/owner/repo"));
+}
+
+/// Write a fake `ssh` serving the repositories of `dir` like a git server would
+#[cfg(unix)]
+fn fake_ssh(dir: &Path) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let script = dir.join("fake-ssh");
+    // With the `simple` variant, git calls it with the host and the command to run
+    fs::write(&script, format!("#!/bin/sh\ncd '{}' && eval \"$2\"\n", dir.display())).unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    script
+}
+
+#[cfg(unix)]
+#[test]
+fn can_use_templates_from_ssh_urls() {
+    let (dir, repo, _) = make_git_template(&[
+        (
+            "templates/rust/template.toml",
+            "name = \"Rust\"\nkickstart_version = 1\nvariables = []\n",
+        ),
+        ("templates/rust/README.md", "rust"),
+    ]);
+    let ssh = fake_ssh(dir.path());
+    let name = repo.file_name().unwrap().to_string_lossy().to_string();
+
+    for url in
+        [format!("git@git.internal:{name}"), format!("ssh://git@git.internal{}", repo.display())]
+    {
+        let output = tempdir().unwrap();
+        let res = kickstart()
+            .arg(&url)
+            .args(["-d", "templates/rust", "--no-input", "-o"])
+            .arg(output.path())
+            .env("GIT_SSH_COMMAND", &ssh)
+            .env("GIT_SSH_VARIANT", "simple")
+            .output()
+            .unwrap();
+        assert!(res.status.success(), "{url}: {}", String::from_utf8_lossy(&res.stderr));
+        assert_eq!(fs::read_to_string(output.path().join("README.md")).unwrap(), "rust");
+    }
+
+    // The error of git is shown
+    fs::write(
+        &ssh,
+        "#!/bin/sh\necho 'git@git.internal: Permission denied (publickey).' >&2\nexit 255\n",
+    )
+    .unwrap();
+    let res = kickstart()
+        .arg(format!("git@git.internal:{name}"))
+        .args(["--no-input", "-o"])
+        .arg(dir.path().join("out"))
+        .env("GIT_SSH_COMMAND", &ssh)
+        .env("GIT_SSH_VARIANT", "simple")
+        .output()
+        .unwrap();
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("Permission denied (publickey)"));
+}
+
+/// The total size of the files in a directory
+#[cfg(unix)]
+fn dir_size(dir: &Path) -> u64 {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Some bytes that don't compress
+#[cfg(unix)]
+fn noise(len: usize, mut seed: u64) -> String {
+    (0..len)
+        .map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            char::from(b'a' + (seed % 26) as u8)
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+#[test]
+fn remote_templates_are_cloned_shallow_and_sparse() {
+    let big = noise(1 << 20, 1);
+    let old = noise(1 << 20, 2);
+    let (_dir, repo, url) = make_git_template(&[
+        (
+            "templates/service/template.toml",
+            "name = \"Service\"\nkickstart_version = 1\nvariables = []\n",
+        ),
+        ("templates/service/README.md", "service"),
+        ("other/big.txt", &big),
+        ("old.txt", &old),
+    ]);
+    git(&repo, &["rm", "-q", "old.txt"]);
+    git(&repo, &["commit", "-q", "-m", "remove old"]);
+    // Needed for `--filter` to work over `file://`
+    git(&repo, &["config", "uploadpack.allowFilter", "true"]);
+
+    let clone = |args: &[&str]| {
+        let tmp = tempdir().unwrap();
+        let output = tempdir().unwrap();
+        let res = kickstart()
+            .arg(&url)
+            .args(["-d", "templates/service", "--no-input", "-o"])
+            .arg(output.path())
+            .args(args)
+            .env("KICKSTART_CACHE_DIR", tmp.path())
+            .output()
+            .unwrap();
+        assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+        assert_eq!(fs::read_to_string(output.path().join("README.md")).unwrap(), "service");
+        let clone = fs::read_dir(tmp.path()).unwrap().next().unwrap().unwrap().path();
+        (dir_size(&clone), clone.join("other").exists(), tmp)
+    };
+
+    let (shallow, has_other, _tmp) = clone(&[]);
+    let (full, has_other_full, _tmp_full) = clone(&["--full-clone"]);
+    assert!(!has_other);
+    assert!(has_other_full);
+    // Neither the file outside of the directory nor the history were cloned
+    assert!(shallow < (1 << 20), "{shallow}");
+    assert!(full > 2 * (1 << 20), "{full}");
+}
+
+#[cfg(unix)]
+#[test]
+fn remote_templates_are_cached() {
+    let (dir, repo, url) = make_git_template(&[
+        ("template.toml", "name = \"Cached\"\nkickstart_version = 1\nvariables = []\n"),
+        ("README.md", "one"),
+    ]);
+    git(&repo, &["tag", "v1"]);
+    let commit = |content: &str| {
+        fs::write(repo.join("README.md"), content).unwrap();
+        git(&repo, &["commit", "-q", "-am", content]);
+    };
+    let cache = dir.path().join("cache");
+    let generate = |url: &str, args: &[&str]| {
+        let output = tempdir().unwrap();
+        let res = kickstart()
+            .arg(url)
+            .args(["--no-input", "-o"])
+            .arg(output.path())
+            .args(args)
+            .env("KICKSTART_CACHE_DIR", &cache)
+            .output()
+            .unwrap();
+        let readme = fs::read_to_string(output.path().join("README.md")).ok();
+        (readme, String::from_utf8_lossy(&res.stderr).to_string())
+    };
+
+    assert_eq!(generate(&url, &[]).0.as_deref(), Some("one"));
+    assert_eq!(fs::read_dir(&cache).unwrap().count(), 1);
+    // The cached clone is updated when used
+    commit("two");
+    assert_eq!(generate(&url, &[]).0.as_deref(), Some("two"));
+    commit("three");
+    assert_eq!(generate(&url, &["--offline"]).0.as_deref(), Some("two"));
+    assert_eq!(generate(&url, &["--refresh"]).0.as_deref(), Some("three"));
+    // Tags have their own entry
+    assert_eq!(generate(&url, &["--tag", "v1"]).0.as_deref(), Some("one"));
+    assert_eq!(generate(&url, &["--offline"]).0.as_deref(), Some("three"));
+    assert_eq!(fs::read_dir(&cache).unwrap().count(), 2);
+
+    let (readme, stderr) = generate(&format!("{url}-other"), &["--offline"]);
+    assert_eq!(readme, None);
+    assert!(stderr.contains("was never cloned so it can't be used offline"), "{stderr}");
+
+    let res =
+        kickstart().args(["cache", "clear"]).env("KICKSTART_CACHE_DIR", &cache).output().unwrap();
+    assert!(res.status.success());
+    assert_eq!(fs::read_dir(&cache).unwrap().count(), 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn can_use_templates_from_archives() {
+    let dir = make_template(&[
+        (
+            "bundle/templates/rust/template.toml",
+            "name = \"Rust\"\nkickstart_version = 1\nvariables = []\n",
+        ),
+        ("bundle/templates/rust/README.md", "rust"),
+        ("bundle/other.txt", "other"),
+    ]);
+    let tarball = dir.path().join("bundle.tar.gz");
+    let zip = dir.path().join("bundle.zip");
+    assert!(Command::new("tar")
+        .arg("-czf")
+        .arg(&tarball)
+        .arg("bundle")
+        .current_dir(dir.path())
+        .status()
+        .unwrap()
+        .success());
+    assert!(Command::new("zip")
+        .arg("-qr")
+        .arg(&zip)
+        .arg("bundle")
+        .current_dir(dir.path())
+        .status()
+        .unwrap()
+        .success());
+
+    let generate = |archive: &Path, directory: &str| {
+        let output = tempdir().unwrap();
+        let res = kickstart()
+            .arg(archive)
+            .args(["-d", directory, "--no-input", "-o"])
+            .arg(output.path())
+            .output()
+            .unwrap();
+        let readme = fs::read_to_string(output.path().join("README.md")).ok();
+        (readme, String::from_utf8_lossy(&res.stderr).to_string())
+    };
+
+    // The single top-level directory is the root
+    assert_eq!(generate(&tarball, "templates/rust").0.as_deref(), Some("rust"));
+    assert_eq!(generate(&zip, "templates/rust").0.as_deref(), Some("rust"));
+    let (readme, stderr) = generate(&tarball, "nope");
+    assert_eq!(readme, None);
+    assert!(stderr.contains("No template.toml found in the `nope` directory of"), "{stderr}");
+}
+
+#[cfg(unix)]
+#[test]
+fn archives_cannot_extract_outside_of_their_directory() {
+    let dir = make_template(&[
+        ("inside/template.toml", "name = \"Evil\"\nkickstart_version = 1\nvariables = []\n"),
+        ("evil", "evil"),
+    ]);
+    let zip = dir.path().join("evil.zip");
+    assert!(Command::new("zip")
+        .arg("-q")
+        .arg(&zip)
+        .args(["template.toml", "../evil"])
+        .current_dir(dir.path().join("inside"))
+        .status()
+        .unwrap()
+        .success());
+
+    let res = kickstart()
+        .arg(&zip)
+        .args(["--no-input", "-o"])
+        .arg(dir.path().join("out"))
+        .output()
+        .unwrap();
+    assert!(!res.status.success());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("`../evil` would be extracted outside of the archive"), "{stderr}");
+
+    // Download errors are reported as such
+    let res = kickstart()
+        .args(["http://127.0.0.1:9/template.tar.gz", "--no-input", "-o"])
+        .arg(dir.path().join("out"))
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&res.stderr)
+        .contains("Could not download http://127.0.0.1:9/template.tar.gz"));
+}
+
+#[test]
+fn info_shows_the_template_without_running_anything() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Info"
+description = "What it asks"
+kickstart_version = 1
+pre_gen_hooks = [{ name = "touch", path = "touch.sh" }]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+validation = "identifier"
+
+[[variables]]
+name = "database"
+default = "postgres"
+prompt = "Database?"
+choices = ["postgres", { value = "sqlite", label = "SQLite 3" }]
+help = "Where the data is stored"
+
+[[variables]]
+name = "port"
+default = 5432
+prompt = "Port?"
+only_if = { name = "database", value = "postgres" }
+"#,
+        ),
+        ("touch.sh", "#!/bin/sh\ntouch hook-ran\n"),
+    ]);
+
+    let res = kickstart().arg("info").arg(dir.path()).current_dir(dir.path()).output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(stdout.contains("What it asks"));
+    assert!(stdout.contains("hello") && stdout.contains("identifier"));
+    assert!(stdout.contains("[postgres, sqlite (SQLite 3)]"), "{stdout}");
+    assert!(stdout.contains("integer") && stdout.contains("`database` is postgres"));
+    assert!(stdout.contains("  database: Where the data is stored"), "{stdout}");
+    assert!(stdout.contains("- touch (touch.sh)"));
+    assert!(!dir.path().join("hook-ran").exists());
+
+    let res = kickstart().arg("info").arg(dir.path()).args(["--format", "json"]).output().unwrap();
+    let info: serde_json::Value = serde_json::from_slice(&res.stdout).unwrap();
+    assert_eq!(info["name"], "Info");
+    assert_eq!(info["variables"][1]["choices"][1]["label"], "SQLite 3");
+    assert_eq!(info["variables"][1]["help"], "Where the data is stored");
+    assert_eq!(info["variables"][2]["default"], 5432);
+    assert_eq!(info["variables"][2]["only_if"]["name"], "database");
+    assert_eq!(info["pre_gen_hooks"][0]["path"], "touch.sh");
+}
+
+#[test]
+fn choices_show_their_label_but_store_their_value() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Labels"
+kickstart_version = 1
+
+[[variables]]
+name = "database"
+default = "postgres"
+prompt = "Database?"
+choices = ["postgres", { value = "sqlite", label = "SQLite 3" }]
+"#,
+        ),
+        ("database.txt", "{{ database }}"),
+    ]);
+    let (res, out) = generate_with_input(dir.path(), &["--yes", "--assume-tty"], "2\n");
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(stdout.contains("2. SQLite 3"), "{stdout}");
+    assert_eq!(fs::read_to_string(out.path().join("database.txt")).unwrap(), "sqlite");
+}
+
+#[test]
+fn several_templates_need_a_directory_without_input() {
+    let output = tempdir().unwrap();
+    let res =
+        kickstart().args(["examples", "--no-input", "-o"]).arg(output.path()).output().unwrap();
+    assert!(!res.status.success());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("choose one with --directory"), "{stderr}");
+    assert!(stderr.contains("  - complex: A fully-featured Django template"), "{stderr}");
+    assert!(stderr.contains("  - super-basic"), "{stderr}");
+}
+
+#[test]
+fn can_use_aliases_of_templates() {
+    let dir = make_template(&[
+        (
+            "templates/svc/template.toml",
+            "name = \"Service\"\nkickstart_version = 1\nvariables = []\n",
+        ),
+        ("templates/svc/README.md", "service"),
+    ]);
+    let config = dir.path().join("config").join("config.toml");
+    let run = |args: &[&str]| {
+        let res = kickstart().args(args).env("KICKSTART_CONFIG", &config).output().unwrap();
+        assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+        String::from_utf8_lossy(&res.stdout).to_string()
+    };
+
+    let source = dir.path().to_string_lossy().to_string();
+    run(&["alias", "add", "service", &source, "-d", "templates/svc"]);
+    assert!(run(&["alias", "list"]).contains("service: "));
+    assert!(run(&["info", "service"]).contains("Service"));
+    let output = tempdir().unwrap();
+    run(&["service", "--no-input", "-o", &output.path().to_string_lossy()]);
+    assert_eq!(fs::read_to_string(output.path().join("README.md")).unwrap(), "service");
+
+    // A local path of the same name wins
+    let cwd = make_template(&[(
+        "service/template.toml",
+        "name = \"Local\"\nkickstart_version = 1\nvariables = []\n",
+    )]);
+    let res = kickstart()
+        .args(["info", "service"])
+        .current_dir(cwd.path())
+        .env("KICKSTART_CONFIG", &config)
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&res.stdout).contains("Local"));
+
+    run(&["alias", "remove", "service"]);
+    assert!(!run(&["alias", "list"]).contains("service"));
+    let res = kickstart()
+        .args(["alias", "remove", "service"])
+        .env("KICKSTART_CONFIG", &config)
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&res.stderr).contains("There is no `service` alias"));
+}
+
+#[cfg(unix)]
+#[test]
+fn aliases_of_branches_follow_them() {
+    let (dir, repo, url) = make_git_template(&[
+        ("template.toml", "name = \"Service\"\nkickstart_version = 1\nvariables = []\n"),
+        ("README.md", "first"),
+    ]);
+    git(&repo, &["checkout", "-q", "-b", "dev"]);
+    let config = dir.path().join("config.toml");
+    let cache = dir.path().join("cache");
+    let run = |args: &[&str]| {
+        let res = kickstart()
+            .args(args)
+            .env("KICKSTART_CONFIG", &config)
+            .env("KICKSTART_CACHE_DIR", &cache)
+            .output()
+            .unwrap();
+        assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+        String::from_utf8_lossy(&res.stdout).to_string()
+    };
+    let generate = || {
+        let output = tempdir().unwrap();
+        run(&["service", "--no-input", "-o", &output.path().to_string_lossy()]);
+        fs::read_to_string(output.path().join("README.md")).unwrap()
+    };
+
+    run(&["alias", "add", "service", &url, "--branch", "dev"]);
+    assert!(run(&["alias", "list"]).contains("(branch `dev`)"));
+    assert_eq!(generate(), "first");
+
+    fs::write(repo.join("README.md"), "second").unwrap();
+    git(&repo, &["commit", "-q", "-am", "second"]);
+    assert_eq!(generate(), "second");
+}
+
+#[test]
+fn output_dir_defaults_to_the_one_of_the_template() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Output dir"
+kickstart_version = 1
+output_dir = "{{ project_name | lower | replace(from=' ', to='-') }}"
+
+[[variables]]
+name = "project_name"
+default = "My Project"
+prompt = "Name?"
+"#,
+        ),
+        ("README.md", "{{ project_name }}"),
+    ]);
+    let cwd = tempdir().unwrap();
+    let generate = |args: &[&str]| {
+        kickstart()
+            .arg(dir.path())
+            .arg("--no-input")
+            .args(args)
+            .current_dir(cwd.path())
+            .output()
+            .unwrap()
+    };
+
+    let res = generate(&[]);
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let expected = cwd.path().canonicalize().unwrap().join("my-project");
+    assert_eq!(fs::read_to_string(expected.join("README.md")).unwrap(), "My Project");
+    assert!(String::from_utf8_lossy(&res.stdout)
+        .contains(&format!("Generated in {}", expected.display())));
+
+    // -o wins
+    let res = generate(&["-o", "elsewhere"]);
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert!(cwd.path().join("elsewhere").join("README.md").exists());
+
+    let res = generate(&["-D", "project_name=.."]);
+    assert!(!res.status.success());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("The `output_dir` of the template renders to `..`"), "{stderr}");
+}
+
+#[test]
+fn templates_can_use_the_values_of_kickstart() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Builtins"
+kickstart_version = 1
+output_dir = "project-{{ kickstart.year }}"
+
+[[variables]]
+name = "author"
+default = "{{ kickstart.username }}"
+prompt = "Author?"
+"#,
+        ),
+        (
+            "LICENSE",
+            "Copyright {{ kickstart.year }} {{ author }}\n\
+             Generated on {{ kickstart.date }} ({{ kickstart.timestamp }}) on {{ kickstart.os }}/{{ kickstart.arch }} by kickstart {{ kickstart.version }}",
+        ),
+    ]);
+    let cwd = tempdir().unwrap();
+    let res = kickstart()
+        .arg(dir.path())
+        .args(["--no-input", "--deterministic"])
+        .env("USER", "ferris")
+        .current_dir(cwd.path())
+        .output()
+        .unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let license = fs::read_to_string(cwd.path().join("project-2000").join("LICENSE")).unwrap();
+    assert_eq!(
+        license,
+        format!(
+            "Copyright 2000 ferris\nGenerated on 2000-01-01 (946684800) on {}/{} by kickstart {}",
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+            env!("CARGO_PKG_VERSION")
+        )
+    );
+}
+
+#[test]
+fn templates_can_use_the_git_identity() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Git identity"
+kickstart_version = 1
+
+[[variables]]
+name = "author"
+default = "Unknown"
+default_from = "git.user.name"
+prompt = "Author?"
+"#,
+        ),
+        (
+            "Cargo.toml",
+            r#"authors = ["{{ kickstart.git.user_name }} <{{ kickstart.git.user_email }}>"] # {{ author }}"#,
+        ),
+    ]);
+    let gitconfig = dir.path().join("gitconfig");
+    fs::write(&gitconfig, "[user]\n\tname = Ferris\n\temail = ferris@example.com\n").unwrap();
+    let generate = |gitconfig: &Path, path: Option<&str>| {
+        let out = tempdir().unwrap();
+        let mut cmd = kickstart();
+        cmd.arg(dir.path())
+            .arg("--no-input")
+            .arg("-o")
+            .arg(out.path())
+            // Not in this repository, which might have its own identity
+            .current_dir(out.path())
+            .env("GIT_CONFIG_GLOBAL", gitconfig)
+            .env("GIT_CONFIG_NOSYSTEM", "1");
+        if let Some(path) = path {
+            cmd.env("PATH", path);
+        }
+        let res = cmd.output().unwrap();
+        assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+        fs::read_to_string(out.path().join("Cargo.toml")).unwrap()
+    };
+
+    assert_eq!(generate(&gitconfig, None), r#"authors = ["Ferris <ferris@example.com>"] # Ferris"#);
+    // Neither set nor git installed: everything is empty and the static default is used
+    let empty = dir.path().join("empty-gitconfig");
+    fs::write(&empty, "").unwrap();
+    assert_eq!(generate(&empty, None), r#"authors = [" <>"] # Unknown"#);
+    let no_git = tempdir().unwrap();
+    assert_eq!(generate(&gitconfig, no_git.path().to_str()), r#"authors = [" <>"] # Unknown"#);
+}
+
+#[test]
+fn computed_variables_are_not_asked() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Computed"
+kickstart_version = 1
+
+[[variables]]
+name = "project_name"
+default = "My Project"
+prompt = "Name?"
+
+[[variables]]
+name = "project_slug"
+computed = "{{ project_name | slugify }}"
+"#,
+        ),
+        ("README.md", "{{ project_name }} ({{ project_slug }})"),
+    ]);
+    let generate = |args: &[&str]| {
+        let out = tempdir().unwrap();
+        let res =
+            kickstart().arg(dir.path()).arg("-o").arg(out.path()).args(args).output().unwrap();
+        (res, out)
+    };
+
+    let (res, out) = generate(&["--no-input", "-D", "project_name=Hello World"]);
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(
+        fs::read_to_string(out.path().join("README.md")).unwrap(),
+        "Hello World (hello-world)"
+    );
+
+    let (res, _) = generate(&["--no-input", "-D", "project_slug=nope"]);
+    assert!(!res.status.success());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("Variable project_slug is computed"), "{stderr}");
+
+    let answers = tempdir().unwrap();
+    let input = answers.path().join("answers.json");
+    fs::write(&input, r#"{"project_name": "Hello", "project_slug": "nope"}"#).unwrap();
+    let (res, _) = generate(&["--input-file", input.to_str().unwrap()]);
+    assert!(!res.status.success());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("variable `project_slug` is computed"), "{stderr}");
+
+    fs::write(&input, r#"{"project_name": "Hello"}"#).unwrap();
+    let (res, out) = generate(&["--input-file", input.to_str().unwrap()]);
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(fs::read_to_string(out.path().join("README.md")).unwrap(), "Hello (hello)");
+}
+
+#[test]
+fn validate_can_render_the_template() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Typos"
+kickstart_version = 1
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        ("README.md", "# {{ projetc_name }}"),
+        ("src/main.rs", "fn main() {\n    println!(\"{{ project_nmae }}\");\n}\n"),
+    ]);
+    let validate = |args: &[&str]| {
+        kickstart()
+            .arg("validate")
+            .arg(dir.path().join("template.toml"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let res = validate(&[]);
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+
+    let res = validate(&["--render"]);
+    assert!(!res.status.success());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(
+        stderr.contains("README.md:1:6: the variable `projetc_name` is not defined"),
+        "{stderr}"
+    );
+    let main = Path::new("src").join("main.rs");
+    assert!(
+        stderr.contains(&format!("{}:2:18: the variable `project_nmae`", main.display())),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn validate_render_reports_every_failure() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Broken"
+kickstart_version = 1
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+
+[[variables]]
+name = "ci"
+default = false
+prompt = "CI?"
+
+[[conditional_files]]
+paths = [".github/{{ ci_dir }}"]
+only_if = { name = "ci", value = true }
+
+[[post_gen_hooks]]
+name = "after"
+path = "after.sh"
+os = ["windows"]
+"#,
+        ),
+        ("README.md", "# {{ project_name"),
+        ("{{ project_name | nope }}.txt", "hi"),
+        ("after.sh", "echo {{ project_name }"),
+    ]);
+
+    // The hook files are looked for from the current directory when validating the definition
+    let res = kickstart()
+        .current_dir(dir.path())
+        .args(["validate", "template.toml", "--render"])
+        .output()
+        .unwrap();
+    assert!(!res.status.success());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("Failed to parse `README.md`\n --> 1:"), "{stderr}");
+    assert!(stderr.contains("`{{ project_name | nope }}.txt`"), "{stderr}");
+    assert!(stderr.contains("Filter 'nope' not found"), "{stderr}");
+    assert!(stderr.contains("Failed to parse `after.sh`"), "{stderr}");
+    assert!(stderr.contains("Variable `ci_dir` not found"), "{stderr}");
+}
+
+#[test]
+fn validate_can_check_how_variables_are_used() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Usage"
+kickstart_version = 1
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+
+[[variables]]
+name = "forgotten"
+default = "x"
+prompt = "Forgotten?"
+"#,
+        ),
+        ("README.md", "# {{ project_name }} by {{ author }}"),
+    ]);
+
+    let res = kickstart()
+        .arg("validate")
+        .arg(dir.path().join("template.toml"))
+        .arg("--check-usage")
+        .output()
+        .unwrap();
+    // False positives are possible so those are only warnings
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("the variable `forgotten` doesn't seem to be used"), "{stderr}");
+    assert!(stderr.contains("`README.md` uses `author`"), "{stderr}");
+}
+
+#[test]
+fn templates_can_require_a_kickstart_version() {
+    let template = |version: &str| {
+        make_template(&[
+            (
+                "template.toml",
+                &format!(
+                    "name = 'test'\nkickstart_version = '{version}'\nfrom_the_future = true\n\
+                     [[variables]]\nname = 'name'\ndefault = 'World'\nprompt = 'Name?'\n\
+                     kind = 'from the future too'\n"
+                ),
+            ),
+            ("README.md", "Hello {{ name }}\n"),
+        ])
+    };
+    let output = tempdir().unwrap();
+
+    // Rather than failing on `kind`, before asking anything
+    let dir = template(">=99");
+    let res = kickstart()
+        .arg(dir.path())
+        .arg("-o")
+        .arg(output.path())
+        .arg("--assume-tty")
+        .output()
+        .unwrap();
+    assert!(!res.status.success());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("This template requires kickstart >=99, you have"), "{stderr}");
+    assert!(!String::from_utf8_lossy(&res.stdout).contains("Name?"));
+
+    let dir = template(">=0.5");
+    fs::write(
+        dir.path().join("template.toml"),
+        "name = 'test'\nkickstart_version = '>=0.5'\nfrom_the_future = true\n\
+         [[variables]]\nname = 'name'\ndefault = 'World'\nprompt = 'Name?'\n",
+    )
+    .unwrap();
+    let res = kickstart().arg("validate").arg(dir.path().join("template.toml")).output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert_eq!(stderr.matches("Unknown key `from_the_future`").count(), 1, "{stderr}");
+
+    // Warnings are still shown when quiet
+    let (res, _) = generate_with_input(dir.path(), &["--no-input", "--quiet"], "");
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("Unknown key `from_the_future`"), "{stderr}");
+}
+
+#[test]
+fn integers_need_to_be_within_their_range() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Workers"
+kickstart_version = 1
+
+[[variables]]
+name = "workers"
+default = 4
+prompt = "How many workers?"
+min = 1
+max = 8
+"#,
+        ),
+        ("workers.txt", "{{ workers }}"),
+    ]);
+    let generate = |args: &[&str], stdin: &str| {
+        let args = [args, &["--assume-tty"]].concat();
+        let (res, out) = generate_with_input(dir.path(), &args, stdin);
+        let workers = fs::read_to_string(out.path().join("workers.txt")).ok();
+        (res, workers)
+    };
+
+    // Out of range answers are asked again
+    let (res, workers) = generate(&["--yes"], "9\n0\n8\n");
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(workers.as_deref(), Some("8"));
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(stdout.contains("between 1 and 8"), "{stdout}");
+
+    for (define, expected) in
+        [("workers=1", Some("1")), ("workers=8", Some("8")), ("workers=9", None)]
+    {
+        let (res, workers) = generate(&["--no-input", "-D", define], "");
+        assert_eq!(workers.as_deref(), expected, "{define}");
+        if expected.is_none() {
+            let stderr = String::from_utf8_lossy(&res.stderr);
+            assert!(
+                stderr.contains("- workers: The value needs to be between 1 and 8"),
+                "{stderr}"
+            );
+        }
+    }
+}
+
+#[test]
+fn strings_need_to_have_the_right_length() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Names"
+kickstart_version = 1
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+min_length = 3
+max_length = 6
+validation = "^\\p{Ll}+$"
+pattern_hint = "lowercase letters"
+"#,
+        ),
+        ("name.txt", "{{ project_name }}"),
+    ]);
+    let generate = |args: &[&str], stdin: &str| {
+        let args = [args, &["--assume-tty"]].concat();
+        let (res, out) = generate_with_input(dir.path(), &args, stdin);
+        let name = fs::read_to_string(out.path().join("name.txt")).ok();
+        (res, name)
+    };
+
+    // 6 characters but 12 bytes
+    let (res, name) = generate(&["--yes"], "ab\nHello\nгоспод\n");
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(name.as_deref(), Some("господ"));
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(stdout.contains("lowercase letters, between 3 and 6 characters"), "{stdout}");
+
+    let (res, name) = generate(&["--no-input", "-D", "project_name=toolong"], "");
+    assert!(name.is_none());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("- project_name: The value must be between 3 and 6"), "{stderr}");
+}
+
+#[test]
+fn help_is_shown_when_asking() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Help"
+kickstart_version = 1
+
+[[variables]]
+name = "database"
+default = "postgres"
+prompt = "Database?"
+help = "The database the service stores its data in, which can be changed later on"
+choices = [
+    { value = "postgres", description = "Recommended in production" },
+    { value = "sqlite", label = "SQLite 3" },
+]
+"#,
+        ),
+        ("database.txt", "{{ database }}"),
+    ]);
+    let generate = |args: &[&str], stdin: &str| {
+        let out = tempdir().unwrap();
+        let mut cmd = kickstart();
+        cmd.arg(dir.path()).arg("-o").arg(out.path()).args(args).env("COLUMNS", "40");
+        let res = run_with_input(cmd.arg("--assume-tty"), stdin);
+        let database = fs::read_to_string(out.path().join("database.txt")).ok();
+        (res, database)
+    };
+
+    let (res, database) = generate(&["--yes"], "2\n");
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(database.as_deref(), Some("sqlite"));
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(stdout.contains("  The database the service stores its\n"), "{stdout}");
+    assert!(stdout.contains("  data in, which can be changed later on\n"), "{stdout}");
+    assert!(stdout.contains("  1. postgres - Recommended in production\n"), "{stdout}");
+    assert!(stdout.contains("  2. SQLite 3\n"), "{stdout}");
+
+    let (res, database) = generate(&["--no-input"], "");
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(database.as_deref(), Some("postgres"));
+    assert!(!String::from_utf8_lossy(&res.stdout).contains("The database"));
+}
+
+#[test]
+fn prompts_can_use_previous_answers() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Prompts"
+kickstart_version = 1
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+
+[[variables]]
+name = "cache"
+default = false
+prompt = "Should {{ project_name | upper }} have a cache?"
+
+[[variables]]
+name = "cache_size"
+default = 64
+prompt = "Size of the cache of {{ project_name }}?"
+only_if = { name = "cache", value = true }
+
+[[variables]]
+name = "database"
+default = "postgres"
+prompt = "Database of {{ project_name }} next to {{ cache_size }} MB of cache?"
+"#,
+        ),
+        ("database.txt", "{{ database }}"),
+    ]);
+    let (res, out) = generate_with_input(dir.path(), &["--yes", "--assume-tty"], "shop\n\nmysql\n");
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(fs::read_to_string(out.path().join("database.txt")).unwrap(), "mysql");
+
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(stdout.contains("Should SHOP have a cache?"), "{stdout}");
+
+    // `cache_size` was skipped so the last prompt can't be rendered
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("Could not render the prompt of `database`"), "{stderr}");
+    assert!(stdout.contains("Database of {{ project_name }} next to"), "{stdout}");
+}
+
+#[cfg(unix)]
+#[test]
+fn interruptions_stop_everything_cleanly() {
+    use std::io::Read;
+    use std::time::{Duration, Instant};
+
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Slow"
+kickstart_version = 1
+post_gen_hooks = [{ name = "slow", path = "slow.sh" }]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        ("slow.sh", "#!/bin/sh\necho started\nexec sleep 30\n"),
+        ("name.txt", "{{ project_name }}"),
+    ]);
+    // Interrupted once `ready` is on its stdout
+    let interrupt = |args: &[&str], ready: &str| {
+        let mut child = kickstart()
+            .arg(dir.path())
+            .args(args)
+            .arg("--assume-tty")
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        // Kept open until it exits so it can still write to it
+        let mut stdout = child.stdout.take().unwrap();
+        let (mut seen, mut buf) = (Vec::new(), [0; 1024]);
+        while !String::from_utf8_lossy(&seen).contains(ready) {
+            let n = stdout.read(&mut buf).unwrap();
+            assert!(n > 0, "{ready:?} not in {:?}", String::from_utf8_lossy(&seen));
+            seen.extend_from_slice(&buf[..n]);
+        }
+        unsafe {
+            libc::kill(child.id() as i32, libc::SIGINT);
+        }
+        let started = Instant::now();
+        let res = child.wait_with_output().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10), "the hook was not killed");
+        assert_eq!(res.status.code(), Some(130));
+        drop(stdout);
+        String::from_utf8_lossy(&res.stderr).to_string()
+    };
+
+    // While asking
+    let out = tempdir().unwrap();
+    let output_dir = out.path().join("project");
+    let stderr = interrupt(&["-o", output_dir.to_str().unwrap()], "Name?");
+    assert!(stderr.contains("Aborted."), "{stderr}");
+    assert!(!output_dir.exists());
+
+    // While running a post-gen hook, once everything was generated
+    let stderr = interrupt(&["-o", output_dir.to_str().unwrap(), "--no-input"], "started");
+    assert!(stderr.contains("created in the output directory and were kept"), "{stderr}");
+    assert!(stderr.contains(&format!("  - {}\n", output_dir.display())), "{stderr}");
+    assert!(stderr.contains("Aborted."), "{stderr}");
+}
+
+#[test]
+fn questions_are_not_asked_without_a_terminal() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Questions"
+kickstart_version = 1
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+
+[[variables]]
+name = "port"
+default = 80
+prompt = "Port?"
+"#,
+        ),
+        ("name.txt", "{{ project_name }}:{{ port }}"),
+    ]);
+    let generate = |args: &[&str], stdin: &str| {
+        let (res, out) = generate_with_input(dir.path(), args, stdin);
+        let name = fs::read_to_string(out.path().join("name.txt")).ok();
+        let stderr = String::from_utf8_lossy(&res.stderr).to_string();
+        (res, stderr, name)
+    };
+
+    let (res, stderr, name) = generate(&[], "world\n8080\n");
+    assert!(!res.status.success());
+    assert!(
+        stderr.contains("stdin is not a terminal") && stderr.contains("--no-input"),
+        "{stderr}"
+    );
+    assert!(name.is_none());
+
+    // Fails at the first question that is not answered
+    let (_, stderr, name) = generate(&["-D", "project_name=world", "--yes"], "8080\n");
+    assert!(stderr.contains("stdin is not a terminal"), "{stderr}");
+    assert!(name.is_none());
+    let (res, _, name) = generate(&["-D", "project_name=world", "-D", "port=8080", "--yes"], "");
+    assert!(res.status.success());
+    assert_eq!(name.as_deref(), Some("world:8080"));
+    // The confirmation is asked too
+    let (_, stderr, name) = generate(&["-D", "project_name=world", "-D", "port=8080"], "y\n");
+    assert!(stderr.contains("stdin is not a terminal"), "{stderr}");
+    assert!(name.is_none());
+
+    // Only known once the template is loaded
+    let out = tempdir().unwrap();
+    let mut command = kickstart();
+    command.arg(dir.path()).arg("-o").arg(out.path()).arg("--yes");
+    command.env("KICKSTART_VAR_PROJECT_NAME", "env").env("KICKSTART_VAR_PORT", "8080");
+    let res = run_with_input(&mut command, "");
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(fs::read_to_string(out.path().join("name.txt")).unwrap(), "env:8080");
+
+    let (res, _, name) = generate(&["-i", "-"], r#"{"project_name": "world", "port": 8080}"#);
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(name.as_deref(), Some("world:8080"));
+
+    let (res, _, name) = generate(&["--assume-tty", "--yes"], "world\n8080\n");
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(name.as_deref(), Some("world:8080"));
+}
+
+#[test]
+fn colors_are_only_used_in_terminals() {
+    let info = |args: &[&str], no_color: Option<&str>| {
+        let mut command = kickstart();
+        command.args(["info", "examples/super-basic"]).args(args).env("TERM", "xterm");
+        match no_color {
+            Some(value) => command.env("NO_COLOR", value),
+            None => command.env_remove("NO_COLOR"),
+        };
+        let res = command.output().unwrap();
+        assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+        String::from_utf8_lossy(&res.stdout).to_string()
+    };
+
+    for (args, no_color) in [
+        (&[][..], None),
+        (&["--color", "never"][..], None),
+        (&["--no-color"][..], None),
+        (&["--color", "always", "--no-color"][..], None),
+        (&["--color", "auto"][..], Some("1")),
+    ] {
+        let stdout = info(args, no_color);
+        assert!(
+            stdout.starts_with("Super basic\nA very simple template\n"),
+            "{args:?}: {stdout:?}"
+        );
+        assert!(!stdout.contains('\x1b'), "{args:?}: {stdout:?}");
+    }
+    #[cfg(unix)]
+    assert!(info(&["--color", "always"], Some("1")).contains('\x1b'));
+
+    // Errors are on stderr only
+    let res = kickstart().args(["info", "examples/not-there"]).output().unwrap();
+    assert!(!res.status.success());
+    assert!(res.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.starts_with("Error: ") && !stderr.contains('\x1b'), "{stderr:?}");
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_can_be_reported_as_json() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Report"
+kickstart_version = 1
+post_gen_hooks = [
+    { name = "greet", path = "greet.sh" },
+    { name = "fail", path = "fail.sh", only_if = { name = "fail", value = true } },
+]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+
+[[variables]]
+name = "port"
+default = 8080
+prompt = "Port?"
+
+[[variables]]
+name = "fail"
+default = false
+prompt = "Fail?"
+
+[[variables]]
+name = "token"
+default = ""
+prompt = "Token?"
+secret = true
+"#,
+        ),
+        ("README.md", "# {{ project_name }}\n"),
+        ("greet.sh", "#!/bin/sh\necho hello from the hook\n"),
+        ("fail.sh", "#!/bin/sh\necho broken >&2\nexit 3\n"),
+    ]);
+    let run = |output: &Path, args: &[&str]| {
+        let res = kickstart()
+            .arg(tpl.path())
+            .args(["--format", "json", "-o"])
+            .arg(output)
+            .args(args)
+            .output()
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&res.stdout)
+            .unwrap_or_else(|e| panic!("{e}: {}", String::from_utf8_lossy(&res.stdout)));
+        (res.status.success(), json, String::from_utf8_lossy(&res.stderr).to_string())
+    };
+
+    let output = tempdir().unwrap();
+    let (success, json, _) = run(output.path(), &["--no-input"]);
+    assert!(success, "{json}");
+    assert_eq!(json["success"], true);
+    assert_eq!(json["commit"], serde_json::Value::Null);
+    assert_eq!(json["variables"]["project_name"], "hello");
+    assert_eq!(json["variables"]["port"], 8080);
+    assert!(json["variables"].get("token").is_none());
+    assert_eq!(json["files"][0]["path"], "README.md");
+    assert_eq!(json["files"][0]["size"], 8);
+    assert_eq!(json["files"][0]["rendered"], true);
+    assert_eq!(json["files"][0]["sha256"].as_str().map(str::len), Some(64));
+    assert_eq!(json["hooks"][0]["name"], "greet");
+    assert_eq!(json["hooks"][0]["stage"], "post-gen");
+    assert_eq!(json["hooks"][0]["exit_code"], 0);
+    assert!(json["hooks"][0]["duration_ms"].is_u64());
+    assert_eq!(json["warnings"], serde_json::json!(["Skipping hook fail (condition not met)"]));
+    assert_eq!(json["error"], serde_json::Value::Null);
+    // The same files as when generating
+    let dry_run = tempdir().unwrap();
+    let (success, dry_json, _) = run(dry_run.path(), &["--no-input", "--dry-run"]);
+    assert!(success, "{dry_json}");
+    assert_eq!(dry_json["dry_run"], true);
+    assert_eq!(dry_json["files"], json["files"]);
+
+    let output = tempdir().unwrap();
+    let (success, json, stderr) = run(output.path(), &["--no-input", "-D", "fail=true"]);
+    assert!(!success);
+    assert_eq!(json["success"], false);
+    assert_eq!(json["hooks"][1]["exit_code"], 3);
+    assert_eq!(json["error"]["kind"], "Other");
+    assert_eq!(json["error"]["message"], "Hook `fail` exited with code 3");
+    assert!(stderr.contains("broken"), "{stderr}");
+
+    let (success, json, _) = run(output.path(), &["--no-input", "-D", "port=http"]);
+    assert!(!success);
+    assert_eq!(json["error"]["kind"], "InvalidVariableValue");
+
+    // Nothing can be asked
+    let (success, json, _) = run(output.path(), &["--assume-tty"]);
+    assert!(!success);
+    assert!(json["error"]["message"].as_str().unwrap().contains("--no-input"), "{json}");
+}
+
+#[test]
+fn what_happens_can_be_logged() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Logs"
+kickstart_version = 1
+ignore = ["NOTES.md"]
+
+[[variables]]
+name = "docker"
+default = false
+prompt = "Docker?"
+
+[[conditional_files]]
+paths = ["Dockerfile"]
+only_if = { name = "docker", value = true }
+"#,
+        ),
+        ("README.md", "Hello\n"),
+        ("NOTES.md", "Notes\n"),
+        ("Dockerfile", "FROM scratch\n"),
+    ]);
+
+    let output = tempdir().unwrap();
+    let res = kickstart()
+        .arg(tpl.path())
+        .args(["--no-input", "-v", "-o"])
+        .arg(output.path())
+        .output()
+        .unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    for expected in [
+        "DEBUG kickstart::generation: Rendering README.md",
+        "Skipping NOTES.md: it is ignored by `NOTES.md`",
+        "Skipping Dockerfile: its `conditional_files` condition is not met",
+    ] {
+        assert!(stderr.contains(expected), "{expected} not found in:\n{stderr}");
+    }
+    assert!(String::from_utf8_lossy(&res.stdout).contains("Everything done"));
+
+    // Quiet on the terminal but everything is in the log file
+    let output = tempdir().unwrap();
+    let log_file = output.path().join("kickstart.log");
+    let res = kickstart()
+        .arg(tpl.path())
+        .args(["--no-input", "-q", "--log-file"])
+        .arg(&log_file)
+        .arg("-o")
+        .arg(output.path().join("project"))
+        .output()
+        .unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert!(res.stdout.is_empty(), "{}", String::from_utf8_lossy(&res.stdout));
+    assert!(res.stderr.is_empty(), "{}", String::from_utf8_lossy(&res.stderr));
+    let log = fs::read_to_string(&log_file).unwrap();
+    assert!(log.contains("DEBUG kickstart::generation: Rendering README.md"), "{log}");
+}
+
+#[cfg(unix)]
+#[test]
+fn progress_is_only_shown_in_terminals() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Progress"
+kickstart_version = 1
+post_gen_hooks = [{ name = "greet", path = "greet.sh" }]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        ("README.md", "# {{ project_name }}\n"),
+        ("greet.sh", "#!/bin/sh\necho hello\n"),
+    ]);
+    let output = tempdir().unwrap();
+
+    let res =
+        kickstart().arg(tpl.path()).args(["--no-input", "-o"]).arg(output.path()).output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    // How long each hook took
+    assert!(stdout.lines().any(|l| l.starts_with("  ✓ greet (") && l.ends_with("s)")), "{stdout}");
+    assert!(stdout.ends_with("Everything done, ready to go!\n"), "{stdout}");
+    assert!(!String::from_utf8_lossy(&res.stderr).contains("Rendering"));
+}
+
+#[test]
+fn can_write_a_manifest_of_the_generated_files() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Manifest"
+kickstart_version = 1
+copy_without_render = ["assets/*"]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        ("README.md", "# {{ project_name }}\n"),
+        ("assets/logo.txt", "{{ not rendered }}\n"),
+    ]);
+    let output = tempdir().unwrap();
+    let manifest_path = output.path().join("manifest.json");
+
+    let res = kickstart()
+        .arg(tpl.path())
+        .args(["--no-input", "--manifest"])
+        .arg(&manifest_path)
+        .arg("-o")
+        .arg(output.path().join("project"))
+        .output()
+        .unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(stdout.contains("Created 2 files, 1 directory, 27 B\n"), "{stdout}");
+    assert!(stdout.ends_with("Everything done, ready to go!\n"), "{stdout}");
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&manifest_path).unwrap()).unwrap();
+    assert_eq!(manifest["directories"], serde_json::json!(["assets"]));
+    let files = manifest["files"].as_array().unwrap();
+    let readme = files.iter().find(|f| f["path"] == "README.md").unwrap();
+    assert_eq!(readme["size"], 8);
+    assert_eq!(readme["rendered"], true);
+    assert_eq!(readme["sha256"].as_str().unwrap().len(), 64);
+    let logo = files.iter().find(|f| f["path"] == "assets/logo.txt").unwrap();
+    assert_eq!(logo["size"], 19);
+    assert_eq!(logo["rendered"], false);
+}
+
+#[test]
+fn check_lists_what_changed_since_the_generation() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Check"
+kickstart_version = 1
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        ("README.md", "# {{ project_name }}\n"),
+        ("main.rs", "fn main() {}\n"),
+        ("LICENSE", "MIT\n"),
+    ]);
+    let output = tempdir().unwrap();
+    let res =
+        kickstart().arg(tpl.path()).args(["--no-input", "-o"]).arg(output.path()).output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+
+    let check = |args: &[&str]| {
+        let res = kickstart().arg("check").arg(output.path()).args(args).output().unwrap();
+        (res.status.success(), String::from_utf8_lossy(&res.stdout).to_string())
+    };
+    let (success, stdout) = check(&[]);
+    assert!(success);
+    assert_eq!(stdout, "The project is as it was generated\n");
+
+    fs::write(output.path().join("README.md"), "# Mine\n").unwrap();
+    fs::remove_file(output.path().join("LICENSE")).unwrap();
+    fs::write(tpl.path().join("CHANGELOG.md"), "# Changelog\n").unwrap();
+    let (success, stdout) = check(&[]);
+    assert!(!success);
+    assert_eq!(
+        stdout, " modified: README.md\n  deleted: LICENSE\nuntracked: CHANGELOG.md\n",
+        "{stdout}"
+    );
+
+    let (success, stdout) = check(&["--format", "json", "--lockfile-only"]);
+    assert!(!success);
+    let drift: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        drift,
+        serde_json::json!({ "modified": ["README.md"], "deleted": ["LICENSE"], "untracked": [] })
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn update_and_check_compare_with_the_commit_of_the_lockfile() {
+    let (_dir, repo, url) = make_git_template(&[
+        ("template.toml", "name = \"Locked\"\nkickstart_version = 1\nvariables = []\n"),
+        ("a.txt", "one"),
+        ("b.txt", "b"),
+        ("gone.txt", "gone"),
+    ]);
+    let output = tempdir().unwrap();
+    let res = kickstart().arg(&url).args(["--no-input", "-o"]).arg(output.path()).output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    fs::write(output.path().join("b.txt"), "mine").unwrap();
+
+    fs::write(repo.join("a.txt"), "two").unwrap();
+    fs::write(repo.join("new.txt"), "new").unwrap();
+    git(&repo, &["rm", "-q", "gone.txt"]);
+    git(&repo, &["add", "."]);
+    git(&repo, &["commit", "-q", "-m", "two"]);
+
+    // Added to the template after the generation, not to the project
+    let res = kickstart().arg("check").arg(output.path()).output().unwrap();
+    assert!(!res.status.success());
+    assert_eq!(String::from_utf8_lossy(&res.stdout), " modified: b.txt\n");
+
+    let res = kickstart().arg("update").arg(output.path()).arg("--no-input").output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(stdout.contains("Created 1 file(s), updated 1 file(s), deleted 1 file(s)"), "{stdout}");
+    // Only changed in the project
+    assert!(!String::from_utf8_lossy(&res.stderr).contains("b.txt"));
+    assert_eq!(fs::read_to_string(output.path().join("a.txt")).unwrap(), "two");
+    assert_eq!(fs::read_to_string(output.path().join("b.txt")).unwrap(), "mine");
+    assert!(!output.path().join("b.txt.new").exists());
+    assert!(!output.path().join("gone.txt").exists());
+    assert!(output.path().join("new.txt").exists());
+}
+
+#[test]
+fn can_generate_cookiecutter_templates() {
+    let tpl = make_template(&[
+        (
+            "cookiecutter.json",
+            r#"{
+    "project_name": "My Project",
+    "project_slug": "{{ cookiecutter.project_name.lower().replace(' ', '_') }}",
+    "license": ["MIT", "BSD-3"],
+    "_copy_without_render": ["*.html"]
+}"#,
+        ),
+        ("{{cookiecutter.project_slug}}/README.md", "# {{ cookiecutter.project_name.upper() }}\n"),
+        ("{{cookiecutter.project_slug}}/LICENSE", "{{ cookiecutter.license }}\n"),
+        ("{{cookiecutter.project_slug}}/index.html", "{{ not rendered }}\n"),
+        ("hooks/post_gen_project.sh", "echo '{{ cookiecutter.project_slug }}' > hooked\n"),
+    ]);
+    let output = tempdir().unwrap();
+
+    let res =
+        kickstart().arg(tpl.path()).args(["--no-input", "-o"]).arg(output.path()).output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let project = output.path().join("my_project");
+    assert_eq!(fs::read_to_string(project.join("README.md")).unwrap(), "# MY PROJECT\n");
+    assert_eq!(fs::read_to_string(project.join("LICENSE")).unwrap(), "MIT\n");
+    assert_eq!(fs::read_to_string(project.join("index.html")).unwrap(), "{{ not rendered }}\n");
+    // The hooks run in the generated project
+    assert_eq!(fs::read_to_string(project.join("hooked")).unwrap(), "my_project\n");
+    assert!(!output.path().join("cookiecutter.json").exists());
+
+    fs::write(
+        tpl.path().join("{{cookiecutter.project_slug}}/setup.py"),
+        "{{ cookiecutter.project_name if cookiecutter.license else 'x' }}\n",
+    )
+    .unwrap();
+    let res = kickstart()
+        .arg(tpl.path())
+        .args(["--no-input", "-o"])
+        .arg(output.path().join("other"))
+        .output()
+        .unwrap();
+    assert!(!res.status.success());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(
+        stderr.contains(
+            "Unsupported cookiecutter feature in `{{cookiecutter.project_slug}}/setup.py`"
+        ),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn can_convert_cookiecutter_templates() {
+    let tpl = make_template(&[
+        (
+            "cookiecutter.json",
+            r#"{"project_name": "My Project", "_extensions": ["jinja2_time.TimeExtension"]}"#,
+        ),
+        ("{{cookiecutter.project_name}}/README.md", "# {{ cookiecutter.project_name.upper() }}\n"),
+    ]);
+    let output = tempdir().unwrap();
+    let converted = output.path().join("converted");
+
+    let res = kickstart()
+        .arg("convert")
+        .arg(tpl.path())
+        .arg(&converted)
+        .arg("--dry-run")
+        .output()
+        .unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(
+        stdout.contains(
+            "rewrite: {{cookiecutter.project_name}}/README.md -> {{project_name}}/README.md\n"
+        ),
+        "{stdout}"
+    );
+    assert!(stdout.contains("directory = \"{{project_name}}\"\n"), "{stdout}");
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("`_extensions` are Jinja extensions"), "{stderr}");
+    assert!(!converted.exists());
+
+    let res = kickstart().arg("convert").arg(tpl.path()).arg(&converted).output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(
+        fs::read_to_string(converted.join("{{project_name}}/README.md")).unwrap(),
+        "# {{ project_name | upper }}\n"
+    );
+    let res = kickstart()
+        .arg(&converted)
+        .args(["--no-input", "-o"])
+        .arg(output.path().join("generated"))
+        .output()
+        .unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(
+        fs::read_to_string(output.path().join("generated/My Project/README.md")).unwrap(),
+        "# MY PROJECT\n"
+    );
+
+    let res = kickstart().arg("convert").arg(tpl.path()).arg(&converted).output().unwrap();
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("is not empty"));
+}
+
+#[test]
+fn can_print_the_schema_of_the_input_file() {
+    let tpl = make_template(&[(
+        "template.toml",
+        r#"
+name = "Schema"
+kickstart_version = 1
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+
+[[variables]]
+name = "database"
+default = "postgres"
+prompt = "Database?"
+choices = ["postgres", "sqlite"]
+"#,
+    )]);
+
+    let res = kickstart().arg("schema").arg(tpl.path()).output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let schema: serde_json::Value = serde_json::from_slice(&res.stdout).unwrap();
+    assert_eq!(schema["$schema"], "https://json-schema.org/draft/2020-12/schema");
+    assert_eq!(schema["properties"]["database"]["enum"], serde_json::json!(["postgres", "sqlite"]));
+    assert_eq!(schema["required"], serde_json::json!(["project_name", "database"]));
+
+    let out = tempdir().unwrap();
+    let path = out.path().join("schema.json");
+    let res = kickstart().arg("schema").arg(tpl.path()).arg("--out").arg(&path).output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let written: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+    assert_eq!(written, schema);
+}
+
+#[test]
+fn can_show_a_rendered_file_without_generating() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            "name = 'test'\nkickstart_version = 1\n[[variables]]\nname = 'name'\ndefault = 'World'\nprompt = 'Name?'\n",
+        ),
+        ("{{ name }}/README.md", "Hello {{ name }}\n"),
+    ]);
+    let output = tempdir().unwrap();
+    let show = |path: &str| {
+        kickstart()
+            .arg(tpl.path())
+            .args(["--no-input", "-o"])
+            .arg(output.path())
+            .args(["-D", "name=There", "--dry-run", "--show", path])
+            .output()
+            .unwrap()
+    };
+
+    let res = show("There/README.md");
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(String::from_utf8_lossy(&res.stdout), "Hello There\n");
+    assert_eq!(fs::read_dir(output.path()).unwrap().count(), 0);
+
+    let res = show("World/README.md");
+    assert!(!res.status.success());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("`World/README.md` is not generated with these answers"), "{stderr}");
+}
+
+#[test]
+fn shows_the_post_gen_message_at_the_end() {
+    let template = |message: &str| {
+        make_template(&[
+            (
+                "template.toml",
+                &format!(
+                    "name = 'test'\nkickstart_version = 1\npost_gen_message = \"Next steps:\\n  cd {message}\\n\"\n[[variables]]\nname = 'name'\ndefault = 'World'\nprompt = 'Name?'\n"
+                ),
+            ),
+            ("README.md", "Hello {{ name }}\n"),
+        ])
+    };
+    let run = |tpl: &tempfile::TempDir, args: &[&str]| {
+        let output = tempdir().unwrap();
+        kickstart()
+            .arg(tpl.path())
+            .args(["--no-input", "-o"])
+            .arg(output.path())
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let tpl = template("{{ name | lower }}");
+    let res = run(&tpl, &[]);
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(stdout.trim_end().ends_with("Next steps:\n  cd world"), "{stdout}");
+
+    let res = run(&tpl, &["--quiet"]);
+    assert!(!String::from_utf8_lossy(&res.stdout).contains("Next steps"));
+
+    let res = run(&tpl, &["--format", "json"]);
+    let json: serde_json::Value = serde_json::from_slice(&res.stdout).unwrap();
+    assert_eq!(json["post_gen_message"], "Next steps:\n  cd world\n");
+
+    // The project is generated anyway
+    let tpl = template("{{ nope }}");
+    let res = run(&tpl, &[]);
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("Could not render the post_gen_message"), "{stderr}");
+    assert!(!String::from_utf8_lossy(&res.stdout).contains("Next steps"));
+}
+
+#[test]
+fn can_exclude_paths_and_see_them_in_dry_runs() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            "name = 'test'\nkickstart_version = 1\n[[variables]]\nname = 'name'\ndefault = 'World'\nprompt = 'Name?'\n",
+        ),
+        (".kickstartignore", "*.swp\n"),
+        ("README.md", "Hello {{ name }}\n"),
+        ("README.md.swp", ""),
+        ("docs/index.md", "Docs\n"),
+    ]);
+    let output = tempdir().unwrap();
+    let dry_run = |verbose: bool| {
+        let mut cmd = kickstart();
+        cmd.arg(tpl.path())
+            .args(["--no-input", "--dry-run", "--exclude", "docs/", "-o"])
+            .arg(output.path().join("out"));
+        if verbose {
+            cmd.arg("-v");
+        }
+        let res = cmd.output().unwrap();
+        assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+        String::from_utf8_lossy(&res.stdout).into_owned()
+    };
+
+    let stdout = dry_run(false);
+    assert!(stdout.contains("README.md (12 B)"), "{stdout}");
+    assert!(!stdout.contains("docs") && !stdout.contains(".swp"), "{stdout}");
+    let stdout = dry_run(true);
+    assert!(stdout.contains("docs/ (skipped, excluded by `docs/`)"), "{stdout}");
+    assert!(
+        stdout.contains("README.md.swp (skipped, ignored by `*.swp` in .kickstartignore)"),
+        "{stdout}"
+    );
+
+    let answers = output.path().join("answers.json");
+    let res = kickstart()
+        .arg(tpl.path())
+        .args(["--no-input", "--dry-run", "--save-answers"])
+        .arg(&answers)
+        .arg("-o")
+        .arg(output.path().join("out"))
+        .output()
+        .unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(stdout.contains(&format!("Would save the answers to {}", answers.display())));
+    assert!(!answers.exists());
+
+    let res = kickstart()
+        .arg(tpl.path())
+        .args(["--no-input", "--exclude", "docs/", "-o"])
+        .arg(output.path().join("out"))
+        .output()
+        .unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert!(output.path().join("out/README.md").exists());
+    assert!(!output.path().join("out/docs").exists());
+    assert!(!output.path().join("out/README.md.swp").exists());
+    assert!(!output.path().join("out/.kickstartignore").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn can_regenerate_only_some_files() {
+    let tpl = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Only"
+kickstart_version = 1
+post_gen_hooks = [{ name = "touch", path = "touch.sh" }]
+
+[[variables]]
+name = "name"
+default = "app"
+prompt = "Name?"
+"#,
+        ),
+        ("touch.sh", "#!/bin/sh\ntouch hook-ran\n"),
+        ("{{ name }}/src/main.rs", "fn main() {}\n"),
+        ("{{ name }}/README.md", "# {{ name }}\n"),
+    ]);
+    let output = tempdir().unwrap();
+    let res =
+        kickstart().arg(tpl.path()).args(["--no-input", "-o"]).arg(output.path()).output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    fs::remove_file(output.path().join("hook-ran")).unwrap();
+    fs::write(output.path().join("app/README.md"), "changed").unwrap();
+    fs::write(tpl.path().join("{{ name }}/src/main.rs"), "fn main() { todo!() }\n").unwrap();
+
+    let only = |globs: &[&str]| {
+        let mut cmd = kickstart();
+        cmd.arg(tpl.path()).args(["--no-input", "--force", "-o"]).arg(output.path());
+        for glob in globs {
+            cmd.args(["--only", glob]);
+        }
+        cmd.output().unwrap()
+    };
+    let res = only(&["app/src/main.rs"]);
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(
+        fs::read_to_string(output.path().join("app/src/main.rs")).unwrap(),
+        "fn main() { todo!() }\n"
+    );
+    assert_eq!(fs::read_to_string(output.path().join("app/README.md")).unwrap(), "changed");
+    assert!(!output.path().join("hook-ran").exists());
+    let lockfile = fs::read_to_string(output.path().join(".kickstart.json")).unwrap();
+    assert!(lockfile.contains("app/README.md"), "{lockfile}");
+
+    let res = only(&["app/src/mian.rs"]);
+    assert!(!res.status.success());
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("No file of the template matches `app/src/mian.rs`"), "{stderr}");
+    assert!(stderr.contains("- app/src/main.rs\n- app/README.md"), "{stderr}");
+}
+
+#[test]
+fn groups_are_asked_under_their_title() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Groups"
+kickstart_version = 1
+
+[groups.database]
+title = "Database settings"
+only_if = { name = "use_db", value = true }
+
+[[variables]]
+name = "use_db"
+default = true
+prompt = "Use a database?"
+
+[[variables]]
+name = "db_name"
+default = "app"
+prompt = "Database name?"
+group = "database"
+
+[[variables]]
+name = "db_port"
+default = 5432
+prompt = "Database port?"
+group = "database"
+
+[[variables]]
+name = "name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        ("out.txt", "{{ name }}{% if use_db %}:{{ db_name }}:{{ db_port }}{% endif %}"),
+    ]);
+    let generate = |stdin: &str| {
+        let (res, out) = generate_with_input(dir.path(), &["--assume-tty", "--yes"], stdin);
+        assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+        let stdout = String::from_utf8_lossy(&res.stdout).to_string();
+        (stdout, fs::read_to_string(out.path().join("out.txt")).unwrap())
+    };
+
+    let (stdout, out) = generate("y\nmain\n5433\nworld\n");
+    assert_eq!(out, "world:main:5433");
+    assert_eq!(stdout.matches("Database settings").count(), 1, "{stdout}");
+    let title = stdout.find("Database settings").unwrap();
+    assert!(stdout.find("Use a database?").unwrap() < title);
+    assert!(title < stdout.find("Database name?").unwrap());
+
+    let (stdout, out) = generate("n\nworld\n");
+    assert_eq!(out, "world");
+    assert!(!stdout.contains("Database settings") && !stdout.contains("Database port?"));
+
+    let res = kickstart().arg("info").arg(dir.path()).output().unwrap();
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(stdout.contains("Database settings (only if `use_db` is true)\n  db_name"), "{stdout}");
+    assert!(stdout.contains("\n  db_port ") && stdout.contains("\nname "), "{stdout}");
+}
+
+#[test]
+fn choices_can_come_from_another_answer() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Regions"
+kickstart_version = 1
+
+[[variables]]
+name = "cloud"
+default = true
+prompt = "Deploy to the cloud?"
+
+[[variables]]
+name = "provider"
+default = "aws"
+prompt = "Cloud provider?"
+choices = ["aws", "gcp"]
+only_if = { name = "cloud", value = true }
+
+[[variables]]
+name = "region"
+default = "{% if provider == 'aws' %}us-east-1{% else %}us-east1{% endif %}"
+prompt = "Region?"
+choices_from = { name = "provider", choices = { aws = ["us-east-1", "eu-west-1"], gcp = ["us-east1", "europe-west1"] } }
+"#,
+        ),
+        ("out.txt", "{{ provider | default(value='') }}:{{ region | default(value='') }}"),
+    ]);
+    let generate = |args: &[&str], stdin: &str| {
+        let (res, out) = generate_with_input(dir.path(), args, stdin);
+        let stderr = String::from_utf8_lossy(&res.stderr).to_string();
+        (fs::read_to_string(out.path().join("out.txt")).ok(), stderr)
+    };
+
+    let (out, stderr) = generate(&["--no-input", "-D", "provider=gcp"], "");
+    assert_eq!(out.as_deref(), Some("gcp:us-east1"), "{stderr}");
+    let (out, _) = generate(&["--no-input"], "");
+    assert_eq!(out.as_deref(), Some("aws:us-east-1"));
+
+    let (out, stderr) =
+        generate(&["-i", "-"], r#"{"cloud": true, "provider": "aws", "region": "europe-west1"}"#);
+    assert!(out.is_none());
+    assert!(stderr.contains("The value `europe-west1` is not one of the choices"), "{stderr}");
+    let (out, stderr) =
+        generate(&["-i", "-"], r#"{"cloud": true, "provider": "gcp", "region": "europe-west1"}"#);
+    assert_eq!(out.as_deref(), Some("gcp:europe-west1"), "{stderr}");
+
+    let (out, stderr) = generate(&["--no-input", "-D", "cloud=false"], "");
+    assert_eq!(out.as_deref(), Some(":"), "{stderr}");
+    assert!(stderr.contains("`region` is not asked as `provider` was not asked"), "{stderr}");
+}
+
+#[cfg(unix)]
+#[test]
+fn choices_can_come_from_a_command() {
+    let files = [
+        (
+            "template.toml",
+            r#"
+name = "Tags"
+kickstart_version = 1
+
+[[variables]]
+name = "tag"
+default = "v1.0"
+prompt = "Tag?"
+choices = ["latest", "v1.0"]
+choices_cmd = ["cat", "tags.txt"]
+"#,
+        ),
+        ("tags.txt", "v2.0\nv1.0\n"),
+        ("out.txt", "{{ tag }}"),
+    ];
+    let generate = |template: &str, args: &[&str]| {
+        let output = tempdir().unwrap();
+        let res =
+            kickstart().arg(template).args(args).arg("-o").arg(output.path()).output().unwrap();
+        let stderr = String::from_utf8_lossy(&res.stderr).to_string();
+        (fs::read_to_string(output.path().join("out.txt")).ok(), stderr)
+    };
+
+    let dir = make_template(&files);
+    let template = dir.path().to_str().unwrap();
+    let (out, stderr) = generate(template, &["--no-input"]);
+    assert_eq!(out.as_deref(), Some("v1.0"), "{stderr}");
+    fs::write(dir.path().join("tags.txt"), "v3.0\nv2.0\n").unwrap();
+    let (out, _) = generate(template, &["--no-input"]);
+    assert_eq!(out.as_deref(), Some("v3.0"));
+    let (out, stderr) = generate(template, &["--no-input", "-D", "tag=v1.0"]);
+    assert!(out.is_none());
+    assert!(stderr.contains("The value `v1.0` is not one of the choices: v3.0, v2.0"), "{stderr}");
+
+    // Remote templates can't run it without being trusted, like hooks
+    let (_dir, _, url) = make_git_template(&files);
+    let (out, stderr) = generate(&url, &["--no-input", "-D", "tag=latest"]);
+    assert!(out.is_none());
+    assert!(stderr.contains("use --trust"), "{stderr}");
+    let (out, stderr) = generate(&url, &["--no-input", "--trust"]);
+    assert_eq!(out.as_deref(), Some("v1.0"), "{stderr}");
+}
+
+#[test]
+fn list_variables_are_asked_one_entry_at_a_time() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Workspace"
+kickstart_version = 1
+
+[[variables]]
+name = "members"
+type = "list"
+default = ["core"]
+prompt = "Member crates?"
+validation = "^[a-z-]+$"
+max_items = 3
+"#,
+        ),
+        ("Cargo.toml", "members = [{% for m in members %}\"{{ m }}\",{% endfor %}]"),
+    ]);
+    let generate = |args: &[&str], stdin: &str| {
+        let (res, out) = generate_with_input(dir.path(), args, stdin);
+        let output = format!(
+            "{}{}",
+            String::from_utf8_lossy(&res.stdout),
+            String::from_utf8_lossy(&res.stderr)
+        );
+        (fs::read_to_string(out.path().join("Cargo.toml")).ok(), output)
+    };
+
+    let (out, output) = generate(&["--assume-tty", "--yes"], "core\nNope\ncli\n\n");
+    assert_eq!(out.as_deref(), Some(r#"members = ["core","cli",]"#), "{output}");
+    assert!(output.contains("one per line, an empty line to finish"), "{output}");
+    assert!(output.contains("The value needs to pass the regex"), "{output}");
+
+    let (out, output) = generate(&["--assume-tty", "--yes"], "\n");
+    assert_eq!(out.as_deref(), Some(r#"members = ["core",]"#), "{output}");
+    let (out, _) = generate(&["--no-input"], "");
+    assert_eq!(out.as_deref(), Some(r#"members = ["core",]"#));
+
+    let (out, output) = generate(&["-i", "-"], r#"{"members": ["a", "b"]}"#);
+    assert_eq!(out.as_deref(), Some(r#"members = ["a","b",]"#), "{output}");
+    let (out, output) = generate(&["-i", "-"], r#"{"members": ["a", "b", "c", "d"]}"#);
+    assert!(out.is_none());
+    assert!(output.contains("The list must have at most 3 entries"), "{output}");
+}
+
+#[cfg(unix)]
+#[test]
+fn pre_prompt_hooks_can_give_defaults() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Defaults"
+kickstart_version = 1
+pre_prompt_hooks = [{ name = "detect author", path = "hooks/author.sh", interpreter = ["sh"] }]
+
+[[variables]]
+name = "author"
+default = "someone"
+prompt = "Author?"
+"#,
+        ),
+        (
+            "hooks/author.sh",
+            "printf '{\"author\": %s}' \"$DETECTED\" > \"$KICKSTART_DEFAULTS_FILE\"",
+        ),
+        ("AUTHORS", "{{ author }}"),
+    ]);
+    let generate = |args: &[&str], detected: &str, stdin: &str| {
+        let out = tempdir().unwrap();
+        let mut cmd = kickstart();
+        cmd.arg(dir.path()).arg("-o").arg(out.path()).args(args);
+        let res = run_with_input(cmd.env("DETECTED", detected), stdin);
+        let output = format!(
+            "{}{}",
+            String::from_utf8_lossy(&res.stdout),
+            String::from_utf8_lossy(&res.stderr)
+        );
+        (fs::read_to_string(out.path().join("AUTHORS")).ok(), output)
+    };
+
+    let (out, output) = generate(&["--no-input"], "\"Vincent\"", "");
+    assert_eq!(out.as_deref(), Some("Vincent"), "{output}");
+    assert!(output.contains("Running pre-prompt hooks"), "{output}");
+    // Still only a default
+    let (out, output) = generate(&["--assume-tty", "--yes"], "\"Vincent\"", "Bob\n");
+    assert_eq!(out.as_deref(), Some("Bob"), "{output}");
+    // Not run when skipped
+    let (out, output) =
+        generate(&["--no-input", "--skip-hook", "detect author"], "\"Vincent\"", "");
+    assert_eq!(out.as_deref(), Some("someone"), "{output}");
+
+    let (out, output) = generate(&["--no-input"], "1", "");
+    assert!(out.is_none());
+    assert!(
+        output
+            .contains("The hook `detect author` gave invalid defaults: `author` is of type string"),
+        "{output}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn pre_gen_hooks_can_give_extra_context() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Extra"
+kickstart_version = 1
+pre_gen_hooks = [
+    { name = "pick port", path = "hooks/port.sh", interpreter = ["sh"] },
+    { name = "say hi", path = "hooks/hi.sh", interpreter = ["sh"] },
+]
+
+[[variables]]
+name = "name"
+default = "app"
+prompt = "Name?"
+"#,
+        ),
+        ("hooks/port.sh", "echo '{\"port\": 8080}' > \"$KICKSTART_EXTRA_CONTEXT_FILE\""),
+        ("hooks/hi.sh", "echo hi"),
+        ("config", "{{ name }}:{{ extra.port }}"),
+    ]);
+    let out = tempdir().unwrap();
+    let res =
+        kickstart().arg(dir.path()).arg("-o").arg(out.path()).arg("--no-input").output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert_eq!(fs::read_to_string(out.path().join("config")).unwrap(), "app:8080");
+
+    let out = tempdir().unwrap();
+    let res = kickstart()
+        .arg(dir.path())
+        .arg("-o")
+        .arg(out.path())
+        .args(["--no-input", "--dry-run"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(stderr.contains("the `extra` values they can give are not available"), "{stderr}");
+}
+
+#[cfg(unix)]
+#[test]
+fn hooks_are_given_their_rendered_args() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Args"
+kickstart_version = 1
+post_gen_hooks = [
+    { name = "record", path = "hooks/record.sh", interpreter = ["sh"], args = ["--name", "{{ project_name }}", "--token={{ token }}"] },
+]
+
+[[variables]]
+name = "project_name"
+default = "my app"
+prompt = "Name?"
+
+[[variables]]
+name = "token"
+default = ""
+prompt = "Token?"
+secret = true
+"#,
+        ),
+        ("hooks/record.sh", "printf '%s|%s|%s' \"$1\" \"$2\" \"$3\" > args.txt"),
+        ("README.md", "{{ project_name }}"),
+    ]);
+    let out = tempdir().unwrap();
+    let res = kickstart()
+        .arg(dir.path())
+        .arg("-o")
+        .arg(out.path())
+        .args(["--no-input", "-vv", "-D", "token=s3cret"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(res.status.success(), "{stderr}");
+    assert_eq!(
+        fs::read_to_string(out.path().join("args.txt")).unwrap(),
+        "--name|my app|--token=s3cret"
+    );
+    // Secrets are never logged
+    assert!(stderr.contains("--name my app --token=********`"), "{stderr}");
+    assert!(stderr.contains("KICKSTART_TOKEN"), "{stderr}");
+    assert!(!stderr.contains("s3cret"), "{stderr}");
+}
+
+#[cfg(unix)]
+#[test]
+fn hooks_can_be_allowed_to_fail() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Failing"
+kickstart_version = 1
+post_gen_hooks = [
+    { name = "pre-commit", path = "hooks/fail.sh", interpreter = ["sh"], allow_failure = true },
+    { name = "finish", path = "hooks/finish.sh", interpreter = ["sh"] },
+]
+variables = []
+"#,
+        ),
+        ("hooks/fail.sh", "exit 3"),
+        ("hooks/finish.sh", "touch finished"),
+        ("README.md", "Hello"),
+    ]);
+    let out = tempdir().unwrap();
+    let res =
+        kickstart().arg(dir.path()).arg("-o").arg(out.path()).arg("--no-input").output().unwrap();
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    assert!(res.status.success(), "{stderr}");
+    assert!(out.path().join("finished").exists());
+    assert!(stderr.contains("Hook `pre-commit` exited with code 3, continuing"), "{stderr}");
+    assert!(
+        stderr.contains("Hooks that failed but are allowed to:\n  - Hook `pre-commit`"),
+        "{stderr}"
+    );
+
+    let out = tempdir().unwrap();
+    let res = kickstart()
+        .arg(dir.path())
+        .arg("-o")
+        .arg(out.path())
+        .args(["--no-input", "--strict-hooks"])
+        .output()
+        .unwrap();
+    assert!(!res.status.success());
+    assert!(!out.path().join("finished").exists());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("Hook `pre-commit` exited with code 3"));
+}
+
+#[cfg(unix)]
+#[test]
+fn parallel_hooks_run_at_the_same_time() {
+    // Each of them waits for the other one to start
+    let wait_for = |other: &str| {
+        format!(
+            "touch started-$1; echo waiting; for i in $(seq 20); do [ -f started-{other} ] && exit 0; sleep 0.1; done; exit 1"
+        )
+    };
+    let (wait_for_b, wait_for_a) = (wait_for("b"), wait_for("a"));
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Parallel"
+kickstart_version = 1
+post_gen_hooks = [
+    { name = "a", path = "hooks/a", interpreter = ["sh"], args = ["a"], parallel = true },
+    { name = "b", path = "hooks/b", interpreter = ["sh"], args = ["b"], parallel = true },
+]
+variables = []
+"#,
+        ),
+        ("hooks/a", &wait_for_b),
+        ("hooks/b", &wait_for_a),
+        ("README.md", "Hello"),
+    ]);
+    let generate = |args: &[&str]| {
+        let out = tempdir().unwrap();
+        let res =
+            kickstart().arg(dir.path()).arg("-o").arg(out.path()).args(args).output().unwrap();
+        (
+            res.status.success(),
+            String::from_utf8_lossy(&res.stdout).into_owned(),
+            String::from_utf8_lossy(&res.stderr).into_owned(),
+        )
+    };
+
+    let (success, stdout, stderr) = generate(&["--no-input"]);
+    assert!(success, "{stdout}{stderr}");
+    assert!(stdout.contains("[a] waiting") && stdout.contains("[b] waiting"), "{stdout}");
+
+    let (success, _, stderr) = generate(&["--no-input", "--jobs", "1"]);
+    assert!(!success);
+    assert!(stderr.contains("Hook `a` exited with code 1"), "{stderr}");
+}
+
+#[cfg(unix)]
+#[test]
+fn parallel_hooks_start_as_soon_as_one_is_done() {
+    // `slow` only succeeds if `last` starts while it is still running, once `quick` is done
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Parallel"
+kickstart_version = 1
+post_gen_hooks = [
+    { name = "slow", path = "hooks/slow", interpreter = ["sh"], parallel = true, timeout_secs = 10 },
+    { name = "quick", path = "hooks/quick", interpreter = ["sh"], parallel = true },
+    { name = "last", path = "hooks/last", interpreter = ["sh"], parallel = true },
+]
+variables = []
+"#,
+        ),
+        ("hooks/slow", "for i in $(seq 50); do [ -f last ] && exit 0; sleep 0.1; done; exit 1"),
+        ("hooks/quick", "exit 0"),
+        ("hooks/last", "touch last"),
+        ("README.md", "Hello"),
+    ]);
+    let out = tempdir().unwrap();
+    let res = kickstart()
+        .arg(dir.path())
+        .arg("-o")
+        .arg(out.path())
+        .args(["--no-input", "--jobs", "2"])
+        .output()
+        .unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+}
+
+#[cfg(unix)]
+#[test]
+fn parallel_hooks_without_timeout_are_killed_with_what_they_started() {
+    use std::time::{Duration, Instant};
+
+    // `fail` interrupts kickstart once `sleep` started: the `sleep` it runs would keep its output
+    // open, and kickstart waiting for it, if only the shell was killed
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Parallel"
+kickstart_version = 1
+post_gen_hooks = [
+    { name = "fail", path = "hooks/fail", interpreter = ["sh"], parallel = true },
+    { name = "sleep", path = "hooks/sleep", interpreter = ["sh"], parallel = true },
+]
+variables = []
+"#,
+        ),
+        ("hooks/fail", "while [ ! -f started ]; do sleep 0.1; done; kill -INT $PPID; exit 1"),
+        ("hooks/sleep", "touch started; sleep 30; echo done"),
+        ("README.md", "Hello"),
+    ]);
+    let out = tempdir().unwrap();
+    let start = Instant::now();
+    let res =
+        kickstart().arg(dir.path()).arg("-o").arg(out.path()).arg("--no-input").output().unwrap();
+    assert!(!res.status.success());
+    assert!(start.elapsed() < Duration::from_secs(20), "{:?}", start.elapsed());
+    assert!(!String::from_utf8_lossy(&res.stdout).contains("[sleep] done"));
+}
+
+#[test]
+fn git_init_creates_a_repository_before_the_post_gen_hooks() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Git init"
+kickstart_version = 1
+git_init = true
+git_init_message = "Start {{ project_name }}"
+post_gen_hooks = [{ name = "log", path = "hooks/log", interpreter = ["sh"] }]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        ("hooks/log", "git log --format=%s > log.txt || echo none > log.txt"),
+        ("README.md", "Hello"),
+    ]);
+    let generate = |args: &[&str]| {
+        let out = tempdir().unwrap();
+        let res =
+            without_git_config(kickstart().arg(dir.path()).arg("-o").arg(out.path()).args(args))
+                .output()
+                .unwrap();
+        assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+        (out, String::from_utf8_lossy(&res.stdout).into_owned())
+    };
+
+    let (out, stdout) = generate(&["--no-input"]);
+    assert!(stdout.contains("Created a git repository"), "{stdout}");
+    assert_eq!(fs::read_to_string(out.path().join("log.txt")).unwrap(), "Start hello\n");
+
+    let (out, _) = generate(&["--no-input", "--no-git-init"]);
+    assert!(!out.path().join(".git").exists());
+    assert_eq!(fs::read_to_string(out.path().join("log.txt")).unwrap(), "none\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn git_init_commits_the_generated_project() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Git init"
+kickstart_version = 1
+git_init = true
+git_init_message = "Start {{ project_name }}"
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        ("README.md", "Hello"),
+        ("src/main.rs", "fn main() {}"),
+    ]);
+    let out = tempdir().unwrap();
+    let res = without_git_config(kickstart().arg(dir.path()).arg("-o").arg(out.path()))
+        .arg("--no-input")
+        .output()
+        .unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+
+    assert!(out.path().join(".git").is_dir());
+    assert_eq!(git(out.path(), &["rev-list", "--count", "HEAD"]), "1");
+    assert_eq!(git(out.path(), &["log", "--format=%s"]), "Start hello");
+    assert_eq!(git(out.path(), &["ls-files"]), ".kickstart.json\nREADME.md\nsrc/main.rs");
+    assert_eq!(git(out.path(), &["status", "--porcelain"]), "");
+}
+
+#[cfg(unix)]
+#[test]
+fn can_generate_in_an_archive() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Archive"
+kickstart_version = 1
+post_gen_hooks = [{ name = "touch", path = "touch.sh", interpreter = ["sh"] }]
+
+[[variables]]
+name = "project_name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        ("touch.sh", "touch ran"),
+        ("{{ project_name }}.txt", "Hello {{ project_name }}"),
+    ]);
+    let out = tempdir().unwrap();
+    let generate = |name: &str, args: &[&str]| {
+        kickstart()
+            .arg(dir.path())
+            .arg("--archive")
+            .arg(out.path().join(name))
+            .args(["--no-input", "--deterministic"])
+            .args(args)
+            .current_dir(out.path())
+            .output()
+            .unwrap()
+    };
+
+    let res = generate("project.tar.gz", &[]);
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert!(String::from_utf8_lossy(&res.stderr).contains("hooks of the template are not run"));
+    let listed =
+        Command::new("tar").arg("-tzf").arg(out.path().join("project.tar.gz")).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&listed.stdout), ".kickstart.json\nhello.txt\n");
+    let first = fs::read(out.path().join("project.tar.gz")).unwrap();
+
+    let res = generate("project.tar.gz", &[]);
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("use --force to overwrite it"));
+    let res = generate("project.tar.gz", &["--force"]);
+    assert!(res.status.success());
+    assert_eq!(fs::read(out.path().join("project.tar.gz")).unwrap(), first);
+
+    let res = generate("project.zip", &["--run-hooks"]);
+    assert!(!res.status.success());
+    let res = generate("project.rar", &[]);
+    assert!(!res.status.success());
+    let mut left: Vec<_> =
+        fs::read_dir(out.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+    left.sort();
+    assert_eq!(left, ["project.tar.gz"]);
+}
+
+#[test]
+fn init_creates_a_template_that_validates() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("my-template");
+    let res = kickstart().arg("init").arg(&path).output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert!(path.join("hooks/post-gen.sh").exists());
+
+    let res = kickstart()
+        .arg("validate")
+        .arg(path.join("template.toml"))
+        .args(["--render", "--check-usage"])
+        .output()
+        .unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    assert!(!String::from_utf8_lossy(&res.stderr).contains("Warning"));
+
+    let res = kickstart().arg("init").arg(&path).output().unwrap();
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("Use --force to overwrite them"));
+    let res = kickstart().arg("init").arg(&path).arg("--force").output().unwrap();
+    assert!(res.status.success());
+}
+
+#[cfg(unix)]
+#[test]
+fn dev_renders_again_what_changes() {
+    use std::io::{BufRead, BufReader};
+    use std::time::{Duration, Instant};
+
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Dev"
+kickstart_version = 1
+
+[[variables]]
+name = "name"
+default = "hello"
+prompt = "Name?"
+"#,
+        ),
+        ("README.md", "# {{ name }}"),
+    ]);
+    let scratch = tempdir().unwrap();
+    let answers = scratch.path().join("answers.json");
+    fs::write(&answers, r#"{"name": "dev"}"#).unwrap();
+    let out = scratch.path().join("out");
+    // Not left running if the test fails
+    struct KillOnDrop(std::process::Child);
+    impl Drop for KillOnDrop {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+        }
+    }
+    // Started once it rendered everything, its stdout being kept open
+    let start_dev = || {
+        let mut child = kickstart()
+            .arg("dev")
+            .arg(dir.path())
+            .arg("--answers")
+            .arg(&answers)
+            .arg("-o")
+            .arg(&out)
+            .arg("--clean")
+            .stdout(Stdio::piped())
+            .spawn()
+            .map(KillOnDrop)
+            .unwrap();
+        let mut stdout = BufReader::new(child.0.stdout.take().unwrap());
+        let mut line = String::new();
+        while !line.contains("watching") {
+            line.clear();
+            assert!(stdout.read_line(&mut line).unwrap() > 0);
+        }
+        (child, stdout)
+    };
+    let (mut child, _stdout) = start_dev();
+    assert_eq!(fs::read_to_string(out.join("README.md")).unwrap(), "# dev");
+
+    let wait_for = |path: &Path, expected: Option<&str>| {
+        let start = Instant::now();
+        while fs::read_to_string(path).ok().as_deref() != expected {
+            assert!(start.elapsed() < Duration::from_secs(10), "{}", path.display());
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    };
+    fs::write(dir.path().join("README.md"), "# {{ name }}!").unwrap();
+    wait_for(&out.join("README.md"), Some("# dev!"));
+    fs::write(dir.path().join("{{ name }}.txt"), "new").unwrap();
+    wait_for(&out.join("dev.txt"), Some("new"));
+    fs::remove_file(dir.path().join("{{ name }}.txt")).unwrap();
+    wait_for(&out.join("dev.txt"), None);
+
+    Command::new("kill").arg("-INT").arg(child.0.id().to_string()).status().unwrap();
+    assert!(child.0.wait().unwrap().success());
+    assert!(!out.exists());
+
+    // Only what it rendered is removed from a directory that already existed
+    fs::create_dir(&out).unwrap();
+    fs::write(out.join("notes.txt"), "Mine").unwrap();
+    let (mut child, _stdout) = start_dev();
+    assert_eq!(fs::read_to_string(out.join("README.md")).unwrap(), "# dev!");
+    Command::new("kill").arg("-INT").arg(child.0.id().to_string()).status().unwrap();
+    assert!(child.0.wait().unwrap().success());
+    assert!(!out.join("README.md").exists());
+    assert_eq!(fs::read_to_string(out.join("notes.txt")).unwrap(), "Mine");
+}
+
+#[test]
+fn test_runs_the_cases_of_the_template() {
+    let dir = make_template(&[
+        (
+            "template.toml",
+            r#"
+name = "Tested"
+kickstart_version = 1
+pre_gen_hooks = [{ name = "fail", path = "fail.sh", interpreter = ["sh"] }]
+
+[[variables]]
+name = "edition"
+default = "2021"
+prompt = "Edition?"
+"#,
+        ),
+        (".kickstartignore", "fail.sh\n"),
+        ("fail.sh", "exit 1"),
+        ("Cargo.toml", "[package]\nedition = \"{{ edition }}\"\n"),
+        (
+            "kickstart-tests/default.toml",
+            "expect_contains = { \"Cargo.toml\" = ['edition = \"2021\"'] }",
+        ),
+    ]);
+    let res = kickstart().arg("test").arg(dir.path()).output().unwrap();
+    assert!(res.status.success(), "{}", String::from_utf8_lossy(&res.stderr));
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(stdout.contains("default ... ok"), "{stdout}");
+
+    fs::write(
+        dir.path().join("kickstart-tests").join("old.json"),
+        r#"{"answers": {"edition": "2018"}, "expect_contains": {"Cargo.toml": ["edition = \"2021\""]}, "expect_files": ["src/main.rs"]}"#,
+    )
+    .unwrap();
+    let res = kickstart().arg("test").arg(dir.path()).output().unwrap();
+    assert!(!res.status.success());
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    assert!(stdout.contains("`src/main.rs` is not generated"), "{stdout}");
+    assert!(stdout.contains("-edition = \"2018\"\n+edition = \"2021\""), "{stdout}");
+    assert!(String::from_utf8_lossy(&res.stderr).contains("1 of the 2 test cases failed"));
+}